@@ -0,0 +1,90 @@
+//! The ACPI Root System Description Pointer, enough to copy it (and the
+//! XSDT it points to) somewhere the kernel can rely on.
+//!
+//! ```text
+//! 0  "RSD PTR "   8 checksum   9 OEM ID   15 revision   16 u32 RSDT
+//! 20 u32 length   24 u64 XSDT  32 extended checksum     33 reserved
+//! ```
+
+pub const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// Size of an ACPI 1.0 RSDP, covered by the first checksum.
+pub const RSDP_V1_LEN: usize = 20;
+/// Size of an ACPI 2.0+ RSDP.
+pub const RSDP_V2_LEN: usize = 36;
+/// Size of the header every system description table starts with.
+pub const SDT_HEADER_LEN: usize = 36;
+
+/// Length of the RSDP at the start of `rsdp`, which must hold at least
+/// [`RSDP_V2_LEN`] bytes unless the revision is 0.
+pub fn rsdp_len(rsdp: &[u8]) -> Option<usize> {
+    if rsdp.len() < RSDP_V1_LEN || &rsdp[..8] != RSDP_SIGNATURE {
+        return None;
+    }
+    if rsdp[15] < 2 {
+        return Some(RSDP_V1_LEN);
+    }
+    let len = u32::from_le_bytes(rsdp.get(20..24)?.try_into().ok()?) as usize;
+    (len >= RSDP_V2_LEN).then_some(len)
+}
+
+/// The XSDT address of an ACPI 2.0+ RSDP; `None` for 1.0 or when unset.
+pub fn xsdt_addr(rsdp: &[u8]) -> Option<u64> {
+    if rsdp_len(rsdp)? < RSDP_V2_LEN {
+        return None;
+    }
+    let addr = u64::from_le_bytes(rsdp.get(24..32)?.try_into().ok()?);
+    (addr != 0).then_some(addr)
+}
+
+/// Length of a system description table from its header.
+pub fn sdt_len(header: &[u8]) -> Option<usize> {
+    let len = u32::from_le_bytes(header.get(4..8)?.try_into().ok()?) as usize;
+    (len >= SDT_HEADER_LEN).then_some(len)
+}
+
+/// Point an ACPI 2.0+ RSDP at `xsdt` and fix its extended checksum.
+pub fn set_xsdt(rsdp: &mut [u8], xsdt: u64) {
+    rsdp[24..32].copy_from_slice(&xsdt.to_le_bytes());
+    rsdp[32] = 0;
+    rsdp[32] = checksum(rsdp);
+}
+
+/// The byte that makes `bytes` sum to zero.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_sub(*b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rsdp(revision: u8, xsdt: u64) -> [u8; RSDP_V2_LEN] {
+        let mut out = [0u8; RSDP_V2_LEN];
+        out[..8].copy_from_slice(RSDP_SIGNATURE);
+        out[15] = revision;
+        out[20..24].copy_from_slice(&(RSDP_V2_LEN as u32).to_le_bytes());
+        out[8] = checksum(&out[..RSDP_V1_LEN]);
+        set_xsdt(&mut out, xsdt);
+        out
+    }
+
+    #[test]
+    fn reads_lengths_and_xsdt() {
+        assert_eq!(rsdp_len(&rsdp(0, 0)[..RSDP_V1_LEN]), Some(RSDP_V1_LEN));
+        assert_eq!(xsdt_addr(&rsdp(0, 0)[..RSDP_V1_LEN]), None);
+        assert_eq!(rsdp_len(&rsdp(2, 0x1000)), Some(RSDP_V2_LEN));
+        assert_eq!(xsdt_addr(&rsdp(2, 0x1000)), Some(0x1000));
+        assert_eq!(xsdt_addr(&rsdp(2, 0x1000)[..28]), None);
+        assert_eq!(rsdp_len(b"RSD PTX 0123456789abcdef"), None);
+    }
+
+    #[test]
+    fn relocation_keeps_both_checksums() {
+        let mut table = rsdp(2, 0x1000);
+        set_xsdt(&mut table, 0x7654_3000);
+        assert_eq!(xsdt_addr(&table), Some(0x7654_3000));
+        let sum = |b: &[u8]| b.iter().fold(0u8, |s, x| s.wrapping_add(*x));
+        assert_eq!(sum(&table[..RSDP_V1_LEN]), 0);
+        assert_eq!(sum(&table), 0);
+    }
+}
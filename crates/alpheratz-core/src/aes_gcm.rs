@@ -0,0 +1,274 @@
+//! AES-128/256 in GCM mode (NIST SP 800-38D), for decrypting artifacts laid
+//! out as `nonce (12) || ciphertext || tag (16)`.
+
+use alloc::vec::Vec;
+
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    KeyLength,
+    Truncated,
+    TagMismatch,
+}
+
+struct Aes {
+    round_keys: [[u8; 16]; 15],
+    rounds: usize,
+}
+
+fn xtime(x: u8) -> u8 {
+    (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+impl Aes {
+    fn new(key: &[u8]) -> Result<Self, Error> {
+        let nk = match key.len() {
+            16 => 4,
+            32 => 8,
+            _ => return Err(Error::KeyLength),
+        };
+        let rounds = nk + 6;
+        let total = 4 * (rounds + 1);
+
+        let mut w = [[0u8; 4]; 60];
+        for (i, c) in key.chunks_exact(4).enumerate() {
+            w[i].copy_from_slice(c);
+        }
+        for i in nk..total {
+            let mut t = w[i - 1];
+            if i % nk == 0 {
+                t = [
+                    SBOX[t[1] as usize],
+                    SBOX[t[2] as usize],
+                    SBOX[t[3] as usize],
+                    SBOX[t[0] as usize],
+                ];
+                t[0] ^= RCON[i / nk - 1];
+            } else if nk > 6 && i % nk == 4 {
+                t = t.map(|b| SBOX[b as usize]);
+            }
+            for j in 0..4 {
+                w[i][j] = w[i - nk][j] ^ t[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; 15];
+        for (r, rk) in round_keys.iter_mut().enumerate().take(rounds + 1) {
+            for c in 0..4 {
+                rk[4 * c..4 * c + 4].copy_from_slice(&w[4 * r + c]);
+            }
+        }
+        Ok(Aes { round_keys, rounds })
+    }
+
+    fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let add = |s: &mut [u8; 16], rk: &[u8; 16]| {
+            for (a, b) in s.iter_mut().zip(rk) {
+                *a ^= b;
+            }
+        };
+
+        add(block, &self.round_keys[0]);
+        for round in 1..=self.rounds {
+            for b in block.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+
+            let s = *block;
+            for c in 0..4 {
+                for r in 0..4 {
+                    block[r + 4 * c] = s[r + 4 * ((c + r) % 4)];
+                }
+            }
+
+            if round != self.rounds {
+                for c in 0..4 {
+                    let a = [
+                        block[4 * c],
+                        block[4 * c + 1],
+                        block[4 * c + 2],
+                        block[4 * c + 3],
+                    ];
+                    let x = a.map(xtime);
+                    block[4 * c] = x[0] ^ x[1] ^ a[1] ^ a[2] ^ a[3];
+                    block[4 * c + 1] = a[0] ^ x[1] ^ x[2] ^ a[2] ^ a[3];
+                    block[4 * c + 2] = a[0] ^ a[1] ^ x[2] ^ x[3] ^ a[3];
+                    block[4 * c + 3] = x[0] ^ a[0] ^ a[1] ^ a[2] ^ x[3];
+                }
+            }
+
+            add(block, &self.round_keys[round]);
+        }
+    }
+}
+
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xE1 << 120;
+    let mut z = 0u128;
+    let mut v = y;
+    for i in (0..128).rev() {
+        if (x >> i) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    z
+}
+
+fn ghash(h: u128, data: &[u8], y: &mut u128) {
+    for chunk in data.chunks(16) {
+        let mut block = [0u8; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        *y = gf_mul(*y ^ u128::from_be_bytes(block), h);
+    }
+}
+
+fn inc32(counter: &mut [u8; 16]) {
+    let n =
+        u32::from_be_bytes([counter[12], counter[13], counter[14], counter[15]]).wrapping_add(1);
+    counter[12..].copy_from_slice(&n.to_be_bytes());
+}
+
+/// Decrypt `nonce || ciphertext || tag` with `key`, verifying the tag before
+/// returning any plaintext.
+pub fn decrypt(key: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    open(key, &[], sealed)
+}
+
+/// [`decrypt`] with additional authenticated data, which artifacts do not
+/// carry but the NIST vectors do.
+fn open(key: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    let aes = Aes::new(key)?;
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(Error::Truncated);
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    let mut h_block = [0u8; 16];
+    aes.encrypt_block(&mut h_block);
+    let h = u128::from_be_bytes(h_block);
+
+    let mut j0 = [0u8; 16];
+    j0[..NONCE_LEN].copy_from_slice(nonce);
+    j0[15] = 1;
+
+    let mut s = 0u128;
+    ghash(h, aad, &mut s);
+    ghash(h, ciphertext, &mut s);
+    let lengths = (((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8)).to_be_bytes();
+    ghash(h, &lengths, &mut s);
+
+    let mut ek_j0 = j0;
+    aes.encrypt_block(&mut ek_j0);
+    let expected = (s ^ u128::from_be_bytes(ek_j0)).to_be_bytes();
+
+    let diff = expected
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if diff != 0 {
+        return Err(Error::TagMismatch);
+    }
+
+    let mut out = Vec::with_capacity(ciphertext.len());
+    let mut counter = j0;
+    for chunk in ciphertext.chunks(16) {
+        inc32(&mut counter);
+        let mut ks = counter;
+        aes.encrypt_block(&mut ks);
+        out.extend(chunk.iter().zip(ks.iter()).map(|(c, k)| c ^ k));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::parse_hex;
+
+    /// `nonce || ciphertext || tag` from the hex strings of a test case.
+    fn sealed(iv: &str, c: &str, t: &str) -> Vec<u8> {
+        parse_hex(&alloc::format!("{}{}{}", iv, c, t)).unwrap()
+    }
+
+    const K15: &str = "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308";
+    const IV15: &str = "cafebabefacedbaddecaf888";
+    const P15: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                       1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255";
+    const C15: &str = "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+                       8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662898015ad";
+
+    // Test cases 2 and 13-16 of McGrew & Viega, "The Galois/Counter Mode of
+    // Operation", as republished in NIST's GCM validation material.
+    #[test]
+    fn matches_nist_vectors() {
+        let zero128 = [0u8; 16];
+        let zero256 = [0u8; 32];
+        let iv0 = "000000000000000000000000";
+
+        let tc2 = sealed(
+            iv0,
+            "0388dace60b6a392f328c2b971b2fe78",
+            "ab6e47d42cec13bdf53a67b21257bddf",
+        );
+        assert_eq!(decrypt(&zero128, &tc2), Ok(zero128.to_vec()));
+
+        let tc13 = sealed(iv0, "", "530f8afbc74536b9a963b4f1c4cb738b");
+        assert_eq!(decrypt(&zero256, &tc13), Ok(Vec::new()));
+
+        let tc14 = sealed(
+            iv0,
+            "cea7403d4d606b6e074ec5d3baf39d18",
+            "d0d1c8a799996bf0265b98b5d48ab919",
+        );
+        assert_eq!(decrypt(&zero256, &tc14), Ok(zero128.to_vec()));
+
+        let key = parse_hex(K15).unwrap();
+        let plain = parse_hex(P15).unwrap();
+        let tc15 = sealed(IV15, C15, "b094dac5d93471bdec1a502270e3cc6c");
+        assert_eq!(decrypt(&key, &tc15), Ok(plain.clone()));
+
+        let aad = parse_hex("feedfacedeadbeeffeedfacedeadbeefabaddad2").unwrap();
+        let tc16 = sealed(IV15, &C15[..120], "76fc6ece0f4e1768cddf8853bb2d551b");
+        assert_eq!(open(&key, &aad, &tc16), Ok(plain[..60].to_vec()));
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let key = parse_hex(K15).unwrap();
+        let mut tc15 = sealed(IV15, C15, "b094dac5d93471bdec1a502270e3cc6c");
+        assert_eq!(decrypt(&key[..20], &tc15), Err(Error::KeyLength));
+        assert_eq!(decrypt(&key, &tc15[..27]), Err(Error::Truncated));
+
+        let last = tc15.len() - 1;
+        tc15[last] ^= 1;
+        assert_eq!(decrypt(&key, &tc15), Err(Error::TagMismatch));
+        tc15[last] ^= 1;
+        tc15[NONCE_LEN] ^= 0x80;
+        assert_eq!(decrypt(&key, &tc15), Err(Error::TagMismatch));
+    }
+}
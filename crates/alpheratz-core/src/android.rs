@@ -0,0 +1,329 @@
+//! Android `boot.img` (header v0–v4) and `vendor_boot.img` (v3–v4).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const BOOT_MAGIC: &[u8] = b"ANDROID!";
+const VENDOR_BOOT_MAGIC: &[u8] = b"VNDRBOOT";
+/// Page size of v3+ boot images; earlier ones record their own.
+const V3_PAGE_SIZE: usize = 4096;
+const BOOTCONFIG_TRAILER: &[u8] = b"#BOOTCONFIG\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    BadMagic,
+    Truncated,
+    UnsupportedVersion(u32),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::BadMagic => f.write_str("not an Android boot image"),
+            Error::Truncated => f.write_str("Android boot image is truncated"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported header version {}", v),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BootImage<'a> {
+    pub header_version: u32,
+    pub kernel: &'a [u8],
+    pub ramdisk: &'a [u8],
+    pub cmdline: String,
+    /// Only in v2 headers; later versions keep it in `vendor_boot`.
+    pub dtb: Option<&'a [u8]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VendorBootImage<'a> {
+    pub header_version: u32,
+    /// All vendor ramdisk fragments, concatenated.
+    pub ramdisk: &'a [u8],
+    pub cmdline: String,
+    pub dtb: Option<&'a [u8]>,
+    /// Raw bootconfig parameters (v4).
+    pub bootconfig: &'a [u8],
+}
+
+/// Kernel, ramdisk, command line and device tree ready for the Linux path.
+#[derive(Debug, Clone)]
+pub struct Combined<'a> {
+    pub kernel: &'a [u8],
+    pub ramdisk: Vec<u8>,
+    pub cmdline: String,
+    pub dtb: Option<&'a [u8]>,
+}
+
+fn u32_at(data: &[u8], off: usize) -> Result<u32, Error> {
+    let b = data.get(off..off + 4).ok_or(Error::Truncated)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// NUL-padded string field of `len` bytes.
+fn str_at(data: &[u8], off: usize, len: usize) -> Result<String, Error> {
+    let field = data.get(off..off + len).ok_or(Error::Truncated)?;
+    let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+    Ok(String::from_utf8_lossy(&field[..end]).into_owned())
+}
+
+/// Splits an image into page-aligned sections following the header.
+struct Sections<'a> {
+    data: &'a [u8],
+    page: usize,
+    pos: usize,
+}
+
+impl<'a> Sections<'a> {
+    fn new(data: &'a [u8], page: usize, header_size: usize) -> Self {
+        Sections {
+            data,
+            page,
+            pos: header_size.next_multiple_of(page),
+        }
+    }
+
+    fn next(&mut self, size: usize) -> Result<&'a [u8], Error> {
+        let section = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or(Error::Truncated)?;
+        self.pos = (self.pos + size).next_multiple_of(self.page);
+        Ok(section)
+    }
+}
+
+fn join_cmdline(a: &str, b: &str) -> String {
+    let mut out = String::from(a.trim());
+    if !b.trim().is_empty() {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(b.trim());
+    }
+    out
+}
+
+pub fn parse_boot(data: &[u8]) -> Result<BootImage<'_>, Error> {
+    if !data.starts_with(BOOT_MAGIC) {
+        return Err(Error::BadMagic);
+    }
+    let kernel_size = u32_at(data, 8)? as usize;
+    let header_version = u32_at(data, 40)?;
+
+    match header_version {
+        0..=2 => {
+            let ramdisk_size = u32_at(data, 16)? as usize;
+            let second_size = u32_at(data, 24)? as usize;
+            let page = u32_at(data, 36)? as usize;
+            if page == 0 || !page.is_power_of_two() {
+                return Err(Error::Truncated);
+            }
+            let cmdline = join_cmdline(&str_at(data, 64, 512)?, &str_at(data, 608, 1024)?);
+            let mut sections = Sections::new(data, page, page);
+            let kernel = sections.next(kernel_size)?;
+            let ramdisk = sections.next(ramdisk_size)?;
+            sections.next(second_size)?;
+            let dtb = if header_version >= 1 {
+                sections.next(u32_at(data, 1632)? as usize)?;
+                if header_version == 2 {
+                    Some(sections.next(u32_at(data, 1648)? as usize)?).filter(|d| !d.is_empty())
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+            Ok(BootImage {
+                header_version,
+                kernel,
+                ramdisk,
+                cmdline,
+                dtb,
+            })
+        }
+        3 | 4 => {
+            let ramdisk_size = u32_at(data, 12)? as usize;
+            let header_size = u32_at(data, 20)? as usize;
+            let mut sections = Sections::new(data, V3_PAGE_SIZE, header_size);
+            Ok(BootImage {
+                header_version,
+                kernel: sections.next(kernel_size)?,
+                ramdisk: sections.next(ramdisk_size)?,
+                cmdline: str_at(data, 44, 1536)?,
+                dtb: None,
+            })
+        }
+        v => Err(Error::UnsupportedVersion(v)),
+    }
+}
+
+pub fn parse_vendor_boot(data: &[u8]) -> Result<VendorBootImage<'_>, Error> {
+    if !data.starts_with(VENDOR_BOOT_MAGIC) {
+        return Err(Error::BadMagic);
+    }
+    let header_version = u32_at(data, 8)?;
+    if !(3..=4).contains(&header_version) {
+        return Err(Error::UnsupportedVersion(header_version));
+    }
+    let page = u32_at(data, 12)? as usize;
+    if page == 0 || !page.is_power_of_two() {
+        return Err(Error::Truncated);
+    }
+    let ramdisk_size = u32_at(data, 24)? as usize;
+    let cmdline = str_at(data, 28, 2048)?;
+    let header_size = u32_at(data, 2096)? as usize;
+    let dtb_size = u32_at(data, 2100)? as usize;
+
+    let mut sections = Sections::new(data, page, header_size);
+    let ramdisk = sections.next(ramdisk_size)?;
+    let dtb = Some(sections.next(dtb_size)?).filter(|d| !d.is_empty());
+    let bootconfig = if header_version == 4 {
+        sections.next(u32_at(data, 2112)? as usize)?;
+        sections.next(u32_at(data, 2124)? as usize)?
+    } else {
+        &[]
+    };
+    Ok(VendorBootImage {
+        header_version,
+        ramdisk,
+        cmdline,
+        dtb,
+        bootconfig,
+    })
+}
+
+/// Append `params` to `ramdisk` in the kernel's bootconfig format: the
+/// parameters, their size and checksum, then the `#BOOTCONFIG` magic.
+fn append_bootconfig(ramdisk: &mut Vec<u8>, params: &[u8]) {
+    let checksum = params.iter().fold(0u32, |s, &b| s.wrapping_add(b as u32));
+    ramdisk.extend_from_slice(params);
+    ramdisk.extend_from_slice(&(params.len() as u32).to_le_bytes());
+    ramdisk.extend_from_slice(&checksum.to_le_bytes());
+    ramdisk.extend_from_slice(BOOTCONFIG_TRAILER);
+}
+
+/// Merge a boot image with its vendor_boot counterpart the way the Android
+/// bootloader does: vendor ramdisk first, then the generic one, with any
+/// bootconfig appended, and the vendor command line before the boot one.
+pub fn combine<'a>(boot: &BootImage<'a>, vendor: Option<&VendorBootImage<'a>>) -> Combined<'a> {
+    let mut ramdisk = Vec::new();
+    let mut cmdline = boot.cmdline.clone();
+    let mut dtb = boot.dtb;
+    if let Some(v) = vendor {
+        ramdisk.extend_from_slice(v.ramdisk);
+        cmdline = join_cmdline(&v.cmdline, &boot.cmdline);
+        dtb = v.dtb.or(dtb);
+    }
+    ramdisk.extend_from_slice(boot.ramdisk);
+    if let Some(v) = vendor.filter(|v| !v.bootconfig.is_empty()) {
+        append_bootconfig(&mut ramdisk, v.bootconfig);
+        cmdline = join_cmdline(&cmdline, "bootconfig");
+    }
+    Combined {
+        kernel: boot.kernel,
+        ramdisk,
+        cmdline,
+        dtb,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn put(buf: &mut [u8], off: usize, v: u32) {
+        buf[off..off + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn pad(buf: &mut Vec<u8>, page: usize) {
+        buf.resize(buf.len().next_multiple_of(page), 0);
+    }
+
+    #[test]
+    fn parses_v2_with_dtb() {
+        let page = 2048;
+        let mut img = vec![0u8; page];
+        img[..8].copy_from_slice(BOOT_MAGIC);
+        put(&mut img, 8, 3);
+        put(&mut img, 16, 2);
+        put(&mut img, 36, page as u32);
+        put(&mut img, 40, 2);
+        img[64..72].copy_from_slice(b"quiet ro");
+        img[608..617].copy_from_slice(b"loglevel=");
+        put(&mut img, 1648, 4);
+        img.extend_from_slice(b"KRN");
+        pad(&mut img, page);
+        img.extend_from_slice(b"RD");
+        pad(&mut img, page);
+        img.extend_from_slice(b"DTB!");
+
+        let boot = parse_boot(&img).unwrap();
+        assert_eq!(boot.kernel, b"KRN");
+        assert_eq!(boot.ramdisk, b"RD");
+        assert_eq!(boot.cmdline, "quiet ro loglevel=");
+        assert_eq!(boot.dtb, Some(&b"DTB!"[..]));
+    }
+
+    #[test]
+    fn combines_v4_with_vendor_boot() {
+        let mut img = vec![0u8; V3_PAGE_SIZE];
+        img[..8].copy_from_slice(BOOT_MAGIC);
+        put(&mut img, 8, 1);
+        put(&mut img, 12, 1);
+        put(&mut img, 20, 1584);
+        put(&mut img, 40, 4);
+        img[44..48].copy_from_slice(b"boot");
+        img.push(b'K');
+        pad(&mut img, V3_PAGE_SIZE);
+        img.push(b'G');
+
+        let page = 2048;
+        let mut vnd = vec![0u8; page * 2];
+        vnd[..8].copy_from_slice(VENDOR_BOOT_MAGIC);
+        put(&mut vnd, 8, 4);
+        put(&mut vnd, 12, page as u32);
+        put(&mut vnd, 24, 1);
+        vnd[28..34].copy_from_slice(b"vendor");
+        put(&mut vnd, 2096, 2128);
+        put(&mut vnd, 2100, 2);
+        put(&mut vnd, 2112, 3);
+        put(&mut vnd, 2124, 4);
+        vnd.push(b'V');
+        pad(&mut vnd, page);
+        vnd.extend_from_slice(b"DT");
+        pad(&mut vnd, page);
+        vnd.extend_from_slice(b"tbl");
+        pad(&mut vnd, page);
+        vnd.extend_from_slice(b"a=1\n");
+
+        let boot = parse_boot(&img).unwrap();
+        let vendor = parse_vendor_boot(&vnd).unwrap();
+        let c = combine(&boot, Some(&vendor));
+        assert_eq!(c.kernel, b"K");
+        assert_eq!(c.cmdline, "vendor boot bootconfig");
+        assert_eq!(c.dtb, Some(&b"DT"[..]));
+
+        let mut expected = Vec::from(&b"VGa=1\n"[..]);
+        expected.extend_from_slice(&4u32.to_le_bytes());
+        expected
+            .extend_from_slice(&(b"a=1\n".iter().map(|&b| b as u32).sum::<u32>()).to_le_bytes());
+        expected.extend_from_slice(BOOTCONFIG_TRAILER);
+        assert_eq!(c.ramdisk, expected);
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        assert_eq!(parse_boot(b"NOTANDROID").unwrap_err(), Error::BadMagic);
+        let mut img = vec![0u8; 4096];
+        img[..8].copy_from_slice(BOOT_MAGIC);
+        put(&mut img, 40, 9);
+        assert_eq!(parse_boot(&img).unwrap_err(), Error::UnsupportedVersion(9));
+        put(&mut img, 40, 3);
+        put(&mut img, 8, 100);
+        put(&mut img, 20, 1584);
+        assert_eq!(parse_boot(&img).unwrap_err(), Error::Truncated);
+    }
+}
@@ -0,0 +1,360 @@
+//! The Windows Boot Configuration Data store (`\EFI\Microsoft\Boot\BCD`),
+//! read far enough to name the installations `bootmgfw.efi` offers and to
+//! tell whether it is about to resume one from hibernation.
+//!
+//! The store is a registry hive: a 4 KiB `regf` header, then cells whose
+//! offsets count from the end of that header. Each cell starts with its
+//! size, negative while allocated. Objects live under `Objects\{guid}`,
+//! their settings under `Elements\<type>` in a value named `Element`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The Windows Boot Manager object.
+pub const BOOTMGR: &str = "{9dea862c-5cdd-4e70-acc1-f32b344d4795}";
+
+/// BcdLibraryString_Description.
+const DESCRIPTION: &str = "12000004";
+/// BcdBootMgrObject_DefaultObject.
+const DEFAULT_OBJECT: &str = "23000003";
+/// BcdBootMgrObjectList_DisplayOrder.
+const DISPLAY_ORDER: &str = "24000001";
+/// BcdBootMgrBoolean_AttemptResume, set while Windows is hibernated,
+/// including after a Fast Startup shutdown.
+const ATTEMPT_RESUME: &str = "26000006";
+
+const HIVE_BINS: usize = 0x1000;
+/// `nk` flag: the key name is stored as Latin-1 rather than UTF-16.
+const KEY_COMP_NAME: u16 = 0x20;
+/// `vk` flag: the value name is stored as Latin-1 rather than UTF-16.
+const VALUE_COMP_NAME: u16 = 0x01;
+/// Set in a value's data size when the data sits in the offset field.
+const DATA_INLINE: u32 = 0x8000_0000;
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn name(data: &[u8], compressed: bool) -> String {
+    if compressed {
+        data.iter().map(|&b| char::from(b)).collect()
+    } else {
+        utf16(data)
+    }
+}
+
+struct Hive<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Hive<'a> {
+    /// Contents of the allocated cell at `offset`.
+    fn cell(&self, offset: u32) -> Option<&'a [u8]> {
+        let at = HIVE_BINS.checked_add(offset as usize)?;
+        let size = u32_at(self.data, at)? as i32;
+        if size >= 0 {
+            return None;
+        }
+        self.data.get(at + 4..at + size.unsigned_abs() as usize)
+    }
+
+    fn root(&self) -> Option<Key<'a, '_>> {
+        if self.data.get(..4)? != b"regf" {
+            return None;
+        }
+        self.key(u32_at(self.data, 0x24)?)
+    }
+
+    fn key(&self, offset: u32) -> Option<Key<'a, '_>> {
+        let cell = self.cell(offset)?;
+        (cell.get(..2)? == b"nk").then_some(Key { hive: self, cell })
+    }
+
+    /// Key offsets of the subkey list at `offset`, following `ri` indexes.
+    fn subkey_offsets(&self, offset: u32, out: &mut Vec<u32>, depth: u32) -> Option<()> {
+        let list = self.cell(offset)?;
+        let count = u16_at(list, 2)? as usize;
+        match list.get(..2)? {
+            b"lf" | b"lh" => {
+                for i in 0..count {
+                    out.push(u32_at(list, 4 + i * 8)?);
+                }
+            }
+            b"li" => {
+                for i in 0..count {
+                    out.push(u32_at(list, 4 + i * 4)?);
+                }
+            }
+            b"ri" if depth == 0 => {
+                for i in 0..count {
+                    self.subkey_offsets(u32_at(list, 4 + i * 4)?, out, depth + 1)?;
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+struct Key<'a, 'h> {
+    hive: &'h Hive<'a>,
+    cell: &'a [u8],
+}
+
+impl<'a, 'h> Key<'a, 'h> {
+    fn name(&self) -> String {
+        let flags = u16_at(self.cell, 0x02).unwrap_or(0);
+        let len = u16_at(self.cell, 0x48).unwrap_or(0) as usize;
+        let raw = self.cell.get(0x4C..0x4C + len).unwrap_or(&[]);
+        name(raw, flags & KEY_COMP_NAME != 0)
+    }
+
+    fn subkey(&self, wanted: &str) -> Option<Key<'a, 'h>> {
+        let count = u32_at(self.cell, 0x14)?;
+        if count == 0 {
+            return None;
+        }
+        let mut offsets = Vec::new();
+        self.hive
+            .subkey_offsets(u32_at(self.cell, 0x1C)?, &mut offsets, 0)?;
+        offsets
+            .into_iter()
+            .filter_map(|o| self.hive.key(o))
+            .find(|k| k.name().eq_ignore_ascii_case(wanted))
+    }
+
+    /// Data of the value named `wanted`.
+    fn value(&self, wanted: &str) -> Option<&'a [u8]> {
+        let count = u32_at(self.cell, 0x24)? as usize;
+        let list = self.hive.cell(u32_at(self.cell, 0x28)?)?;
+        (0..count)
+            .filter_map(|i| self.hive.cell(u32_at(list, i * 4)?))
+            .filter(|vk| vk.get(..2) == Some(&b"vk"[..]))
+            .find(|vk| {
+                let len = u16_at(vk, 0x02).unwrap_or(0) as usize;
+                let flags = u16_at(vk, 0x10).unwrap_or(0);
+                let raw = vk.get(0x14..0x14 + len).unwrap_or(&[]);
+                name(raw, flags & VALUE_COMP_NAME != 0).eq_ignore_ascii_case(wanted)
+            })
+            .and_then(|vk| {
+                let size = u32_at(vk, 0x04)?;
+                if size & DATA_INLINE != 0 {
+                    let len = (size & !DATA_INLINE) as usize;
+                    return vk.get(0x08..0x08 + len.min(4));
+                }
+                self.hive.cell(u32_at(vk, 0x08)?)?.get(..size as usize)
+            })
+    }
+
+    /// The `Element` data of element `id` of this object.
+    fn element(&self, id: &str) -> Option<&'a [u8]> {
+        self.subkey("Elements")?.subkey(id)?.value("Element")
+    }
+}
+
+/// Descriptions of the Windows installations in the boot manager's
+/// display order, its default first. `None` if `data` is not a BCD store.
+pub fn installations(data: &[u8]) -> Option<Vec<String>> {
+    let hive = Hive { data };
+    let objects = hive.root()?.subkey("Objects")?;
+    let bootmgr = objects.subkey(BOOTMGR)?;
+
+    let mut order: Vec<String> = Vec::new();
+    if let Some(default) = bootmgr.element(DEFAULT_OBJECT) {
+        order.push(utf16(default));
+    }
+    if let Some(list) = bootmgr.element(DISPLAY_ORDER) {
+        let units: Vec<u16> = list
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        for guid in units.split(|&u| u == 0).filter(|g| !g.is_empty()) {
+            let guid = String::from_utf16_lossy(guid);
+            if !order.iter().any(|g| g.eq_ignore_ascii_case(&guid)) {
+                order.push(guid);
+            }
+        }
+    }
+
+    Some(
+        order
+            .iter()
+            .filter_map(|guid| objects.subkey(guid)?.element(DESCRIPTION))
+            .map(utf16)
+            .filter(|d| !d.is_empty())
+            .collect(),
+    )
+}
+
+/// Whether the boot manager will resume Windows from its hibernation file
+/// rather than start it afresh.
+pub fn attempts_resume(data: &[u8]) -> bool {
+    let hive = Hive { data };
+    hive.root()
+        .and_then(|root| {
+            root.subkey("Objects")?
+                .subkey(BOOTMGR)?
+                .element(ATTEMPT_RESUME)
+        })
+        .is_some_and(|flag| flag.iter().any(|&b| b != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REG_SZ: u32 = 1;
+    const REG_BINARY: u32 = 3;
+    const REG_MULTI_SZ: u32 = 7;
+    const WIN11: &str = "{1a2b3c4d-0000-0000-0000-000000000011}";
+    const WIN10: &str = "{1a2b3c4d-0000-0000-0000-000000000010}";
+
+    fn wide(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .chain([0])
+            .flat_map(|u| u.to_le_bytes())
+            .collect()
+    }
+
+    /// Cells of a hive under construction, after a 32-byte `hbin` header.
+    struct Builder {
+        bins: Vec<u8>,
+    }
+
+    impl Builder {
+        fn new() -> Self {
+            let mut bins = Vec::from(&b"hbin"[..]);
+            bins.resize(0x20, 0);
+            Builder { bins }
+        }
+
+        fn cell(&mut self, data: &[u8]) -> u32 {
+            let offset = self.bins.len() as u32;
+            let size = (data.len() + 4).next_multiple_of(8);
+            self.bins.extend_from_slice(&(-(size as i32)).to_le_bytes());
+            self.bins.extend_from_slice(data);
+            self.bins.resize(offset as usize + size, 0);
+            offset
+        }
+
+        fn value(&mut self, name: &str, ty: u32, data: &[u8]) -> u32 {
+            let offset = self.cell(data);
+            let mut vk = Vec::from(&b"vk"[..]);
+            vk.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            vk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            vk.extend_from_slice(&offset.to_le_bytes());
+            vk.extend_from_slice(&ty.to_le_bytes());
+            vk.extend_from_slice(&VALUE_COMP_NAME.to_le_bytes());
+            vk.extend_from_slice(&[0, 0]);
+            vk.extend_from_slice(name.as_bytes());
+            self.cell(&vk)
+        }
+
+        fn key(&mut self, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+            let mut nk = Vec::from(&b"nk"[..]);
+            nk.extend_from_slice(&KEY_COMP_NAME.to_le_bytes());
+            nk.resize(0x14, 0);
+            nk.extend_from_slice(&(subkeys.len() as u32).to_le_bytes());
+            nk.resize(0x1C, 0);
+            let mut lf = Vec::from(&b"lf"[..]);
+            lf.extend_from_slice(&(subkeys.len() as u16).to_le_bytes());
+            for &k in subkeys {
+                lf.extend_from_slice(&k.to_le_bytes());
+                lf.extend_from_slice(&[0; 4]);
+            }
+            let lf = self.cell(&lf);
+            nk.extend_from_slice(&lf.to_le_bytes());
+            nk.resize(0x24, 0);
+            nk.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            let list: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            let list = self.cell(&list);
+            nk.extend_from_slice(&list.to_le_bytes());
+            nk.resize(0x48, 0);
+            nk.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend_from_slice(&[0, 0]);
+            nk.extend_from_slice(name.as_bytes());
+            self.cell(&nk)
+        }
+
+        /// An object key with one element.
+        fn object(&mut self, guid: &str, element: &str, ty: u32, data: &[u8]) -> u32 {
+            let value = self.value("Element", ty, data);
+            let element = self.key(element, &[], &[value]);
+            let elements = self.key("Elements", &[element], &[]);
+            self.key(guid, &[elements], &[])
+        }
+
+        fn finish(self, root: u32) -> Vec<u8> {
+            let mut hive = Vec::from(&b"regf"[..]);
+            hive.resize(0x24, 0);
+            hive.extend_from_slice(&root.to_le_bytes());
+            hive.resize(HIVE_BINS, 0);
+            hive.extend_from_slice(&self.bins);
+            hive
+        }
+    }
+
+    fn store() -> Vec<u8> {
+        store_resuming(false)
+    }
+
+    fn store_resuming(resume: bool) -> Vec<u8> {
+        let mut b = Builder::new();
+        let win11 = b.object(WIN11, DESCRIPTION, REG_SZ, &wide("Windows 11 Pro"));
+        let win10 = b.object(WIN10, DESCRIPTION, REG_SZ, &wide("Windows 10 Home"));
+        let mut order = wide(WIN10);
+        order.extend(wide(WIN11));
+        order.extend_from_slice(&[0, 0]);
+        let display = b.value("Element", REG_MULTI_SZ, &order);
+        let display = b.key(DISPLAY_ORDER, &[], &[display]);
+        let default = b.value("Element", REG_SZ, &wide(WIN11));
+        let default = b.key(DEFAULT_OBJECT, &[], &[default]);
+        let mut bootmgr_elements = alloc::vec![display, default];
+        if resume {
+            let flag = b.value("Element", REG_BINARY, &[1]);
+            bootmgr_elements.push(b.key(ATTEMPT_RESUME, &[], &[flag]));
+        }
+        let elements = b.key("Elements", &bootmgr_elements, &[]);
+        let bootmgr = b.key(BOOTMGR, &[elements], &[]);
+        let objects = b.key("Objects", &[bootmgr, win10, win11], &[]);
+        let root = b.key("NewStoreRoot", &[objects], &[]);
+        b.finish(root)
+    }
+
+    #[test]
+    fn lists_installations_default_first() {
+        assert_eq!(
+            installations(&store()).unwrap(),
+            ["Windows 11 Pro", "Windows 10 Home"]
+        );
+    }
+
+    #[test]
+    fn sees_pending_resume() {
+        assert!(!attempts_resume(&store()));
+        assert!(attempts_resume(&store_resuming(true)));
+        assert!(!attempts_resume(b"MZ"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(installations(b"MZ"), None);
+        let mut hive = store();
+        hive[0] = b'x';
+        assert_eq!(installations(&hive), None);
+        assert_eq!(installations(&store()[..HIVE_BINS + 0x40]), None);
+    }
+}
@@ -0,0 +1,111 @@
+//! Applying bsdiff patches in the uncompressed stream format written by
+//! the `bsdiff` crate and bsdiff 4.3's core: a sequence of control triples,
+//! each followed by its diff and extra bytes, with no header. Transport
+//! compression is left to HTTP.
+
+use alloc::vec::Vec;
+
+/// An 8-byte sign-magnitude little-endian integer, as bsdiff writes them.
+fn offtin(b: &[u8]) -> i64 {
+    let raw = u64::from_le_bytes(b.try_into().unwrap());
+    let magnitude = (raw & !(1 << 63)) as i64;
+    if raw & (1 << 63) != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Rebuild the new file from `old` and `patch`. `None` if the patch is
+/// malformed or the result would exceed `max` bytes.
+pub fn apply(old: &[u8], patch: &[u8], max: usize) -> Option<Vec<u8>> {
+    let mut new = Vec::new();
+    let mut rest = patch;
+    let mut old_pos: i64 = 0;
+
+    while !rest.is_empty() {
+        if rest.len() < 24 {
+            return None;
+        }
+        let add = usize::try_from(offtin(&rest[0..8])).ok()?;
+        let copy = usize::try_from(offtin(&rest[8..16])).ok()?;
+        let seek = offtin(&rest[16..24]);
+        rest = &rest[24..];
+
+        if add > rest.len() || new.len().checked_add(add)? > max {
+            return None;
+        }
+        for (i, &d) in rest[..add].iter().enumerate() {
+            // Bytes outside the old file are taken as zero, like bspatch.
+            let o = old_pos
+                .checked_add(i as i64)
+                .and_then(|p| usize::try_from(p).ok())
+                .and_then(|p| old.get(p))
+                .copied()
+                .unwrap_or(0);
+            new.push(o.wrapping_add(d));
+        }
+        rest = &rest[add..];
+
+        if copy > rest.len() || new.len() + copy > max {
+            return None;
+        }
+        new.extend_from_slice(&rest[..copy]);
+        rest = &rest[copy..];
+
+        old_pos = old_pos.checked_add(add as i64)?.checked_add(seek)?;
+    }
+    Some(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(add: i64, copy: i64, seek: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        for v in [add, copy, seek] {
+            let mut raw = v.unsigned_abs();
+            if v < 0 {
+                raw |= 1 << 63;
+            }
+            out.extend_from_slice(&raw.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn applies_diff_extra_and_seek() {
+        let old = b"hello world";
+        let mut patch = control(5, 1, 1);
+        patch.extend_from_slice(&[0, 0, 0, 0, 2]); // "hellq"
+        patch.push(b'-');
+        patch.extend(control(5, 0, 0));
+        patch.extend_from_slice(&[0; 5]); // "world"
+        assert_eq!(apply(old, &patch, 64).unwrap(), b"hellq-world");
+    }
+
+    #[test]
+    fn negative_seek_rereads_old_bytes() {
+        let mut patch = control(2, 0, -2);
+        patch.extend_from_slice(&[0, 0]);
+        patch.extend(control(2, 0, 0));
+        patch.extend_from_slice(&[0, 0]);
+        assert_eq!(apply(b"ab", &patch, 64).unwrap(), b"abab");
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_patches() {
+        let mut patch = control(4, 0, 0);
+        patch.extend_from_slice(&[0, 0]);
+        assert!(apply(b"abcd", &patch, 64).is_none());
+        patch.extend_from_slice(&[0, 0]);
+        assert!(apply(b"abcd", &patch, 3).is_none());
+        assert!(apply(b"abcd", &patch[..10], 64).is_none());
+
+        let mut patch = control(0, 0, i64::MAX);
+        patch.extend(control(2, 0, 0));
+        patch.extend_from_slice(&[0, 0]);
+        assert!(apply(b"abcd", &patch, 64).is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! The x86 Linux setup header of a bzImage (Documentation/arch/x86/boot.rst):
+//! the version string and boot protocol shown before booting, and whether
+//! the kernel can be started through its EFI stub at all.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::validate::Severity;
+
+/// The first boot protocol with an EFI stub (Linux 3.3).
+const MIN_PROTOCOL: u16 = 0x020B;
+/// The first release whose EFI stub asks for the initrd over LoadFile2.
+const LOAD_FILE2_INITRD: (u32, u32) = (5, 8);
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+pub struct Header<'a> {
+    /// Boot protocol version, major in the high byte.
+    pub protocol: u16,
+    /// The `kernel_version` string, e.g. `6.1.0-13-amd64 (builder@host) #1 SMP ...`.
+    pub version: Option<&'a str>,
+    /// Whether the image is also a PE/COFF file, i.e. has an EFI stub.
+    pub efi_stub: bool,
+}
+
+impl<'a> Header<'a> {
+    /// The setup header of `image`, or `None` when it is not a bzImage
+    /// (an arm64 `Image`, say, which has no such header).
+    pub fn parse(image: &'a [u8]) -> Option<Self> {
+        if u16_at(image, 0x1FE)? != 0xAA55 || image.get(0x202..0x206)? != b"HdrS" {
+            return None;
+        }
+        let protocol = u16_at(image, 0x206)?;
+        let version = match u16_at(image, 0x20E)? {
+            0 => None,
+            at => {
+                let tail = image.get(at as usize + 0x200..)?;
+                let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+                core::str::from_utf8(&tail[..end.min(256)]).ok()
+            }
+        };
+        let efi_stub = image.starts_with(b"MZ")
+            && u32_at(image, 0x3C)
+                .and_then(|pe| image.get(pe as usize..pe as usize + 4))
+                .is_some_and(|sig| sig == b"PE\0\0");
+        Some(Header {
+            protocol,
+            version,
+            efi_stub,
+        })
+    }
+
+    /// The `major.minor` release at the start of the version string.
+    pub fn release(&self) -> Option<(u32, u32)> {
+        let mut parts = self.version?.split(|c: char| !c.is_ascii_digit());
+        Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+    }
+
+    /// `2.15` for protocol `0x020F`.
+    pub fn protocol_string(&self) -> String {
+        format!("{}.{:02}", self.protocol >> 8, self.protocol & 0xFF)
+    }
+
+    /// What stands in the way of starting this kernel through its EFI stub
+    /// with an initrd (`initrd`) handed over as alpheratz does.
+    pub fn problems(&self, initrd: bool) -> Vec<(Severity, String)> {
+        let mut problems = Vec::new();
+        if !self.efi_stub {
+            problems.push((
+                Severity::Error,
+                String::from("this kernel has no EFI stub; rebuild it with CONFIG_EFI_STUB=y"),
+            ));
+        } else if self.protocol < MIN_PROTOCOL {
+            problems.push((
+                Severity::Error,
+                format!(
+                    "boot protocol {} predates the EFI stub (2.11, Linux 3.3)",
+                    self.protocol_string()
+                ),
+            ));
+        }
+        if initrd && self.release().is_some_and(|r| r < LOAD_FILE2_INITRD) {
+            problems.push((
+                Severity::Warning,
+                String::from("kernels before 5.8 do not load the initrd over LoadFile2"),
+            ));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn bzimage(protocol: u16, version: &str, pe: bool) -> Vec<u8> {
+        let mut image = vec![0u8; 0x400];
+        if pe {
+            image[..2].copy_from_slice(b"MZ");
+            image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+            image[0x80..0x84].copy_from_slice(b"PE\0\0");
+        }
+        image[0x1FE..0x200].copy_from_slice(&0xAA55u16.to_le_bytes());
+        image[0x202..0x206].copy_from_slice(b"HdrS");
+        image[0x206..0x208].copy_from_slice(&protocol.to_le_bytes());
+        image[0x20E..0x210].copy_from_slice(&0x100u16.to_le_bytes());
+        image[0x300..0x300 + version.len()].copy_from_slice(version.as_bytes());
+        image
+    }
+
+    #[test]
+    fn reads_the_setup_header() {
+        let image = bzimage(
+            0x020F,
+            "6.1.0-13-amd64 (debian-kernel@lists.debian.org) #1",
+            true,
+        );
+        let header = Header::parse(&image).unwrap();
+        assert_eq!(header.protocol_string(), "2.15");
+        assert_eq!(
+            header.version,
+            Some("6.1.0-13-amd64 (debian-kernel@lists.debian.org) #1")
+        );
+        assert_eq!(header.release(), Some((6, 1)));
+        assert!(header.efi_stub);
+        assert!(header.problems(true).is_empty());
+        assert!(Header::parse(b"MZ\0\0").is_none());
+    }
+
+    #[test]
+    fn reports_kernels_that_cannot_boot() {
+        let image = bzimage(0x020F, "6.6.1", false);
+        let problems = Header::parse(&image).unwrap().problems(false);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].0, Severity::Error);
+        assert!(problems[0].1.contains("no EFI stub"));
+
+        let image = bzimage(0x020A, "3.2.0", true);
+        let problems = Header::parse(&image).unwrap().problems(true);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0].1,
+            "boot protocol 2.10 predates the EFI stub (2.11, Linux 3.3)"
+        );
+        assert_eq!(problems[1].0, Severity::Warning);
+
+        let image = bzimage(0x020D, "5.4.0", true);
+        let problems = Header::parse(&image).unwrap().problems(false);
+        assert!(problems.is_empty());
+    }
+}
@@ -0,0 +1,1249 @@
+#![allow(dead_code)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Deserialize;
+
+use crate::smbios::Machine;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Default {
+    Saved(SavedTag),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SavedTag;
+
+impl TryFrom<String> for SavedTag {
+    type Error = &'static str;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s == "@saved" {
+            Ok(SavedTag)
+        } else {
+            Err("expected \"@saved\"")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Canicula,
+    Linux,
+    Multiboot1,
+    /// Chainload another `.efi` application.
+    Efi,
+}
+
+impl core::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Protocol::Canicula => f.write_str("canicula"),
+            Protocol::Linux => f.write_str("linux"),
+            Protocol::Multiboot1 => f.write_str("multiboot1"),
+            Protocol::Efi => f.write_str("efi"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Manual,
+    Name,
+    Version,
+}
+
+/// What to do when a system is found hibernated, whose file systems another
+/// one could corrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hibernation {
+    Off,
+    /// Ask before booting any entry but the hibernated system's.
+    #[default]
+    Warn,
+    /// Also make the hibernated system's entry the default.
+    Select,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Reboot,
+    Shutdown,
+    Firmware,
+    ColdReset,
+    Exit,
+    /// Replace the loader with the image `[self_update]` points to and
+    /// reboot into it.
+    SelfUpdate,
+}
+
+/// What to do when an entry fails to resolve or boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    /// Wait for a key, then show the menu again.
+    #[default]
+    Menu,
+    /// Try the next bootable entry.
+    Next,
+    Reboot,
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    Kernel,
+    Initrd,
+    Cmdline,
+    /// U-Boot FIT image (`.itb`) supplying kernel, initrd and device tree.
+    Fit,
+    /// Android `boot.img` supplying kernel, ramdisk and command line.
+    #[serde(rename = "android-boot")]
+    AndroidBoot,
+    /// Android `vendor_boot.img` paired with a v3+ `android-boot`.
+    #[serde(rename = "vendor-boot")]
+    VendorBoot,
+    /// `System.map` handed to Canicula kernels for symbolized backtraces.
+    Symbols,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMethod {
+    Esp,
+    Https,
+    Inline,
+    /// TFTP through the PXE stack the loader was booted by.
+    Tftp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectStrategy {
+    Latest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkType {
+    Dhcp,
+    /// Fixed `address`/`netmask`, with optional `gateway` and `dns`.
+    Static,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BootFile {
+    #[serde(rename = "type")]
+    pub file_type: FileType,
+    pub search: SearchMethod,
+    pub file: Option<String>,
+    pub content: Option<String>,
+    pub select: Option<SelectStrategy>,
+    pub max_size: Option<usize>,
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Expected SHA-256 (hex) of the file as read or downloaded, before
+    /// decryption. Pinned HTTPS files are kept in the [`Store`].
+    pub sha256: Option<String>,
+    /// URL of a bsdiff patch from the copy last stored for this file,
+    /// whose SHA-256 is substituted for `${from}`.
+    pub delta: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Identity {
+    pub hostname: Option<String>,
+    pub uuid: Option<String>,
+    pub mac: Option<String>,
+    pub token: Option<String>,
+}
+
+/// Hardware an entry is limited to: every field set must equal the
+/// machine's SMBIOS string, or start with it when it ends in `*`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct When {
+    pub smbios_vendor: Option<String>,
+    pub smbios_product: Option<String>,
+    pub smbios_family: Option<String>,
+    pub smbios_board_vendor: Option<String>,
+    pub smbios_board_product: Option<String>,
+}
+
+impl When {
+    pub fn matches(&self, machine: &Machine) -> bool {
+        fn matches(pattern: &Option<String>, value: &Option<String>) -> bool {
+            let Some(pattern) = pattern else {
+                return true;
+            };
+            let value = value.as_deref().unwrap_or("");
+            match pattern.strip_suffix('*') {
+                Some(prefix) => value.starts_with(prefix),
+                None => value == pattern,
+            }
+        }
+        matches(&self.smbios_vendor, &machine.vendor)
+            && matches(&self.smbios_product, &machine.product)
+            && matches(&self.smbios_family, &machine.family)
+            && matches(&self.smbios_board_vendor, &machine.board_vendor)
+            && matches(&self.smbios_board_product, &machine.board_product)
+    }
+}
+
+impl Identity {
+    /// Layer `over` on top of `self`: every field set in `over` wins.
+    pub fn merged(&self, over: &Identity) -> Identity {
+        Identity {
+            hostname: over.hostname.clone().or_else(|| self.hostname.clone()),
+            uuid: over.uuid.clone().or_else(|| self.uuid.clone()),
+            mac: over.mac.clone().or_else(|| self.mac.clone()),
+            token: over.token.clone().or_else(|| self.token.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Network {
+    pub bind: Option<String>,
+    #[serde(rename = "type")]
+    pub network_type: Option<NetworkType>,
+    pub address: Option<String>,
+    pub netmask: Option<String>,
+    pub gateway: Option<String>,
+    #[serde(default)]
+    pub dns: Vec<String>,
+    pub link_timeout_secs: Option<usize>,
+    /// DHCP tries before giving up on the interface.
+    pub dhcp_attempts: Option<u32>,
+    /// Wait for a lease on the first DHCP try; each retry waits twice as long.
+    pub dhcp_timeout_secs: Option<usize>,
+    pub vlan: Option<u16>,
+    pub assume_time: Option<String>,
+    pub wifi: Option<Wifi>,
+    /// How long to wait for a TCP connection to a server before its first
+    /// request; 0 skips the check.
+    pub probe_timeout_secs: Option<u64>,
+    /// DNS-over-HTTPS endpoint, by IP address, for hosts firmware DNS
+    /// fails to resolve.
+    pub doh: Option<String>,
+    /// Replaces the `alpheratz/<version> (<arch>; <uuid>)` User-Agent.
+    pub user_agent: Option<String>,
+    /// Extra request headers, each sent as `X-Alpheratz-<name>`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Wifi {
+    pub ssid: String,
+    pub psk: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Iscsi {
+    pub initiator: Option<String>,
+    pub target: String,
+    /// The LUN's first level, as firmware setup takes it; at most 0xFFFF.
+    pub lun: Option<u64>,
+    /// Target address, `ip` or `ip:port`. When set, the firmware's first
+    /// iSCSI attempt is configured from this section; otherwise it must
+    /// have been set up in firmware setup.
+    pub portal: Option<String>,
+    pub chap_user: Option<String>,
+    pub chap_secret: Option<String>,
+    /// The target's credentials, for mutual CHAP.
+    pub reverse_chap_user: Option<String>,
+    pub reverse_chap_secret: Option<String>,
+}
+
+/// Well-known iSCSI port, used when `portal` has none.
+pub const ISCSI_PORT: u16 = 3260;
+
+impl Iscsi {
+    /// Address and port of `portal`.
+    pub fn portal_address(&self) -> Option<([u8; 4], u16)> {
+        let portal = self.portal.as_deref()?;
+        let (ip, port) = match portal.rsplit_once(':') {
+            Some((ip, port)) => (ip, port.parse().ok()?),
+            None => (portal, ISCSI_PORT),
+        };
+        Some((crate::vars::parse_ipv4(ip)?, port))
+    }
+
+    /// The 8-byte SCSI LUN as it appears in an iSCSI device path node:
+    /// `lun` as the first 16-bit level, big-endian, the rest zero.
+    pub fn lun_bytes(&self) -> [u8; 8] {
+        let mut out = [0; 8];
+        out[..2].copy_from_slice(&(self.lun.unwrap_or(0) as u16).to_be_bytes());
+        out
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Storage {
+    pub iscsi: Option<Iscsi>,
+}
+
+/// Content-addressed copies of pinned downloads under
+/// `\EFI\alpheratz\store`, one file per SHA-256.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Store {
+    /// Bytes the store may occupy; the oldest files are evicted past it.
+    pub max_size: usize,
+}
+
+/// UEFI memory types for what the loader leaves behind for a Canicula
+/// kernel, so it can tell what to reclaim once consumed from what to keep.
+/// Unset fields use LoaderData.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub struct MemoryTypes {
+    /// Kernel image, stack and the copied env, symbol and attribute blocks.
+    pub artifacts: Option<u32>,
+    pub page_tables: Option<u32>,
+}
+
+/// First memory type outside the UEFI-defined range (the OEM range).
+pub const MEMORY_TYPE_OEM_MIN: u32 = 0x7000_0000;
+/// Memory type of the pages holding a Canicula kernel's `BootInfo`.
+pub const MEMORY_TYPE_BOOT_INFO: u32 = 0x8000_0000;
+/// Memory type of a Canicula kernel's initrd, free once it is unpacked.
+pub const MEMORY_TYPE_INITRD: u32 = 0x8000_0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VarAttribute {
+    NonVolatile,
+    BootserviceAccess,
+    RuntimeAccess,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetVar {
+    pub name: String,
+    /// Vendor GUID, or `global` for EFI_GLOBAL_VARIABLE.
+    pub vendor: String,
+    #[serde(default = "default_var_attributes")]
+    pub attributes: Vec<VarAttribute>,
+    /// Variable contents as hex bytes.
+    #[serde(default)]
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NfsRoot {
+    /// NFS server address; defaults to the DHCP server.
+    pub server: Option<String>,
+    pub path: String,
+    pub options: Option<String>,
+    /// Kernel interface name for `ip=`; empty lets the kernel pick.
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub sort_key: Option<String>,
+    /// Shown with `os` and `version` on a line under the menu while the
+    /// entry is selected.
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub os: Option<String>,
+    /// Only offer the entry on matching hardware.
+    pub when: Option<When>,
+    pub protocol: Option<Protocol>,
+    pub action: Option<Action>,
+    pub identity: Option<Identity>,
+    pub max_size: Option<usize>,
+    pub decryption_key: Option<String>,
+    /// `sha256:<hex>` of the passphrase asked before booting the entry;
+    /// unsalted, so only as strong as the passphrase itself.
+    pub password_hash: Option<String>,
+    pub auto_console: Option<bool>,
+    /// Hide all loader output on screen, keeping it on serial only.
+    #[serde(default)]
+    pub quiet: bool,
+    /// Failure policy; unset returns to the menu, except that an
+    /// automatically booted entry that times out falls through to the next.
+    pub on_error: Option<OnError>,
+    pub nfsroot: Option<NfsRoot>,
+    /// Directory a chainloaded image is presented as loaded from, so it
+    /// finds its own files (e.g. `\EFI\VMware` for `mboot.efi`).
+    pub workdir: Option<String>,
+    #[serde(default)]
+    pub setvar: Vec<SetVar>,
+    /// Key/value pairs handed to Canicula kernels, see [`crate::env`].
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Without a `symbols` file, build one from the kernel's ELF symbol
+    /// table for Canicula kernels, see [`crate::symbols`].
+    #[serde(default)]
+    pub symbols: bool,
+    /// Stop before jumping to a Canicula or Multiboot kernel, print its
+    /// addresses to serial and wait there for a debugger.
+    #[serde(default)]
+    pub debug_halt: bool,
+    /// Hand Canicula kernels the firmware's ACPI RSDP rather than a copy
+    /// (with its XSDT) in loader memory.
+    #[serde(default)]
+    pub original_rsdp: bool,
+    /// Listed inside the submenu of this name rather than on the top-level
+    /// menu; set on every entry merged from a `remote_menu`.
+    pub submenu: Option<String>,
+    #[serde(default)]
+    pub files: Vec<BootFile>,
+}
+
+impl Entry {
+    /// Whether the entry reads from an iSCSI volume, named by a `devpath:`
+    /// file or workdir through an `iSCSI(...)` node.
+    pub fn uses_iscsi(&self) -> bool {
+        let on_iscsi = |p: &str| p.starts_with("devpath:") && p.contains("iSCSI(");
+        self.workdir.as_deref().is_some_and(on_iscsi)
+            || self
+                .files
+                .iter()
+                .any(|f| f.file.as_deref().is_some_and(on_iscsi))
+    }
+
+    /// Path a chainloaded image is presented as loaded from: the kernel
+    /// file's name inside `workdir`.
+    pub fn image_path(&self) -> Option<String> {
+        let dir = self.workdir.as_deref()?;
+        let name = self
+            .files
+            .iter()
+            .find(|f| f.file_type == FileType::Kernel)
+            .and_then(|f| f.file.as_deref())
+            .map(|f| f.split(['?', '#']).next().unwrap_or(f))
+            .and_then(|f| f.rsplit(['\\', '/']).next())
+            .filter(|n| !n.is_empty())
+            .unwrap_or("image.efi");
+        let mut path = String::from(dir.trim_end_matches(['\\', '/']));
+        path.push('\\');
+        path.push_str(name);
+        Some(path)
+    }
+}
+
+/// A menu of entries published at `url`, fetched at startup and listed in
+/// the submenu `title`. The last copy fetched is kept on the ESP and used
+/// while the URL cannot be reached.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteMenu {
+    pub url: String,
+    #[serde(default = "default_remote_menu_title")]
+    pub title: String,
+}
+
+/// A GRUB environment block on the ESP, see [`crate::grubenv`], read for
+/// `default = "@saved"` and, with `write`, updated like GRUB would at boot.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrubEnv {
+    pub path: String,
+    #[serde(default)]
+    pub write: bool,
+}
+
+/// Where the `self-update` action downloads a new loader image from. The
+/// image must be Authenticode-signed and accepted by firmware; unless
+/// Secure Boot is enabled to check that signature, it must match `sha256`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelfUpdate {
+    pub url: String,
+    pub sha256: Option<String>,
+}
+
+/// The document served for a [`RemoteMenu`]: `[[entry]]` tables as in the
+/// main configuration, and nothing else.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RemoteMenuFile {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+/// A `#rrggbb` colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for Rgb {
+    type Error = &'static str;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|h| h.len() == 6 && h.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or("expected a colour like \"#rrggbb\"")?;
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Ok(Rgb(byte(0), byte(2), byte(4)))
+    }
+}
+
+/// Screen resolution to switch to before the menu is drawn. The mode is
+/// kept for the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Video {
+    /// Leave the firmware's mode alone.
+    #[default]
+    Keep,
+    /// The mode with the most pixels.
+    Max,
+    /// A `WxH` mode, if the display offers it.
+    Size(u32, u32),
+}
+
+impl TryFrom<String> for Video {
+    type Error = &'static str;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "keep" => Ok(Video::Keep),
+            "max" => Ok(Video::Max),
+            _ => s
+                .split_once('x')
+                .and_then(|(w, h)| Some(Video::Size(w.parse().ok()?, h.parse().ok()?)))
+                .filter(|v| !matches!(v, Video::Size(0, _) | Video::Size(_, 0)))
+                .ok_or("expected \"keep\", \"max\" or a size like \"1920x1080\""),
+        }
+    }
+}
+
+/// Controllers a loaded driver is connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Connect {
+    /// PCI network controllers.
+    Nic,
+    /// Every handle, recursively.
+    #[default]
+    All,
+    /// None; for drivers that install protocols rather than bind devices.
+    None,
+}
+
+/// A `drivers` item: a driver `.efi` file or a directory of them, written
+/// as a plain path or as `{ path, connect, order }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "DriverSpec")]
+pub struct Driver {
+    pub path: String,
+    pub connect: Connect,
+    /// Drivers load in ascending `order`, then as listed.
+    pub order: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DriverSpec {
+    Path(String),
+    Table(DriverTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DriverTable {
+    path: String,
+    #[serde(default)]
+    connect: Connect,
+    #[serde(default)]
+    order: i32,
+}
+
+impl From<DriverSpec> for Driver {
+    fn from(spec: DriverSpec) -> Self {
+        match spec {
+            DriverSpec::Path(path) => Driver {
+                path,
+                connect: Connect::All,
+                order: 0,
+            },
+            DriverSpec::Table(t) => Driver {
+                path: t.path,
+                connect: t.connect,
+                order: t.order,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Theme {
+    /// Draw a progress bar through the boot stages for `quiet` entries.
+    #[serde(default)]
+    pub splash: bool,
+    #[serde(default = "default_splash_bar")]
+    pub bar: Rgb,
+    #[serde(default = "default_splash_background")]
+    pub background: Rgb,
+    /// Draw the menu in white on black, with highlights inverted.
+    #[serde(default)]
+    pub high_contrast: bool,
+    /// Draw the menu on the framebuffer at twice the usual text size.
+    #[serde(default)]
+    pub large_text: bool,
+}
+
+impl core::default::Default for Theme {
+    fn default() -> Self {
+        Theme {
+            splash: false,
+            bar: default_splash_bar(),
+            background: default_splash_background(),
+            high_contrast: false,
+            large_text: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    #[serde(default = "default_index_zero")]
+    pub default: Default,
+    #[serde(default = "default_timeout")]
+    pub timeout: usize,
+    #[serde(default = "default_timeout_resume_secs")]
+    pub timeout_resume_secs: usize,
+    #[serde(default)]
+    pub sort: SortOrder,
+    pub resolve_timeout: Option<usize>,
+    #[serde(default)]
+    pub shutdown: bool,
+    #[serde(default)]
+    pub firmware: bool,
+    #[serde(default)]
+    pub require_secure_boot: bool,
+    #[serde(default)]
+    pub auto_console: bool,
+    pub serial_console: Option<String>,
+    /// Also draw the menu on the serial port with ANSI escape sequences.
+    #[serde(default)]
+    pub serial_menu: bool,
+    #[serde(default)]
+    pub backgrounds: Vec<String>,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub video: Video,
+    /// Beep when the menu appears, the selection moves and a boot fails.
+    #[serde(default)]
+    pub beep: bool,
+    #[serde(default)]
+    pub drivers: Vec<Driver>,
+    /// ESP path of a [`crate::manifest`] signed by a `db` certificate in
+    /// `<path>.p7s`; when set, only drivers it lists with a matching digest
+    /// are loaded.
+    pub driver_manifest: Option<String>,
+    pub audit_log: Option<String>,
+    pub decryption_key: Option<String>,
+    pub identity: Option<Identity>,
+    pub network: Option<Network>,
+    pub storage: Option<Storage>,
+    pub store: Option<Store>,
+    #[serde(default)]
+    pub memory: MemoryTypes,
+    /// URLs of iPXE scripts whose `boot`s are added as entries, see
+    /// [`crate::ipxe`].
+    #[serde(default)]
+    pub ipxe_scripts: Vec<String>,
+    pub remote_menu: Option<RemoteMenu>,
+    pub self_update: Option<SelfUpdate>,
+    pub grubenv: Option<GrubEnv>,
+    #[serde(default)]
+    pub hibernation: Hibernation,
+    #[serde(default)]
+    pub entry: Vec<Entry>,
+}
+
+/// Compare two strings treating runs of digits as numbers, so that
+/// `6.10` sorts after `6.9`.
+fn version_cmp(a: &str, b: &str) -> core::cmp::Ordering {
+    use core::cmp::Ordering;
+
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let na = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let nb = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (da, db) = (&a[..na], &b[..nb]);
+                let da = &da[da.iter().take_while(|&&c| c == b'0').count()..];
+                let db = &db[db.iter().take_while(|&&c| c == b'0').count()..];
+                let ord = da.len().cmp(&db.len()).then_with(|| da.cmp(db));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a = &a[na..];
+                b = &b[nb..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn sort<T>(items: &mut [T], order: SortOrder, entry: fn(&T) -> &Entry) {
+    fn key(e: &Entry) -> &str {
+        e.sort_key.as_deref().unwrap_or(&e.name)
+    }
+
+    match order {
+        SortOrder::Manual => {}
+        SortOrder::Name => items.sort_by(|a, b| {
+            key(entry(a))
+                .to_ascii_lowercase()
+                .cmp(&key(entry(b)).to_ascii_lowercase())
+        }),
+        SortOrder::Version => items.sort_by(|a, b| version_cmp(key(entry(b)), key(entry(a)))),
+    }
+}
+
+fn default_index_zero() -> Default {
+    Default::Index(0)
+}
+
+fn default_timeout() -> usize {
+    3
+}
+
+fn default_timeout_resume_secs() -> usize {
+    10
+}
+
+fn default_remote_menu_title() -> String {
+    String::from("Remote")
+}
+
+fn default_splash_bar() -> Rgb {
+    Rgb(0xff, 0xff, 0xff)
+}
+
+fn default_splash_background() -> Rgb {
+    Rgb(0, 0, 0)
+}
+
+fn default_var_attributes() -> Vec<VarAttribute> {
+    alloc::vec![VarAttribute::BootserviceAccess, VarAttribute::RuntimeAccess]
+}
+
+impl Config {
+    pub fn from_str(s: &str) -> Result<Config, toml::de::Error> {
+        let mut cfg: Config = toml::from_str(s)?;
+        cfg.sort_entries();
+        Ok(cfg)
+    }
+
+    /// Order entries according to `sort`, keyed by `sort_key` (falling back
+    /// to the entry name). `version` puts the newest first; the sort is
+    /// stable so equal keys keep their declared order. An index `default`
+    /// follows its entry to where it lands.
+    pub fn sort_entries(&mut self) {
+        let mut indexed: Vec<(usize, Entry)> = core::mem::take(&mut self.entry)
+            .into_iter()
+            .enumerate()
+            .collect();
+        sort(&mut indexed, self.sort, |(_, e)| e);
+        if let Default::Index(i) = self.default
+            && let Some(moved) = indexed.iter().position(|&(old, _)| old == i)
+        {
+            self.default = Default::Index(moved);
+        }
+        self.entry = indexed.into_iter().map(|(_, e)| e).collect();
+    }
+
+    /// Append the entries of a [`RemoteMenu`] document, sorted like the
+    /// configuration's own and placed in the submenu `title`. Returns how
+    /// many were added; a document that does not parse adds none.
+    pub fn merge_remote(&mut self, text: &str, title: &str) -> Result<usize, toml::de::Error> {
+        let mut remote: RemoteMenuFile = toml::from_str(text)?;
+        sort(&mut remote.entry, self.sort, |e| e);
+        for entry in &mut remote.entry {
+            entry.submenu = Some(String::from(title));
+        }
+        let added = remote.entry.len();
+        self.entry.append(&mut remote.entry);
+        Ok(added)
+    }
+
+    /// Give entries that repeat an earlier entry's name a ` (2)`, ` (3)`, …
+    /// suffix, so the menu and `@saved` can tell them apart.
+    pub fn disambiguate_names(&mut self) {
+        for i in 1..self.entry.len() {
+            let name = &self.entry[i].name;
+            if !self.entry[..i].iter().any(|e| &e.name == name) {
+                continue;
+            }
+            let unique = (2..)
+                .map(|n| alloc::format!("{} ({})", name, n))
+                .find(|candidate| self.entry.iter().all(|e| &e.name != candidate))
+                .unwrap();
+            self.entry[i].name = unique;
+        }
+    }
+
+    /// Drop the entries whose `when` does not match `machine`, keeping an
+    /// index `default` on the same entry (or the first, if it was dropped).
+    pub fn retain_matching(&mut self, machine: &Machine) {
+        let keep: Vec<bool> = self
+            .entry
+            .iter()
+            .map(|e| e.when.as_ref().is_none_or(|w| w.matches(machine)))
+            .collect();
+        self.retain_flagged(keep);
+    }
+
+    /// Drop the entries with neither `protocol` nor `action`, which
+    /// validation reports and which have nothing to run, keeping an index
+    /// `default` on the same entry (or the first, if it was dropped).
+    pub fn retain_runnable(&mut self) {
+        let keep: Vec<bool> = self
+            .entry
+            .iter()
+            .map(|e| e.protocol.is_some() || e.action.is_some())
+            .collect();
+        self.retain_flagged(keep);
+    }
+
+    fn retain_flagged(&mut self, keep: Vec<bool>) {
+        if let Default::Index(i) = self.default {
+            let kept_before = keep.iter().take(i).filter(|&&k| k).count();
+            let index = if keep.get(i) == Some(&true) {
+                kept_before
+            } else {
+                0
+            };
+            self.default = Default::Index(index);
+        }
+        let mut keep = keep.into_iter();
+        self.entry.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    /// Effective identity for `entry`: the entry's fields override the
+    /// global `[identity]` field by field.
+    pub fn identity_for(&self, entry: &Entry) -> Option<Identity> {
+        match (&self.identity, &entry.identity) {
+            (Some(g), Some(e)) => Some(g.merged(e)),
+            (g, e) => e.clone().or_else(|| g.clone()),
+        }
+    }
+
+    pub fn default_entry_index(&self) -> usize {
+        match &self.default {
+            Default::Index(i) => *i,
+            Default::Saved(_) => 0,
+        }
+    }
+}
+
+impl core::default::Default for Config {
+    fn default() -> Self {
+        Config {
+            default: Default::Index(0),
+            timeout: 3,
+            timeout_resume_secs: 10,
+            sort: SortOrder::Manual,
+            resolve_timeout: None,
+            shutdown: false,
+            firmware: false,
+            require_secure_boot: false,
+            auto_console: false,
+            serial_console: None,
+            serial_menu: false,
+            backgrounds: Vec::new(),
+            theme: Theme::default(),
+            video: Video::Keep,
+            beep: false,
+            drivers: Vec::new(),
+            driver_manifest: None,
+            audit_log: None,
+            decryption_key: None,
+            identity: None,
+            network: None,
+            storage: None,
+            store: None,
+            memory: MemoryTypes::default(),
+            ipxe_scripts: Vec::new(),
+            remote_menu: None,
+            self_update: None,
+            grubenv: None,
+            hibernation: Hibernation::default(),
+            entry: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cmp::Ordering;
+
+    fn names(cfg: &Config) -> Vec<&str> {
+        cfg.entry.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn version_cmp_compares_digit_runs_numerically() {
+        assert_eq!(version_cmp("6.10", "6.9"), Ordering::Greater);
+        assert_eq!(version_cmp("linux-5.4", "linux-5.04"), Ordering::Equal);
+        assert_eq!(version_cmp("6.1", "6.1-rc1"), Ordering::Less);
+        assert_eq!(version_cmp("abc", "abd"), Ordering::Less);
+    }
+
+    #[test]
+    fn defaults_for_empty_file() {
+        let cfg = Config::from_str("").unwrap();
+        assert_eq!(cfg.default_entry_index(), 0);
+        assert_eq!(cfg.timeout, 3);
+        assert_eq!(cfg.timeout_resume_secs, 10);
+        assert_eq!(cfg.sort, SortOrder::Manual);
+        assert!(cfg.entry.is_empty());
+    }
+
+    #[test]
+    fn disambiguates_duplicate_names() {
+        let mut cfg = Config::from_str(
+            r#"
+            [[entry]]
+            name = "Linux"
+            [[entry]]
+            name = "Linux (2)"
+            [[entry]]
+            name = "Linux"
+            [[entry]]
+            name = "Linux"
+            "#,
+        )
+        .unwrap();
+        cfg.disambiguate_names();
+        assert_eq!(
+            names(&cfg),
+            ["Linux", "Linux (2)", "Linux (3)", "Linux (4)"]
+        );
+    }
+
+    #[test]
+    fn parses_theme_colours() {
+        let cfg = Config::from_str("[theme]\nsplash = true\nbar = \"#3B82f6\"").unwrap();
+        assert!(cfg.theme.splash);
+        assert_eq!(cfg.theme.bar, Rgb(0x3b, 0x82, 0xf6));
+        assert_eq!(cfg.theme.background, Rgb(0, 0, 0));
+        assert!(Config::from_str("[theme]\nbar = \"3b82f6\"").is_err());
+        assert!(Config::from_str("[theme]\nbar = \"#3b82g6\"").is_err());
+    }
+
+    #[test]
+    fn parses_video_modes() {
+        assert_eq!(Config::from_str("").unwrap().video, Video::Keep);
+        assert_eq!(
+            Config::from_str("video = \"max\"").unwrap().video,
+            Video::Max
+        );
+        let cfg = Config::from_str("video = \"1920x1080\"").unwrap();
+        assert_eq!(cfg.video, Video::Size(1920, 1080));
+        assert!(Config::from_str("video = \"0x600\"").is_err());
+        assert!(Config::from_str("video = \"1024*768\"").is_err());
+    }
+
+    #[test]
+    fn parses_driver_items() {
+        let cfg = Config::from_str(
+            r#"
+            drivers = [
+                "\\EFI\\drivers",
+                { path = "\\EFI\\nic.efi", connect = "nic", order = -1 },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.drivers,
+            [
+                Driver {
+                    path: String::from("\\EFI\\drivers"),
+                    connect: Connect::All,
+                    order: 0,
+                },
+                Driver {
+                    path: String::from("\\EFI\\nic.efi"),
+                    connect: Connect::Nic,
+                    order: -1,
+                },
+            ]
+        );
+        assert!(Config::from_str("drivers = [{ path = \"x\", connect = \"usb\" }]").is_err());
+        assert!(Config::from_str("drivers = [{ path = \"x\", after = \"y\" }]").is_err());
+    }
+
+    #[test]
+    fn drops_entries_for_other_hardware() {
+        let mut cfg = Config::from_str(
+            r#"
+            default = 2
+
+            [[entry]]
+            name = "R650"
+            when = { smbios_product = "PowerEdge R650" }
+
+            [[entry]]
+            name = "R7xx"
+            when = { smbios_product = "PowerEdge R7*", smbios_board_vendor = "Dell Inc." }
+
+            [[entry]]
+            name = "Any"
+            "#,
+        )
+        .unwrap();
+        let machine = Machine {
+            product: Some(String::from("PowerEdge R750")),
+            board_vendor: Some(String::from("Dell Inc.")),
+            ..Machine::default()
+        };
+        cfg.retain_matching(&machine);
+        assert_eq!(names(&cfg), ["R7xx", "Any"]);
+        assert_eq!(cfg.default, Default::Index(1));
+        assert!(Config::from_str("[[entry]]\nname = \"A\"\nwhen = { model = \"x\" }").is_err());
+    }
+
+    #[test]
+    fn drops_entries_with_nothing_to_run() {
+        let mut cfg = Config::from_str(
+            r#"
+            default = 2
+
+            [[entry]]
+            name = "Typo"
+            protocl = "linux"
+
+            [[entry]]
+            name = "Reboot"
+            action = "reboot"
+
+            [[entry]]
+            name = "Linux"
+            protocol = "linux"
+            "#,
+        )
+        .unwrap();
+        cfg.retain_runnable();
+        assert_eq!(names(&cfg), ["Reboot", "Linux"]);
+        assert_eq!(cfg.default, Default::Index(1));
+    }
+
+    #[test]
+    fn parses_on_error_policy() {
+        let cfg = Config::from_str("[[entry]]\nname = \"A\"\non_error = \"reboot\"").unwrap();
+        assert_eq!(cfg.entry[0].on_error, Some(OnError::Reboot));
+        assert!(Config::from_str("[[entry]]\nname = \"A\"\non_error = \"retry\"").is_err());
+    }
+
+    #[test]
+    fn parses_saved_default_and_rejects_other_strings() {
+        let cfg = Config::from_str("default = \"@saved\"").unwrap();
+        assert_eq!(cfg.default, Default::Saved(SavedTag));
+        assert!(Config::from_str("default = \"latest\"").is_err());
+    }
+
+    #[test]
+    fn version_sort_puts_newest_first() {
+        let cfg = Config::from_str(
+            r#"
+            sort = "version"
+
+            [[entry]]
+            name = "Linux 6.9"
+
+            [[entry]]
+            name = "Linux 6.10"
+
+            [[entry]]
+            name = "Rescue"
+            sort_key = "Linux 0"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(names(&cfg), ["Linux 6.10", "Linux 6.9", "Rescue"]);
+    }
+
+    #[test]
+    fn name_sort_ignores_case() {
+        let cfg = Config::from_str(
+            r#"
+            sort = "name"
+            default = 1
+
+            [[entry]]
+            name = "beta"
+
+            [[entry]]
+            name = "Alpha"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(names(&cfg), ["Alpha", "beta"]);
+        assert_eq!(cfg.default, Default::Index(0));
+    }
+
+    #[test]
+    fn entry_identity_overrides_global_per_field() {
+        let cfg = Config::from_str(
+            r#"
+            [identity]
+            hostname = "global"
+            token = "secret"
+
+            [[entry]]
+            name = "a"
+            identity = { hostname = "entry" }
+
+            [[entry]]
+            name = "b"
+            "#,
+        )
+        .unwrap();
+        let a = cfg.identity_for(&cfg.entry[0]).unwrap();
+        assert_eq!(a.hostname.as_deref(), Some("entry"));
+        assert_eq!(a.token.as_deref(), Some("secret"));
+        let b = cfg.identity_for(&cfg.entry[1]).unwrap();
+        assert_eq!(b.hostname.as_deref(), Some("global"));
+    }
+
+    #[test]
+    fn merges_remote_entries_into_submenu() {
+        let mut cfg = Config::from_str(
+            r#"
+            sort = "name"
+
+            [remote_menu]
+            url = "https://boot.example.com/menu.toml"
+
+            [[entry]]
+            name = "Local"
+            "#,
+        )
+        .unwrap();
+        let title = cfg.remote_menu.as_ref().unwrap().title.clone();
+        assert_eq!(title, "Remote");
+        let added = cfg
+            .merge_remote(
+                r#"
+                [[entry]]
+                name = "Rescue"
+                protocol = "linux"
+
+                [[entry]]
+                name = "Installer"
+                protocol = "linux"
+                "#,
+                &title,
+            )
+            .unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(names(&cfg), ["Local", "Installer", "Rescue"]);
+        assert_eq!(cfg.entry[0].submenu, None);
+        assert_eq!(cfg.entry[1].submenu.as_deref(), Some("Remote"));
+        assert!(cfg.merge_remote("timeout = 0", &title).is_err());
+        assert_eq!(cfg.entry.len(), 3);
+    }
+
+    #[test]
+    fn setvar_attributes_default_to_bs_rt() {
+        let cfg = Config::from_str(
+            r#"
+            [[entry]]
+            name = "a"
+
+            [[entry.setvar]]
+            name = "Foo"
+            vendor = "global"
+            "#,
+        )
+        .unwrap();
+        let var = &cfg.entry[0].setvar[0];
+        assert_eq!(
+            var.attributes,
+            [VarAttribute::BootserviceAccess, VarAttribute::RuntimeAccess]
+        );
+        assert!(var.data.is_empty());
+    }
+
+    #[test]
+    fn reads_iscsi_portal_lun_and_volumes() {
+        let cfg = Config::from_str(
+            r#"
+            [storage.iscsi]
+            target = "iqn.2026-01.org.canicula:boot"
+            lun = 0x102
+            portal = "10.0.0.5:3261"
+
+            [[entry]]
+            name = "SAN"
+            files = [{ type = "kernel", search = "esp", file = "devpath:PciRoot(0x0)/Pci(0x3,0x0)/MAC(525400123456,0x1)/IPv4(0.0.0.0)/iSCSI(iqn.2026-01.org.canicula:boot,0x1,0x0,None,None,None,TCP)/HD(1,GPT,0)/\\vmlinuz" }]
+
+            [[entry]]
+            name = "Local"
+            files = [{ type = "kernel", search = "esp", file = "\\vmlinuz" }]
+            "#,
+        )
+        .unwrap();
+        let iscsi = cfg.storage.unwrap().iscsi.unwrap();
+        assert_eq!(iscsi.portal_address(), Some(([10, 0, 0, 5], 3261)));
+        assert_eq!(iscsi.lun_bytes(), [0x01, 0x02, 0, 0, 0, 0, 0, 0]);
+        assert!(cfg.entry[0].uses_iscsi());
+        assert!(!cfg.entry[1].uses_iscsi());
+    }
+
+    #[test]
+    fn places_chainloaded_image_in_workdir() {
+        let cfg = Config::from_str(
+            r#"
+            [[entry]]
+            name = "ESXi"
+            protocol = "efi"
+            workdir = "\\EFI\\VMware\\"
+            files = [{ type = "kernel", search = "https", file = "https://boot.example.com/esxi/mboot.efi?v=8" }]
+
+            [[entry]]
+            name = "Inline"
+            protocol = "efi"
+            workdir = "devpath:PciRoot(0x0)/Pci(0x1,0x1)/HD(1,GPT,0)/\\EFI\\tool"
+            files = [{ type = "kernel", search = "inline", content = "" }]
+
+            [[entry]]
+            name = "Plain"
+            protocol = "efi"
+            files = [{ type = "kernel", search = "esp", file = "\\EFI\\tool.efi" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.entry[0].image_path().as_deref(),
+            Some("\\EFI\\VMware\\mboot.efi")
+        );
+        assert_eq!(
+            cfg.entry[1].image_path().as_deref(),
+            Some("devpath:PciRoot(0x0)/Pci(0x1,0x1)/HD(1,GPT,0)/\\EFI\\tool\\image.efi")
+        );
+        assert_eq!(cfg.entry[2].image_path(), None);
+    }
+}
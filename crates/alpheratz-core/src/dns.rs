@@ -0,0 +1,180 @@
+//! DNS-over-HTTPS (RFC 8484) for `[network] doh`: A-record queries encoded
+//! for the `?dns=` GET parameter, their answers, and the URL host and port
+//! handling used to reach the address found.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::vars::ipv4_to_string;
+
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// A recursive query for the A records of `name`, or `None` when `name`
+/// is not a valid domain name.
+pub fn query(name: &str, id: u16) -> Option<Vec<u8>> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    let mut msg = Vec::with_capacity(18 + name.len());
+    msg.extend_from_slice(&id.to_be_bytes());
+    // RD set; one question.
+    msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    if msg.len() - 12 > 255 {
+        return None;
+    }
+    msg.extend_from_slice(&TYPE_A.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Some(msg)
+}
+
+/// Unpadded base64url, as RFC 8484 wants the `dns` parameter.
+pub fn base64url(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Offset just past the (possibly compressed) name at `at`.
+fn skip_name(msg: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(at)?;
+        match len {
+            0 => return Some(at + 1),
+            l if l & 0xC0 == 0xC0 => return Some(at + 2),
+            l => at += 1 + l as usize,
+        }
+    }
+}
+
+/// The first A record in response `msg` to query `id`, following any CNAME
+/// records the server put before it.
+pub fn answer(msg: &[u8], id: u16) -> Option<[u8; 4]> {
+    let flags = u16_at(msg, 2)?;
+    // A response, without an error code.
+    if u16_at(msg, 0)? != id || flags & 0x8000 == 0 || flags & 0x000F != 0 {
+        return None;
+    }
+    let questions = u16_at(msg, 4)?;
+    let answers = u16_at(msg, 6)?;
+    let mut at = 12;
+    for _ in 0..questions {
+        at = skip_name(msg, at)? + 4;
+    }
+    for _ in 0..answers {
+        at = skip_name(msg, at)?;
+        let (ty, class) = (u16_at(msg, at)?, u16_at(msg, at + 2)?);
+        let len = u16_at(msg, at + 8)? as usize;
+        let data = msg.get(at + 10..at + 10 + len)?;
+        if ty == TYPE_A && class == CLASS_IN && len == 4 {
+            return data.try_into().ok();
+        }
+        at += 10 + len;
+    }
+    None
+}
+
+/// The scheme, host and the rest (port, path, query) of `url`.
+fn split_url(url: &str) -> Option<(&str, &str, &str)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let end = rest.find([':', '/', '?', '#']).unwrap_or(rest.len());
+    let host = &rest[..end];
+    (!host.is_empty()).then_some((scheme, host, &rest[end..]))
+}
+
+/// The host name of `url`, without the port.
+pub fn url_host(url: &str) -> Option<&str> {
+    split_url(url).map(|(_, host, _)| host)
+}
+
+/// The port `url` connects to: the explicit one, else the scheme's.
+pub fn url_port(url: &str) -> Option<u16> {
+    let (scheme, _, rest) = split_url(url)?;
+    match rest.strip_prefix(':') {
+        Some(port) => port.split(['/', '?', '#']).next()?.parse().ok(),
+        None if scheme.eq_ignore_ascii_case("https") => Some(443),
+        None if scheme.eq_ignore_ascii_case("http") => Some(80),
+        None => None,
+    }
+}
+
+/// `url` with its host replaced by `ip`, keeping the port and path.
+pub fn with_ip(url: &str, ip: [u8; 4]) -> Option<String> {
+    let (scheme, _, rest) = split_url(url)?;
+    Some(format!("{}://{}{}", scheme, ipv4_to_string(ip), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_queries() {
+        let q = query("boot.example.", 0).unwrap();
+        assert_eq!(
+            q,
+            b"\0\0\x01\0\0\x01\0\0\0\0\0\0\x04boot\x07example\0\0\x01\0\x01"
+        );
+        assert_eq!(base64url(&q), "AAABAAABAAAAAAAABGJvb3QHZXhhbXBsZQAAAQAB");
+        assert_eq!(base64url(b"ab"), "YWI");
+        assert_eq!(query("a..b", 0), None);
+        assert_eq!(query(&"x".repeat(64), 0), None);
+    }
+
+    #[test]
+    fn reads_the_first_address() {
+        let mut msg = query("boot.example", 7).unwrap();
+        msg[2] |= 0x80;
+        msg[7] = 2;
+        // CNAME to a compressed name, then the A record.
+        msg.extend_from_slice(&[0xC0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xC0, 17]);
+        msg.extend_from_slice(&[0xC0, 17, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 10]);
+        assert_eq!(answer(&msg, 7), Some([192, 0, 2, 10]));
+        assert_eq!(answer(&msg, 8), None);
+        msg[3] = 3; // NXDOMAIN
+        assert_eq!(answer(&msg, 7), None);
+        assert_eq!(answer(&msg[..11], 7), None);
+    }
+
+    #[test]
+    fn rewrites_urls() {
+        let url = "https://boot.example:8443/k?x=1";
+        assert_eq!(url_host(url), Some("boot.example"));
+        assert_eq!(
+            with_ip(url, [10, 0, 0, 5]).as_deref(),
+            Some("https://10.0.0.5:8443/k?x=1")
+        );
+        assert_eq!(
+            with_ip("http://h/", [1, 2, 3, 4]).as_deref(),
+            Some("http://1.2.3.4/")
+        );
+        assert_eq!(url_host("boot.example/k"), None);
+        assert_eq!(url_port(url), Some(8443));
+        assert_eq!(url_port("HTTPS://h/k"), Some(443));
+        assert_eq!(url_port("http://h?x"), Some(80));
+        assert_eq!(url_port("tftp://h/k"), None);
+        assert_eq!(url_port("http://h:x/"), None);
+    }
+}
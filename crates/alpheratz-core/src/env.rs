@@ -0,0 +1,89 @@
+//! The `[entry.env]` block handed to Canicula kernels: a header followed by
+//! length-prefixed key/value strings, all little-endian.
+//!
+//! ```text
+//! u32 magic = ENV_MAGIC   u32 size (whole block)   u32 count
+//! count × { u32 key_len, key bytes, u32 value_len, value bytes }
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// `"AENV"` read as a little-endian u32.
+pub const ENV_MAGIC: u32 = u32::from_le_bytes(*b"AENV");
+
+fn push_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Serialize `env` in key order.
+pub fn serialize(env: &BTreeMap<String, String>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&ENV_MAGIC.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(env.len() as u32).to_le_bytes());
+    for (k, v) in env {
+        push_str(&mut out, k);
+        push_str(&mut out, v);
+    }
+    let size = out.len() as u32;
+    out[4..8].copy_from_slice(&size.to_le_bytes());
+    out
+}
+
+/// Read a block written by [`serialize`]; `None` if it is malformed.
+pub fn parse(data: &[u8]) -> Option<Vec<(&str, &str)>> {
+    let u32_at = |at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+    };
+    if u32_at(0)? != ENV_MAGIC || u32_at(4)? as usize != data.len() {
+        return None;
+    }
+    let count = u32_at(8)?;
+    let mut at = 12;
+    let str_at = |at: &mut usize| -> Option<&str> {
+        let len = u32_at(*at)? as usize;
+        let bytes = data.get(*at + 4..(*at + 4).checked_add(len)?)?;
+        *at += 4 + len;
+        core::str::from_utf8(bytes).ok()
+    };
+    let mut out = Vec::new();
+    for _ in 0..count {
+        let key = str_at(&mut at)?;
+        let value = str_at(&mut at)?;
+        out.push((key, value));
+    }
+    (at == data.len()).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_in_key_order() {
+        let mut env = BTreeMap::new();
+        env.insert(String::from("log"), String::from("debug"));
+        env.insert(String::from("init"), String::from("/sbin/init"));
+        let data = serialize(&env);
+        assert_eq!(&data[..4], b"AENV");
+        assert_eq!(
+            parse(&data).unwrap(),
+            [("init", "/sbin/init"), ("log", "debug")]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        let mut env = BTreeMap::new();
+        env.insert(String::from("k"), String::from("v"));
+        let data = serialize(&env);
+        assert!(parse(&data[..data.len() - 1]).is_none());
+        let mut long = data.clone();
+        long[12] = 9;
+        assert!(parse(&long).is_none());
+        assert_eq!(parse(&serialize(&BTreeMap::new())).unwrap(), []);
+    }
+}
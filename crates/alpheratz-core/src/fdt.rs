@@ -0,0 +1,270 @@
+//! Read-only parser for flattened device tree blobs (DTB, FIT).
+
+use alloc::vec::Vec;
+
+const MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+#[cfg(test)]
+const FDT_END: u32 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    BadMagic,
+    /// An offset or length points outside the blob.
+    Truncated,
+    /// Unexpected token or unterminated name.
+    Malformed,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::BadMagic => f.write_str("not a flattened device tree"),
+            Error::Truncated => f.write_str("device tree is truncated"),
+            Error::Malformed => f.write_str("device tree is malformed"),
+        }
+    }
+}
+
+/// A node and everything below it, borrowing names and values from the blob.
+#[derive(Debug, Clone)]
+pub struct Node<'a> {
+    /// Unit name including any `@address` suffix; empty for the root.
+    pub name: &'a str,
+    pub props: Vec<(&'a str, &'a [u8])>,
+    pub children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    pub fn prop(&self, name: &str) -> Option<&'a [u8]> {
+        self.props.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+    }
+
+    /// A string property, without its terminating NUL.
+    pub fn prop_str(&self, name: &str) -> Option<&'a str> {
+        self.prop_strs(name).next()
+    }
+
+    /// The entries of a string-list property such as `compatible`.
+    pub fn prop_strs(&self, name: &str) -> impl Iterator<Item = &'a str> {
+        let value = self.prop(name).unwrap_or(&[]);
+        let value = value.strip_suffix(&[0]).unwrap_or(value);
+        (!value.is_empty())
+            .then_some(value)
+            .into_iter()
+            .flat_map(|v| v.split(|&b| b == 0))
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    /// A property holding one big-endian cell (`u32`) or two (`u64`).
+    pub fn prop_u64(&self, name: &str) -> Option<u64> {
+        match *self.prop(name)? {
+            [a, b, c, d] => Some(u32::from_be_bytes([a, b, c, d]) as u64),
+            [a, b, c, d, e, f, g, h] => Some(u64::from_be_bytes([a, b, c, d, e, f, g, h])),
+            _ => None,
+        }
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Node<'a>> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Look up a `/`-separated path relative to this node.
+    pub fn find(&self, path: &str) -> Option<&Node<'a>> {
+        path.split('/')
+            .filter(|p| !p.is_empty())
+            .try_fold(self, |node, part| node.child(part))
+    }
+}
+
+fn be32(data: &[u8], off: usize) -> Result<u32, Error> {
+    let b = data.get(off..off + 4).ok_or(Error::Truncated)?;
+    Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Size of the blob in bytes, as recorded in its header.
+pub fn total_size(data: &[u8]) -> Result<usize, Error> {
+    if be32(data, 0)? != MAGIC {
+        return Err(Error::BadMagic);
+    }
+    Ok(be32(data, 4)? as usize)
+}
+
+/// Parse the whole tree and return its root node.
+pub fn parse(data: &[u8]) -> Result<Node<'_>, Error> {
+    let total = total_size(data)?;
+    let data = data.get(..total).ok_or(Error::Truncated)?;
+    let off_struct = be32(data, 8)? as usize;
+    let off_strings = be32(data, 12)? as usize;
+    let size_strings = be32(data, 32)? as usize;
+    let size_struct = be32(data, 36)? as usize;
+    let structure = data
+        .get(off_struct..off_struct + size_struct)
+        .ok_or(Error::Truncated)?;
+    let strings = data
+        .get(off_strings..off_strings + size_strings)
+        .ok_or(Error::Truncated)?;
+
+    let mut pos = 0;
+    let mut stack: Vec<Node> = Vec::new();
+    loop {
+        let token = be32(structure, pos)?;
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = cstr(structure, pos)?;
+                pos = (pos + name.len() + 1).next_multiple_of(4);
+                stack.push(Node {
+                    name,
+                    props: Vec::new(),
+                    children: Vec::new(),
+                });
+            }
+            FDT_END_NODE => {
+                let node = stack.pop().ok_or(Error::Malformed)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(node),
+                }
+            }
+            FDT_PROP => {
+                let len = be32(structure, pos)? as usize;
+                let name = cstr(strings, be32(structure, pos + 4)? as usize)?;
+                pos += 8;
+                let value = structure.get(pos..pos + len).ok_or(Error::Truncated)?;
+                pos = (pos + len).next_multiple_of(4);
+                stack
+                    .last_mut()
+                    .ok_or(Error::Malformed)?
+                    .props
+                    .push((name, value));
+            }
+            FDT_NOP => {}
+            // FDT_END before the root node closed, or an unknown token.
+            _ => return Err(Error::Malformed),
+        }
+    }
+}
+
+fn cstr(data: &[u8], off: usize) -> Result<&str, Error> {
+    let rest = data.get(off..).ok_or(Error::Truncated)?;
+    let len = rest.iter().position(|&b| b == 0).ok_or(Error::Malformed)?;
+    core::str::from_utf8(&rest[..len]).map_err(|_| Error::Malformed)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    /// Minimal FDT writer for building test blobs.
+    pub(crate) enum Item<'a> {
+        Prop(&'a str, &'a [u8]),
+        Node(&'a str, Vec<Item<'a>>),
+    }
+
+    pub(crate) fn build(root: Vec<Item>) -> Vec<u8> {
+        fn emit(items: &[Item], st: &mut Vec<u8>, strings: &mut String) {
+            for item in items {
+                match item {
+                    Item::Prop(name, value) => {
+                        let off =
+                            strings
+                                .find(&alloc::format!("{}\0", name))
+                                .unwrap_or_else(|| {
+                                    let off = strings.len();
+                                    strings.push_str(name);
+                                    strings.push('\0');
+                                    off
+                                });
+                        st.extend_from_slice(&FDT_PROP.to_be_bytes());
+                        st.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                        st.extend_from_slice(&(off as u32).to_be_bytes());
+                        st.extend_from_slice(value);
+                        st.resize(st.len().next_multiple_of(4), 0);
+                    }
+                    Item::Node(name, children) => {
+                        st.extend_from_slice(&FDT_BEGIN_NODE.to_be_bytes());
+                        st.extend_from_slice(name.as_bytes());
+                        st.push(0);
+                        st.resize(st.len().next_multiple_of(4), 0);
+                        emit(children, st, strings);
+                        st.extend_from_slice(&FDT_END_NODE.to_be_bytes());
+                    }
+                }
+            }
+        }
+
+        let mut st = Vec::new();
+        let mut strings = String::new();
+        emit(&[Item::Node("", root)], &mut st, &mut strings);
+        st.extend_from_slice(&FDT_END.to_be_bytes());
+
+        let off_struct = 40 + 16;
+        let off_strings = off_struct + st.len();
+        let total = off_strings + strings.len();
+        let mut out = Vec::new();
+        for v in [
+            MAGIC,
+            total as u32,
+            off_struct as u32,
+            off_strings as u32,
+            40,
+            17,
+            16,
+            0,
+            strings.len() as u32,
+            st.len() as u32,
+        ] {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&st);
+        out.extend_from_slice(strings.as_bytes());
+        out
+    }
+
+    #[test]
+    fn parses_nested_nodes_and_properties() {
+        let blob = build(alloc::vec![
+            Item::Prop("compatible", b"acme,board-v2\0acme,board\0"),
+            Item::Node(
+                "soc",
+                alloc::vec![
+                    Item::Prop("#address-cells", &[0, 0, 0, 2]),
+                    Item::Node(
+                        "uart@1000",
+                        alloc::vec![Item::Prop("reg", &[0, 0, 0x10, 0])]
+                    ),
+                ],
+            ),
+        ]);
+        let root = parse(&blob).unwrap();
+        assert_eq!(
+            root.prop_strs("compatible").collect::<Vec<_>>(),
+            ["acme,board-v2", "acme,board"]
+        );
+        assert_eq!(
+            root.find("soc/uart@1000").unwrap().prop_u64("reg"),
+            Some(0x1000)
+        );
+        assert_eq!(
+            root.find("/soc").unwrap().prop_u64("#address-cells"),
+            Some(2)
+        );
+        assert!(root.find("soc/missing").is_none());
+        assert_eq!(root.prop_strs("absent").count(), 0);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(parse(b"not a dtb").unwrap_err(), Error::BadMagic);
+        let mut blob = build(alloc::vec![Item::Prop("model", b"x\0")]);
+        blob.truncate(blob.len() - 4);
+        assert_eq!(parse(&blob).unwrap_err(), Error::Truncated);
+    }
+}
@@ -0,0 +1,324 @@
+//! U-Boot Flattened Image Tree (`.itb`): pick the configuration for the
+//! running board and locate its kernel, ramdisk and device tree.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::fdt::{self, Node};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Fdt(fdt::Error),
+    /// No `/images` or `/configurations` node, or no configuration at all.
+    NoConfiguration,
+    /// The selected configuration names no kernel.
+    NoKernel,
+    /// A configuration refers to an image that does not exist.
+    MissingImage(String),
+    /// The image has neither `data` nor a valid external data range.
+    MissingData(String),
+    /// The image is compressed with the given algorithm.
+    Compressed(String, String),
+}
+
+impl From<fdt::Error> for Error {
+    fn from(e: fdt::Error) -> Self {
+        Error::Fdt(e)
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Fdt(e) => write!(f, "{}", e),
+            Error::NoConfiguration => f.write_str("no configuration to boot"),
+            Error::NoKernel => f.write_str("configuration has no kernel"),
+            Error::MissingImage(n) => write!(f, "image \"{}\" does not exist", n),
+            Error::MissingData(n) => write!(f, "image \"{}\" has no data", n),
+            Error::Compressed(n, c) => write!(f, "image \"{}\" is {}-compressed", n, c),
+        }
+    }
+}
+
+/// A `hash-N` node of an image.
+#[derive(Debug, Clone)]
+pub struct Hash<'a> {
+    pub algo: &'a str,
+    pub value: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub struct Image<'a> {
+    pub name: &'a str,
+    pub data: &'a [u8],
+    pub hashes: Vec<Hash<'a>>,
+}
+
+/// The images of one configuration.
+#[derive(Debug, Clone)]
+pub struct Selection<'a> {
+    pub config: &'a str,
+    pub kernel: Image<'a>,
+    pub ramdisk: Option<Image<'a>>,
+    pub fdt: Option<Image<'a>>,
+}
+
+/// Where an image's bytes live: inline `data`, or outside the tree via
+/// `data-position` (absolute) or `data-offset` (from the end of the tree).
+fn image_data<'a>(node: &Node<'a>, fit: &'a [u8], external_base: usize) -> Option<&'a [u8]> {
+    if let Some(data) = node.prop("data") {
+        return Some(data);
+    }
+    let size = node.prop_u64("data-size")? as usize;
+    let start = match node.prop_u64("data-position") {
+        Some(pos) => pos as usize,
+        None => external_base.checked_add(node.prop_u64("data-offset")? as usize)?,
+    };
+    fit.get(start..start.checked_add(size)?)
+}
+
+fn load_image<'a>(
+    images: &Node<'a>,
+    name: &'a str,
+    fit: &'a [u8],
+    external_base: usize,
+) -> Result<Image<'a>, Error> {
+    let node = images
+        .child(name)
+        .ok_or_else(|| Error::MissingImage(String::from(name)))?;
+    if let Some(c) = node.prop_str("compression").filter(|&c| c != "none") {
+        return Err(Error::Compressed(String::from(name), String::from(c)));
+    }
+    let data = image_data(node, fit, external_base)
+        .ok_or_else(|| Error::MissingData(String::from(name)))?;
+    let hashes = node
+        .children
+        .iter()
+        .filter(|c| c.name.starts_with("hash"))
+        .filter_map(|c| {
+            Some(Hash {
+                algo: c.prop_str("algo")?,
+                value: c.prop("value")?,
+            })
+        })
+        .collect();
+    Ok(Image { name, data, hashes })
+}
+
+/// Compatible strings a configuration claims: its own `compatible`, else the
+/// root `compatible` of the device tree it carries.
+fn config_compatible<'a>(
+    config: &Node<'a>,
+    images: &Node<'a>,
+    fit: &'a [u8],
+    external_base: usize,
+) -> Vec<&'a str> {
+    if config.prop("compatible").is_some() {
+        return config.prop_strs("compatible").collect();
+    }
+    config
+        .prop_str("fdt")
+        .and_then(|name| images.child(name))
+        .and_then(|node| image_data(node, fit, external_base))
+        .and_then(|dtb| fdt::parse(dtb).ok())
+        .map(|root| root.prop_strs("compatible").collect())
+        .unwrap_or_default()
+}
+
+/// Choose the configuration for a board whose root `compatible` list is
+/// `board` (most specific first), falling back to the `default`
+/// configuration, and resolve its images.
+pub fn select<'a>(fit: &'a [u8], board: &[&str]) -> Result<Selection<'a>, Error> {
+    let root = fdt::parse(fit)?;
+    let external_base = fdt::total_size(fit)?.next_multiple_of(4);
+    let images = root.child("images").ok_or(Error::NoConfiguration)?;
+    let configs = root.child("configurations").ok_or(Error::NoConfiguration)?;
+
+    let claims: Vec<(&Node, Vec<&str>)> = configs
+        .children
+        .iter()
+        .map(|c| (c, config_compatible(c, images, fit, external_base)))
+        .collect();
+    let config = board
+        .iter()
+        .find_map(|b| claims.iter().find(|(_, compat)| compat.contains(b)))
+        .map(|(c, _)| *c)
+        .or_else(|| configs.prop_str("default").and_then(|d| configs.child(d)))
+        .or(configs.children.first())
+        .ok_or(Error::NoConfiguration)?;
+
+    let image = |prop: &str| {
+        config
+            .prop_str(prop)
+            .map(|name| load_image(images, name, fit, external_base))
+            .transpose()
+    };
+    Ok(Selection {
+        config: config.name,
+        kernel: image("kernel")?.ok_or(Error::NoKernel)?,
+        ramdisk: image("ramdisk")?,
+        fdt: image("fdt")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdt::tests::{Item, build};
+    use alloc::vec;
+
+    fn board_dtb(compatible: &[u8]) -> Vec<u8> {
+        build(vec![Item::Prop("compatible", compatible)])
+    }
+
+    fn sample_fit(dtb_a: &[u8], dtb_b: &[u8]) -> Vec<u8> {
+        build(vec![
+            Item::Node(
+                "images",
+                vec![
+                    Item::Node(
+                        "kernel",
+                        vec![
+                            Item::Prop("data", b"KERNEL"),
+                            Item::Prop("compression", b"none\0"),
+                            Item::Node(
+                                "hash-1",
+                                vec![
+                                    Item::Prop("algo", b"crc32\0"),
+                                    Item::Prop("value", &[1, 2, 3, 4]),
+                                ],
+                            ),
+                        ],
+                    ),
+                    Item::Node("ramdisk", vec![Item::Prop("data", b"RAMDISK")]),
+                    Item::Node("fdt-a", vec![Item::Prop("data", dtb_a)]),
+                    Item::Node("fdt-b", vec![Item::Prop("data", dtb_b)]),
+                ],
+            ),
+            Item::Node(
+                "configurations",
+                vec![
+                    Item::Prop("default", b"conf-a\0"),
+                    Item::Node(
+                        "conf-a",
+                        vec![
+                            Item::Prop("kernel", b"kernel\0"),
+                            Item::Prop("ramdisk", b"ramdisk\0"),
+                            Item::Prop("fdt", b"fdt-a\0"),
+                            Item::Prop("compatible", b"acme,a\0"),
+                        ],
+                    ),
+                    Item::Node(
+                        "conf-b",
+                        vec![
+                            Item::Prop("kernel", b"kernel\0"),
+                            Item::Prop("fdt", b"fdt-b\0"),
+                        ],
+                    ),
+                ],
+            ),
+        ])
+    }
+
+    #[test]
+    fn selects_by_board_compatible() {
+        let (a, b) = (board_dtb(b"acme,a\0"), board_dtb(b"acme,b-rev2\0acme,b\0"));
+        let fit = sample_fit(&a, &b);
+
+        let sel = select(&fit, &["acme,b-rev3", "acme,b"]).unwrap();
+        assert_eq!(sel.config, "conf-b");
+        assert_eq!(sel.kernel.data, b"KERNEL");
+        assert_eq!(sel.kernel.hashes[0].algo, "crc32");
+        assert!(sel.ramdisk.is_none());
+        assert_eq!(sel.fdt.unwrap().data, &b[..]);
+
+        let sel = select(&fit, &["acme,a"]).unwrap();
+        assert_eq!(sel.config, "conf-a");
+        assert_eq!(sel.ramdisk.unwrap().data, b"RAMDISK");
+    }
+
+    #[test]
+    fn falls_back_to_default() {
+        let (a, b) = (board_dtb(b"acme,a\0"), board_dtb(b"acme,b\0"));
+        let fit = sample_fit(&a, &b);
+        assert_eq!(select(&fit, &["other,board"]).unwrap().config, "conf-a");
+        assert_eq!(select(&fit, &[]).unwrap().config, "conf-a");
+    }
+
+    #[test]
+    fn reads_external_data() {
+        let mut fit = build(vec![
+            Item::Node(
+                "images",
+                vec![Item::Node(
+                    "kernel",
+                    vec![
+                        Item::Prop("data-offset", &[0, 0, 0, 0]),
+                        Item::Prop("data-size", &[0, 0, 0, 3]),
+                    ],
+                )],
+            ),
+            Item::Node(
+                "configurations",
+                vec![Item::Node("conf", vec![Item::Prop("kernel", b"kernel\0")])],
+            ),
+        ]);
+        fit.resize(fit.len().next_multiple_of(4), 0);
+        fit.extend_from_slice(b"EXT");
+        assert_eq!(select(&fit, &[]).unwrap().kernel.data, b"EXT");
+    }
+
+    #[test]
+    fn rejects_overflowing_data_offset() {
+        let fit = build(vec![
+            Item::Node(
+                "images",
+                vec![Item::Node(
+                    "kernel",
+                    vec![
+                        Item::Prop("data-offset", &[0xff; 8]),
+                        Item::Prop("data-size", &[0, 0, 0, 3]),
+                    ],
+                )],
+            ),
+            Item::Node(
+                "configurations",
+                vec![Item::Node("conf", vec![Item::Prop("kernel", b"kernel\0")])],
+            ),
+        ]);
+        assert_eq!(
+            select(&fit, &[]).unwrap_err(),
+            Error::MissingData(String::from("kernel"))
+        );
+    }
+
+    #[test]
+    fn rejects_compressed_images() {
+        let fit = build(vec![
+            Item::Node(
+                "images",
+                vec![Item::Node(
+                    "kernel",
+                    vec![
+                        Item::Prop("data", b"x"),
+                        Item::Prop("compression", b"gzip\0"),
+                    ],
+                )],
+            ),
+            Item::Node(
+                "configurations",
+                vec![Item::Node(
+                    "conf",
+                    vec![
+                        Item::Prop("kernel", b"kernel\0"),
+                        Item::Prop("fdt", b"dtb\0"),
+                    ],
+                )],
+            ),
+        ]);
+        assert_eq!(
+            select(&fit, &[]).unwrap_err(),
+            Error::Compressed(String::from("kernel"), String::from("gzip"))
+        );
+    }
+}
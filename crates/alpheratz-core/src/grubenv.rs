@@ -0,0 +1,184 @@
+//! GRUB environment blocks (`grubenv`), shared with a GRUB install being
+//! migrated from: `saved_entry` and `next_entry` as written by
+//! `grub-set-default` and `grub-reboot`, and the `boot_counter` /
+//! `boot_success` pair used for automatic rollback.
+//!
+//! ```text
+//! # GRUB Environment Block
+//! name=value            ('\' and newline in values escaped with '\')
+//! ###...###             (padding to the block's fixed size)
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const SIGNATURE: &str = "# GRUB Environment Block\n";
+
+/// Size of a block as `grub-editenv create` makes it.
+pub const DEFAULT_SIZE: usize = 1024;
+
+/// Variables of a block, in the order they appear.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Env {
+    vars: Vec<(String, String)>,
+}
+
+impl Env {
+    /// Read a block; `None` without the signature.
+    pub fn parse(data: &[u8]) -> Option<Env> {
+        let body = data.strip_prefix(SIGNATURE.as_bytes())?;
+        let text = String::from_utf8_lossy(body);
+        let mut env = Env::default();
+        let mut line = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => line.extend(chars.next()),
+                '\n' => {
+                    env.push_line(&line);
+                    line.clear();
+                }
+                c => line.push(c),
+            }
+        }
+        env.push_line(&line);
+        Some(env)
+    }
+
+    /// Comment and padding lines start with `#`; lines without `=` are
+    /// ignored, as GRUB does.
+    fn push_line(&mut self, line: &str) {
+        if line.starts_with('#') {
+            return;
+        }
+        if let Some((name, value)) = line.split_once('=') {
+            self.set(name, value);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.vars.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = String::from(value),
+            None => self.vars.push((String::from(name), String::from(value))),
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.vars.retain(|(n, _)| n != name);
+    }
+
+    /// The block padded with `#` to `size` bytes, which GRUB requires to
+    /// stay the same; `None` if the variables do not fit.
+    pub fn to_block(&self, size: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::from(SIGNATURE.as_bytes());
+        for (name, value) in &self.vars {
+            out.extend_from_slice(name.as_bytes());
+            out.push(b'=');
+            for b in value.bytes() {
+                if b == b'\\' || b == b'\n' {
+                    out.push(b'\\');
+                }
+                out.push(b);
+            }
+            out.push(b'\n');
+        }
+        if out.len() > size {
+            return None;
+        }
+        out.resize(size, b'#');
+        Some(out)
+    }
+
+    /// Index among `names` of the entry to boot by default: `next_entry`
+    /// (a one-shot choice) over `saved_entry`. Values are an index or an
+    /// entry title, where a GRUB `submenu>title` path matches on its title.
+    pub fn saved_index(&self, names: &[&str]) -> Option<usize> {
+        let value = self
+            .get("next_entry")
+            .filter(|v| !v.is_empty())
+            .or_else(|| self.get("saved_entry"))?;
+        if let Ok(index) = value.parse::<usize>() {
+            return (index < names.len()).then_some(index);
+        }
+        let title = value.rsplit('>').next().unwrap_or(value);
+        names.iter().position(|&n| n == value || n == title)
+    }
+
+    /// Count this boot against `boot_counter` while `boot_success` is 0,
+    /// as GRUB's rollback script does, and mark the boot unconfirmed until
+    /// the OS sets `boot_success=1`. Returns whether the counter has run
+    /// out, meaning the fallback (second) entry should be booted.
+    pub fn count_boot(&mut self) -> bool {
+        let mut fallback = false;
+        if self.get("boot_success") == Some("0")
+            && let Some(counter) = self.get("boot_counter").and_then(|c| c.parse::<i64>().ok())
+        {
+            if counter <= 0 {
+                self.set("boot_counter", "-1");
+                fallback = true;
+            } else {
+                self.set("boot_counter", &alloc::format!("{}", counter - 1));
+            }
+        }
+        self.set("boot_success", "0");
+        fallback
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(vars: &str) -> Vec<u8> {
+        let mut data = Vec::from(SIGNATURE.as_bytes());
+        data.extend_from_slice(vars.as_bytes());
+        data.resize(DEFAULT_SIZE, b'#');
+        data
+    }
+
+    #[test]
+    fn round_trips_with_escapes() {
+        let data = block("saved_entry=Debian\nnote=a\\\\b\\\nc\n");
+        let env = Env::parse(&data).unwrap();
+        assert_eq!(env.get("saved_entry"), Some("Debian"));
+        assert_eq!(env.get("note"), Some("a\\b\nc"));
+        assert_eq!(env.to_block(DEFAULT_SIZE).unwrap(), data);
+        assert!(env.to_block(16).is_none());
+        assert!(Env::parse(b"saved_entry=0\n").is_none());
+    }
+
+    #[test]
+    fn picks_next_entry_over_saved_entry() {
+        let names = ["Debian", "Windows", "Rescue"];
+        let mut env = Env::parse(&block("saved_entry=Windows\n")).unwrap();
+        assert_eq!(env.saved_index(&names), Some(1));
+        env.set("saved_entry", "Advanced options>Rescue");
+        assert_eq!(env.saved_index(&names), Some(2));
+        env.set("next_entry", "0");
+        assert_eq!(env.saved_index(&names), Some(0));
+        env.set("next_entry", "");
+        env.set("saved_entry", "7");
+        assert_eq!(env.saved_index(&names), None);
+    }
+
+    #[test]
+    fn counts_unconfirmed_boots_down_to_fallback() {
+        let mut env = Env::parse(&block("boot_counter=1\nboot_success=0\n")).unwrap();
+        assert!(!env.count_boot());
+        assert_eq!(env.get("boot_counter"), Some("0"));
+        assert!(env.count_boot());
+        assert_eq!(env.get("boot_counter"), Some("-1"));
+
+        let mut env = Env::parse(&block("boot_counter=3\nboot_success=1\n")).unwrap();
+        assert!(!env.count_boot());
+        assert_eq!(env.get("boot_counter"), Some("3"));
+        assert_eq!(env.get("boot_success"), Some("0"));
+    }
+}
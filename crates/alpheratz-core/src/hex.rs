@@ -0,0 +1,31 @@
+use alloc::vec::Vec;
+
+/// Decode a string of hex byte pairs; surrounding whitespace is ignored.
+pub fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_pairs() {
+        assert_eq!(parse_hex(" 00ff1A \n"), Some(alloc::vec![0x00, 0xff, 0x1a]));
+        assert_eq!(parse_hex(""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert_eq!(parse_hex("abc"), None);
+        assert_eq!(parse_hex("zz"), None);
+        assert_eq!(parse_hex("é"), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Linux hibernation images in swap areas, and the `resume=` parameter that
+//! names the one a kernel resumes from. Booting another system while one is
+//! hibernated can corrupt the file systems it still has mounted; Windows'
+//! side of this is [`crate::bcd::attempts_resume`].
+//!
+//! The last 10 bytes of a swap area's first page hold `SWAPSPACE2`, which
+//! hibernation replaces with its own signature until the image is resumed.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+const SWAP: &[u8] = b"SWAPSPACE2";
+
+/// Signatures of a hibernation image: the kernel's, uswsusp's and that of
+/// older kernels.
+const SUSPENDED: [&[u8]; 4] = [b"S1SUSPEND", b"S2SUSPEND", b"ULSUSPEND", b"LINHIB0001"];
+
+/// Page sizes the header may be written for.
+const PAGE_SIZES: [usize; 3] = [4096, 16384, 65536];
+
+/// Bytes from the start of a partition needed to find the header.
+pub const HEADER_MAX: usize = 65536;
+
+/// Offset of the swap area's UUID, after the boot block and version,
+/// last page and bad page count.
+const UUID_AT: usize = 0x40C;
+
+/// A swap area found at the start of a partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swap {
+    /// As `UUID=` would name it.
+    pub uuid: String,
+    /// Holds a hibernation image that has not been resumed.
+    pub suspended: bool,
+}
+
+/// The swap area whose header `data` (the first [`HEADER_MAX`] bytes of a
+/// partition, or fewer) starts with; `None` for anything else.
+pub fn swap(data: &[u8]) -> Option<Swap> {
+    for page in PAGE_SIZES {
+        let sig = data.get(page - SWAP.len()..page)?;
+        let suspended = SUSPENDED.iter().any(|s| sig.starts_with(s));
+        if suspended || sig == SWAP {
+            let mut uuid = String::new();
+            for (i, b) in data.get(UUID_AT..UUID_AT + 16)?.iter().enumerate() {
+                if matches!(i, 4 | 6 | 8 | 10) {
+                    uuid.push('-');
+                }
+                let _ = write!(uuid, "{:02x}", b);
+            }
+            return Some(Swap { uuid, suspended });
+        }
+    }
+    None
+}
+
+/// The device the last `resume=` in `cmdline` names.
+pub fn resume_device(cmdline: &str) -> Option<&str> {
+    cmdline
+        .split_ascii_whitespace()
+        .filter_map(|arg| arg.strip_prefix("resume="))
+        .next_back()
+}
+
+/// Whether `device`, a `resume=` value, is the swap area `uuid` on the
+/// partition `partuuid`. Kernel device names (`/dev/sda2`) cannot be told
+/// from firmware and never match.
+pub fn names(device: &str, uuid: &str, partuuid: Option<&str>) -> bool {
+    let by = |prefixes: [&str; 2]| prefixes.into_iter().find_map(|p| device.strip_prefix(p));
+    if let Some(id) = by(["UUID=", "/dev/disk/by-uuid/"]) {
+        return id.eq_ignore_ascii_case(uuid);
+    }
+    if let Some(id) = by(["PARTUUID=", "/dev/disk/by-partuuid/"]) {
+        return partuuid.is_some_and(|p| id.eq_ignore_ascii_case(p));
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const UUID: &str = "0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0";
+
+    fn header(page: usize, sig: &[u8]) -> Vec<u8> {
+        let mut data = alloc::vec![0; HEADER_MAX];
+        data[UUID_AT..UUID_AT + 16].copy_from_slice(&[
+            0x0f, 0x1e, 0x2d, 0x3c, 0x4b, 0x5a, 0x69, 0x78, 0x87, 0x96, 0xa5, 0xb4, 0xc3, 0xd2,
+            0xe1, 0xf0,
+        ]);
+        data[page - 10..page - 10 + sig.len()].copy_from_slice(sig);
+        data
+    }
+
+    #[test]
+    fn tells_hibernated_swap_apart() {
+        let idle = swap(&header(4096, b"SWAPSPACE2")).unwrap();
+        assert_eq!(idle.uuid, UUID);
+        assert!(!idle.suspended);
+        assert!(swap(&header(4096, b"S1SUSPEND\0")).unwrap().suspended);
+        assert!(swap(&header(65536, b"ULSUSPEND\0")).unwrap().suspended);
+        assert!(swap(&header(16384, b"S1SUSPEND\0")[..4096]).is_none());
+        assert_eq!(swap(&header(4096, b"")), None);
+    }
+
+    #[test]
+    fn matches_resume_devices() {
+        let cmdline = "root=/dev/vda2 resume=/dev/sda3 quiet resume=UUID=0F1E2D3C-4B5A-6978-8796-A5B4C3D2E1F0";
+        let device = resume_device(cmdline).unwrap();
+        assert!(names(device, UUID, None));
+        assert!(names(
+            "/dev/disk/by-partuuid/abcd-01",
+            UUID,
+            Some("ABCD-01")
+        ));
+        assert!(!names("PARTUUID=abcd-01", UUID, None));
+        assert!(!names("/dev/sda3", UUID, Some("abcd-01")));
+        assert_eq!(resume_device("root=/dev/vda2"), None);
+    }
+}
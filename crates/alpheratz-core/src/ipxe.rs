@@ -0,0 +1,309 @@
+//! Entries from iPXE scripts, for menus that already exist as iPXE
+//! infrastructure. Only a subset is understood: `set` variables, `item`
+//! titles and `:labels` to name entries, and `kernel`, `initrd` and
+//! `imgargs` images that each `boot` turns into a Linux entry. Control flow
+//! (`goto`, `choose`, `iseq`, …) is not followed; every `boot` in the script
+//! becomes an entry of its own.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::config::{BootFile, Entry, FileType, Protocol, SearchMethod};
+use crate::vars;
+
+/// Commands that only steer an interactive iPXE session; dropped quietly.
+const IGNORED: &[&str] = &[
+    "dhcp", "ifconf", "ifopen", "echo", "sleep", "prompt", "menu", "choose", "goto", "imgfree",
+];
+
+/// Options whose value is the following word.
+const VALUED_OPTIONS: &[&str] = &["--name", "-n", "--timeout", "-t", "--key", "-k"];
+
+/// The entries read from one script.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub entries: Vec<Entry>,
+    /// Lines using commands outside the subset, left out.
+    pub skipped: Vec<String>,
+}
+
+/// The images loaded since the last `boot`.
+#[derive(Default)]
+struct Pending {
+    kernel: Option<String>,
+    args: String,
+    initrds: Vec<String>,
+}
+
+/// Expand `${name}` from `set` variables. iPXE's own settings keep their
+/// Alpheratz names (`${net0/mac}` becomes `${mac}`, `${buildarch}` becomes
+/// `${arch}`); anything else is left as written.
+fn expand(line: &str, set: &BTreeMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 2..start + len];
+        let name = name.split(':').next().unwrap_or(name);
+        let name = match name.split_once('/') {
+            Some((scope, setting)) if scope.starts_with("net") => setting,
+            _ => name,
+        };
+        match set.get(name) {
+            Some(value) => out.push_str(value),
+            None if name == "buildarch" => out.push_str("${arch}"),
+            None => {
+                out.push_str("${");
+                out.push_str(name);
+                out.push('}');
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The words of a command after its name, without options.
+fn positional<'a>(mut words: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    while let Some(word) = words.next() {
+        if out.is_empty() && word.starts_with('-') {
+            if VALUED_OPTIONS.contains(&word) {
+                words.next();
+            }
+            continue;
+        }
+        out.push(word);
+    }
+    out
+}
+
+/// `url` made absolute against the directory the script came from; iPXE
+/// treats a bare name as relative to the script.
+fn absolute(url: &str, base: Option<&str>) -> String {
+    match base {
+        Some(base) if !url.contains("://") && !url.starts_with("${") => {
+            if vars::is_relative(url) {
+                vars::resolve(url, base)
+            } else {
+                vars::resolve(&format!("./{}", url), base)
+            }
+        }
+        _ => String::from(url),
+    }
+}
+
+fn file(file_type: FileType, search: SearchMethod) -> BootFile {
+    BootFile {
+        file_type,
+        search,
+        file: None,
+        content: None,
+        select: None,
+        max_size: None,
+        encrypted: false,
+        sha256: None,
+        delta: None,
+    }
+}
+
+fn remote(file_type: FileType, url: String) -> BootFile {
+    let search = if url.starts_with("tftp://") {
+        SearchMethod::Tftp
+    } else {
+        SearchMethod::Https
+    };
+    BootFile {
+        file: Some(url),
+        ..file(file_type, search)
+    }
+}
+
+/// Read the iPXE script `script`, fetched from `url`.
+pub fn parse(script: &str, url: &str) -> Import {
+    let base = vars::origin(url);
+    let mut set: BTreeMap<String, String> = BTreeMap::new();
+    let mut titles: BTreeMap<String, String> = BTreeMap::new();
+    let mut label: Option<String> = None;
+    let mut pending = Pending::default();
+    let mut import = Import::default();
+
+    for line in script.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix(':') {
+            label = Some(String::from(name.trim()));
+            continue;
+        }
+        let line = expand(line, &set);
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let args = positional(words);
+        match command {
+            "set" => {
+                if let Some((name, value)) = args.split_first() {
+                    let name = name.split(':').next().unwrap_or(name);
+                    set.insert(String::from(name), value.join(" "));
+                }
+            }
+            "item" => {
+                if let Some((name, text)) = args.split_first()
+                    && !text.is_empty()
+                {
+                    titles.insert(String::from(*name), text.join(" "));
+                }
+            }
+            "kernel" => {
+                if let Some((image, rest)) = args.split_first() {
+                    pending.kernel = Some(absolute(image, base));
+                    pending.args = rest.join(" ");
+                }
+            }
+            "initrd" => {
+                if let Some(image) = args.first() {
+                    pending.initrds.push(absolute(image, base));
+                }
+            }
+            "imgargs" => {
+                if let Some((_, rest)) = args.split_first() {
+                    pending.args = rest.join(" ");
+                }
+            }
+            "boot" => {
+                let taken = core::mem::take(&mut pending);
+                let Some(kernel) = taken.kernel else {
+                    import.skipped.push(String::from(line.trim()));
+                    continue;
+                };
+                let name = label
+                    .as_ref()
+                    .map(|l| titles.get(l).cloned().unwrap_or_else(|| l.clone()))
+                    .unwrap_or_else(|| format!("iPXE {}", import.entries.len() + 1));
+
+                let mut files = alloc::vec![remote(FileType::Kernel, kernel)];
+                files.extend(
+                    taken
+                        .initrds
+                        .into_iter()
+                        .map(|u| remote(FileType::Initrd, u)),
+                );
+                // The initrd is handed over by Alpheratz, not by the stub
+                // reading `initrd=` from a file system.
+                let cmdline: Vec<&str> = taken
+                    .args
+                    .split_whitespace()
+                    .filter(|a| !a.starts_with("initrd="))
+                    .collect();
+                if !cmdline.is_empty() {
+                    files.push(BootFile {
+                        content: Some(cmdline.join(" ")),
+                        ..file(FileType::Cmdline, SearchMethod::Inline)
+                    });
+                }
+                import.entries.push(Entry {
+                    name,
+                    protocol: Some(Protocol::Linux),
+                    files,
+                    ..Entry::default()
+                });
+            }
+            _ if IGNORED.contains(&command) => {}
+            _ => import.skipped.push(String::from(line.trim())),
+        }
+    }
+    import
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"#!ipxe
+dhcp
+set base http://10.0.0.1/debian
+menu Boot
+item install Install Debian
+item rescue
+choose target && goto ${target}
+
+:install
+kernel ${base}/vmlinuz initrd=initrd.gz auto=true mac=${net0/mac}
+initrd ${base}/initrd.gz
+boot
+
+:rescue
+kernel --name k ../rescue/vmlinuz-${buildarch}
+initrd rescue.img
+imgargs k rescue/enable=true
+boot || goto failed
+
+:failed
+shell
+"#;
+
+    fn urls(entry: &Entry) -> Vec<(&str, &str)> {
+        entry
+            .files
+            .iter()
+            .map(|f| {
+                let kind = match f.search {
+                    SearchMethod::Inline => "inline",
+                    SearchMethod::Tftp => "tftp",
+                    _ => "https",
+                };
+                (kind, f.file.as_deref().or(f.content.as_deref()).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn turns_boots_into_entries() {
+        let import = parse(SCRIPT, "http://10.0.0.1/menus/boot.ipxe");
+        assert_eq!(import.entries.len(), 2);
+        let install = &import.entries[0];
+        assert_eq!(install.name, "Install Debian");
+        assert_eq!(install.protocol, Some(Protocol::Linux));
+        assert_eq!(
+            urls(install),
+            [
+                ("https", "http://10.0.0.1/debian/vmlinuz"),
+                ("https", "http://10.0.0.1/debian/initrd.gz"),
+                ("inline", "auto=true mac=${mac}"),
+            ]
+        );
+        let rescue = &import.entries[1];
+        assert_eq!(rescue.name, "rescue");
+        assert_eq!(
+            urls(rescue),
+            [
+                ("https", "http://10.0.0.1/rescue/vmlinuz-${arch}"),
+                ("https", "http://10.0.0.1/menus/rescue.img"),
+                ("inline", "rescue/enable=true"),
+            ]
+        );
+        assert_eq!(import.skipped, ["shell"]);
+    }
+
+    #[test]
+    fn names_unlabelled_boots() {
+        let import = parse(
+            "kernel tftp://10.0.0.1/k\nboot\nboot\n",
+            "tftp://10.0.0.1/x.ipxe",
+        );
+        assert_eq!(import.entries.len(), 1);
+        assert_eq!(import.entries[0].name, "iPXE 1");
+        assert_eq!(urls(&import.entries[0]), [("tftp", "tftp://10.0.0.1/k")]);
+        assert_eq!(import.skipped, ["boot"]);
+    }
+}
@@ -0,0 +1,190 @@
+//! The `Canicula` ELF note through which a kernel states what it needs
+//! from the loader, so a loader that cannot provide it refuses to boot
+//! instead of the kernel faulting on a missing BootInfo field.
+//!
+//! The note lives in a `PT_NOTE` segment with name `"Canicula"`, type
+//! [`NOTE_FEATURES`] and a 16-byte little-endian descriptor:
+//!
+//! ```text
+//! u32 flags (FEATURE_*)   u32 reserved   u64 minimum physical map size
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const NOTE_NAME: &[u8] = b"Canicula";
+pub const NOTE_FEATURES: u32 = 1;
+
+pub const FEATURE_FRAMEBUFFER: u32 = 1 << 0;
+pub const FEATURE_SMP: u32 = 1 << 1;
+pub const FEATURE_DTB: u32 = 1 << 2;
+
+const PT_NOTE: u32 = 4;
+
+/// What a kernel requires, or what a loader offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Features {
+    pub flags: u32,
+    /// Bytes of physical memory mapped at the physical memory offset.
+    pub phys_map: u64,
+}
+
+impl Features {
+    /// Requirements in `self` that `offered` does not meet, as messages.
+    pub fn unmet(&self, offered: &Features) -> Vec<String> {
+        let mut out = Vec::new();
+        for (flag, what) in [
+            (FEATURE_FRAMEBUFFER, "a framebuffer"),
+            (FEATURE_SMP, "SMP start-up"),
+            (FEATURE_DTB, "a device tree"),
+        ] {
+            if self.flags & flag != 0 && offered.flags & flag == 0 {
+                out.push(format!("kernel requires {}", what));
+            }
+        }
+        let unknown = self.flags & !(FEATURE_FRAMEBUFFER | FEATURE_SMP | FEATURE_DTB);
+        if unknown != 0 {
+            out.push(format!("kernel requires unknown features {:#x}", unknown));
+        }
+        if self.phys_map > offered.phys_map {
+            out.push(format!(
+                "kernel requires {:#x} bytes of physical map, loader maps {:#x}",
+                self.phys_map, offered.phys_map
+            ));
+        }
+        out
+    }
+}
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(b.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn align4(n: usize) -> Option<usize> {
+    n.checked_add(3).map(|n| n & !3)
+}
+
+/// Find the feature note in the notes of one segment.
+fn scan_notes(mut notes: &[u8]) -> Option<Option<Features>> {
+    while notes.len() >= 12 {
+        let namesz = u32_at(notes, 0)? as usize;
+        let descsz = u32_at(notes, 4)? as usize;
+        let kind = u32_at(notes, 8)?;
+        let name_end = 12usize.checked_add(namesz)?;
+        let desc_start = align4(name_end)?;
+        let desc_end = desc_start.checked_add(descsz)?;
+        let name = notes.get(12..name_end)?;
+        let desc = notes.get(desc_start..desc_end)?;
+        if name.strip_suffix(&[0]).unwrap_or(name) == NOTE_NAME && kind == NOTE_FEATURES {
+            return Some(Some(Features {
+                flags: u32_at(desc, 0)?,
+                phys_map: u64_at(desc, 8)?,
+            }));
+        }
+        notes = notes.get(align4(desc_end)?.min(notes.len())..)?;
+    }
+    Some(None)
+}
+
+/// The kernel's feature note, `Ok(None)` when it has none. Only 64-bit
+/// little-endian ELF files are read; a note that runs past its segment is
+/// an error.
+pub fn required(elf: &[u8]) -> Result<Option<Features>, &'static str> {
+    if elf.get(..4) != Some(b"\x7fELF") || elf.get(4) != Some(&2) || elf.get(5) != Some(&1) {
+        return Err("not a 64-bit little-endian ELF file");
+    }
+    let truncated = "truncated ELF program headers";
+    let phoff = u64_at(elf, 0x20).ok_or(truncated)? as usize;
+    let phentsize = u16_at(elf, 0x36).ok_or(truncated)? as usize;
+    let phnum = u16_at(elf, 0x38).ok_or(truncated)? as usize;
+
+    for i in 0..phnum {
+        let ph = phoff.checked_add(i * phentsize).ok_or(truncated)?;
+        if u32_at(elf, ph).ok_or(truncated)? != PT_NOTE {
+            continue;
+        }
+        let offset = u64_at(elf, ph + 8).ok_or(truncated)? as usize;
+        let size = u64_at(elf, ph + 32).ok_or(truncated)? as usize;
+        let notes = offset
+            .checked_add(size)
+            .and_then(|end| elf.get(offset..end))
+            .ok_or("note segment lies outside the file")?;
+        if let Some(found) = scan_notes(notes).ok_or("malformed ELF note")? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ELF header, one PT_NOTE program header and `notes`.
+    fn elf(notes: &[u8]) -> Vec<u8> {
+        let mut out = alloc::vec![0u8; 64 + 56];
+        out[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        out[0x20..0x28].copy_from_slice(&64u64.to_le_bytes());
+        out[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        out[0x38..0x3a].copy_from_slice(&1u16.to_le_bytes());
+        out[64..68].copy_from_slice(&PT_NOTE.to_le_bytes());
+        out[72..80].copy_from_slice(&120u64.to_le_bytes());
+        out[96..104].copy_from_slice(&(notes.len() as u64).to_le_bytes());
+        out.extend_from_slice(notes);
+        out
+    }
+
+    fn note(name: &[u8], kind: u32, desc: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(name);
+        out.resize(align4(out.len()).unwrap(), 0);
+        out.extend_from_slice(desc);
+        out.resize(align4(out.len()).unwrap(), 0);
+        out
+    }
+
+    #[test]
+    fn finds_features_after_other_notes() {
+        let mut desc = Vec::new();
+        desc.extend_from_slice(&(FEATURE_FRAMEBUFFER | FEATURE_SMP).to_le_bytes());
+        desc.extend_from_slice(&0u32.to_le_bytes());
+        desc.extend_from_slice(&(8u64 << 30).to_le_bytes());
+        let mut notes = note(b"GNU\0", 3, &[1, 2, 3, 4, 5]);
+        notes.extend(note(b"Canicula\0", NOTE_FEATURES, &desc));
+
+        let req = required(&elf(&notes)).unwrap().unwrap();
+        assert_eq!(req.flags, FEATURE_FRAMEBUFFER | FEATURE_SMP);
+        let offered = Features {
+            flags: FEATURE_FRAMEBUFFER,
+            phys_map: 4 << 30,
+        };
+        assert_eq!(
+            req.unmet(&offered),
+            [
+                "kernel requires SMP start-up",
+                "kernel requires 0x200000000 bytes of physical map, loader maps 0x100000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn kernels_without_the_note_need_nothing() {
+        assert_eq!(required(&elf(&note(b"GNU\0", 3, &[0; 4]))), Ok(None));
+        assert!(required(b"MZ").is_err());
+        let mut bad = note(b"Canicula\0", NOTE_FEATURES, &[0; 16]);
+        bad.truncate(20);
+        assert_eq!(required(&elf(&bad)), Err("malformed ELF note"));
+    }
+}
@@ -0,0 +1,44 @@
+//! Firmware-independent parts of alpheratz: configuration parsing and
+//! validation, entry ordering, variable expansion, DNS-over-HTTPS messages,
+//! device tree / FIT parsing, ACPI RSDP relocation, bsdiff patching, kernel
+//! feature notes, the Canicula loader info block, UKI `.osrel` metadata,
+//! SMBIOS machine strings, driver manifests, file type sniffing, the bzImage
+//! setup header, PXE boot server replies, iPXE script import, boot manager
+//! load options, GRUB environment blocks, Windows BCD stores, Linux
+//! hibernation signatures, SHA-1/SHA-256 and AES-GCM. Nothing here touches
+//! UEFI, so it builds for the host and is unit tested with a plain
+//! `cargo test`.
+
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
+
+pub mod acpi;
+pub mod aes_gcm;
+pub mod android;
+pub mod bcd;
+pub mod bsdiff;
+pub mod bzimage;
+pub mod config;
+pub mod dns;
+pub mod env;
+pub mod fdt;
+pub mod fit;
+pub mod grubenv;
+pub mod hex;
+pub mod hibernate;
+pub mod ipxe;
+pub mod kernel_note;
+pub mod load_option;
+pub mod loader_info;
+pub mod manifest;
+pub mod mat;
+pub mod pxe;
+pub mod sha1;
+pub mod sha256;
+pub mod smbios;
+pub mod sniff;
+pub mod symbols;
+pub mod uki;
+pub mod validate;
+pub mod vars;
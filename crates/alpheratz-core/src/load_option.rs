@@ -0,0 +1,94 @@
+//! UEFI boot manager variables: `Boot####` load options (EFI_LOAD_OPTION)
+//! and the `BootOrder` list, as the loader's fallback copy needs them.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// `LOAD_OPTION_ACTIVE`: the boot manager may boot the option.
+pub const ACTIVE: u32 = 0x0000_0001;
+
+/// An EFI_LOAD_OPTION named `description` that boots `device_path`, the
+/// bytes of a device path list ending in an end node.
+pub fn encode(description: &str, device_path: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&ACTIVE.to_le_bytes());
+    out.extend_from_slice(&(device_path.len() as u16).to_le_bytes());
+    for unit in description.encode_utf16().chain([0]) {
+        out.extend_from_slice(&unit.to_le_bytes());
+    }
+    out.extend_from_slice(device_path);
+    out
+}
+
+/// The description of an EFI_LOAD_OPTION.
+pub fn description(option: &[u8]) -> Option<String> {
+    let units: Vec<u16> = option
+        .get(6..)?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+/// Decode `BootOrder`, a list of little-endian option numbers.
+pub fn parse_order(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect()
+}
+
+pub fn encode_order(order: &[u16]) -> Vec<u8> {
+    order.iter().flat_map(|n| n.to_le_bytes()).collect()
+}
+
+/// `order` with `fallback` moved directly before `main`, or after it when
+/// not `first`. `main` is added at the front if missing.
+pub fn place(order: &[u16], main: u16, fallback: u16, first: bool) -> Vec<u16> {
+    let mut out: Vec<u16> = order.iter().copied().filter(|&n| n != fallback).collect();
+    let at = match out.iter().position(|&n| n == main) {
+        Some(at) => at,
+        None => {
+            out.insert(0, main);
+            0
+        }
+    };
+    out.insert(if first { at } else { at + 1 }, fallback);
+    out
+}
+
+/// Name of the `Boot####` variable for option `n`.
+pub fn variable_name(n: u16) -> String {
+    alloc::format!("Boot{:04X}", n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_description() {
+        let option = encode("Alpheratz (previous)", &[0x7f, 0xff, 4, 0]);
+        assert_eq!(&option[..6], [1, 0, 0, 0, 4, 0]);
+        assert_eq!(&option[option.len() - 4..], [0x7f, 0xff, 4, 0]);
+        assert_eq!(
+            description(&option).as_deref(),
+            Some("Alpheratz (previous)")
+        );
+        assert_eq!(description(&[1, 0]), None);
+    }
+
+    #[test]
+    fn places_fallback_around_main() {
+        let order = parse_order(&encode_order(&[3, 1, 7, 2]));
+        assert_eq!(order, [3, 1, 7, 2]);
+        assert_eq!(place(&order, 1, 7, true), [3, 7, 1, 2]);
+        assert_eq!(place(&[3, 7, 1, 2], 1, 7, false), [3, 1, 7, 2]);
+        assert_eq!(place(&[3, 2], 1, 7, false), [1, 7, 3, 2]);
+    }
+
+    #[test]
+    fn names_variables_in_upper_hex() {
+        assert_eq!(variable_name(0x2a), "Boot002A");
+    }
+}
@@ -0,0 +1,110 @@
+//! The `LoaderInfo` block handed to Canicula kernels beside `BootInfo`
+//! (which canicula-common defines, so it cannot grow loader-specific
+//! fields). All fields are little-endian u64s after an 8-byte header;
+//! `size` lets kernels tell which fields a loader knows about.
+//!
+//! ```text
+//! u32 magic = LOADER_INFO_MAGIC   u32 size (whole block)
+//! u64 initrd_addr   u64 initrd_len
+//! u64 cmdline_addr  u64 cmdline_len
+//! ```
+//!
+//! The command line is UTF-8 and NUL-terminated; `cmdline_len` excludes the
+//! NUL and is below [`CMDLINE_MAX`]. Unlike Linux's, it is never converted
+//! to UTF-16, so [`check_cmdline`] rejects what cannot be passed as is.
+
+use alloc::vec::Vec;
+
+/// `"ALDR"` read as a little-endian u32.
+pub const LOADER_INFO_MAGIC: u32 = u32::from_le_bytes(*b"ALDR");
+
+/// Size of the command line buffer, NUL included.
+pub const CMDLINE_MAX: usize = 4096;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoaderInfo {
+    pub magic: u32,
+    pub size: u32,
+    /// Physical address of the initrd, in pages of type
+    /// [`crate::config::MEMORY_TYPE_INITRD`]; 0 without one.
+    pub initrd_addr: u64,
+    pub initrd_len: u64,
+    /// Physical address of the command line; 0 without one.
+    pub cmdline_addr: u64,
+    pub cmdline_len: u64,
+}
+
+impl Default for LoaderInfo {
+    fn default() -> Self {
+        LoaderInfo {
+            magic: LOADER_INFO_MAGIC,
+            size: size_of::<LoaderInfo>() as u32,
+            initrd_addr: 0,
+            initrd_len: 0,
+            cmdline_addr: 0,
+            cmdline_len: 0,
+        }
+    }
+}
+
+impl LoaderInfo {
+    /// The block as it is laid out in memory.
+    pub fn to_bytes(&self) -> [u8; size_of::<LoaderInfo>()] {
+        let mut out = [0u8; size_of::<LoaderInfo>()];
+        out[0..4].copy_from_slice(&self.magic.to_le_bytes());
+        out[4..8].copy_from_slice(&self.size.to_le_bytes());
+        out[8..16].copy_from_slice(&self.initrd_addr.to_le_bytes());
+        out[16..24].copy_from_slice(&self.initrd_len.to_le_bytes());
+        out[24..32].copy_from_slice(&self.cmdline_addr.to_le_bytes());
+        out[32..40].copy_from_slice(&self.cmdline_len.to_le_bytes());
+        out
+    }
+}
+
+/// Why `cmdline` cannot be handed to a Canicula kernel, if it cannot.
+pub fn check_cmdline(cmdline: &str) -> Result<(), &'static str> {
+    if cmdline.contains('\0') {
+        return Err("cmdline contains a NUL byte");
+    }
+    if cmdline.len() >= CMDLINE_MAX {
+        return Err("cmdline is longer than 4095 bytes");
+    }
+    Ok(())
+}
+
+/// `cmdline` as handed over: its UTF-8 bytes and a terminating NUL.
+pub fn cmdline_bytes(cmdline: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(cmdline.len() + 1);
+    out.extend_from_slice(cmdline.as_bytes());
+    out.push(0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_match_the_repr_c_layout() {
+        let info = LoaderInfo {
+            initrd_addr: 0x1234_5000,
+            initrd_len: 0x800,
+            ..LoaderInfo::default()
+        };
+        let bytes = info.to_bytes();
+        assert_eq!(&bytes[..4], b"ALDR");
+        assert_eq!(bytes[4], 40);
+        let raw = unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const LoaderInfo) };
+        assert_eq!(raw, info);
+    }
+
+    #[test]
+    fn cmdline_limits() {
+        assert_eq!(check_cmdline("console=ttyS0 log=débug"), Ok(()));
+        assert!(check_cmdline("a\0b").is_err());
+        assert!(check_cmdline(&"x".repeat(CMDLINE_MAX - 1)).is_ok());
+        assert!(check_cmdline(&"x".repeat(CMDLINE_MAX)).is_err());
+        assert_eq!(cmdline_bytes("ab"), b"ab\0");
+    }
+}
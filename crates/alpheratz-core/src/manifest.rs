@@ -0,0 +1,130 @@
+//! Driver manifests (`driver_manifest`): `<sha256>  <path>` lines, in the
+//! format of sha256sum(1), naming the only drivers that may be loaded.
+//! Blank lines and `#` comments are ignored. The manifest is trusted through
+//! a detached signature checked against the Secure Boot `db`, whose
+//! signature lists [`signature_lists`] splits out.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// `(path, lowercase hex digest)` pairs, paths with `\` separators.
+    entries: Vec<(String, String)>,
+}
+
+/// Size of an EFI_SIGNATURE_LIST header: type GUID, then list, header and
+/// signature sizes.
+const SIGNATURE_LIST_HEADER: usize = 28;
+
+/// Split the contents of a `db` or `dbx` variable into its
+/// EFI_SIGNATURE_LISTs, stopping at the first malformed one.
+pub fn signature_lists(db: &[u8]) -> Vec<&[u8]> {
+    let mut lists = Vec::new();
+    let mut rest = db;
+    while let Some(size) = rest.get(16..20) {
+        let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+        if size < SIGNATURE_LIST_HEADER || size > rest.len() {
+            break;
+        }
+        let (list, tail) = rest.split_at(size);
+        lists.push(list);
+        rest = tail;
+    }
+    lists
+}
+
+/// `path` with `/` turned into `\`, for comparing ESP paths.
+fn esp_path(path: &str) -> String {
+    path.replace('/', "\\")
+}
+
+impl Manifest {
+    /// Parse a manifest, returning the 1-based number of the first line
+    /// that is not a 64-digit digest followed by a path.
+    pub fn parse(text: &str) -> Result<Manifest, usize> {
+        let mut entries = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (digest, path) = line.split_once(char::is_whitespace).ok_or(i + 1)?;
+            // sha256sum marks binary-mode lines with `*`.
+            let path = path.trim_start().trim_start_matches('*');
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(i + 1);
+            }
+            if path.is_empty() {
+                return Err(i + 1);
+            }
+            entries.push((esp_path(path), digest.to_ascii_lowercase()));
+        }
+        Ok(Manifest { entries })
+    }
+
+    /// The digest listed for `path`. FAT names are case-insensitive, so
+    /// paths compare that way too.
+    pub fn digest(&self, path: &str) -> Option<&str> {
+        let path = esp_path(path);
+        self.entries
+            .iter()
+            .find(|(p, _)| p.eq_ignore_ascii_case(&path))
+            .map(|(_, d)| d.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sha256sum_lines() {
+        let a = "ab".repeat(32);
+        let text = alloc::format!(
+            "# NIC drivers\n\n{}  \\EFI\\drivers\\e1000.efi\n{} */EFI/drivers/Ax88179.efi\n",
+            a.to_ascii_uppercase(),
+            "0".repeat(64)
+        );
+        let manifest = Manifest::parse(&text).unwrap();
+        assert_eq!(
+            manifest.digest("\\efi\\DRIVERS\\e1000.efi"),
+            Some(a.as_str())
+        );
+        assert_eq!(
+            manifest.digest("\\EFI\\drivers\\ax88179.efi"),
+            Some("0".repeat(64).as_str())
+        );
+        assert_eq!(manifest.digest("\\EFI\\drivers\\other.efi"), None);
+    }
+
+    fn signature_list(payload: usize) -> Vec<u8> {
+        let size = (SIGNATURE_LIST_HEADER + payload) as u32;
+        let mut list = alloc::vec![0xA5; 16];
+        list.extend_from_slice(&size.to_le_bytes());
+        list.extend_from_slice(&[0; 8]);
+        list.resize(size as usize, 0x5A);
+        list
+    }
+
+    #[test]
+    fn splits_signature_lists() {
+        let (a, b) = (signature_list(48), signature_list(0));
+        let mut db = [a.as_slice(), b.as_slice()].concat();
+        assert_eq!(signature_lists(&db), [a.as_slice(), b.as_slice()]);
+        // A list claiming more than is left ends the walk.
+        db.extend_from_slice(&signature_list(16)[..30]);
+        assert_eq!(signature_lists(&db).len(), 2);
+        assert!(signature_lists(&[0; 12]).is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(Manifest::parse("# ok\nabc  \\x.efi\n"), Err(2));
+        assert_eq!(Manifest::parse(&"0".repeat(64)), Err(1));
+        assert_eq!(
+            Manifest::parse(&alloc::format!("{}  ", "0".repeat(64))),
+            Err(1)
+        );
+    }
+}
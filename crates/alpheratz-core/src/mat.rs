@@ -0,0 +1,136 @@
+//! The EFI_MEMORY_ATTRIBUTES_TABLE, which splits runtime services images
+//! into code and data so they can be mapped RX and RW/NX.
+
+use alloc::vec::Vec;
+
+const EFI_MEMORY_RP: u64 = 0x2000;
+const EFI_MEMORY_XP: u64 = 0x4000;
+const EFI_MEMORY_RO: u64 = 0x20000;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// One runtime region and the access it should be mapped with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: u64,
+    pub size: u64,
+    pub write: bool,
+    pub exec: bool,
+}
+
+/// Bytes taken by the table at the start of `data`, header included.
+pub fn table_len(data: &[u8]) -> Option<usize> {
+    let u32_at = |at: usize| -> Option<usize> {
+        Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    let count = u32_at(4)?;
+    let desc_size = u32_at(8)?;
+    count.checked_mul(desc_size)?.checked_add(16)
+}
+
+/// The regions described by a table, sorted by address. `None` if the
+/// table is truncated or its descriptors are too small. Read-protected
+/// regions come out neither writable nor executable.
+pub fn parse(data: &[u8]) -> Option<Vec<Region>> {
+    let u64_at = |at: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+    };
+    let version = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+    let count = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+    let desc_size = u32::from_le_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+    if version == 0 || desc_size < 40 || data.len() < table_len(data)? {
+        return None;
+    }
+
+    let mut regions = Vec::with_capacity(count);
+    for i in 0..count {
+        let desc = 16 + i * desc_size;
+        let pages = u64_at(desc + 24)?;
+        let attr = u64_at(desc + 32)?;
+        let denied = attr & EFI_MEMORY_RP != 0;
+        regions.push(Region {
+            start: u64_at(desc + 8)?,
+            size: pages.checked_mul(PAGE_SIZE)?,
+            write: !denied && attr & EFI_MEMORY_RO == 0,
+            exec: !denied && attr & EFI_MEMORY_XP == 0,
+        });
+    }
+    regions.sort_unstable_by_key(|r| r.start);
+    Some(regions)
+}
+
+/// The parts of `start..end` not covered by `regions` (sorted, as from
+/// [`parse`]), as `(start, size)` pairs.
+pub fn gaps(start: u64, end: u64, regions: &[Region]) -> Vec<(u64, u64)> {
+    let mut out = Vec::new();
+    let mut at = start;
+    for r in regions {
+        let r_end = r.start.saturating_add(r.size);
+        if r_end <= at || r.start >= end {
+            continue;
+        }
+        if r.start > at {
+            out.push((at, r.start - at));
+        }
+        at = r_end;
+    }
+    if at < end {
+        out.push((at, end - at));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(entries: &[(u64, u64, u64)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        out.extend_from_slice(&48u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        for &(start, pages, attr) in entries {
+            out.extend_from_slice(&5u32.to_le_bytes());
+            out.extend_from_slice(&0u32.to_le_bytes());
+            out.extend_from_slice(&start.to_le_bytes());
+            out.extend_from_slice(&0u64.to_le_bytes());
+            out.extend_from_slice(&pages.to_le_bytes());
+            out.extend_from_slice(&(attr | 1 << 63).to_le_bytes());
+            out.extend_from_slice(&[0; 8]);
+        }
+        out
+    }
+
+    #[test]
+    fn splits_code_and_data() {
+        let data = table(&[
+            (0x7f01_0000, 2, EFI_MEMORY_XP),
+            (0x7f00_0000, 16, EFI_MEMORY_RO),
+        ]);
+        assert_eq!(table_len(&data), Some(data.len()));
+        let regions = parse(&data).unwrap();
+        assert_eq!(
+            regions,
+            [
+                Region {
+                    start: 0x7f00_0000,
+                    size: 0x10000,
+                    write: false,
+                    exec: true,
+                },
+                Region {
+                    start: 0x7f01_0000,
+                    size: 0x2000,
+                    write: true,
+                    exec: false,
+                },
+            ]
+        );
+        assert_eq!(
+            gaps(0, 0x1_0000_0000, &regions),
+            [(0, 0x7f00_0000), (0x7f01_2000, 0x1_0000_0000 - 0x7f01_2000)]
+        );
+        assert!(parse(&data[..data.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! The boot server a PXE ROM was sent to, read from the DHCP reply it cached,
+//! so `tftp` files can be named relative to where the loader came from.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::vars::{self, ipv4_to_string, parse_ipv4};
+
+const BOOTREPLY: u8 = 2;
+const MAGIC: [u8; 4] = [99, 130, 83, 99];
+
+const OPT_PAD: u8 = 0;
+const OPT_OVERLOAD: u8 = 52;
+const OPT_TFTP_SERVER: u8 = 66;
+const OPT_BOOTFILE: u8 = 67;
+const OPT_END: u8 = 255;
+
+/// Where a PXE ROM fetched its boot file from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootServer {
+    pub server: [u8; 4],
+    /// The boot file's path on the server, as DHCP gave it.
+    pub file: String,
+}
+
+fn c_string(field: &[u8]) -> Option<&str> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..end]).ok()
+}
+
+impl BootServer {
+    /// Read a BOOTP/DHCP reply. The TFTP server name (option 66) wins over
+    /// `siaddr` when it is an address, and the bootfile name (option 67) over
+    /// the `file` field. `None` when no server is named.
+    pub fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() < 240 || packet[0] != BOOTREPLY {
+            return None;
+        }
+        let mut server: [u8; 4] = packet[20..24].try_into().ok()?;
+        let mut file = c_string(&packet[108..236]);
+        let mut overload = 0;
+
+        if packet[236..240] == MAGIC {
+            let mut at = 240;
+            while let Some(&code) = packet.get(at) {
+                match code {
+                    OPT_PAD => {
+                        at += 1;
+                        continue;
+                    }
+                    OPT_END => break,
+                    _ => {}
+                }
+                let len = *packet.get(at + 1)? as usize;
+                let data = packet.get(at + 2..at + 2 + len)?;
+                match code {
+                    OPT_OVERLOAD => overload = data.first().copied().unwrap_or(0),
+                    OPT_TFTP_SERVER => {
+                        if let Some(ip) = c_string(data).and_then(parse_ipv4) {
+                            server = ip;
+                        }
+                    }
+                    OPT_BOOTFILE => file = c_string(data),
+                    _ => {}
+                }
+                at += 2 + len;
+            }
+        }
+        // An overloaded `file` field holds more options, not a name.
+        if overload & 1 != 0 && file == c_string(&packet[108..236]) {
+            file = None;
+        }
+
+        (server != [0; 4]).then(|| BootServer {
+            server,
+            file: String::from(file.unwrap_or("")),
+        })
+    }
+
+    /// `tftp://<server>/<directory of the boot file>`, which relative `tftp`
+    /// paths resolve against.
+    pub fn origin(&self) -> String {
+        let url = format!(
+            "tftp://{}/{}",
+            ipv4_to_string(self.server),
+            self.file.trim_start_matches('/')
+        );
+        vars::origin(&url).map(String::from).unwrap_or(url)
+    }
+}
+
+/// The server and path of a `tftp://<IPv4>/<path>` URL.
+pub fn split_url(url: &str) -> Option<([u8; 4], &str)> {
+    let rest = url.strip_prefix("tftp://")?;
+    let (host, path) = rest.split_once('/')?;
+    Some((parse_ipv4(host)?, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn reply(siaddr: [u8; 4], file: &str, options: &[u8]) -> Vec<u8> {
+        let mut p = vec![0u8; 240];
+        p[0] = BOOTREPLY;
+        p[20..24].copy_from_slice(&siaddr);
+        p[108..108 + file.len()].copy_from_slice(file.as_bytes());
+        p[236..240].copy_from_slice(&MAGIC);
+        p.extend_from_slice(options);
+        p
+    }
+
+    #[test]
+    fn reads_the_boot_server() {
+        let p = reply([10, 0, 0, 1], "/pxe/efi/alpheratz.efi", &[OPT_END]);
+        let boot = BootServer::parse(&p).unwrap();
+        assert_eq!(boot.server, [10, 0, 0, 1]);
+        assert_eq!(boot.origin(), "tftp://10.0.0.1/pxe/efi");
+
+        let mut opts = vec![OPT_PAD, OPT_TFTP_SERVER, 8];
+        opts.extend_from_slice(b"10.0.0.9");
+        opts.extend_from_slice(&[OPT_BOOTFILE, 5]);
+        opts.extend_from_slice(b"a.efi");
+        opts.push(OPT_END);
+        let boot = BootServer::parse(&reply([10, 0, 0, 1], "x/y.efi", &opts)).unwrap();
+        assert_eq!(boot.server, [10, 0, 0, 9]);
+        assert_eq!(boot.origin(), "tftp://10.0.0.9");
+
+        let overloaded = reply(
+            [10, 0, 0, 1],
+            "\x03\x01\x00",
+            &[OPT_OVERLOAD, 1, 1, OPT_END],
+        );
+        assert_eq!(BootServer::parse(&overloaded).unwrap().file, "");
+        assert_eq!(BootServer::parse(&reply([0; 4], "a.efi", &[])), None);
+        assert_eq!(BootServer::parse(&p[..200]), None);
+    }
+
+    #[test]
+    fn splits_tftp_urls() {
+        assert_eq!(
+            split_url("tftp://10.0.0.1/pxe/vmlinuz"),
+            Some(([10, 0, 0, 1], "pxe/vmlinuz"))
+        );
+        assert_eq!(split_url("tftp://boot.example/k"), None);
+        assert_eq!(split_url("http://10.0.0.1/k"), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! The SMBIOS strings entries can be conditioned on (`when.smbios_*`):
+//! system manufacturer, product and family (type 1) and baseboard
+//! manufacturer and product (type 2).
+
+use alloc::string::String;
+
+const TYPE_SYSTEM: u8 = 1;
+const TYPE_BASEBOARD: u8 = 2;
+const TYPE_END: u8 = 127;
+
+/// Identification strings of the machine; `None` where SMBIOS has none.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Machine {
+    pub vendor: Option<String>,
+    pub product: Option<String>,
+    pub family: Option<String>,
+    pub board_vendor: Option<String>,
+    pub board_product: Option<String>,
+}
+
+/// String number `index` (1-based, 0 for none) of the string set starting
+/// at `strings`.
+fn string_at(strings: &[u8], index: u8) -> Option<String> {
+    if index == 0 {
+        return None;
+    }
+    let s = strings.split(|&b| b == 0).nth(index as usize - 1)?;
+    let s = core::str::from_utf8(s).ok()?.trim();
+    (!s.is_empty()).then(|| String::from(s))
+}
+
+/// Read the machine strings from an SMBIOS structure table (the area the
+/// entry point's table address points to, not the entry point itself).
+pub fn parse(table: &[u8]) -> Machine {
+    let mut machine = Machine::default();
+    let mut at = 0;
+    while let Some(header) = table.get(at..at + 2) {
+        let (ty, len) = (header[0], header[1] as usize);
+        let Some(formatted) = table.get(at..at + len).filter(|_| len >= 4) else {
+            break;
+        };
+        // The string set ends with two NULs.
+        let rest = &table[at + len..];
+        let Some(end) = rest.windows(2).position(|w| w == [0, 0]) else {
+            break;
+        };
+        let strings = &rest[..end + 1];
+        let field = |off: usize| string_at(strings, formatted.get(off).copied().unwrap_or(0));
+        match ty {
+            TYPE_SYSTEM => {
+                machine.vendor = field(4);
+                machine.product = field(5);
+                machine.family = field(0x1A);
+            }
+            TYPE_BASEBOARD => {
+                machine.board_vendor = field(4);
+                machine.board_product = field(5);
+            }
+            TYPE_END => break,
+            _ => {}
+        }
+        at += len + end + 2;
+    }
+    machine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn structure(ty: u8, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut out = Vec::from([ty, formatted.len() as u8 + 4, 0, 0]);
+        out.extend_from_slice(formatted);
+        for s in strings {
+            out.extend_from_slice(s.as_bytes());
+            out.push(0);
+        }
+        if strings.is_empty() {
+            out.push(0);
+        }
+        out.push(0);
+        out
+    }
+
+    #[test]
+    fn reads_system_and_board_strings() {
+        let mut system = [0u8; 0x17];
+        system[0] = 1; // manufacturer
+        system[1] = 2; // product
+        system[0x16] = 3; // family, at offset 0x1A
+        let mut table = structure(1, &system, &["Dell Inc.", "PowerEdge R650 ", "PowerEdge"]);
+        table.extend(structure(2, &[1, 0], &["Dell Inc."]));
+        table.extend(structure(127, &[], &[]));
+        table.extend(structure(1, &[1], &["after the end"]));
+        let machine = parse(&table);
+        assert_eq!(machine.vendor.as_deref(), Some("Dell Inc."));
+        assert_eq!(machine.product.as_deref(), Some("PowerEdge R650"));
+        assert_eq!(machine.family.as_deref(), Some("PowerEdge"));
+        assert_eq!(machine.board_vendor.as_deref(), Some("Dell Inc."));
+        assert_eq!(machine.board_product, None);
+    }
+
+    #[test]
+    fn stops_at_truncated_tables() {
+        let table = structure(1, &[1, 2], &["Vendor", "Product"]);
+        assert_eq!(parse(&table[..table.len() - 1]), Machine::default());
+        assert_eq!(parse(&[1, 2]), Machine::default());
+    }
+}
@@ -0,0 +1,238 @@
+//! Telling what a resolved file is from its first bytes, to catch a file
+//! that cannot work for its entry (an ELF kernel under `protocol = "linux"`,
+//! an HTML error page saved as the initrd) before the loader fails on it.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::bzimage::Header;
+use crate::config::{FileType, Protocol};
+use crate::validate::{Severity, type_name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Pe,
+    Elf,
+    Gzip,
+    Xz,
+    Zstd,
+    Lz4,
+    Bzip2,
+    Lzma,
+    Cpio,
+    /// An x86 Linux kernel that is not also a PE/COFF image.
+    BzImage,
+    /// A flattened device tree, which is also what a FIT image is.
+    Dtb,
+    AndroidBoot,
+    VendorBoot,
+    Html,
+    Text,
+    Unknown,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Pe => "a PE/COFF image",
+            Kind::Elf => "an ELF image",
+            Kind::Gzip => "gzip data",
+            Kind::Xz => "xz data",
+            Kind::Zstd => "zstd data",
+            Kind::Lz4 => "lz4 data",
+            Kind::Bzip2 => "bzip2 data",
+            Kind::Lzma => "lzma data",
+            Kind::Cpio => "a cpio archive",
+            Kind::BzImage => "a bzImage without an EFI stub",
+            Kind::Dtb => "a device tree",
+            Kind::AndroidBoot => "an Android boot image",
+            Kind::VendorBoot => "an Android vendor boot image",
+            Kind::Html => "an HTML page",
+            Kind::Text => "text",
+            Kind::Unknown => "unrecognised data",
+        }
+    }
+
+    fn compressed(self) -> bool {
+        matches!(
+            self,
+            Kind::Gzip | Kind::Xz | Kind::Zstd | Kind::Lz4 | Kind::Bzip2 | Kind::Lzma
+        )
+    }
+}
+
+/// What `data` looks like, from its magic number.
+pub fn sniff(data: &[u8]) -> Kind {
+    const MAGICS: &[(&[u8], Kind)] = &[
+        (b"MZ", Kind::Pe),
+        (b"\x7fELF", Kind::Elf),
+        (b"\x1f\x8b", Kind::Gzip),
+        (b"\xfd7zXZ\0", Kind::Xz),
+        (b"\x28\xb5\x2f\xfd", Kind::Zstd),
+        (b"\x04\x22\x4d\x18", Kind::Lz4),
+        (b"\x02\x21\x4c\x18", Kind::Lz4),
+        (b"BZh", Kind::Bzip2),
+        (b"\x5d\0\0", Kind::Lzma),
+        (b"070701", Kind::Cpio),
+        (b"070702", Kind::Cpio),
+        (b"070707", Kind::Cpio),
+        (b"\xd0\x0d\xfe\xed", Kind::Dtb),
+        (b"ANDROID!", Kind::AndroidBoot),
+        (b"VNDRBOOT", Kind::VendorBoot),
+    ];
+    if let Some(&(_, kind)) = MAGICS.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return kind;
+    }
+    if Header::parse(data).is_some() {
+        return Kind::BzImage;
+    }
+    let head = &data[..data.len().min(512)];
+    let text = head.trim_ascii_start();
+    let lower = |s: &[u8], prefix: &[u8]| {
+        s.len() >= prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+    if lower(text, b"<!doctype html") || lower(text, b"<html") || lower(text, b"<?xml") {
+        return Kind::Html;
+    }
+    if !head.is_empty() && core::str::from_utf8(head).is_ok() && !head.contains(&0) {
+        return Kind::Text;
+    }
+    Kind::Unknown
+}
+
+/// Whether a Multiboot 1 header (magic, flags and a checksum summing to
+/// zero) sits 4-byte aligned in the first 8 KiB of `data`.
+fn has_multiboot_header(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(8192)];
+    head.chunks_exact(4)
+        .enumerate()
+        .filter(|(_, w)| *w == 0x1BADB002u32.to_le_bytes())
+        .any(|(i, _)| {
+            let word = |n: usize| {
+                head.get((i + n) * 4..(i + n) * 4 + 4)
+                    .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            };
+            match (word(1), word(2)) {
+                (Some(flags), Some(sum)) => {
+                    0x1BADB002u32.wrapping_add(flags).wrapping_add(sum) == 0
+                }
+                _ => false,
+            }
+        })
+}
+
+/// Check a `file_type` file of an entry booted with `protocol` against what
+/// it looks like. An error means it cannot boot; a warning that it may.
+pub fn check(
+    protocol: Option<Protocol>,
+    file_type: FileType,
+    data: &[u8],
+) -> Option<(Severity, String)> {
+    let kind = sniff(data);
+    let what = type_name(file_type);
+    let error = |hint: &str| {
+        Some((
+            Severity::Error,
+            format!("{} is {}{}", what, kind.name(), hint),
+        ))
+    };
+    if kind == Kind::Html && file_type != FileType::Symbols {
+        return error("; the server may have sent an error page");
+    }
+    match file_type {
+        FileType::Kernel => match protocol? {
+            Protocol::Linux | Protocol::Efi if kind != Kind::Pe => error(match kind {
+                Kind::Elf => ", not an EFI image; ELF kernels need multiboot1 or canicula",
+                Kind::BzImage => "; rebuild it with CONFIG_EFI_STUB=y",
+                k if k.compressed() => ", not an EFI image; boot the uncompressed kernel",
+                _ => ", not an EFI image",
+            }),
+            Protocol::Canicula if kind != Kind::Elf => error(", not an ELF image"),
+            Protocol::Multiboot1 if !has_multiboot_header(data) => {
+                error(" without a Multiboot header in its first 8 KiB")
+            }
+            _ => None,
+        },
+        FileType::Initrd => {
+            let expected = kind == Kind::Cpio || kind.compressed();
+            (!expected && protocol == Some(Protocol::Linux)).then(|| {
+                (
+                    Severity::Warning,
+                    format!(
+                        "initrd is {}, not a cpio archive or compressed data",
+                        kind.name()
+                    ),
+                )
+            })
+        }
+        FileType::Fit if kind != Kind::Dtb => error(", not a FIT image"),
+        FileType::AndroidBoot if kind != Kind::AndroidBoot => error(""),
+        FileType::VendorBoot if kind != Kind::VendorBoot => error(""),
+        FileType::Cmdline if kind == Kind::Unknown => Some((
+            Severity::Warning,
+            String::from("cmdline is binary data, not text"),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn sniffs_magic_numbers() {
+        assert_eq!(sniff(b"MZ\x90\0"), Kind::Pe);
+        assert_eq!(sniff(b"\x7fELF\x02\x01"), Kind::Elf);
+        assert_eq!(sniff(b"07070100000001"), Kind::Cpio);
+        assert_eq!(sniff(b"\xd0\x0d\xfe\xed\0\0"), Kind::Dtb);
+        assert_eq!(sniff(b"\n  <!DOCTYPE html><html>"), Kind::Html);
+        assert_eq!(sniff(b"console=ttyS0"), Kind::Text);
+        assert_eq!(sniff(b"\x00\x01\x02"), Kind::Unknown);
+
+        let mut bzimage = vec![0u8; 0x210];
+        bzimage[0x1FE..0x206].copy_from_slice(b"\x55\xaa\xeb\x66HdrS");
+        assert_eq!(sniff(&bzimage), Kind::BzImage);
+        bzimage[..2].copy_from_slice(b"MZ");
+        assert_eq!(sniff(&bzimage), Kind::Pe);
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let elf = b"\x7fELF\x02\x01\x01";
+        let (severity, message) = check(Some(Protocol::Linux), FileType::Kernel, elf).unwrap();
+        assert_eq!(severity, Severity::Error);
+        assert!(message.starts_with("kernel is an ELF image, not an EFI image"));
+        assert_eq!(check(Some(Protocol::Canicula), FileType::Kernel, elf), None);
+        assert_eq!(check(Some(Protocol::Linux), FileType::Kernel, b"MZ"), None);
+
+        let page = b"<html><body>404 Not Found</body></html>";
+        let (severity, message) = check(Some(Protocol::Linux), FileType::Initrd, page).unwrap();
+        assert_eq!(severity, Severity::Error);
+        assert_eq!(
+            message,
+            "initrd is an HTML page; the server may have sent an error page"
+        );
+        let (severity, _) = check(Some(Protocol::Linux), FileType::Initrd, b"\0\0\0\0").unwrap();
+        assert_eq!(severity, Severity::Warning);
+        assert_eq!(
+            check(Some(Protocol::Linux), FileType::Initrd, b"\x1f\x8b\x08"),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_multiboot_headers() {
+        let mut kernel = vec![0u8; 64];
+        kernel[16..20].copy_from_slice(&0x1BADB002u32.to_le_bytes());
+        kernel[20..24].copy_from_slice(&3u32.to_le_bytes());
+        assert!(check(Some(Protocol::Multiboot1), FileType::Kernel, &kernel).is_some());
+        let sum = 0u32.wrapping_sub(0x1BADB002 + 3);
+        kernel[24..28].copy_from_slice(&sum.to_le_bytes());
+        assert_eq!(
+            check(Some(Protocol::Multiboot1), FileType::Kernel, &kernel),
+            None
+        );
+    }
+}
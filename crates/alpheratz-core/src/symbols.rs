@@ -0,0 +1,161 @@
+//! Symbol maps for kernel debugging, in `System.map` form: one
+//! `<address> <type> <name>` line per symbol, sorted by address. Built from
+//! an ELF `.symtab` when the entry has no separate symbols file.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+const SHT_SYMTAB: u32 = 2;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STB_LOCAL: u8 = 0;
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        b.get(at..at.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        b.get(at..at.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn u64_at(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        b.get(at..at.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
+
+/// `(offset, size, type, link)` of section header `index`.
+fn section(elf: &[u8], index: usize) -> Option<(usize, usize, u32, u32)> {
+    let shoff = u64_at(elf, 0x28)? as usize;
+    let shentsize = u16_at(elf, 0x3a)? as usize;
+    let sh = shoff.checked_add(index.checked_mul(shentsize)?)?;
+    let kind = u32_at(elf, sh + 4)?;
+    let offset = u64_at(elf, sh + 24)? as usize;
+    let size = u64_at(elf, sh + 32)? as usize;
+    let link = u32_at(elf, sh + 40)?;
+    Some((offset, size, kind, link))
+}
+
+fn contents(elf: &[u8], offset: usize, size: usize) -> Option<&[u8]> {
+    elf.get(offset..offset.checked_add(size)?)
+}
+
+/// A `System.map` of the function and data symbols in a 64-bit
+/// little-endian ELF's `.symtab`; `None` if it has none or is malformed.
+pub fn from_elf(elf: &[u8]) -> Option<Vec<u8>> {
+    if elf.get(..6)? != b"\x7fELF\x02\x01" {
+        return None;
+    }
+    let shnum = u16_at(elf, 0x3c)? as usize;
+    let (offset, size, link) = (0..shnum)
+        .filter_map(|i| section(elf, i))
+        .find(|s| s.2 == SHT_SYMTAB)
+        .map(|(offset, size, _, link)| (offset, size, link))?;
+    let symtab = contents(elf, offset, size)?;
+    let (str_off, str_size, _, _) = section(elf, link as usize)?;
+    let strtab = contents(elf, str_off, str_size)?;
+
+    let mut symbols = Vec::new();
+    for sym in symtab.chunks_exact(24) {
+        let info = sym[4];
+        let letter = match info & 0xf {
+            STT_FUNC => 'T',
+            STT_OBJECT => 'D',
+            _ => continue,
+        };
+        let value = u64_at(sym, 8)?;
+        let name_off = u32_at(sym, 0)? as usize;
+        let name = strtab.get(name_off..)?;
+        let name = &name[..name.iter().position(|&b| b == 0)?];
+        if value == 0 || name.is_empty() {
+            continue;
+        }
+        let letter = if info >> 4 == STB_LOCAL {
+            letter.to_ascii_lowercase()
+        } else {
+            letter
+        };
+        symbols.push((value, letter, core::str::from_utf8(name).ok()?));
+    }
+    if symbols.is_empty() {
+        return None;
+    }
+    symbols.sort_unstable_by_key(|s| s.0);
+
+    let mut out = Vec::new();
+    for (value, letter, name) in symbols {
+        out.extend_from_slice(format!("{:016x} {} {}\n", value, letter, name).as_bytes());
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: u32, info: u8, value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&name.to_le_bytes());
+        out.extend_from_slice(&[info, 0, 1, 0]);
+        out.extend_from_slice(&value.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out
+    }
+
+    fn section_header(kind: u32, offset: usize, size: usize, link: u32) -> Vec<u8> {
+        let mut out = alloc::vec![0u8; 64];
+        out[4..8].copy_from_slice(&kind.to_le_bytes());
+        out[24..32].copy_from_slice(&(offset as u64).to_le_bytes());
+        out[32..40].copy_from_slice(&(size as u64).to_le_bytes());
+        out[40..44].copy_from_slice(&link.to_le_bytes());
+        out
+    }
+
+    /// An ELF with a null section, `.symtab` and `.strtab`.
+    fn elf() -> Vec<u8> {
+        let strtab = b"\0kmain\0panic_count\0local_fn\0section\0";
+        let mut symtab = symbol(0, 0, 0);
+        symtab.extend(symbol(1, 0x12, 0xffff_8000_0000_2000));
+        symtab.extend(symbol(7, 0x11, 0xffff_8000_0001_0000));
+        symtab.extend(symbol(19, 0x02, 0xffff_8000_0000_1000));
+        symtab.extend(symbol(28, 0x03, 0xffff_8000_0000_0000));
+
+        let mut out = alloc::vec![0u8; 64];
+        out[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        let sym_off = out.len();
+        out.extend_from_slice(&symtab);
+        let str_off = out.len();
+        out.extend_from_slice(strtab);
+        let shoff = out.len();
+        out.extend(section_header(0, 0, 0, 0));
+        out.extend(section_header(SHT_SYMTAB, sym_off, symtab.len(), 2));
+        out.extend(section_header(3, str_off, strtab.len(), 0));
+        out[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        out[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        out[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn lists_functions_and_data_by_address() {
+        let map = from_elf(&elf()).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&map).unwrap(),
+            "ffff800000001000 t local_fn\n\
+             ffff800000002000 T kmain\n\
+             ffff800000010000 D panic_count\n"
+        );
+    }
+
+    #[test]
+    fn stripped_or_foreign_files_have_no_map() {
+        let mut stripped = elf();
+        stripped[0x3c] = 1;
+        assert!(from_elf(&stripped).is_none());
+        assert!(from_elf(b"MZ\x90\0").is_none());
+    }
+}
@@ -0,0 +1,173 @@
+//! Unified kernel images and other PE files: section lookup, the
+//! `.osrel` section's os-release(5) fields used to describe discovered
+//! entries, and whether an image carries an Authenticode signature.
+
+use alloc::string::String;
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The raw contents of PE section `name` in `image`, cut to its virtual
+/// size when that is smaller than the file size.
+pub fn section<'a>(image: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    if image.get(..2)? != b"MZ" {
+        return None;
+    }
+    let pe = u32_at(image, 0x3C)? as usize;
+    if image.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let count = u16_at(image, pe + 6)? as usize;
+    let optional = u16_at(image, pe + 20)? as usize;
+    let table = pe + 24 + optional;
+    (0..count).find_map(|i| {
+        let header = table + i * 40;
+        let raw_name = image.get(header..header + 8)?;
+        let len = raw_name.iter().position(|&b| b == 0).unwrap_or(8);
+        if &raw_name[..len] != name.as_bytes() {
+            return None;
+        }
+        let virtual_size = u32_at(image, header + 8)? as usize;
+        let raw_size = u32_at(image, header + 16)? as usize;
+        let offset = u32_at(image, header + 20)? as usize;
+        let size = if virtual_size != 0 {
+            virtual_size.min(raw_size)
+        } else {
+            raw_size
+        };
+        image.get(offset..offset.checked_add(size)?)
+    })
+}
+
+/// Whether `image` is a PE file with a non-empty certificate table (the
+/// security data directory) inside the file, i.e. Authenticode-signed.
+/// Whether the signature is trusted is for firmware to decide.
+pub fn is_signed(image: &[u8]) -> bool {
+    (|| {
+        if image.get(..2)? != b"MZ" {
+            return None;
+        }
+        let pe = u32_at(image, 0x3C)? as usize;
+        if image.get(pe..pe + 4)? != b"PE\0\0" {
+            return None;
+        }
+        let optional = pe + 24;
+        let directories = match u16_at(image, optional)? {
+            0x10B => optional + 96,
+            0x20B => optional + 112,
+            _ => return None,
+        };
+        let count = u32_at(image, directories - 4)?;
+        if count <= 4 {
+            return None;
+        }
+        // Entry 4; its address is a file offset, not an RVA.
+        let offset = u32_at(image, directories + 32)? as usize;
+        let size = u32_at(image, directories + 36)? as usize;
+        let end = offset.checked_add(size)?;
+        Some(size != 0 && end <= image.len())
+    })()
+    .unwrap_or(false)
+}
+
+/// What the menu shows of an os-release file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OsRelease {
+    /// `PRETTY_NAME`, else `NAME`.
+    pub name: Option<String>,
+    /// `VERSION`, else `VERSION_ID`.
+    pub version: Option<String>,
+}
+
+/// Parse os-release(5) text: `KEY=value` lines, values optionally quoted.
+pub fn os_release(text: &str) -> OsRelease {
+    let mut pretty_name = None;
+    let mut name = None;
+    let mut version = None;
+    let mut version_id = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value);
+        let slot = match key {
+            "PRETTY_NAME" => &mut pretty_name,
+            "NAME" => &mut name,
+            "VERSION" => &mut version,
+            "VERSION_ID" => &mut version_id,
+            _ => continue,
+        };
+        *slot = (!value.is_empty()).then(|| String::from(value));
+    }
+    OsRelease {
+        name: pretty_name.or(name),
+        version: version.or(version_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn finds_a_section() {
+        let mut image = vec![0u8; 0x200];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        image[0x40..0x44].copy_from_slice(b"PE\0\0");
+        image[0x46..0x48].copy_from_slice(&2u16.to_le_bytes());
+        // No optional header: the section table follows at 0x58.
+        for (i, (name, offset)) in [(&b".text\0\0\0"[..], 0x100u32), (b".osrel\0\0", 0x180)]
+            .into_iter()
+            .enumerate()
+        {
+            let h = 0x58 + i * 40;
+            image[h..h + 8].copy_from_slice(name);
+            image[h + 8..h + 12].copy_from_slice(&5u32.to_le_bytes());
+            image[h + 16..h + 20].copy_from_slice(&0x20u32.to_le_bytes());
+            image[h + 20..h + 24].copy_from_slice(&offset.to_le_bytes());
+        }
+        image[0x180..0x185].copy_from_slice(b"ID=x\n");
+        assert_eq!(section(&image, ".osrel"), Some(&b"ID=x\n"[..]));
+        assert_eq!(section(&image, ".linux"), None);
+        assert_eq!(section(b"\x7fELF", ".osrel"), None);
+    }
+
+    #[test]
+    fn detects_a_certificate_table() {
+        let mut image = vec![0u8; 0x200];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        image[0x40..0x44].copy_from_slice(b"PE\0\0");
+        // PE32+ optional header at 0x58 with 16 data directories from 0xC8.
+        image[0x58..0x5A].copy_from_slice(&0x20Bu16.to_le_bytes());
+        image[0xC4..0xC8].copy_from_slice(&16u32.to_le_bytes());
+        assert!(!is_signed(&image));
+        image[0xE8..0xEC].copy_from_slice(&0x180u32.to_le_bytes());
+        image[0xEC..0xF0].copy_from_slice(&0x80u32.to_le_bytes());
+        assert!(is_signed(&image));
+        assert!(!is_signed(&image[..0x1FF]));
+        assert!(!is_signed(b"MZ"));
+    }
+
+    #[test]
+    fn parses_os_release() {
+        let rel = os_release("NAME=Fedora\nPRETTY_NAME=\"Fedora Linux 40\"\nVERSION_ID=40\n");
+        assert_eq!(rel.name.as_deref(), Some("Fedora Linux 40"));
+        assert_eq!(rel.version.as_deref(), Some("40"));
+        let rel = os_release("# comment\nNAME='Arch'\nVERSION=\"\"\n");
+        assert_eq!(rel.name.as_deref(), Some("Arch"));
+        assert_eq!(rel.version, None);
+    }
+}
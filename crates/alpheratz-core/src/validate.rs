@@ -0,0 +1,776 @@
+//! Checks on a parsed [`Config`] that TOML deserialization cannot express:
+//! field combinations that can never boot, unresolvable variables, and
+//! settings that point at nothing.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::config::{
+    Action, BootFile, Config, Default, Entry, FileType, Iscsi, MEMORY_TYPE_BOOT_INFO,
+    MEMORY_TYPE_INITRD, MEMORY_TYPE_OEM_MIN, Protocol, SearchMethod,
+};
+use crate::{dns, loader_info, vars};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The entry cannot work as written.
+    Error,
+    /// Suspicious, but may be intended.
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Name of the entry the issue is in, `None` for global settings.
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+impl core::fmt::Display for Issue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.entry {
+            Some(name) => write!(f, "{}: entry \"{}\": {}", level, name, self.message),
+            None => write!(f, "{}: {}", level, self.message),
+        }
+    }
+}
+
+struct Report<'a> {
+    entry: Option<&'a Entry>,
+    issues: Vec<Issue>,
+}
+
+impl Report<'_> {
+    fn push(&mut self, severity: Severity, message: String) {
+        self.issues.push(Issue {
+            severity,
+            entry: self.entry.map(|e| e.name.clone()),
+            message,
+        });
+    }
+}
+
+/// How `t` is spelled in the config.
+pub fn type_name(t: FileType) -> &'static str {
+    match t {
+        FileType::Kernel => "kernel",
+        FileType::Initrd => "initrd",
+        FileType::Cmdline => "cmdline",
+        FileType::Fit => "fit",
+        FileType::AndroidBoot => "android-boot",
+        FileType::VendorBoot => "vendor-boot",
+        FileType::Symbols => "symbols",
+    }
+}
+
+/// Report `${name}` references that can never expand: unknown names, and
+/// identity variables no identity sets.
+fn check_vars(report: &mut Report, cfg: &Config, entry: &Entry, text: &str) {
+    let identity = cfg.identity_for(entry);
+    for name in vars::references(text) {
+        if name == "arch"
+            || name == vars::ORIGIN_VAR
+            || name == vars::PXE_VAR
+            || vars::LEASE_VARS.contains(&name)
+        {
+            continue;
+        }
+        if !vars::IDENTITY_VARS.contains(&name) {
+            report.push(Severity::Error, format!("unknown variable ${{{}}}", name));
+            continue;
+        }
+        let set = identity.as_ref().is_some_and(|id| match name {
+            "hostname" => id.hostname.is_some(),
+            "uuid" => id.uuid.is_some(),
+            "mac" => id.mac.is_some(),
+            _ => id.token.is_some(),
+        });
+        if !set {
+            report.push(
+                Severity::Warning,
+                format!("${{{}}} is not set by any [identity]", name),
+            );
+        }
+    }
+}
+
+fn check_file(report: &mut Report, cfg: &Config, entry: &Entry, f: &BootFile) {
+    let kind = type_name(f.file_type);
+    if let Some(pin) = &f.sha256 {
+        if f.search == SearchMethod::Inline {
+            report.push(
+                Severity::Warning,
+                format!("`sha256` is ignored on inline {} files", kind),
+            );
+        } else if pin.len() != 64 || !pin.bytes().all(|b| b.is_ascii_hexdigit()) {
+            report.push(
+                Severity::Error,
+                format!("{} `sha256` is not 64 hex digits", kind),
+            );
+        }
+    }
+    if let Some(delta) = &f.delta {
+        if f.search != SearchMethod::Https {
+            report.push(
+                Severity::Error,
+                format!("`delta` only applies to https files, not {}", kind),
+            );
+        } else if f.sha256.is_none() {
+            report.push(
+                Severity::Error,
+                format!("{} `delta` needs a `sha256` pin", kind),
+            );
+        } else if cfg.store.is_none() {
+            report.push(
+                Severity::Warning,
+                format!("{} `delta` is unused without [store]", kind),
+            );
+        }
+        check_vars(report, cfg, entry, &delta.replace("${from}", ""));
+    }
+    match f.search {
+        SearchMethod::Inline => {
+            if !matches!(f.file_type, FileType::Cmdline | FileType::Initrd) {
+                report.push(
+                    Severity::Error,
+                    format!(
+                        "{} cannot be inline; use search = \"esp\" or \"https\"",
+                        kind
+                    ),
+                );
+            }
+            match f.content.as_deref() {
+                Some(content) => {
+                    check_vars(report, cfg, entry, content);
+                    if f.file_type == FileType::Cmdline
+                        && entry.protocol == Some(Protocol::Canicula)
+                        && let Err(reason) = loader_info::check_cmdline(content)
+                    {
+                        report.push(Severity::Error, String::from(reason));
+                    }
+                }
+                None => report.push(
+                    Severity::Error,
+                    format!("inline {} file has no `content`", kind),
+                ),
+            }
+        }
+        SearchMethod::Esp | SearchMethod::Https | SearchMethod::Tftp => match f.file.as_deref() {
+            Some(file) if !file.is_empty() => {
+                let lc = file.to_ascii_lowercase();
+                if f.search == SearchMethod::Https
+                    && !lc.starts_with("https://")
+                    && !lc.starts_with("http://")
+                    && !lc.starts_with("${origin}")
+                    && !vars::is_relative(file)
+                {
+                    report.push(
+                        Severity::Error,
+                        format!("https {} file \"{}\" is not an http(s):// URL", kind, file),
+                    );
+                }
+                if f.search == SearchMethod::Tftp
+                    && !lc.starts_with("tftp://")
+                    && !vars::is_relative(file)
+                {
+                    report.push(
+                        Severity::Error,
+                        format!(
+                            "tftp {} file \"{}\" is neither a tftp:// URL nor relative",
+                            kind, file
+                        ),
+                    );
+                }
+                check_vars(report, cfg, entry, file);
+            }
+            _ => report.push(Severity::Error, format!("{} file has no `file`", kind)),
+        },
+    }
+}
+
+/// CHAP secrets firmware initiators accept, per RFC 3720's minimum.
+const CHAP_SECRET_LEN: core::ops::RangeInclusive<usize> = 12..=16;
+
+fn check_iscsi(iscsi: &Iscsi, report: &mut Report) {
+    if iscsi.portal.is_some() && iscsi.portal_address().is_none() {
+        report.push(
+            Severity::Error,
+            String::from("[storage.iscsi] portal is not an IPv4 address with an optional :port"),
+        );
+    }
+    if iscsi.lun.is_some_and(|l| l > 0xFFFF) {
+        report.push(
+            Severity::Error,
+            String::from("[storage.iscsi] lun is above 0xFFFF"),
+        );
+    }
+    let pairs = [
+        ("chap", &iscsi.chap_user, &iscsi.chap_secret),
+        (
+            "reverse_chap",
+            &iscsi.reverse_chap_user,
+            &iscsi.reverse_chap_secret,
+        ),
+    ];
+    for (name, user, secret) in pairs {
+        match (user, secret) {
+            (Some(_), Some(secret)) if !CHAP_SECRET_LEN.contains(&secret.len()) => report.push(
+                Severity::Error,
+                format!(
+                    "[storage.iscsi] {}_secret must be 12 to 16 characters",
+                    name
+                ),
+            ),
+            (Some(_), None) | (None, Some(_)) => report.push(
+                Severity::Error,
+                format!("[storage.iscsi] {0}_user and {0}_secret go together", name),
+            ),
+            _ => {}
+        }
+    }
+    if iscsi.reverse_chap_user.is_some() && iscsi.chap_user.is_none() {
+        report.push(
+            Severity::Error,
+            String::from("[storage.iscsi] reverse_chap needs chap_user and chap_secret"),
+        );
+    }
+    if iscsi.portal.is_none() && (iscsi.chap_user.is_some() || iscsi.reverse_chap_user.is_some()) {
+        report.push(
+            Severity::Warning,
+            String::from("[storage.iscsi] CHAP settings are only applied along with a portal"),
+        );
+    }
+}
+
+/// Turn a TOML error in `text` into an [`Issue`] naming its line.
+pub fn syntax_error(text: &str, err: &toml::de::Error) -> Issue {
+    let message = match err.span() {
+        Some(span) => {
+            let line = text[..span.start.min(text.len())].matches('\n').count() + 1;
+            format!("line {}: {}", line, err.message())
+        }
+        None => String::from(err.message()),
+    };
+    Issue {
+        severity: Severity::Error,
+        entry: None,
+        message,
+    }
+}
+
+/// Check every entry of `cfg`, returning the issues found in entry order.
+pub fn validate(cfg: &Config) -> Vec<Issue> {
+    let mut report = Report {
+        entry: None,
+        issues: Vec::new(),
+    };
+
+    if let Default::Index(i) = cfg.default
+        && i >= cfg.entry.len()
+        && !cfg.entry.is_empty()
+    {
+        report.push(
+            Severity::Warning,
+            format!(
+                "default = {} but there are only {} entries",
+                i,
+                cfg.entry.len()
+            ),
+        );
+    }
+
+    for (key, ty) in [
+        ("artifacts", cfg.memory.artifacts),
+        ("page_tables", cfg.memory.page_tables),
+    ] {
+        match ty {
+            Some(ty) if ty < MEMORY_TYPE_OEM_MIN => report.push(
+                Severity::Error,
+                format!(
+                    "[memory] {} = {:#x} is a UEFI-defined type; use {:#x} or above",
+                    key, ty, MEMORY_TYPE_OEM_MIN
+                ),
+            ),
+            Some(MEMORY_TYPE_BOOT_INFO) => report.push(
+                Severity::Warning,
+                format!(
+                    "[memory] {} = {:#x} is also used for BootInfo",
+                    key, MEMORY_TYPE_BOOT_INFO
+                ),
+            ),
+            Some(MEMORY_TYPE_INITRD) => report.push(
+                Severity::Warning,
+                format!(
+                    "[memory] {} = {:#x} is also used for the initrd",
+                    key, MEMORY_TYPE_INITRD
+                ),
+            ),
+            _ => {}
+        }
+    }
+
+    if cfg.driver_manifest.is_some() && cfg.drivers.is_empty() {
+        report.push(
+            Severity::Warning,
+            String::from("`driver_manifest` is unused without `drivers`"),
+        );
+    }
+
+    for url in &cfg.ipxe_scripts {
+        let lc = url.to_ascii_lowercase();
+        if !lc.starts_with("http://") && !lc.starts_with("https://") {
+            report.push(
+                Severity::Error,
+                format!("ipxe_scripts entry \"{}\" is not an http(s):// URL", url),
+            );
+        }
+    }
+
+    if let Some(remote) = &cfg.remote_menu {
+        let lc = remote.url.to_ascii_lowercase();
+        if !lc.starts_with("http://") && !lc.starts_with("https://") {
+            report.push(
+                Severity::Error,
+                format!(
+                    "remote_menu url \"{}\" is not an http(s):// URL",
+                    remote.url
+                ),
+            );
+        }
+    }
+
+    if let Some(update) = &cfg.self_update {
+        let lc = update.url.to_ascii_lowercase();
+        if !lc.starts_with("http://") && !lc.starts_with("https://") {
+            report.push(
+                Severity::Error,
+                format!(
+                    "[self_update] url \"{}\" is not an http(s):// URL",
+                    update.url
+                ),
+            );
+        }
+        if let Some(pin) = &update.sha256
+            && (pin.len() != 64 || !pin.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            report.push(
+                Severity::Error,
+                String::from("[self_update] sha256 is not 64 hex digits"),
+            );
+        }
+    }
+
+    if let Some(iscsi) = cfg.storage.as_ref().and_then(|s| s.iscsi.as_ref()) {
+        check_iscsi(iscsi, &mut report);
+    }
+
+    if let Some(network) = &cfg.network {
+        if let Some(doh) = &network.doh {
+            let web = doh.starts_with("http://") || doh.starts_with("https://");
+            let by_ip = dns::url_host(doh).is_some_and(|h| vars::parse_ipv4(h).is_some());
+            if !web || !by_ip {
+                report.push(
+                    Severity::Error,
+                    String::from("[network] doh must be an http(s) URL with an IPv4 host"),
+                );
+            }
+        }
+        for name in network.headers.keys() {
+            if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+                report.push(
+                    Severity::Error,
+                    format!(
+                        "[network.headers] name \"{}\" is not letters, digits and '-'",
+                        name
+                    ),
+                );
+            }
+        }
+        let user_agent = network.user_agent.iter().map(|ua| ("user_agent", ua));
+        let headers = network.headers.iter().map(|(k, v)| (k.as_str(), v));
+        for (key, value) in user_agent.chain(headers) {
+            if value.contains(['\r', '\n']) {
+                report.push(
+                    Severity::Error,
+                    format!("[network] {} contains a line break", key),
+                );
+            }
+        }
+    }
+
+    for (i, entry) in cfg.entry.iter().enumerate() {
+        report.entry = Some(entry);
+        // Entries for different hardware may share a name.
+        let clashes =
+            |e: &Entry| e.name == entry.name && (e.when.is_none() || entry.when.is_none());
+        if cfg.entry[..i].iter().any(clashes) {
+            report.push(
+                Severity::Warning,
+                String::from("name is used by an earlier entry too"),
+            );
+        }
+        match (entry.protocol, entry.action) {
+            (Some(_), Some(_)) => report.push(
+                Severity::Error,
+                String::from("has both `protocol` and `action`; the action wins"),
+            ),
+            (None, None) => report.push(
+                Severity::Error,
+                String::from("has neither `protocol` nor `action` and is left off the menu"),
+            ),
+            _ => {}
+        }
+        if entry.action == Some(Action::SelfUpdate) && cfg.self_update.is_none() {
+            report.push(
+                Severity::Error,
+                String::from("`self-update` needs a [self_update] section"),
+            );
+        }
+        if entry.action.is_some() && !entry.files.is_empty() {
+            report.push(
+                Severity::Warning,
+                String::from("`files` are ignored on an action entry"),
+            );
+        }
+        let has_kernel = entry.files.iter().any(|f| {
+            matches!(
+                f.file_type,
+                FileType::Kernel | FileType::Fit | FileType::AndroidBoot
+            )
+        });
+        if entry.protocol.is_some() && entry.action.is_none() && !has_kernel {
+            report.push(
+                Severity::Error,
+                String::from("has no kernel, fit or android-boot file"),
+            );
+        }
+        if !entry.env.is_empty() && entry.protocol != Some(Protocol::Canicula) {
+            report.push(
+                Severity::Warning,
+                String::from("`env` is only passed to canicula kernels"),
+            );
+        }
+        let has_symbols = entry.files.iter().any(|f| f.file_type == FileType::Symbols);
+        if (entry.symbols || has_symbols) && entry.protocol != Some(Protocol::Canicula) {
+            report.push(
+                Severity::Warning,
+                String::from("symbols are only passed to canicula kernels"),
+            );
+        }
+        if entry.debug_halt
+            && !matches!(
+                entry.protocol,
+                Some(Protocol::Canicula | Protocol::Multiboot1)
+            )
+        {
+            report.push(
+                Severity::Warning,
+                String::from("`debug_halt` only applies to canicula and multiboot1"),
+            );
+        }
+        if entry.original_rsdp && entry.protocol != Some(Protocol::Canicula) {
+            report.push(
+                Severity::Warning,
+                String::from("`original_rsdp` only applies to canicula kernels"),
+            );
+        }
+        for f in &entry.files {
+            check_file(&mut report, cfg, entry, f);
+        }
+    }
+    report.issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(toml: &str) -> Vec<String> {
+        let cfg = Config::from_str(toml).unwrap();
+        validate(&cfg)
+            .iter()
+            .map(|i| alloc::format!("{}", i))
+            .collect()
+    }
+
+    #[test]
+    fn clean_config_has_no_issues() {
+        let out = messages(
+            r#"
+            [identity]
+            hostname = "cat"
+
+            [[entry]]
+            name = "Linux"
+            protocol = "linux"
+            files = [
+                { type = "kernel", search = "https", file = "https://h/${arch}/${hostname}" },
+                { type = "initrd", search = "https", file = "./initrd" },
+                { type = "initrd", search = "tftp", file = "tftp://${pxe_server}/extra" },
+                { type = "cmdline", search = "inline", content = "ip=${ip} src=${origin}" },
+            ]
+            "#,
+        );
+        assert!(out.is_empty(), "{:?}", out);
+    }
+
+    #[test]
+    fn reports_bad_field_combinations() {
+        let out = messages(
+            r#"
+            [[entry]]
+            name = "A"
+            protocol = "linux"
+            files = [
+                { type = "kernel", search = "inline", content = "x" },
+                { type = "initrd", search = "esp" },
+                { type = "cmdline", search = "inline" },
+            ]
+
+            [[entry]]
+            name = "B"
+
+            [[entry]]
+            name = "C"
+            protocol = "efi"
+            files = [{ type = "kernel", search = "esp", file = "\\c.efi" }]
+            env = { log = "debug" }
+            symbols = true
+            debug_halt = true
+            original_rsdp = true
+
+            [[entry]]
+            name = "D"
+            protocol = "canicula"
+            files = [
+                { type = "kernel", search = "esp", file = "\\k" },
+                { type = "cmdline", search = "inline", content = "a\u0000b" },
+            ]
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "error: entry \"A\": kernel cannot be inline; use search = \"esp\" or \"https\"",
+                "error: entry \"A\": initrd file has no `file`",
+                "error: entry \"A\": inline cmdline file has no `content`",
+                "error: entry \"B\": has neither `protocol` nor `action` and is left off the menu",
+                "warning: entry \"C\": `env` is only passed to canicula kernels",
+                "warning: entry \"C\": symbols are only passed to canicula kernels",
+                "warning: entry \"C\": `debug_halt` only applies to canicula and multiboot1",
+                "warning: entry \"C\": `original_rsdp` only applies to canicula kernels",
+                "error: entry \"D\": cmdline contains a NUL byte",
+            ]
+        );
+    }
+
+    #[test]
+    fn locates_syntax_errors() {
+        let text = "timeout = 3\n[[entry]]\nname = \n";
+        let err = Config::from_str(text).unwrap_err();
+        let issue = syntax_error(text, &err);
+        assert_eq!(issue.severity, Severity::Error);
+        assert!(issue.message.starts_with("line 3: "), "{}", issue.message);
+    }
+
+    #[test]
+    fn reports_dangling_settings() {
+        let out = messages(
+            r#"
+            default = 3
+            driver_manifest = "\\EFI\\drivers.manifest"
+
+            [memory]
+            artifacts = 0x4
+            page_tables = 0x80000000
+
+            [[entry]]
+            name = "A"
+            protocol = "linux"
+            files = [{ type = "kernel", search = "https", file = "host/k" }]
+
+            [[entry]]
+            name = "A"
+            protocol = "linux"
+            files = [{ type = "initrd", search = "esp", file = "\\initrd" }]
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "warning: default = 3 but there are only 2 entries",
+                "error: [memory] artifacts = 0x4 is a UEFI-defined type; use 0x70000000 or above",
+                "warning: [memory] page_tables = 0x80000000 is also used for BootInfo",
+                "warning: `driver_manifest` is unused without `drivers`",
+                "error: entry \"A\": https kernel file \"host/k\" is not an http(s):// URL",
+                "warning: entry \"A\": name is used by an earlier entry too",
+                "error: entry \"A\": has no kernel, fit or android-boot file",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_iscsi_settings() {
+        let out = messages(
+            r#"
+            [storage.iscsi]
+            target = "iqn.2026-01.org.canicula:boot"
+            lun = 0x10000
+            portal = "san.example.com"
+            chap_user = "cat"
+            chap_secret = "short"
+            reverse_chap_secret = "0123456789ab"
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "error: [storage.iscsi] portal is not an IPv4 address with an optional :port",
+                "error: [storage.iscsi] lun is above 0xFFFF",
+                "error: [storage.iscsi] chap_secret must be 12 to 16 characters",
+                "error: [storage.iscsi] reverse_chap_user and reverse_chap_secret go together",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_self_update_settings() {
+        let out = messages(
+            r#"
+            [[entry]]
+            name = "Update"
+            action = "self-update"
+            "#,
+        );
+        assert_eq!(
+            out,
+            ["error: entry \"Update\": `self-update` needs a [self_update] section"]
+        );
+        let out = messages(
+            r#"
+            [self_update]
+            url = "boot.example.com/alpheratz.efi"
+            sha256 = "abc"
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "error: [self_update] url \"boot.example.com/alpheratz.efi\" is not an http(s):// URL",
+                "error: [self_update] sha256 is not 64 hex digits",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_request_headers() {
+        let out = messages(
+            r#"
+            [network]
+            user_agent = "x\ny"
+            doh = "https://dns.example/dns-query"
+
+            [network.headers]
+            Site = "lab"
+            "Bad Name" = "v"
+            Serial = "a\r\nInjected: 1"
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "error: [network] doh must be an http(s) URL with an IPv4 host",
+                "error: [network.headers] name \"Bad Name\" is not letters, digits and '-'",
+                "error: [network] user_agent contains a line break",
+                "error: [network] Serial contains a line break",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_pins() {
+        let out = messages(
+            r#"
+            [[entry]]
+            name = "A"
+            protocol = "linux"
+            files = [
+                { type = "kernel", search = "https", file = "https://h/k", sha256 = "abc" },
+                { type = "cmdline", search = "inline", content = "ro", sha256 = "abc" },
+            ]
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "error: entry \"A\": kernel `sha256` is not 64 hex digits",
+                "warning: entry \"A\": `sha256` is ignored on inline cmdline files",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unusable_deltas() {
+        let text = alloc::format!(
+            r#"
+            [[entry]]
+            name = "A"
+            protocol = "linux"
+
+            [[entry.files]]
+            type = "kernel"
+            search = "https"
+            file = "https://h/k"
+            delta = "https://h/${{from}}"
+
+            [[entry.files]]
+            type = "initrd"
+            search = "https"
+            file = "https://h/i"
+            sha256 = "{}"
+            delta = "https://h/${{from}}"
+
+            [[entry.files]]
+            type = "cmdline"
+            search = "esp"
+            file = "\\c"
+            delta = "https://h/${{x}}"
+            "#,
+            "0".repeat(64)
+        );
+        assert_eq!(
+            messages(&text),
+            [
+                "error: entry \"A\": kernel `delta` needs a `sha256` pin",
+                "warning: entry \"A\": initrd `delta` is unused without [store]",
+                "error: entry \"A\": `delta` only applies to https files, not cmdline",
+                "error: entry \"A\": unknown variable ${x}",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unresolvable_variables() {
+        let out = messages(
+            r#"
+            [[entry]]
+            name = "A"
+            protocol = "linux"
+            files = [{ type = "kernel", search = "esp", file = "\\${host}\\${mac}" }]
+            "#,
+        );
+        assert_eq!(
+            out,
+            [
+                "error: entry \"A\": unknown variable ${host}",
+                "warning: entry \"A\": ${mac} is not set by any [identity]",
+            ]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::config::Identity;
+
+/// Addresses in effect after the network is brought up, exposed to
+/// [`expand`].
+#[derive(Debug, Clone, Default)]
+pub struct Lease {
+    pub ip: Option<[u8; 4]>,
+    pub netmask: Option<[u8; 4]>,
+    pub gateway: Option<[u8; 4]>,
+    pub dns: Vec<[u8; 4]>,
+    pub dhcp_server: Option<[u8; 4]>,
+    /// How long the DHCP lease lasts, in seconds; `None` for static
+    /// addresses and infinite leases.
+    pub lease_secs: Option<u32>,
+}
+
+pub fn ipv4_to_string(a: [u8; 4]) -> String {
+    let mut s = String::new();
+    let _ = write!(s, "{}.{}.{}.{}", a[0], a[1], a[2], a[3]);
+    s
+}
+
+/// Identity variables, set from `[identity]` or the entry's own.
+pub const IDENTITY_VARS: &[&str] = &["hostname", "uuid", "mac", "token"];
+/// The URL of the directory the loader was served from, when it was
+/// delivered by UEFI HTTP Boot.
+pub const ORIGIN_VAR: &str = "origin";
+/// The TFTP server named by the DHCP reply, when the loader came over PXE.
+pub const PXE_VAR: &str = "pxe_server";
+/// Variables only known once the network is up.
+pub const LEASE_VARS: &[&str] = &["ip", "netmask", "gateway", "dns", "dns2", "dhcp_server"];
+
+/// Names of the `${name}` references in `s`, in order.
+pub fn references(s: &str) -> impl Iterator<Item = &str> {
+    s.split("${")
+        .skip(1)
+        .filter_map(|rest| rest.find('}').map(|end| &rest[..end]))
+}
+
+/// Parse dotted-quad IPv4 text such as `192.168.1.10`.
+pub fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut out = [0u8; 4];
+    let mut parts = s.trim().split('.');
+    for b in &mut out {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        *b = part.parse().ok()?;
+    }
+    parts.next().is_none().then_some(out)
+}
+
+/// Expand `${arch}` to `arch`; when an identity is given, `${hostname}`,
+/// `${uuid}`, `${mac}` and `${token}`; and when the network is up, the DHCP
+/// lease as `${ip}`, `${netmask}`, `${gateway}`, `${dns}`, `${dns2}` and
+/// `${dhcp_server}` (empty when the lease lacks that value).
+pub fn expand(s: &str, arch: &str, identity: Option<&Identity>, lease: Option<&Lease>) -> String {
+    let mut out = String::from(s);
+    if out.contains("${arch}") {
+        out = out.replace("${arch}", arch);
+    }
+    if let Some(id) = identity {
+        for (name, value) in [
+            ("${hostname}", &id.hostname),
+            ("${uuid}", &id.uuid),
+            ("${mac}", &id.mac),
+            ("${token}", &id.token),
+        ] {
+            if let Some(v) = value
+                && out.contains(name)
+            {
+                out = out.replace(name, v);
+            }
+        }
+    }
+    if let Some(l) = lease {
+        for (name, value) in [
+            ("${ip}", l.ip),
+            ("${netmask}", l.netmask),
+            ("${gateway}", l.gateway),
+            ("${dns}", l.dns.first().copied()),
+            ("${dns2}", l.dns.get(1).copied()),
+            ("${dhcp_server}", l.dhcp_server),
+        ] {
+            if out.contains(name) {
+                let v = value.map(ipv4_to_string).unwrap_or_default();
+                out = out.replace(name, &v);
+            }
+        }
+    }
+    out
+}
+
+/// The directory part of `uri`, without the trailing slash and without any
+/// query: `http://h/boot/a.efi?x` gives `http://h/boot`. `None` unless `uri`
+/// is absolute.
+pub fn origin(uri: &str) -> Option<&str> {
+    let (scheme, rest) = uri.split_once("://")?;
+    if scheme.is_empty() || rest.is_empty() {
+        return None;
+    }
+    let rest = &rest[..rest.find(['?', '#']).unwrap_or(rest.len())];
+    let host_end = rest.find('/').unwrap_or(rest.len());
+    let dir_end = rest
+        .rfind('/')
+        .filter(|&i| i >= host_end)
+        .unwrap_or(rest.len());
+    Some(&uri[..scheme.len() + 3 + dir_end])
+}
+
+/// Whether `url` is relative to the origin: `./x`, `../x` or `/x`.
+pub fn is_relative(url: &str) -> bool {
+    url.starts_with("./") || url.starts_with("../") || url.starts_with('/')
+}
+
+/// Resolve the relative `url` against `origin` as [`origin`] returns it:
+/// `.` and `..` walk the origin's path, and a leading `/` starts over at its
+/// host.
+pub fn resolve(url: &str, origin: &str) -> String {
+    let Some((scheme, rest)) = origin.split_once("://") else {
+        return String::from(url);
+    };
+    let host_end = rest.find('/').unwrap_or(rest.len());
+    let mut path: Vec<&str> = Vec::new();
+    if !url.starts_with('/') {
+        path.extend(rest[host_end..].split('/').filter(|s| !s.is_empty()));
+    }
+    for segment in url.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                path.pop();
+            }
+            s => path.push(s),
+        }
+    }
+    let mut out = String::new();
+    let _ = write!(out, "{}://{}", scheme, &rest[..host_end]);
+    for segment in path {
+        out.push('/');
+        out.push_str(segment);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Identity {
+        Identity {
+            hostname: Some(String::from("node1")),
+            uuid: None,
+            mac: Some(String::from("52:54:00:12:34:56")),
+            token: None,
+        }
+    }
+
+    #[test]
+    fn expands_arch_and_identity() {
+        let out = expand(
+            "/${arch}/${hostname}/${mac}/${uuid}",
+            "aarch64",
+            Some(&identity()),
+            None,
+        );
+        // Unset identity fields are left as written.
+        assert_eq!(out, "/aarch64/node1/52:54:00:12:34:56/${uuid}");
+    }
+
+    #[test]
+    fn lists_references() {
+        let refs: Vec<&str> = references("/${arch}/${mac}-${bad/x}$ip${").collect();
+        assert_eq!(refs, ["arch", "mac", "bad/x"]);
+    }
+
+    #[test]
+    fn parses_ipv4() {
+        assert_eq!(parse_ipv4("192.168.1.10"), Some([192, 168, 1, 10]));
+        assert_eq!(parse_ipv4(" 10.0.0.1 "), Some([10, 0, 0, 1]));
+        assert_eq!(parse_ipv4("10.0.0"), None);
+        assert_eq!(parse_ipv4("10.0.0.1.2"), None);
+        assert_eq!(parse_ipv4("10.0.0.256"), None);
+        assert_eq!(parse_ipv4("10.0.+1.2"), None);
+    }
+
+    #[test]
+    fn leaves_lease_vars_without_network() {
+        assert_eq!(expand("ip=${ip}", "x86_64", None, None), "ip=${ip}");
+    }
+
+    #[test]
+    fn expands_lease() {
+        let lease = Lease {
+            ip: Some([10, 0, 2, 15]),
+            netmask: Some([255, 255, 255, 0]),
+            gateway: None,
+            dns: alloc::vec![[10, 0, 2, 3]],
+            dhcp_server: Some([10, 0, 2, 2]),
+            lease_secs: Some(86400),
+        };
+        let out = expand(
+            "${ip}/${netmask} gw=${gateway} dns=${dns},${dns2} srv=${dhcp_server}",
+            "x86_64",
+            None,
+            Some(&lease),
+        );
+        assert_eq!(
+            out,
+            "10.0.2.15/255.255.255.0 gw= dns=10.0.2.3, srv=10.0.2.2"
+        );
+    }
+
+    #[test]
+    fn finds_the_origin() {
+        assert_eq!(
+            origin("http://10.0.0.1/boot/alpheratz.efi"),
+            Some("http://10.0.0.1/boot")
+        );
+        assert_eq!(
+            origin("https://h:8080/a/b/x.efi?v=1/2"),
+            Some("https://h:8080/a/b")
+        );
+        assert_eq!(origin("http://h/x.efi"), Some("http://h"));
+        assert_eq!(origin("http://h"), Some("http://h"));
+        assert_eq!(origin("/boot/x.efi"), None);
+    }
+
+    #[test]
+    fn resolves_relative_urls() {
+        let base = "http://h:8080/boot/x86_64";
+        assert!(is_relative("./vmlinuz") && is_relative("/k") && is_relative("../k"));
+        assert!(!is_relative("http://h/k") && !is_relative("k"));
+        assert_eq!(
+            resolve("./vmlinuz", base),
+            "http://h:8080/boot/x86_64/vmlinuz"
+        );
+        assert_eq!(
+            resolve("../common/./initrd", base),
+            "http://h:8080/boot/common/initrd"
+        );
+        assert_eq!(resolve("/k", base), "http://h:8080/k");
+        assert_eq!(resolve("../../../k", base), "http://h:8080/k");
+        assert_eq!(resolve("./k", "http://h"), "http://h/k");
+    }
+}
@@ -0,0 +1,87 @@
+extern crate alloc;
+
+use alloc::string::String;
+
+use core::fmt::Write;
+
+use crate::config::{Config, Entry};
+use crate::download::ResolvedFiles;
+use crate::fsutil;
+use crate::sha256;
+
+fn timestamp() -> String {
+    let mut s = String::new();
+    match uefi::runtime::get_time() {
+        Ok(t) => {
+            let _ = write!(
+                s,
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                t.year(),
+                t.month(),
+                t.day(),
+                t.hour(),
+                t.minute(),
+                t.second()
+            );
+        }
+        Err(_) => s.push('-'),
+    }
+    s
+}
+
+/// Build a single audit line describing what is about to be booted.
+fn record(cfg: &Config, entry: &Entry, resolved: &ResolvedFiles) -> String {
+    let mut line = String::new();
+    let _ = write!(line, "{} entry=\"{}\"", timestamp(), entry.name);
+    if let Some(protocol) = entry.protocol {
+        let _ = write!(line, " protocol={}", protocol);
+    }
+
+    if let Some(k) = resolved.kernel.as_deref() {
+        let _ = write!(
+            line,
+            " kernel=sha256:{}",
+            sha256::to_hex(&sha256::digest(k))
+        );
+    }
+    if let Some(rd) = resolved.initrd.as_deref() {
+        let _ = write!(
+            line,
+            " initrd=sha256:{}",
+            sha256::to_hex(&sha256::digest(rd))
+        );
+    }
+    if let Some(cl) = resolved.cmdline.as_deref() {
+        let _ = write!(line, " cmdline=\"{}\"", cl.replace('"', "\\\""));
+    }
+
+    if let Some(id) = cfg.identity_for(entry) {
+        if let Some(v) = id.hostname.as_deref() {
+            let _ = write!(line, " hostname={}", v);
+        }
+        if let Some(v) = id.uuid.as_deref() {
+            let _ = write!(line, " uuid={}", v);
+        }
+        if let Some(v) = id.mac.as_deref() {
+            let _ = write!(line, " mac={}", v);
+        }
+    }
+
+    line.push_str("\r\n");
+    line
+}
+
+/// Append a record of the boot decision to `audit_log` on the ESP.
+/// Failures are reported but never block the boot.
+pub fn log_boot(cfg: &Config, entry: &Entry, resolved: &ResolvedFiles) {
+    let Some(path) = cfg.audit_log.as_deref() else {
+        return;
+    };
+
+    let line = record(cfg, entry, resolved);
+    let result = fsutil::open_esp_root()
+        .and_then(|mut root| fsutil::append_file(&mut root, path, line.as_bytes()));
+    if let Err(e) = result {
+        crate::println!("Audit log {}: {:?}", path, e.status());
+    }
+}
@@ -0,0 +1,57 @@
+//! Names for entries chainloading the Windows Boot Manager, read from the
+//! BCD store beside `bootmgfw.efi`, see [`alpheratz_core::bcd`].
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use alpheratz_core::bcd;
+
+use crate::config::{Config, Entry, FileType, SearchMethod};
+use crate::fsutil;
+
+/// Entry name replaced by the name of the default Windows installation.
+const PLACEHOLDER: &str = "@bcd";
+
+/// Used when the store cannot be read or names nothing.
+const GENERIC: &str = "Windows Boot Manager";
+
+/// Largest BCD store read; they are normally tens of KiB.
+pub const BCD_MAX: usize = 4 * 1024 * 1024;
+
+/// ESP path of the BCD store next to the entry's `bootmgfw.efi`.
+pub fn store_path(entry: &Entry) -> Option<String> {
+    let kernel = entry
+        .files
+        .iter()
+        .find(|f| f.file_type == FileType::Kernel && f.search == SearchMethod::Esp)?;
+    let path = fsutil::normalize_path(kernel.file.as_deref()?);
+    let (dir, name) = path.rsplit_once('\\')?;
+    name.eq_ignore_ascii_case("bootmgfw.efi")
+        .then(|| format!("{}\\BCD", dir))
+}
+
+/// Name each entry called `@bcd` after the default installation in its
+/// BCD store, listing any others in its `description` unless it has one.
+pub fn name_entries(cfg: &mut Config) {
+    let mut root = None;
+    for entry in cfg.entry.iter_mut().filter(|e| e.name == PLACEHOLDER) {
+        let installations = store_path(entry).and_then(|path| {
+            if root.is_none() {
+                root = fsutil::open_esp_root().ok();
+            }
+            let data = fsutil::read_file_max(root.as_mut()?, &path, Some(BCD_MAX)).ok()?;
+            bcd::installations(&data)
+        });
+        let mut installations = installations.unwrap_or_default().into_iter();
+        entry.name = installations
+            .next()
+            .unwrap_or_else(|| String::from(GENERIC));
+        let others: Vec<String> = installations.collect();
+        if !others.is_empty() && entry.description.is_none() {
+            entry.description = Some(format!("Also offers {}", others.join(", ")));
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Audible cues for headless racks and users who cannot see the screen:
+//! PC speaker tones on x86_64, and a BEL on the serial port for the cues
+//! that matter most.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use crate::serial;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    /// The menu is up and waiting.
+    Menu,
+    /// The selection moved.
+    Move,
+    /// Resolving or booting an entry failed.
+    Error,
+}
+
+/// Turn cues on or off, from the `beep` config key.
+pub fn enable(on: bool) {
+    ENABLED.store(on, Ordering::Relaxed);
+}
+
+/// Play `cue` if cues are enabled. Blocks for the length of the tones.
+pub fn cue(cue: Cue) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    // (frequency in Hz, milliseconds)
+    let tones: &[(u32, u64)] = match cue {
+        Cue::Menu => &[(880, 80), (1320, 80)],
+        Cue::Move => &[(1760, 15)],
+        Cue::Error => &[(220, 300)],
+    };
+    if cue != Cue::Move {
+        serial::serial_str("\x07");
+    }
+    for &(hz, ms) in tones {
+        tone(hz, Duration::from_millis(ms));
+    }
+}
+
+/// Drive the PC speaker from PIT channel 2 at `hz` for `length`.
+#[cfg(target_arch = "x86_64")]
+fn tone(hz: u32, length: Duration) {
+    use core::arch::asm;
+
+    const PIT_HZ: u32 = 1_193_182;
+    let divisor = (PIT_HZ / hz) as u16;
+    unsafe {
+        // Channel 2, lobyte/hibyte, square wave.
+        asm!("out dx, al", in("dx") 0x43u16, in("al") 0xB6u8);
+        asm!("out dx, al", in("dx") 0x42u16, in("al") divisor as u8);
+        asm!("out dx, al", in("dx") 0x42u16, in("al") (divisor >> 8) as u8);
+        let gate: u8;
+        asm!("in al, dx", out("al") gate, in("dx") 0x61u16);
+        asm!("out dx, al", in("dx") 0x61u16, in("al") gate | 0x03);
+        uefi::boot::stall(length);
+        asm!("out dx, al", in("dx") 0x61u16, in("al") gate & !0x03);
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn tone(_hz: u32, _length: Duration) {}
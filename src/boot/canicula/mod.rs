@@ -1,17 +1,24 @@
 use uefi::prelude::*;
 
+use super::Handoff;
+
 #[cfg(target_arch = "x86_64")]
 mod x86_64;
 
-pub fn boot_canicula(kernel: &[u8], cmdline: Option<&str>) -> Status {
+pub fn boot_canicula(
+    kernel: &[u8],
+    initrd: Option<&[u8]>,
+    cmdline: Option<&str>,
+    handoff: Handoff,
+) -> Status {
     #[cfg(target_arch = "x86_64")]
     {
-        x86_64::boot_canicula_elf(kernel, cmdline)
+        x86_64::boot_canicula_elf(kernel, initrd, cmdline, handoff)
     }
     #[cfg(not(target_arch = "x86_64"))]
     {
-        let _ = (kernel, cmdline);
-        uefi::println!("Canicula ELF boot is currently only implemented for x86_64.");
+        let _ = (kernel, initrd, cmdline, handoff);
+        crate::println!("Canicula ELF boot is currently only implemented for x86_64.");
         Status::UNSUPPORTED
     }
 }
@@ -1,5 +1,9 @@
 use core::arch::asm;
 
+use alloc::vec::Vec;
+
+use alpheratz_core::loader_info::{self, LoaderInfo};
+use alpheratz_core::{config, mat};
 use uefi::boot::{self, AllocateType, MemoryType};
 use uefi::mem::memory_map::MemoryMap;
 use uefi::prelude::*;
@@ -10,16 +14,19 @@ use canicula_common::entry::{
     PixelFormat,
 };
 
-use crate::page_table;
+use crate::boot::Handoff;
+use crate::page_table::{self, PageTableBuilder, Perms};
+use crate::splash::{self, Stage};
 
 pub const PAGE_SIZE: usize = 4096;
 
-static mut BOOT_INFO: BootInfo = BootInfo {
-    memory_regions: MemoryRegions::new(),
-    framebuffer: None,
-    physical_memory_offset: None,
-    rsdp_addr: None,
-};
+/// Memory type of the pages holding `BootInfo`. Kernels see it as
+/// `UnknownUefi` and keep it, unlike loader memory they may reclaim.
+const BOOT_INFO_MEMORY: MemoryType = MemoryType(config::MEMORY_TYPE_BOOT_INFO);
+
+/// Memory type of the initrd, reported as its own `UnknownUefi` kind so the
+/// kernel can free exactly that range once it has unpacked it.
+const INITRD_MEMORY: MemoryType = MemoryType(config::MEMORY_TYPE_INITRD);
 
 fn convert_memory_type(ty: MemoryType) -> MemoryRegionKind {
     match ty {
@@ -44,15 +51,119 @@ fn convert_pixel_format(format: UefiPixelFormat) -> PixelFormat {
     }
 }
 
+const MEMORY_ATTRIBUTES_GUID: uefi::Guid = uefi::guid!("dcfa911d-26eb-469f-a220-38b7dc461220");
+
+/// The EFI_MEMORY_ATTRIBUTES_TABLE, if firmware publishes one.
+fn memory_attributes() -> Option<&'static [u8]> {
+    let addr = uefi::system::with_config_table(|entries| {
+        entries
+            .iter()
+            .find(|e| e.guid == MEMORY_ATTRIBUTES_GUID)
+            .map(|e| e.address as *const u8)
+    })?;
+    let header = unsafe { core::slice::from_raw_parts(addr, 16) };
+    let len = mat::table_len(header)?;
+    Some(unsafe { core::slice::from_raw_parts(addr, len) })
+}
+
+/// Copy `data` into its own pages of type `memory`, since the caller's
+/// buffer is pool memory, and return their address (0 for `None`).
+fn copy_to_pages(what: &str, data: Option<&[u8]>, memory: MemoryType) -> Result<u64, ()> {
+    let Some(data) = data else {
+        return Ok(0);
+    };
+    let pages = data.len().div_ceil(PAGE_SIZE);
+    let Ok(ptr) = boot::allocate_pages(AllocateType::AnyPages, memory, pages) else {
+        log::info!("Failed to allocate {} bytes for {}", data.len(), what);
+        return Err(());
+    };
+    unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len()) };
+    let addr = ptr.as_ptr() as u64;
+    log::info!("{}: {} bytes at {:#x}", what, data.len(), addr);
+    Ok(addr)
+}
+
+/// Copy the RSDP, and the XSDT it points to, into pages of type `memory`,
+/// since some firmware leaves them in memory the kernel may reclaim.
+/// Returns the copy's address, or `rsdp` itself if it cannot be copied.
+fn copy_rsdp(rsdp: u64, memory: MemoryType) -> u64 {
+    use alpheratz_core::acpi;
+
+    let read = |addr: u64, len| unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+    let Some(len) = acpi::rsdp_len(read(rsdp, acpi::RSDP_V2_LEN)) else {
+        log::info!("RSDP at {:#x} is malformed, passing it as is", rsdp);
+        return rsdp;
+    };
+    let mut copy = read(rsdp, len).to_vec();
+    if let Some(xsdt) = acpi::xsdt_addr(&copy)
+        && let Some(xsdt_len) = acpi::sdt_len(read(xsdt, acpi::SDT_HEADER_LEN))
+        && let Ok(xsdt_copy) = copy_to_pages("XSDT", Some(read(xsdt, xsdt_len)), memory)
+    {
+        acpi::set_xsdt(&mut copy, xsdt_copy);
+    }
+    copy_to_pages("RSDP", Some(&copy), memory).unwrap_or(rsdp)
+}
+
+/// Refuse kernels whose feature note asks for more than this loader hands
+/// over: a framebuffer needs GOP, the physical map covers `phys_map` bytes,
+/// and SMP start-up and device trees are not provided on x86_64.
+fn check_features(kernel: &[u8], phys_map: u64) -> Result<(), Status> {
+    use alpheratz_core::kernel_note::{self, FEATURE_FRAMEBUFFER, Features};
+
+    let required = match kernel_note::required(kernel) {
+        Ok(Some(required)) => required,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            crate::println!("Canicula: cannot read kernel feature note: {}", e);
+            return Err(Status::LOAD_ERROR);
+        }
+    };
+    let has_gop = boot::get_handle_for_protocol::<GraphicsOutput>().is_ok();
+    let offered = Features {
+        flags: if has_gop { FEATURE_FRAMEBUFFER } else { 0 },
+        phys_map,
+    };
+    let unmet = required.unmet(&offered);
+    if unmet.is_empty() {
+        return Ok(());
+    }
+    crate::println!("Canicula: this loader cannot satisfy the kernel:");
+    for reason in unmet {
+        crate::println!("  {}", reason);
+    }
+    Err(Status::UNSUPPORTED)
+}
+
 /// Boot a Canicula kernel ELF on x86_64.
 ///
-/// 1. Parses the ELF and loads PT_LOAD segments into physical memory
-/// 2. Sets up 4-level page tables (identity + kernel + physical memory map)
-/// 3. Collects framebuffer, memory map and RSDP into a [`BootInfo`]
+/// 1. Parses the ELF, checks its feature note against what this loader
+///    provides, and loads PT_LOAD segments into physical memory
+/// 2. Sets up 4-level page tables (identity + kernel + physical memory map),
+///    mapping runtime services code read-only per the memory attributes table
+/// 3. Collects framebuffer, memory map and RSDP (a copy, unless the entry
+///    sets `original_rsdp`) into a [`BootInfo`] in pages of its own
+///    ([`BOOT_INFO_MEMORY`]), and calibrates the TSC while firmware timers
+///    are still usable
 /// 4. Exits UEFI boot services
-/// 5. Switches to new page tables and jumps to the kernel entry point
-///    with a pointer to `BootInfo` in `rdi`
-pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
+/// 5. Checks that the entry point, stack and hand-off blocks are mapped,
+///    switches to the new page tables and jumps to the kernel entry point
+///    (after waiting for a debugger with `debug_halt`) with:
+///    - `rdi`: pointer to `BootInfo`
+///    - `rsi`: the `[entry.env]` block
+///    - `rdx`, `rcx`: address and length of the `System.map`
+///    - `r8`: TSC frequency in Hz
+///    - `r9`: a copy of the EFI memory attributes table
+///    - `r10`: the [`LoaderInfo`] block, which locates the initrd and the
+///      command line
+///
+///    Each is 0 when absent. `BootInfo` is defined by canicula-common, so
+///    the rest travel beside it.
+pub fn boot_canicula_elf(
+    kernel: &[u8],
+    initrd: Option<&[u8]>,
+    cmdline: Option<&str>,
+    handoff: Handoff,
+) -> Status {
     use log::info;
     use xmas_elf::ElfFile;
     use xmas_elf::program::Type;
@@ -64,6 +175,15 @@ pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
     let entry_point = elf.header.pt2.entry_point();
     info!("ELF entry point: {:#x}", entry_point);
 
+    const LOW_MAP_SIZE: u64 = 4 << 30;
+    if let Err(status) = check_features(kernel, LOW_MAP_SIZE) {
+        return status;
+    }
+
+    let memory_type = |ty: Option<u32>| ty.map_or(MemoryType::LOADER_DATA, MemoryType);
+    let artifacts = memory_type(handoff.memory.artifacts);
+    let page_table_memory = memory_type(handoff.memory.page_tables);
+
     let mut min_virt: u64 = u64::MAX;
     let mut max_virt: u64 = 0;
 
@@ -87,12 +207,9 @@ pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
     info!("Kernel size: {} pages", num_pages);
 
     let num_pages_aligned = ((total_size + 0x20_0000 - 1) / 0x20_0000) * 512;
-    let kernel_phys_ptr = boot::allocate_pages(
-        AllocateType::AnyPages,
-        MemoryType::LOADER_DATA,
-        num_pages_aligned,
-    )
-    .expect("Failed to allocate memory for kernel");
+    let kernel_phys_ptr =
+        boot::allocate_pages(AllocateType::AnyPages, artifacts, num_pages_aligned)
+            .expect("Failed to allocate memory for kernel");
 
     let kernel_phys_base = kernel_phys_ptr.as_ptr() as u64;
     info!("Kernel physical base: {:#x}", kernel_phys_base);
@@ -124,21 +241,62 @@ pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
         }
     }
 
-    let kernel_pml4_index = ((min_virt >> 39) & 0x1FF) as usize;
+    let kernel_map_size = (num_pages * PAGE_SIZE) as u64;
+
+    let attributes = memory_attributes();
+    let runtime = attributes.and_then(mat::parse).unwrap_or_default();
+    info!("Runtime regions with attributes: {}", runtime.len());
 
     info!("Allocating page tables...");
-    let pt_config =
-        unsafe { page_table::allocate_page_tables(kernel_phys_base, total_size, kernel_pml4_index) };
-    info!("Page table memory allocated at: {:#x}", pt_config.root());
+    // PML4 + two 4 GiB maps of 2 MiB pages (PDPT + 4 PDs each) + kernel,
+    // plus 4 KiB tables for runtime regions and the edges of the gaps
+    // between them.
+    let runtime_pages: usize = runtime
+        .iter()
+        .map(|r| page_table::tables_for(r.size, page_table::LEVELS))
+        .sum();
+    let table_pages = 1
+        + 2 * (1 + 4)
+        + page_table::tables_for(kernel_map_size, page_table::LEVELS)
+        + runtime_pages
+        + 2 * (runtime.len() + 1);
+    let mut page_tables =
+        unsafe { page_table::PageTables::allocate(table_pages, page_table_memory) };
+    info!("Page table memory allocated at: {:#x}", page_tables.root());
+
+    // Identity map the low 4 GiB, runtime regions with the access the
+    // memory attributes table gives them.
+    let mut ranges: Vec<(u64, u64, u64, Perms)> = mat::gaps(0, LOW_MAP_SIZE, &runtime)
+        .into_iter()
+        .map(|(start, size)| (start, start, size, Perms::RWX))
+        .collect();
+    for r in &runtime {
+        let perms = Perms {
+            write: r.write,
+            exec: r.exec,
+        };
+        ranges.push((r.start, r.start, r.size, perms));
+    }
+    let physical_map = (
+        page_table::PHYSICAL_MEMORY_OFFSET,
+        0,
+        LOW_MAP_SIZE,
+        Perms::RW,
+    );
+    ranges.push(physical_map);
+    ranges.push((min_virt, kernel_phys_base, kernel_map_size, Perms::RWX));
+
+    for (virt, phys, size, perms) in ranges {
+        if let Err(e) = page_tables.map_range(virt, phys, size, perms) {
+            info!("Failed to map {:#x} (+{:#x}): {:?}", virt, size, e);
+            return Status::OUT_OF_RESOURCES;
+        }
+    }
 
     const KERNEL_STACK_SIZE: usize = 1024 * 1024;
     let stack_pages = (KERNEL_STACK_SIZE + PAGE_SIZE - 1) / PAGE_SIZE;
-    let stack_ptr = boot::allocate_pages(
-        AllocateType::AnyPages,
-        MemoryType::LOADER_DATA,
-        stack_pages,
-    )
-    .expect("Failed to allocate kernel stack");
+    let stack_ptr = boot::allocate_pages(AllocateType::AnyPages, artifacts, stack_pages)
+        .expect("Failed to allocate kernel stack");
     let stack_top = (stack_ptr.as_ptr() as u64 + KERNEL_STACK_SIZE as u64) & !0xF;
     info!(
         "Kernel stack allocated: base={:#x}, top={:#x}",
@@ -174,13 +332,66 @@ pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
         None
     });
     info!("RSDP address: {:?}", rsdp_addr);
+    let rsdp_addr = match rsdp_addr {
+        Some(rsdp) if !handoff.original_rsdp => Some(copy_rsdp(rsdp, artifacts)),
+        other => other,
+    };
+
+    let Ok(env_addr) = copy_to_pages("Environment", handoff.env, artifacts) else {
+        return Status::OUT_OF_RESOURCES;
+    };
+    let Ok(symbols_addr) = copy_to_pages("Symbol map", handoff.symbols, artifacts) else {
+        return Status::OUT_OF_RESOURCES;
+    };
+    let symbols_len = handoff.symbols.map_or(0, |s| s.len() as u64);
+    let tsc_hz = crate::timer::counter_hz().unwrap_or(0);
+    let Ok(attributes_addr) = copy_to_pages("Memory attributes", attributes, artifacts) else {
+        return Status::OUT_OF_RESOURCES;
+    };
+    let Ok(initrd_addr) = copy_to_pages("Initrd", initrd, INITRD_MEMORY) else {
+        return Status::OUT_OF_RESOURCES;
+    };
+    let cmdline_bytes = cmdline.map(loader_info::cmdline_bytes);
+    let Ok(cmdline_addr) = copy_to_pages("Command line", cmdline_bytes.as_deref(), artifacts)
+    else {
+        return Status::OUT_OF_RESOURCES;
+    };
+    let loader_info = LoaderInfo {
+        initrd_addr,
+        initrd_len: initrd.map_or(0, |i| i.len() as u64),
+        cmdline_addr,
+        cmdline_len: cmdline.map_or(0, |c| c.len() as u64),
+        ..LoaderInfo::default()
+    };
+    let loader_info = loader_info.to_bytes();
+    let Ok(loader_info_addr) = copy_to_pages("LoaderInfo", Some(&loader_info), artifacts) else {
+        return Status::OUT_OF_RESOURCES;
+    };
+
+    let boot_info_pages = size_of::<BootInfo>().div_ceil(PAGE_SIZE);
+    let Ok(boot_info_ptr) =
+        boot::allocate_pages(AllocateType::AnyPages, BOOT_INFO_MEMORY, boot_info_pages)
+    else {
+        info!("Failed to allocate {} pages for BootInfo", boot_info_pages);
+        return Status::OUT_OF_RESOURCES;
+    };
+    let boot_info_ptr = boot_info_ptr.as_ptr() as *mut BootInfo;
+    unsafe {
+        boot_info_ptr.write(BootInfo {
+            memory_regions: MemoryRegions::new(),
+            framebuffer: None,
+            physical_memory_offset: None,
+            rsdp_addr: None,
+        })
+    };
+    info!("BootInfo at {:#x}", boot_info_ptr as u64);
 
     info!("Exiting boot services...");
+    splash::stage(Stage::Handoff);
+    crate::late::prepare();
     let memory_map = unsafe { boot::exit_boot_services(Some(MemoryType::LOADER_DATA)) };
 
     unsafe {
-        let boot_info_ptr = core::ptr::addr_of_mut!(BOOT_INFO);
-
         for desc in memory_map.entries() {
             let start = desc.phys_start;
             let end = start + desc.page_count * PAGE_SIZE as u64;
@@ -207,15 +418,71 @@ pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
         (*boot_info_ptr).rsdp_addr = rsdp_addr;
     }
 
-    let pml4_phys = unsafe { page_table::init_page_tables(&pt_config) };
+    // Boot services are gone: from here on a problem can only be reported
+    // and recovered from by resetting.
+    if !(min_virt..max_virt).contains(&entry_point) {
+        crate::late::fail(format_args!(
+            "Kernel entry point {:#x} lies outside its image ({:#x}-{:#x})",
+            entry_point, min_virt, max_virt
+        ));
+    }
+    if memory_map.entries().next().is_none() {
+        crate::late::fail(format_args!("Firmware returned an empty memory map"));
+    }
+    let expected_entry = kernel_phys_base + (entry_point - min_virt);
+    match page_tables.translate(entry_point) {
+        Some(phys) if phys == expected_entry => {}
+        Some(phys) => crate::late::fail(format_args!(
+            "Kernel entry point {:#x} maps to {:#x}, expected {:#x}",
+            entry_point, phys, expected_entry
+        )),
+        None => crate::late::fail(format_args!(
+            "Kernel entry point {:#x} is not mapped",
+            entry_point
+        )),
+    }
+    // Everything the kernel reaches through a pointer is identity mapped.
+    let boot_info_addr = boot_info_ptr as u64;
+    for (what, addr) in [
+        ("Kernel stack", stack_top - 8),
+        ("BootInfo", boot_info_addr),
+        ("Environment", env_addr),
+        ("Symbol map", symbols_addr),
+        ("Memory attributes", attributes_addr),
+        ("Initrd", initrd_addr),
+        ("LoaderInfo", loader_info_addr),
+        ("Command line", cmdline_addr),
+        ("RSDP", rsdp_addr.unwrap_or(0)),
+    ] {
+        if addr != 0 && page_tables.translate(addr) != Some(addr) {
+            crate::late::fail(format_args!(
+                "{} at {:#x} is not identity mapped (only the low 4 GiB is)",
+                what, addr
+            ));
+        }
+    }
+
+    let pml4_phys = page_tables.finalize();
+
+    if handoff.debug_halt {
+        crate::serial::debug_halt(&[
+            ("entry", entry_point),
+            ("virtual base", min_virt),
+            ("physical base", kernel_phys_base),
+            ("slide", kernel_phys_base.wrapping_sub(min_virt)),
+            ("boot info", boot_info_addr),
+            ("loader info", loader_info_addr),
+            ("initrd", initrd_addr),
+            ("cr3", pml4_phys),
+            ("tsc hz", tsc_hz),
+        ]);
+    }
 
     crate::serial::serial_str("[LOADER] Jumping to kernel at ");
     crate::serial::serial_hex(entry_point);
     crate::serial::serial_str("\r\n");
 
     unsafe {
-        let boot_info_ptr = core::ptr::addr_of_mut!(BOOT_INFO);
-
         asm!(
             "mov rsp, {stack}",
             "mov cr3, {cr3}",
@@ -224,6 +491,12 @@ pub fn boot_canicula_elf(kernel: &[u8], _cmdline: Option<&str>) -> Status {
             cr3 = in(reg) pml4_phys,
             entry = in(reg) entry_point,
             in("rdi") boot_info_ptr,
+            in("rsi") env_addr,
+            in("rdx") symbols_addr,
+            in("rcx") symbols_len,
+            in("r8") tsc_hz,
+            in("r9") attributes_addr,
+            in("r10") loader_info_addr,
             options(noreturn)
         );
     }
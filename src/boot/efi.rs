@@ -0,0 +1,39 @@
+use uefi::prelude::*;
+
+use super::linux::load_and_start;
+use crate::fsutil;
+
+/// Chainload another EFI application such as a bootloader.
+///
+/// `image`   -- PE/COFF bytes of the `.efi` file
+/// `options` -- optional load options, e.g. `mboot.efi -c boot.cfg`
+/// `path`    -- file the image should believe it was loaded from, inside
+///              the entry's `workdir`
+pub fn boot_efi(image: &[u8], options: Option<&str>, path: Option<&str>) -> Status {
+    crate::println!("EFI Chainload");
+    crate::println!("  Image: {} bytes", image.len());
+
+    let file_path = match path.map(fsutil::image_device_path).transpose() {
+        Ok(dp) => dp,
+        Err(e) => {
+            crate::println!(
+                "Cannot build device path for {}: {:?}",
+                path.unwrap_or(""),
+                e.status()
+            );
+            return e.status();
+        }
+    };
+    if let Some(path) = path {
+        crate::println!("  Loaded as: {}", path);
+    }
+
+    let status = load_and_start(image, file_path.as_deref(), options, "EFI image");
+    if status.is_success() {
+        // The application exited instead of taking over; go back to the menu
+        // rather than leaving Alpheratz as if the boot succeeded.
+        crate::println!("Chainloaded image returned.");
+        return Status::ABORTED;
+    }
+    status
+}
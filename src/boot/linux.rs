@@ -1,175 +1,150 @@
 use core::ffi::c_void;
-use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
 
+use alpheratz_core::bzimage;
+use alpheratz_core::validate::Severity;
 use uefi::boot::{self, LoadImageSource};
 use uefi::prelude::*;
+use uefi::proto::device_path::DevicePath;
 use uefi::proto::loaded_image::LoadedImage;
 
-static INITRD_DATA_PTR: AtomicPtr<u8> = AtomicPtr::new(core::ptr::null_mut());
-static INITRD_DATA_LEN: AtomicUsize = AtomicUsize::new(0);
-
-/// Vendor Media Device Path node identifying the Linux initrd, followed by
-/// an End-of-Device-Path node.  The Linux EFI stub (5.8+) searches for a
-/// handle carrying this device path together with the LoadFile2 protocol.
-#[repr(C, packed)]
-struct InitrdDevicePath {
-    vendor_type: u8,
-    vendor_subtype: u8,
-    vendor_length: [u8; 2],
-    vendor_guid: [u8; 16],
-    end_type: u8,
-    end_subtype: u8,
-    end_length: [u8; 2],
-}
-
-unsafe impl Sync for InitrdDevicePath {}
-
-/// LINUX_EFI_INITRD_MEDIA_GUID  {5568e427-68fc-4f3d-ac74-ca555231cc68}
-static INITRD_DEVICE_PATH: InitrdDevicePath = InitrdDevicePath {
-    vendor_type: 0x04,
-    vendor_subtype: 0x03,
-    vendor_length: [20, 0],
-    vendor_guid: [
-        0x27, 0xe4, 0x68, 0x55, 0xfc, 0x68, 0x3d, 0x4f, 0xac, 0x74, 0xca, 0x55, 0x52, 0x31,
-        0xcc, 0x68,
-    ],
-    end_type: 0x7f,
-    end_subtype: 0xff,
-    end_length: [4, 0],
-};
-
-#[repr(C)]
-struct RawLoadFile2Protocol {
-    load_file: unsafe extern "efiapi" fn(
-        this: *mut RawLoadFile2Protocol,
-        file_path: *const c_void,
-        boot_policy: bool,
-        buffer_size: *mut usize,
-        buffer: *mut c_void,
-    ) -> Status,
-}
-
-unsafe impl Sync for RawLoadFile2Protocol {}
+use super::load_file2::{LINUX_INITRD_MEDIA_GUID, LoadFile2Registry};
+use crate::fit::DTB_TABLE_GUID;
+use crate::splash::{self, Stage};
 
-unsafe extern "efiapi" fn initrd_load_file(
-    _this: *mut RawLoadFile2Protocol,
-    _file_path: *const c_void,
-    _boot_policy: bool,
-    buffer_size: *mut usize,
-    buffer: *mut c_void,
+/// Boot a Linux kernel via the EFI stub mechanism.
+///
+/// `kernel`  -- raw vmlinuz / bzImage PE/COFF bytes
+/// `initrd`  -- optional concatenated initrd(s)
+/// `cmdline` -- optional kernel command line
+/// `dtb`     -- optional device tree replacing the firmware's
+pub fn boot_linux(
+    kernel: &[u8],
+    initrd: Option<&[u8]>,
+    cmdline: Option<&str>,
+    dtb: Option<&[u8]>,
 ) -> Status {
-    let ptr = INITRD_DATA_PTR.load(Ordering::Relaxed);
-    let len = INITRD_DATA_LEN.load(Ordering::Relaxed);
-
-    if ptr.is_null() || len == 0 {
-        return Status::NOT_FOUND;
+    crate::println!("Linux EFI Stub Boot");
+    crate::println!("  Kernel: {} bytes", kernel.len());
+    if let Some(header) = bzimage::Header::parse(kernel) {
+        if let Some(version) = header.version {
+            crate::println!("  Version: {}", version);
+        }
+        crate::println!("  Boot protocol: {}", header.protocol_string());
+        let mut fatal = false;
+        for (severity, problem) in header.problems(initrd.is_some()) {
+            match severity {
+                Severity::Error => {
+                    fatal = true;
+                    crate::println!("  Error: {}.", problem);
+                }
+                Severity::Warning => crate::println!("  Warning: {}.", problem),
+            }
+        }
+        if fatal {
+            return Status::LOAD_ERROR;
+        }
     }
 
-    unsafe {
-        if buffer.is_null() || *buffer_size < len {
-            *buffer_size = len;
-            return Status::BUFFER_TOO_SMALL;
+    let mut payloads = LoadFile2Registry::new();
+    if let Some(rd) = initrd {
+        crate::println!("  Initrd: {} bytes", rd.len());
+        if let Err(e) = payloads.serve(LINUX_INITRD_MEDIA_GUID, "initrd", rd) {
+            crate::println!("Installing the initrd failed: {:?}", e.status());
+            return e.status();
         }
-        core::ptr::copy_nonoverlapping(ptr, buffer as *mut u8, len);
-        *buffer_size = len;
     }
 
-    Status::SUCCESS
-}
+    let previous_dtb = dtb.and_then(install_dtb);
 
-static INITRD_LOAD_FILE2: RawLoadFile2Protocol = RawLoadFile2Protocol {
-    load_file: initrd_load_file,
-};
-
-const DEVICE_PATH_PROTOCOL_GUID: uefi::Guid =
-    uefi::guid!("09576e91-6d3f-11d2-8e39-00a0c969723b");
-const LOAD_FILE2_PROTOCOL_GUID: uefi::Guid =
-    uefi::guid!("4006c0c1-fcb3-403e-996d-4a6c8724e06d");
-
-fn install_initrd_load_file2(initrd_data: &[u8]) {
-    INITRD_DATA_PTR.store(initrd_data.as_ptr() as *mut u8, Ordering::Relaxed);
-    INITRD_DATA_LEN.store(initrd_data.len(), Ordering::Relaxed);
-
-    let handle = unsafe {
-        boot::install_protocol_interface(
-            None,
-            &DEVICE_PATH_PROTOCOL_GUID,
-            &INITRD_DEVICE_PATH as *const InitrdDevicePath as *const c_void,
-        )
-    }
-    .expect("install initrd device path");
-
-    unsafe {
-        boot::install_protocol_interface(
-            Some(handle),
-            &LOAD_FILE2_PROTOCOL_GUID,
-            &INITRD_LOAD_FILE2 as *const RawLoadFile2Protocol as *const c_void,
-        )
+    let status = load_and_start(kernel, None, cmdline, "Linux kernel");
+    drop(payloads);
+    if let Some(previous) = previous_dtb {
+        // A null table removes the entry again.
+        let _ = unsafe { boot::install_configuration_table(&DTB_TABLE_GUID, previous) };
     }
-    .expect("install initrd LoadFile2");
+    status
 }
 
-/// Boot a Linux kernel via the EFI stub mechanism.
-///
-/// `kernel`  -- raw vmlinuz / bzImage PE/COFF bytes
-/// `initrd`  -- optional concatenated initrd(s)
-/// `cmdline` -- optional kernel command line
-pub fn boot_linux(kernel: &[u8], initrd: Option<&[u8]>, cmdline: Option<&str>) -> Status {
-    uefi::println!("Linux EFI Stub Boot");
-    uefi::println!("  Kernel: {} bytes", kernel.len());
-
-    if let Some(rd) = initrd {
-        uefi::println!("  Initrd: {} bytes", rd.len());
-        install_initrd_load_file2(rd);
+/// Publish `dtb` as the EFI_DTB_TABLE configuration table, where the EFI
+/// stub looks for the device tree. Returns the table it replaced (null if
+/// none) so it can be put back if the kernel returns.
+fn install_dtb(dtb: &[u8]) -> Option<*const c_void> {
+    crate::println!("  DTB: {} bytes", dtb.len());
+    let previous = uefi::system::with_config_table(|entries| {
+        entries
+            .iter()
+            .find(|e| e.guid == DTB_TABLE_GUID)
+            .map_or(core::ptr::null(), |e| e.address)
+    });
+    match unsafe { boot::install_configuration_table(&DTB_TABLE_GUID, dtb.as_ptr().cast()) } {
+        Ok(()) => Some(previous),
+        Err(e) => {
+            crate::println!("Installing the device tree failed: {:?}", e.status());
+            None
+        }
     }
+}
 
-    uefi::println!("Loading EFI kernel image...");
+/// Load a PE/COFF `image` from memory and start it with `cmdline` as its
+/// load options. `file_path` becomes the image's device and file path.
+pub(super) fn load_and_start(
+    image: &[u8],
+    file_path: Option<&DevicePath>,
+    cmdline: Option<&str>,
+    what: &str,
+) -> Status {
+    crate::println!("Loading {}...", what);
 
     let image_handle = match boot::load_image(
         boot::image_handle(),
         LoadImageSource::FromBuffer {
-            buffer: kernel,
-            file_path: None,
+            buffer: image,
+            file_path,
         },
     ) {
         Ok(h) => h,
         Err(e) => {
-            uefi::println!("LoadImage failed: {:?}", e.status());
-            uefi::println!("Hint: kernel must be a PE/COFF EFI stub image (not ELF).");
+            crate::println!("LoadImage failed: {:?}", e.status());
+            crate::println!("Hint: image must be PE/COFF (EFI application or stub), not ELF.");
             return e.status();
         }
     };
 
-    let mut cmdline_buf = [0u16; 1024];
+    // Owned here so the load options stay valid until start_image returns.
+    let cmdline16: uefi::CString16;
 
     if let Some(cl) = cmdline {
-        uefi::println!("  Cmdline: {}", cl);
+        crate::println!("  Cmdline: {}", cl);
 
-        let cl16 = match uefi::CStr16::from_str_with_buf(cl, &mut cmdline_buf) {
+        cmdline16 = match uefi::CString16::try_from(cl) {
             Ok(v) => v,
             Err(_) => {
-                uefi::println!("Cmdline too long (max 1024 UTF-16 code units)");
+                crate::println!("Cmdline cannot be converted to UCS-2");
+                let _ = boot::unload_image(image_handle);
                 return Status::INVALID_PARAMETER;
             }
         };
-        let size = (cl16.to_u16_slice_with_nul().len() * core::mem::size_of::<u16>()) as u32;
+        let size = cmdline16.num_bytes() as u32;
 
         let mut loaded_image = match boot::open_protocol_exclusive::<LoadedImage>(image_handle) {
             Ok(v) => v,
             Err(e) => {
-                uefi::println!("OpenProtocol(LoadedImage) failed: {:?}", e.status());
+                crate::println!("OpenProtocol(LoadedImage) failed: {:?}", e.status());
+                let _ = boot::unload_image(image_handle);
                 return e.status();
             }
         };
         unsafe {
-            loaded_image.set_load_options(cmdline_buf.as_ptr() as *const u8, size);
+            loaded_image.set_load_options(cmdline16.as_ptr() as *const u8, size);
         }
     }
 
-    uefi::println!("Starting Linux kernel...");
+    crate::println!("Starting {}...", what);
+    splash::stage(Stage::Handoff);
 
     if let Err(e) = boot::start_image(image_handle) {
-        uefi::println!("StartImage failed: {:?}", e.status());
+        crate::println!("StartImage failed: {:?}", e.status());
+        let _ = boot::unload_image(image_handle);
         return e.status();
     }
 
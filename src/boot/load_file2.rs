@@ -0,0 +1,231 @@
+//! Blobs served to a kernel's EFI stub over EFI_LOAD_FILE2_PROTOCOL, each on
+//! its own handle behind a vendor media device path naming what it is. The
+//! Linux initrd is one (LINUX_EFI_INITRD_MEDIA_GUID); others can sit next
+//! to it under their own GUIDs.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+
+use uefi::boot;
+use uefi::prelude::*;
+use uefi::{Guid, guid};
+
+/// LINUX_EFI_INITRD_MEDIA_GUID, where the Linux EFI stub (5.8+) asks for
+/// the initrd.
+pub const LINUX_INITRD_MEDIA_GUID: Guid = guid!("5568e427-68fc-4f3d-ac74-ca555231cc68");
+
+const DEVICE_PATH_PROTOCOL_GUID: Guid = guid!("09576e91-6d3f-11d2-8e39-00a0c969723b");
+const LOAD_FILE2_PROTOCOL_GUID: Guid = guid!("4006c0c1-fcb3-403e-996d-4a6c8724e06d");
+
+/// A Vendor Media Device Path node followed by an End-of-Device-Path node.
+#[repr(C, packed)]
+struct VendorDevicePath {
+    vendor_type: u8,
+    vendor_subtype: u8,
+    vendor_length: [u8; 2],
+    vendor_guid: [u8; 16],
+    end_type: u8,
+    end_subtype: u8,
+    end_length: [u8; 2],
+}
+
+#[repr(C)]
+struct RawLoadFile2Protocol {
+    load_file: unsafe extern "efiapi" fn(
+        this: *mut RawLoadFile2Protocol,
+        file_path: *const c_void,
+        boot_policy: bool,
+        buffer_size: *mut usize,
+        buffer: *mut c_void,
+    ) -> Status,
+}
+
+/// One served blob. The protocol comes first so the `this` pointer the
+/// firmware hands back is also a pointer to the payload.
+#[repr(C)]
+struct Payload {
+    protocol: RawLoadFile2Protocol,
+    path: VendorDevicePath,
+    guid: Guid,
+    name: &'static str,
+    data: *const u8,
+    len: usize,
+    handle: Option<Handle>,
+    /// LoadFile2 calls seen, and how many of them got the blob copied out.
+    calls: usize,
+    copies: usize,
+}
+
+impl Payload {
+    fn copy(&self, boot_policy: bool, buffer_size: *mut usize, buffer: *mut c_void) -> Status {
+        // LoadFile2 is never a boot option load.
+        if boot_policy {
+            return Status::UNSUPPORTED;
+        }
+        if buffer_size.is_null() {
+            return Status::INVALID_PARAMETER;
+        }
+        unsafe {
+            if buffer.is_null() || *buffer_size < self.len {
+                *buffer_size = self.len;
+                return Status::BUFFER_TOO_SMALL;
+            }
+            core::ptr::copy_nonoverlapping(self.data, buffer as *mut u8, self.len);
+            *buffer_size = self.len;
+        }
+        Status::SUCCESS
+    }
+}
+
+unsafe extern "efiapi" fn load_file(
+    this: *mut RawLoadFile2Protocol,
+    _file_path: *const c_void,
+    boot_policy: bool,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> Status {
+    let payload = unsafe { &mut *(this as *mut Payload) };
+    payload.calls += 1;
+    let offered = (!buffer_size.is_null()).then(|| unsafe { *buffer_size });
+    let status = payload.copy(boot_policy, buffer_size, buffer);
+    crate::serial::serial_str(&format!(
+        "[LOADER] {} LoadFile2 #{}: buffer {:#x}, size {:?}, {} {} -> {:?}\r\n",
+        payload.name, payload.calls, buffer as usize, offered, payload.name, payload.len, status
+    ));
+    if status == Status::SUCCESS {
+        payload.copies += 1;
+        if payload.copies == 2 {
+            crate::serial::serial_str(&format!(
+                "[LOADER] {} handed out a second time\r\n",
+                payload.name
+            ));
+        }
+    }
+    status
+}
+
+/// The blobs currently served, by vendor GUID. Dropping the registry
+/// withdraws them all, so no handle outlives the data it points at.
+#[derive(Default)]
+pub struct LoadFile2Registry<'a> {
+    // Boxed so the installed interface pointers survive the Vec growing.
+    #[allow(clippy::vec_box)]
+    payloads: Vec<Box<Payload>>,
+    data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> LoadFile2Registry<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `data` under `guid`, replacing whatever was served there.
+    /// `name` is only used in log messages.
+    pub fn serve(&mut self, guid: Guid, name: &'static str, data: &'a [u8]) -> uefi::Result<()> {
+        self.withdraw(guid);
+        let mut payload = Box::new(Payload {
+            protocol: RawLoadFile2Protocol { load_file },
+            path: VendorDevicePath {
+                vendor_type: 0x04,
+                vendor_subtype: 0x03,
+                vendor_length: [20, 0],
+                vendor_guid: guid.to_bytes(),
+                end_type: 0x7f,
+                end_subtype: 0xff,
+                end_length: [4, 0],
+            },
+            guid,
+            name,
+            data: data.as_ptr(),
+            len: data.len(),
+            handle: None,
+            calls: 0,
+            copies: 0,
+        });
+        let handle = unsafe {
+            boot::install_protocol_interface(
+                None,
+                &DEVICE_PATH_PROTOCOL_GUID,
+                &payload.path as *const VendorDevicePath as *const c_void,
+            )?
+        };
+        payload.handle = Some(handle);
+        let installed = unsafe {
+            boot::install_protocol_interface(
+                Some(handle),
+                &LOAD_FILE2_PROTOCOL_GUID,
+                &payload.protocol as *const RawLoadFile2Protocol as *const c_void,
+            )
+        };
+        match installed {
+            Ok(_) => {
+                self.payloads.push(payload);
+                Ok(())
+            }
+            Err(e) => {
+                let removed = unsafe {
+                    boot::uninstall_protocol_interface(
+                        handle,
+                        &DEVICE_PATH_PROTOCOL_GUID,
+                        &payload.path as *const VendorDevicePath as *const c_void,
+                    )
+                };
+                if removed.is_err() {
+                    core::mem::forget(payload);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Stop serving the blob under `guid`, reporting how the kernel asked
+    /// for it when that was unusual.
+    pub fn withdraw(&mut self, guid: Guid) {
+        let Some(at) = self.payloads.iter().position(|p| p.guid == guid) else {
+            return;
+        };
+        let payload = self.payloads.remove(at);
+        match payload.copies {
+            _ if payload.calls == 0 => {}
+            0 => crate::println!(
+                "  The {} was asked for {} times, never loaded.",
+                payload.name,
+                payload.calls
+            ),
+            1 => {}
+            n => crate::println!("  The {} was loaded {} times.", payload.name, n),
+        }
+        let Some(handle) = payload.handle else {
+            return;
+        };
+        let removed = unsafe {
+            boot::uninstall_protocol_interface(
+                handle,
+                &LOAD_FILE2_PROTOCOL_GUID,
+                &payload.protocol as *const RawLoadFile2Protocol as *const c_void,
+            )
+            .and_then(|()| {
+                boot::uninstall_protocol_interface(
+                    handle,
+                    &DEVICE_PATH_PROTOCOL_GUID,
+                    &payload.path as *const VendorDevicePath as *const c_void,
+                )
+            })
+        };
+        if removed.is_err() {
+            // Still reachable through the handle; better leaked than freed.
+            core::mem::forget(payload);
+        }
+    }
+}
+
+impl Drop for LoadFile2Registry<'_> {
+    fn drop(&mut self) {
+        while let Some(guid) = self.payloads.last().map(|p| p.guid) {
+            self.withdraw(guid);
+        }
+    }
+}
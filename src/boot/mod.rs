@@ -1,5 +1,64 @@
+mod efi;
 mod linux;
+mod load_file2;
+mod multiboot;
+#[cfg(feature = "canicula")]
 mod canicula;
 
+use crate::config::Protocol;
+use crate::error::{self, AlpheratzError};
+
+pub use efi::boot_efi;
 pub use linux::boot_linux;
+pub use multiboot::boot_multiboot1;
+#[cfg(feature = "canicula")]
 pub use canicula::boot_canicula;
+
+/// What is handed over besides the kernel's own files. Only the Canicula
+/// protocol reads most of it.
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(not(feature = "canicula"), allow(dead_code))]
+pub struct Handoff<'a> {
+    /// An [`alpheratz_core::env`] block.
+    pub env: Option<&'a [u8]>,
+    /// A `System.map`, see [`alpheratz_core::symbols`].
+    pub symbols: Option<&'a [u8]>,
+    /// Wait for a debugger before the final jump, see
+    /// [`crate::serial::debug_halt`].
+    pub debug_halt: bool,
+    /// Pass the firmware's RSDP instead of a copy in loader memory.
+    pub original_rsdp: bool,
+    /// Memory types for the kernel's artifacts and page tables.
+    pub memory: alpheratz_core::config::MemoryTypes,
+}
+
+/// Hand off to the loader for `protocol`. Only returns when the boot failed.
+/// `dtb` is only passed on to Linux, `image_path` to chainloaded images and
+/// `handoff` to Canicula, except `debug_halt` which Multiboot also honours.
+pub fn boot(
+    protocol: Protocol,
+    kernel: &[u8],
+    initrd: Option<&[u8]>,
+    cmdline: Option<&str>,
+    dtb: Option<&[u8]>,
+    image_path: Option<&str>,
+    handoff: Handoff,
+) -> error::Result<()> {
+    let status = match protocol {
+        Protocol::Linux => boot_linux(kernel, initrd, cmdline, dtb),
+        Protocol::Efi => boot_efi(kernel, cmdline, image_path),
+        Protocol::Multiboot1 => boot_multiboot1(kernel, initrd, cmdline, handoff.debug_halt),
+        #[cfg(feature = "canicula")]
+        Protocol::Canicula => boot_canicula(kernel, initrd, cmdline, handoff),
+        #[cfg(not(feature = "canicula"))]
+        Protocol::Canicula => {
+            let _ = (kernel, initrd, cmdline, handoff);
+            crate::println!("Canicula boot is not built in (enable the `canicula` feature).");
+            uefi::Status::UNSUPPORTED
+        }
+    };
+    if status.is_error() {
+        return Err(AlpheratzError::Boot { protocol, status });
+    }
+    Ok(())
+}
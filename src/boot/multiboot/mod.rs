@@ -0,0 +1,132 @@
+#![cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+
+use uefi::prelude::*;
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+const HEADER_MAGIC: u32 = 0x1BAD_B002;
+/// The header must be 32-bit aligned and lie within the first 8 KiB.
+const HEADER_SEARCH_LIMIT: usize = 8192;
+
+/// Load modules on page boundaries.
+const FLAG_PAGE_ALIGN: u32 = 1 << 0;
+/// Pass `mem_*` and the memory map.
+const FLAG_MEMORY_INFO: u32 = 1 << 1;
+/// Pass a video mode, preferably the one in the header.
+const FLAG_VIDEO_MODE: u32 = 1 << 2;
+/// Load addresses come from the header instead of the ELF program headers.
+const FLAG_AOUT_KLUDGE: u32 = 1 << 16;
+
+/// Bits 0–15 are requirements: a loader must refuse kernels asking for
+/// ones it does not implement.
+const REQUIRED_MASK: u32 = 0xFFFF;
+const SUPPORTED: u32 = FLAG_PAGE_ALIGN | FLAG_MEMORY_INFO | FLAG_VIDEO_MODE;
+
+/// Header fields present when [`FLAG_AOUT_KLUDGE`] is set.
+#[derive(Debug, Clone, Copy)]
+struct AddressFields {
+    header_addr: u32,
+    load_addr: u32,
+    load_end_addr: u32,
+    bss_end_addr: u32,
+    entry_addr: u32,
+}
+
+/// Header fields present when [`FLAG_VIDEO_MODE`] is set.
+#[derive(Debug, Clone, Copy)]
+struct VideoMode {
+    /// 0 for a linear framebuffer, 1 for EGA text.
+    mode_type: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    /// Byte offset of the header in the kernel file.
+    offset: usize,
+    flags: u32,
+    address: Option<AddressFields>,
+    video: Option<VideoMode>,
+}
+
+fn read_u32(data: &[u8], off: usize) -> Option<u32> {
+    let b = data.get(off..off + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn find_header(kernel: &[u8]) -> Option<Header> {
+    let limit = kernel.len().min(HEADER_SEARCH_LIMIT);
+    (0..limit).step_by(4).find_map(|offset| {
+        let field = |i: usize| read_u32(kernel, offset + 4 * i);
+        if field(0)? != HEADER_MAGIC {
+            return None;
+        }
+        let flags = field(1)?;
+        if HEADER_MAGIC.wrapping_add(flags).wrapping_add(field(2)?) != 0 {
+            return None;
+        }
+        let address = if flags & FLAG_AOUT_KLUDGE != 0 {
+            Some(AddressFields {
+                header_addr: field(3)?,
+                load_addr: field(4)?,
+                load_end_addr: field(5)?,
+                bss_end_addr: field(6)?,
+                entry_addr: field(7)?,
+            })
+        } else {
+            None
+        };
+        let video = if flags & FLAG_VIDEO_MODE != 0 {
+            Some(VideoMode {
+                mode_type: field(8)?,
+                width: field(9)?,
+                height: field(10)?,
+                depth: field(11)?,
+            })
+        } else {
+            None
+        };
+        Some(Header {
+            offset,
+            flags,
+            address,
+            video,
+        })
+    })
+}
+
+/// Boot a Multiboot1 kernel. `initrd`, if any, is passed as the single
+/// module. Only returns when the boot failed.
+pub fn boot_multiboot1(
+    kernel: &[u8],
+    initrd: Option<&[u8]>,
+    cmdline: Option<&str>,
+    debug_halt: bool,
+) -> Status {
+    let Some(header) = find_header(kernel) else {
+        crate::println!("No Multiboot header in the first 8 KiB of the kernel.");
+        return Status::LOAD_ERROR;
+    };
+    let unsupported = header.flags & REQUIRED_MASK & !SUPPORTED;
+    if unsupported != 0 {
+        crate::println!(
+            "Kernel requires unsupported Multiboot features (flags {:#x}).",
+            unsupported
+        );
+        return Status::UNSUPPORTED;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        x86_64::boot_multiboot1(kernel, &header, initrd, cmdline, debug_halt)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (initrd, cmdline, debug_halt);
+        crate::println!("Multiboot is only supported on x86_64.");
+        Status::UNSUPPORTED
+    }
+}
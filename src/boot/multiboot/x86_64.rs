@@ -0,0 +1,545 @@
+use alloc::vec::Vec;
+use core::arch::{asm, global_asm};
+
+use uefi::boot::{self, AllocateType, MemoryType};
+use uefi::mem::memory_map::MemoryMap;
+use uefi::prelude::*;
+use uefi::proto::console::gop::{GraphicsOutput, PixelFormat};
+
+use super::{Header, VideoMode};
+use crate::PAGE_SIZE;
+use crate::splash::{self, Stage};
+
+/// Value in `eax` telling the kernel it was started by a Multiboot loader.
+const BOOTLOADER_MAGIC: u32 = 0x2BAD_B002;
+
+const INFO_MEMORY: u32 = 1 << 0;
+const INFO_CMDLINE: u32 = 1 << 2;
+const INFO_MODS: u32 = 1 << 3;
+const INFO_MMAP: u32 = 1 << 6;
+const INFO_LOADER_NAME: u32 = 1 << 9;
+const INFO_FRAMEBUFFER: u32 = 1 << 12;
+
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+
+const MMAP_AVAILABLE: u32 = 1;
+const MMAP_RESERVED: u32 = 2;
+const MMAP_ACPI_RECLAIMABLE: u32 = 3;
+const MMAP_NVS: u32 = 4;
+const MMAP_BADRAM: u32 = 5;
+
+/// Everything handed to a 32-bit kernel must sit below this address.
+const LOW_LIMIT: u64 = 0x1_0000_0000;
+
+/// `struct multiboot_info` from the Multiboot 0.6.96 specification.
+#[repr(C, packed)]
+struct Info {
+    flags: u32,
+    mem_lower: u32,
+    mem_upper: u32,
+    boot_device: u32,
+    cmdline: u32,
+    mods_count: u32,
+    mods_addr: u32,
+    syms: [u32; 4],
+    mmap_length: u32,
+    mmap_addr: u32,
+    drives_length: u32,
+    drives_addr: u32,
+    config_table: u32,
+    boot_loader_name: u32,
+    apm_table: u32,
+    vbe_control_info: u32,
+    vbe_mode_info: u32,
+    vbe_mode: u16,
+    vbe_interface_seg: u16,
+    vbe_interface_off: u16,
+    vbe_interface_len: u16,
+    framebuffer_addr: u64,
+    framebuffer_pitch: u32,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    framebuffer_bpp: u8,
+    framebuffer_type: u8,
+    /// Red, green and blue field position and mask size.
+    color_info: [u8; 6],
+}
+
+const _: () = assert!(size_of::<Info>() == 116);
+
+#[repr(C, packed)]
+struct Module {
+    mod_start: u32,
+    mod_end: u32,
+    string: u32,
+    reserved: u32,
+}
+
+#[repr(C, packed)]
+struct MmapEntry {
+    /// Size of the rest of the entry; lets kernels skip unknown fields.
+    size: u32,
+    base_addr: u64,
+    length: u64,
+    kind: u32,
+}
+
+/// Bump allocator over one allocation below 4 GiB, holding the info
+/// structure and everything it points to.
+struct LowArena {
+    next: u64,
+    end: u64,
+}
+
+impl LowArena {
+    fn allocate(bytes: usize) -> uefi::Result<Self> {
+        let pages = bytes.div_ceil(PAGE_SIZE);
+        let ptr = boot::allocate_pages(
+            AllocateType::MaxAddress(LOW_LIMIT - 1),
+            MemoryType::LOADER_DATA,
+            pages,
+        )?;
+        let base = ptr.as_ptr() as u64;
+        unsafe { core::ptr::write_bytes(ptr.as_ptr(), 0, pages * PAGE_SIZE) };
+        Ok(LowArena {
+            next: base,
+            end: base + (pages * PAGE_SIZE) as u64,
+        })
+    }
+
+    /// Reserve `size` zeroed bytes; panics if the arena was sized too small.
+    fn take(&mut self, size: usize, align: u64) -> u64 {
+        let addr = self.next.next_multiple_of(align);
+        assert!(addr + size as u64 <= self.end, "Multiboot arena exhausted");
+        self.next = addr + size as u64;
+        addr
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8], align: u64) -> u32 {
+        let addr = self.take(bytes.len(), align);
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), addr as *mut u8, bytes.len()) };
+        addr as u32
+    }
+
+    /// Copy `s` as a NUL-terminated C string.
+    fn push_str(&mut self, s: &str) -> u32 {
+        let addr = self.push_bytes(s.as_bytes(), 1);
+        self.take(1, 1);
+        addr
+    }
+}
+
+/// Reserve `[start, end)` at its fixed physical address for the kernel.
+fn claim(start: u64, end: u64) -> uefi::Result<()> {
+    let base = start & !(PAGE_SIZE as u64 - 1);
+    if end > LOW_LIMIT || end <= start {
+        crate::println!("Kernel load range {:#x}-{:#x} is invalid.", start, end);
+        return Err(uefi::Error::from(Status::LOAD_ERROR));
+    }
+    let pages = (end - base).div_ceil(PAGE_SIZE as u64) as usize;
+    boot::allocate_pages(AllocateType::Address(base), MemoryType::LOADER_DATA, pages).inspect_err(
+        |e| {
+            crate::println!(
+                "Cannot reserve {:#x}-{:#x} for the kernel: {:?}",
+                start,
+                end,
+                e.status()
+            );
+        },
+    )?;
+    Ok(())
+}
+
+/// Copy `src` to physical `dest` and zero the following `zero` bytes.
+unsafe fn place(dest: u64, src: &[u8], zero: usize) {
+    unsafe {
+        core::ptr::copy_nonoverlapping(src.as_ptr(), dest as *mut u8, src.len());
+        core::ptr::write_bytes((dest as *mut u8).add(src.len()), 0, zero);
+    }
+}
+
+/// Load a kernel using the header's a.out kludge addresses.
+fn load_aout(kernel: &[u8], header: &Header) -> uefi::Result<u32> {
+    let a = header.address.unwrap();
+    let invalid = || {
+        crate::println!("Multiboot header load addresses are inconsistent.");
+        uefi::Error::from(Status::LOAD_ERROR)
+    };
+
+    let header_delta = a.header_addr.checked_sub(a.load_addr).ok_or_else(invalid)? as usize;
+    let file_start = header
+        .offset
+        .checked_sub(header_delta)
+        .ok_or_else(invalid)?;
+    let load_end = match a.load_end_addr {
+        0 => a.load_addr as u64 + (kernel.len() - file_start) as u64,
+        end => end as u64,
+    };
+    if load_end < a.load_addr as u64 {
+        return Err(invalid());
+    }
+    let bss_end = (a.bss_end_addr as u64).max(load_end);
+    let file_len = (load_end - a.load_addr as u64) as usize;
+    let image = kernel
+        .get(file_start..file_start + file_len)
+        .ok_or_else(invalid)?;
+
+    claim(a.load_addr as u64, bss_end)?;
+    unsafe { place(a.load_addr as u64, image, (bss_end - load_end) as usize) };
+    Ok(a.entry_addr)
+}
+
+fn read_u16(data: &[u8], off: usize) -> Option<u16> {
+    let b = data.get(off..off + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+/// Load the PT_LOAD segments of an ELF32 kernel at their physical
+/// addresses. The entry point is translated the same way when it falls
+/// inside a segment.
+fn load_elf32(kernel: &[u8]) -> uefi::Result<u32> {
+    const PT_LOAD: u32 = 1;
+    let invalid = || {
+        crate::println!("Kernel is neither ELF32 nor marked with the a.out kludge.");
+        uefi::Error::from(Status::LOAD_ERROR)
+    };
+    let u32_at = |off: usize| super::read_u32(kernel, off).ok_or_else(invalid);
+
+    if !kernel.starts_with(b"\x7fELF\x01") {
+        return Err(invalid());
+    }
+    let entry = u32_at(24)?;
+    let phoff = u32_at(28)? as usize;
+    let phentsize = read_u16(kernel, 42).ok_or_else(invalid)? as usize;
+    let phnum = read_u16(kernel, 44).ok_or_else(invalid)? as usize;
+
+    // (file offset, virtual address, physical address, file size, memory size)
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let ph = phoff + i * phentsize;
+        if u32_at(ph)? != PT_LOAD {
+            continue;
+        }
+        let segment = (
+            u32_at(ph + 4)? as usize,
+            u32_at(ph + 8)?,
+            u32_at(ph + 12)? as u64,
+            u32_at(ph + 16)? as usize,
+            u32_at(ph + 20)? as u64,
+        );
+        if segment.3 as u64 > segment.4 || kernel.len() < segment.0 + segment.3 {
+            return Err(invalid());
+        }
+        segments.push(segment);
+    }
+
+    let start = segments.iter().map(|s| s.2).min().ok_or_else(invalid)?;
+    let end = segments.iter().map(|s| s.2 + s.4).max().unwrap();
+    claim(start, end)?;
+
+    let mut entry_phys = entry;
+    for &(offset, vaddr, paddr, filesz, memsz) in &segments {
+        let data = &kernel[offset..offset + filesz];
+        unsafe { place(paddr, data, memsz as usize - filesz) };
+        if (vaddr as u64..vaddr as u64 + memsz).contains(&(entry as u64)) {
+            entry_phys = (entry - vaddr) + paddr as u32;
+        }
+    }
+    Ok(entry_phys)
+}
+
+/// Switch GOP to the mode the kernel asked for, if available, and describe
+/// the resulting linear framebuffer in `info`.
+fn set_video(video: &VideoMode, info: &mut Info) {
+    if video.mode_type != 0 {
+        crate::println!("  EGA text mode requested; UEFI only offers framebuffers.");
+        return;
+    }
+    let Ok(handle) = boot::get_handle_for_protocol::<GraphicsOutput>() else {
+        return;
+    };
+    let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(handle) else {
+        return;
+    };
+
+    if video.width != 0 && video.height != 0 {
+        let wanted = (video.width as usize, video.height as usize);
+        let mode = gop.modes().find(|m| m.info().resolution() == wanted);
+        match mode {
+            Some(mode) => {
+                if let Err(e) = gop.set_mode(&mode) {
+                    crate::println!(
+                        "  Cannot switch to {}x{}: {:?}",
+                        wanted.0,
+                        wanted.1,
+                        e.status()
+                    );
+                }
+            }
+            None => crate::println!(
+                "  No {}x{} mode; keeping the current one.",
+                wanted.0,
+                wanted.1
+            ),
+        }
+    }
+    if video.depth != 0 && video.depth != 32 {
+        crate::println!(
+            "  {} bpp requested; UEFI framebuffers are 32 bpp.",
+            video.depth
+        );
+    }
+
+    let mode = gop.current_mode_info();
+    // (red, green, blue) field positions; each field is 8 bits wide.
+    let (r, g, b) = match mode.pixel_format() {
+        PixelFormat::Rgb => (0, 8, 16),
+        PixelFormat::Bgr => (16, 8, 0),
+        _ => return,
+    };
+    let (width, height) = mode.resolution();
+    info.flags |= INFO_FRAMEBUFFER;
+    info.framebuffer_addr = gop.frame_buffer().as_mut_ptr() as u64;
+    info.framebuffer_pitch = (mode.stride() * 4) as u32;
+    info.framebuffer_width = width as u32;
+    info.framebuffer_height = height as u32;
+    info.framebuffer_bpp = 32;
+    info.framebuffer_type = FRAMEBUFFER_TYPE_RGB;
+    info.color_info = [r, 8, g, 8, b, 8];
+}
+
+fn mmap_type(ty: MemoryType) -> u32 {
+    match ty {
+        MemoryType::CONVENTIONAL
+        | MemoryType::LOADER_CODE
+        | MemoryType::LOADER_DATA
+        | MemoryType::BOOT_SERVICES_CODE
+        | MemoryType::BOOT_SERVICES_DATA => MMAP_AVAILABLE,
+        MemoryType::ACPI_RECLAIM => MMAP_ACPI_RECLAIMABLE,
+        MemoryType::ACPI_NON_VOLATILE => MMAP_NVS,
+        MemoryType::UNUSABLE => MMAP_BADRAM,
+        _ => MMAP_RESERVED,
+    }
+}
+
+/// End of the run of available memory containing `start` (or `start`
+/// itself when that address is not available).
+fn available_from(map: &impl MemoryMap, start: u64) -> u64 {
+    let mut end = start;
+    while let Some(d) = map.entries().find(|d| {
+        mmap_type(d.ty) == MMAP_AVAILABLE
+            && d.phys_start <= end
+            && end < d.phys_start + d.page_count * PAGE_SIZE as u64
+    }) {
+        end = d.phys_start + d.page_count * PAGE_SIZE as u64;
+    }
+    end
+}
+
+// Leaves long mode and jumps to a 32-bit Multiboot entry point. It is
+// position independent and gets copied below 4 GiB before use, where UEFI's
+// identity mapping keeps it addressable once paging is off.
+//
+// In: rdi = entry point, rsi = multiboot_info, both below 4 GiB.
+global_asm!(
+    ".p2align 4",
+    ".global alpheratz_mb1_trampoline",
+    "alpheratz_mb1_trampoline:",
+    ".code64",
+    "cli",
+    // A PCIDE-enabled CPU refuses to turn paging off.
+    "mov rax, cr4",
+    "btr rax, 17",
+    "mov cr4, rax",
+    "lea rax, [rip + alpheratz_mb1_gdt]",
+    "sub rsp, 16",
+    // GDT limit: three 8-byte descriptors.
+    "mov word ptr [rsp], 23",
+    "mov qword ptr [rsp + 2], rax",
+    "lgdt [rsp]",
+    "lea rax, [rip + alpheratz_mb1_compat]",
+    "push 0x08",
+    "push rax",
+    // retfq: continue in the 32-bit code segment (compatibility mode).
+    ".byte 0x48, 0xcb",
+    ".code32",
+    "alpheratz_mb1_compat:",
+    "mov eax, cr0",
+    "and eax, 0x7fffffff",
+    "mov cr0, eax",
+    // Clear EFER.LME now that paging is off.
+    "mov ecx, 0xc0000080",
+    "rdmsr",
+    "and eax, 0xfffffeff",
+    "wrmsr",
+    "mov ax, 0x10",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov fs, ax",
+    "mov gs, ax",
+    "mov ss, ax",
+    "mov eax, {magic}",
+    "mov ebx, esi",
+    "jmp edi",
+    ".p2align 3",
+    "alpheratz_mb1_gdt:",
+    ".quad 0",
+    // 0x08: 32-bit code, base 0, limit 4 GiB.
+    ".quad 0x00cf9a000000ffff",
+    // 0x10: data, base 0, limit 4 GiB.
+    ".quad 0x00cf92000000ffff",
+    ".global alpheratz_mb1_trampoline_end",
+    "alpheratz_mb1_trampoline_end:",
+    ".code64",
+    magic = const BOOTLOADER_MAGIC,
+);
+
+unsafe extern "C" {
+    static alpheratz_mb1_trampoline: u8;
+    static alpheratz_mb1_trampoline_end: u8;
+}
+
+/// Boot a Multiboot1 kernel on x86_64.
+///
+/// 1. Loads the image at its fixed physical address (ELF32 or a.out kludge)
+/// 2. Copies the initrd below 4 GiB as the only module
+/// 3. Sets the requested video mode, if any
+/// 4. Exits boot services and fills in the memory information
+/// 5. Drops to 32-bit protected mode with paging off and jumps to the entry
+///    with `eax` = 0x2BADB002 and `ebx` = the `multiboot_info` address,
+///    first waiting for a debugger when `debug_halt` is set
+pub fn boot_multiboot1(
+    kernel: &[u8],
+    header: &Header,
+    initrd: Option<&[u8]>,
+    cmdline: Option<&str>,
+    debug_halt: bool,
+) -> Status {
+    crate::println!("Multiboot1 Boot (x86_64)");
+
+    let entry = match header.address {
+        Some(_) => load_aout(kernel, header),
+        None => load_elf32(kernel),
+    };
+    let entry = match entry {
+        Ok(e) => e,
+        Err(e) => return e.status(),
+    };
+    crate::println!("  Entry point: {:#x}", entry);
+
+    let module = match initrd {
+        Some(data) => {
+            let pages = data.len().max(1).div_ceil(PAGE_SIZE);
+            let ptr = match boot::allocate_pages(
+                AllocateType::MaxAddress(LOW_LIMIT - 1),
+                MemoryType::LOADER_DATA,
+                pages,
+            ) {
+                Ok(p) => p,
+                Err(e) => {
+                    crate::println!("Cannot place the module below 4 GiB: {:?}", e.status());
+                    return e.status();
+                }
+            };
+            unsafe { place(ptr.as_ptr() as u64, data, 0) };
+            Some((ptr.as_ptr() as u32, data.len() as u32))
+        }
+        None => None,
+    };
+
+    // Room for the map as it is now, plus splits caused by our own
+    // allocations and by ExitBootServices.
+    let mmap_capacity = match boot::memory_map(MemoryType::LOADER_DATA) {
+        Ok(m) => m.len() + 16,
+        Err(e) => return e.status(),
+    };
+    let trampoline = unsafe {
+        let start = &raw const alpheratz_mb1_trampoline;
+        let end = &raw const alpheratz_mb1_trampoline_end;
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    };
+    let cmdline = cmdline.unwrap_or("");
+    let mut arena = match LowArena::allocate(
+        2 * PAGE_SIZE + cmdline.len() + mmap_capacity * size_of::<MmapEntry>(),
+    ) {
+        Ok(a) => a,
+        Err(e) => {
+            crate::println!("Cannot allocate Multiboot information: {:?}", e.status());
+            return e.status();
+        }
+    };
+
+    let trampoline_addr = arena.push_bytes(trampoline, 16);
+    let info_addr = arena.take(size_of::<Info>(), 8);
+    let info = unsafe { &mut *(info_addr as *mut Info) };
+
+    info.flags |= INFO_CMDLINE | INFO_LOADER_NAME;
+    info.cmdline = arena.push_str(cmdline);
+    info.boot_loader_name = arena.push_str("alpheratz");
+
+    if let Some((start, len)) = module {
+        let string = arena.push_str("initrd");
+        let addr = arena.take(size_of::<Module>(), 4);
+        unsafe {
+            (addr as *mut Module).write(Module {
+                mod_start: start,
+                mod_end: start + len,
+                string,
+                reserved: 0,
+            });
+        }
+        info.flags |= INFO_MODS;
+        info.mods_count = 1;
+        info.mods_addr = addr as u32;
+    }
+
+    if let Some(video) = &header.video {
+        set_video(video, info);
+    }
+
+    let mmap_addr = arena.take(mmap_capacity * size_of::<MmapEntry>(), 8);
+
+    crate::println!("Exiting boot services...");
+    splash::stage(Stage::Handoff);
+    crate::late::prepare();
+    let memory_map = unsafe { boot::exit_boot_services(Some(MemoryType::LOADER_DATA)) };
+
+    let mut count = 0;
+    for desc in memory_map.entries().take(mmap_capacity) {
+        let entry = MmapEntry {
+            size: (size_of::<MmapEntry>() - 4) as u32,
+            base_addr: desc.phys_start,
+            length: desc.page_count * PAGE_SIZE as u64,
+            kind: mmap_type(desc.ty),
+        };
+        unsafe { (mmap_addr as *mut MmapEntry).add(count).write(entry) };
+        count += 1;
+    }
+    info.flags |= INFO_MEMORY | INFO_MMAP;
+    info.mmap_addr = mmap_addr as u32;
+    info.mmap_length = (count * size_of::<MmapEntry>()) as u32;
+    info.mem_lower = (available_from(&memory_map, 0).min(640 * 1024) / 1024) as u32;
+    info.mem_upper = ((available_from(&memory_map, 0x10_0000) - 0x10_0000) / 1024) as u32;
+
+    if debug_halt {
+        crate::serial::debug_halt(&[
+            ("entry", entry as u64),
+            ("multiboot_info", info_addr),
+            ("module", module.map_or(0, |(start, _)| start as u64)),
+        ]);
+    }
+
+    crate::serial::serial_str("[LOADER] Jumping to Multiboot kernel at ");
+    crate::serial::serial_hex(entry as u64);
+    crate::serial::serial_str("\r\n");
+
+    unsafe {
+        asm!(
+            "jmp {trampoline}",
+            trampoline = in(reg) trampoline_addr as u64,
+            in("rdi") entry as u64,
+            in("rsi") info_addr,
+            options(noreturn)
+        );
+    }
+}
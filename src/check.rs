@@ -0,0 +1,105 @@
+//! `--check` mode: validate the configuration, print a report and exit
+//! without showing the menu, e.g. `alpheratz.efi --check` from the UEFI shell.
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use alpheratz_core::validate::{self, Issue, Severity};
+use alpheratz_core::vars;
+use uefi::boot;
+use uefi::prelude::*;
+use uefi::proto::loaded_image::LoadedImage;
+
+use crate::config::{Config, SearchMethod};
+use crate::download::expand_vars;
+use crate::fsutil;
+
+/// Whether `--check` was passed in the image's load options.
+pub fn requested() -> bool {
+    has_option("--check")
+}
+
+/// Whether `flag` is one of the image's load options.
+pub fn has_option(flag: &str) -> bool {
+    let Ok(loaded_image) = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())
+    else {
+        return false;
+    };
+    loaded_image
+        .load_options_as_cstr16()
+        .is_ok_and(|opts| String::from(opts).split_whitespace().any(|o| o == flag))
+}
+
+/// ESP files named by the entries that do not exist. Paths still holding
+/// network variables and `devpath:` files are left unchecked.
+fn missing_esp_files(cfg: &Config, issues: &mut Vec<Issue>) {
+    let Ok(mut root) = fsutil::open_esp_root() else {
+        return;
+    };
+    for entry in &cfg.entry {
+        let identity = cfg.identity_for(entry);
+        for f in &entry.files {
+            let Some(file) = f.file.as_deref().filter(|_| f.search == SearchMethod::Esp) else {
+                continue;
+            };
+            let path = expand_vars(file, identity.as_ref(), None);
+            if path.starts_with(fsutil::DEVPATH_PREFIX) || vars::references(&path).next().is_some()
+            {
+                continue;
+            }
+            if !fsutil::exists(&mut root, &path) {
+                issues.push(Issue {
+                    severity: Severity::Error,
+                    entry: Some(entry.name.clone()),
+                    message: format!("{} not found on the ESP", path),
+                });
+            }
+        }
+    }
+}
+
+/// Check `text` (the configuration as read, or why it could not be) and
+/// print a report. Succeeds only when there are no errors.
+pub fn run(text: uefi::Result<String>) -> Status {
+    let path = crate::CONFIG_PATH;
+    let text = match text {
+        Ok(t) => t,
+        Err(e) => {
+            crate::println!("{}: cannot read: {:?}", path, e.status());
+            return e.status();
+        }
+    };
+    let cfg = match Config::from_str(&text) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            crate::println!("{}: {}", path, validate::syntax_error(&text, &e));
+            return Status::LOAD_ERROR;
+        }
+    };
+
+    let mut issues = validate::validate(&cfg);
+    missing_esp_files(&cfg, &mut issues);
+
+    for issue in &issues {
+        crate::println!("{}", issue);
+    }
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error)
+        .count();
+    crate::println!(
+        "{}: {} entries, {} errors, {} warnings",
+        path,
+        cfg.entry.len(),
+        errors,
+        issues.len() - errors
+    );
+    if errors == 0 {
+        Status::SUCCESS
+    } else {
+        Status::INVALID_PARAMETER
+    }
+}
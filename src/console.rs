@@ -0,0 +1,160 @@
+extern crate alloc;
+
+use alloc::string::String;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::proto::console::gop::GraphicsOutput;
+use uefi::proto::console::serial::{ControlBits, Serial};
+use uefi::proto::console::text::Input;
+use uefi::{Handle, Identify};
+
+use crate::config::{Config, Entry};
+use crate::serial;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Like `uefi::print!`, but diverted to the serial port in quiet mode.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::console::_print(format_args!($($arg)*)));
+}
+
+/// Like `uefi::println!`, but diverted to the serial port in quiet mode.
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+struct SerialWriter;
+
+impl Write for SerialWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                serial::serial_str("\r\n");
+            }
+            serial::serial_str(line);
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) {
+    if QUIET.load(Ordering::Relaxed) {
+        let _ = SerialWriter.write_fmt(args);
+    } else {
+        uefi::print!("{}", args);
+    }
+}
+
+/// Enter quiet mode for `entry = { quiet = true }`: clear the screen and
+/// send everything printed from now on to the serial port only.
+pub fn enter_quiet() {
+    uefi::system::with_stdout(|out| {
+        let _ = out.clear();
+    });
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+/// Leave quiet mode, returning whether it was active.
+pub fn leave_quiet() -> bool {
+    QUIET.swap(false, Ordering::Relaxed)
+}
+
+unsafe fn open_get<P: uefi::proto::ProtocolPointer + ?Sized>(
+    handle: Handle,
+) -> uefi::Result<boot::ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+fn handles(guid: &uefi::Guid) -> impl Iterator<Item = Handle> {
+    boot::locate_handle_buffer(boot::SearchType::ByProtocol(guid))
+        .map(|h| h.to_vec())
+        .unwrap_or_default()
+        .into_iter()
+}
+
+/// Take one keystroke from ConIn, any other text input device, or a serial
+/// port not wired into ConIn, so headless machines can answer prompts.
+/// Protocols are opened shared, leaving the firmware's terminal drivers in
+/// place.
+pub fn key_pressed() -> bool {
+    if let Ok(Some(_)) = uefi::system::with_stdin(|stdin| stdin.read_key()) {
+        return true;
+    }
+    for handle in handles(&Input::GUID) {
+        if let Ok(mut input) = unsafe { open_get::<Input>(handle) }
+            && let Ok(Some(_)) = input.read_key()
+        {
+            return true;
+        }
+    }
+    for handle in handles(&Serial::GUID) {
+        let Ok(mut port) = (unsafe { open_get::<Serial>(handle) }) else {
+            continue;
+        };
+        let waiting = port
+            .get_control_bits()
+            .is_ok_and(|bits| !bits.contains(ControlBits::INPUT_BUFFER_EMPTY));
+        if waiting && port.read(&mut [0u8]).is_ok() {
+            return true;
+        }
+    }
+    false
+}
+
+fn default_serial() -> &'static str {
+    #[cfg(target_arch = "aarch64")]
+    {
+        "ttyAMA0,115200"
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        "ttyS0,115200"
+    }
+}
+
+fn has_protocol(guid: &uefi::Guid) -> bool {
+    boot::locate_handle_buffer(boot::SearchType::ByProtocol(guid)).is_ok_and(|h| !h.is_empty())
+}
+
+/// With `auto_console`, append `console=` parameters for the serial port
+/// (`serial_console`, or the architecture default) when firmware exposes one,
+/// and `console=tty0` when a GOP display is present. The display goes last so
+/// it becomes /dev/console on desktops. A cmdline that already names a
+/// console is left alone.
+pub fn inject(cfg: &Config, entry: &Entry, cmdline: Option<String>) -> Option<String> {
+    if !entry.auto_console.unwrap_or(cfg.auto_console) {
+        return cmdline;
+    }
+    let mut out = cmdline.unwrap_or_default();
+    if out.split_whitespace().any(|p| p.starts_with("console=")) {
+        return Some(out);
+    }
+
+    let serial = (cfg.serial_console.is_some() || has_protocol(&Serial::GUID))
+        .then(|| cfg.serial_console.as_deref().unwrap_or(default_serial()));
+    let display = has_protocol(&GraphicsOutput::GUID).then_some("tty0");
+    for console in serial.into_iter().chain(display) {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str("console=");
+        out.push_str(console);
+    }
+    Some(out)
+}
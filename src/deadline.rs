@@ -0,0 +1,58 @@
+//! Wall-clock limit on resolving an entry (`resolve_timeout`), checked by
+//! the download loops and by network and iSCSI bring-up on the way there.
+
+use core::cell::Cell;
+
+use uefi::Event;
+use uefi::boot::{self, EventType, TimerTrigger, Tpl};
+use uefi::prelude::*;
+
+/// A one-shot timer event; no limit when `resolve_timeout` is unset or 0.
+pub struct Deadline {
+    event: Option<Event>,
+    fired: Cell<bool>,
+}
+
+impl Deadline {
+    pub fn after(secs: Option<usize>) -> Self {
+        let event = secs.filter(|&s| s > 0).and_then(|s| {
+            let e =
+                unsafe { boot::create_event(EventType::TIMER, Tpl::CALLBACK, None, None) }.ok()?;
+            boot::set_timer(&e, TimerTrigger::Relative(s as u64 * 10_000_000)).ok()?;
+            Some(e)
+        });
+        Deadline {
+            event,
+            fired: Cell::new(false),
+        }
+    }
+
+    /// `TIMEOUT` once the deadline has passed. Callers turn any error into
+    /// [`crate::error::AlpheratzError::Deadline`] when [`Self::expired`].
+    pub fn check(&self) -> uefi::Result<()> {
+        if let Some(e) = &self.event
+            && !self.fired.get()
+            && boot::check_event(unsafe { e.unsafe_clone() }).unwrap_or(false)
+        {
+            self.fired.set(true);
+            crate::println!("  resolve_timeout exceeded");
+        }
+        if self.fired.get() {
+            return Err(uefi::Error::from(Status::TIMEOUT));
+        }
+        Ok(())
+    }
+
+    /// Whether a [`Self::check`] has found the deadline passed.
+    pub fn expired(&self) -> bool {
+        self.fired.get()
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        if let Some(e) = self.event.take() {
+            let _ = boot::close_event(e);
+        }
+    }
+}
@@ -1,131 +1,1262 @@
 extern crate alloc;
 
+use alloc::boxed::Box;
+#[cfg(feature = "network")]
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 
-use uefi::proto::network::http::HttpHelper;
+#[cfg(feature = "network")]
+use core::time::Duration;
 
+use alpheratz_core::android;
+use alpheratz_core::hex::parse_hex;
+#[cfg(feature = "network")]
+use alpheratz_core::ipxe;
+#[cfg(feature = "drivers")]
+use alpheratz_core::manifest::Manifest;
+#[cfg(feature = "network")]
+use alpheratz_core::{bsdiff, dns};
+use alpheratz_core::{sniff, validate, vars};
+use uefi::prelude::*;
+use uefi::proto::media::file::Directory;
+
+use crate::aes_gcm;
 use crate::config;
-use crate::config::{Config, Entry, SearchMethod};
+#[cfg(feature = "network")]
+use crate::config::NfsRoot;
+use crate::config::{BootFile, Config, Entry, Identity, SearchMethod};
+use crate::console;
+use crate::deadline::Deadline;
+#[cfg(feature = "network")]
+use crate::error::Phase;
+use crate::error::{self, AlpheratzError};
+use crate::fit;
 use crate::fsutil;
+#[cfg(feature = "network")]
+use crate::http::{self, HttpClient};
+#[cfg(feature = "network")]
+use crate::iscsi;
+#[cfg(feature = "network")]
+use crate::memcheck;
+use crate::menu;
+#[cfg(feature = "network")]
 use crate::net;
+#[cfg(feature = "drivers")]
+use crate::pkcs7;
+use crate::sha256;
+use crate::splash::{self, Stage};
+#[cfg(feature = "network")]
+use crate::store;
+#[cfg(feature = "network")]
+use crate::tcp;
+
+mod esp;
+#[cfg(feature = "network")]
+mod https;
+mod inline;
+#[cfg(feature = "network")]
+mod tftp;
 
 fn arch_name() -> &'static str {
     #[cfg(target_arch = "x86_64")]
-    { "x86_64" }
+    {
+        "x86_64"
+    }
     #[cfg(target_arch = "aarch64")]
-    { "aarch64" }
+    {
+        "aarch64"
+    }
     #[cfg(target_arch = "riscv64")]
-    { "riscv64" }
+    {
+        "riscv64"
+    }
     #[cfg(target_arch = "loongarch64")]
-    { "loongarch64" }
+    {
+        "loongarch64"
+    }
+}
+
+/// [`vars::expand`] for the architecture this loader was built for.
+pub fn expand_vars(s: &str, identity: Option<&Identity>, lease: Option<&vars::Lease>) -> String {
+    vars::expand(s, arch_name(), identity, lease)
+}
+
+/// Request headers announcing the loader, `entry` (if any) and `identity`
+/// to the provisioning backend: a `User-Agent` of `alpheratz/<version>
+/// (<arch>; <uuid>)` unless `[network] user_agent` replaces it,
+/// `X-Alpheratz-*` metadata, and the configured `[network.headers]`.
+#[cfg(feature = "network")]
+fn request_headers(
+    cfg: &Config,
+    entry: Option<&Entry>,
+    identity: Option<&Identity>,
+) -> Vec<(String, String)> {
+    let network = cfg.network.as_ref();
+    let version = env!("CARGO_PKG_VERSION");
+    let user_agent = match network.and_then(|n| n.user_agent.as_deref()) {
+        Some(ua) => expand_vars(ua, identity, None),
+        None => match identity.and_then(|id| id.uuid.as_deref()) {
+            Some(uuid) => format!("alpheratz/{} ({}; {})", version, arch_name(), uuid),
+            None => format!("alpheratz/{} ({})", version, arch_name()),
+        },
+    };
+    let mut headers = vec![
+        (String::from("User-Agent"), user_agent),
+        (String::from("X-Alpheratz-Version"), String::from(version)),
+        (String::from("X-Alpheratz-Arch"), String::from(arch_name())),
+    ];
+    if let Some(entry) = entry {
+        headers.push((String::from("X-Alpheratz-Entry"), entry.name.clone()));
+    }
+    for (name, value) in network.map(|n| &n.headers).into_iter().flatten() {
+        headers.push((
+            format!("X-Alpheratz-{}", name),
+            expand_vars(value, identity, None),
+        ));
+    }
+    let Some(id) = identity else {
+        return headers;
+    };
+    if let Some(v) = &id.hostname {
+        headers.push((String::from("X-Alpheratz-Hostname"), v.clone()));
+    }
+    if let Some(v) = &id.uuid {
+        headers.push((String::from("X-Alpheratz-UUID"), v.clone()));
+    }
+    if let Some(v) = &id.mac {
+        headers.push((String::from("X-Alpheratz-MAC"), v.clone()));
+    }
+    if let Some(v) = &id.token {
+        headers.push((String::from("Authorization"), format!("Bearer {}", v)));
+    }
+    headers
+}
+
+#[cfg(feature = "network")]
+fn new_http_client(nic: uefi::Handle) -> uefi::Result<HttpClient> {
+    HttpClient::new(nic)
+        .inspect_err(|e| crate::println!("  HTTP client setup failed: {:?}", e.status()))
 }
 
-pub fn expand_vars(s: &str) -> String {
-    let mut out = String::from(s);
-    if out.contains("${arch}") {
-        out = out.replace("${arch}", arch_name());
+#[cfg(feature = "network")]
+const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 3;
+
+/// Largest DNS message a DoH server can send back.
+#[cfg(feature = "network")]
+const DOH_MAX_RESPONSE: usize = 65535;
+
+/// A configured HTTP client kept alive across every file of an entry, so
+/// the TCP connection and TLS session are reused between downloads.
+#[cfg(feature = "network")]
+struct HttpSession {
+    nic: uefi::Handle,
+    client: HttpClient,
+    headers: Vec<(String, String)>,
+    lease: net::Lease,
+    /// Renews `lease` before a request made after its halfway point.
+    renew: net::RenewTimer,
+    /// `[network] doh`, and the hosts looked up through it.
+    doh: Option<String>,
+    resolved: BTreeMap<String, [u8; 4]>,
+    /// `[network] probe_timeout_secs`, and the servers that answered.
+    probe_timeout: Duration,
+    probed: BTreeSet<([u8; 4], u16)>,
+}
+
+#[cfg(feature = "network")]
+impl HttpSession {
+    fn fetch(
+        &mut self,
+        url: &str,
+        headers: &[(String, String)],
+        max: Option<usize>,
+        deadline: &Deadline,
+    ) -> uefi::Result<(http::StatusLine, Option<usize>, Vec<u8>)> {
+        let too_big = |n: usize| max.is_some_and(|m| n > m);
+
+        let rsp = self.client.get(url, headers)?;
+        let expected = rsp.content_length();
+        if expected.is_some_and(too_big) || too_big(rsp.body.len()) {
+            return Err(uefi::Error::from(Status::BAD_BUFFER_SIZE));
+        }
+        let mut data = rsp.body;
+        if let Some(len) = expected {
+            memcheck::ensure_available(len)?;
+            data.reserve_exact(len.saturating_sub(data.len()));
+        }
+        let mut chunk = vec![0u8; 64 * 1024];
+        while expected.is_none_or(|len| data.len() < len) {
+            deadline.check()?;
+            let n = match self.client.read_body(&mut chunk) {
+                Ok(n) => n,
+                Err(e) if expected.is_some() => return Err(e),
+                Err(_) => break,
+            };
+            if n == 0 {
+                break;
+            }
+            if too_big(data.len() + n) {
+                return Err(uefi::Error::from(Status::BAD_BUFFER_SIZE));
+            }
+            data.extend_from_slice(&chunk[..n]);
+        }
+        Ok((rsp.status, expected, data))
     }
+
+    /// Before the first request to a server, check that it accepts a TCP
+    /// connection, so an unreachable one fails as that rather than as a TLS
+    /// or HTTP error. Servers known only by a name firmware DNS resolves
+    /// are left to the request itself.
+    fn probe(&mut self, url: &str) -> uefi::Result<()> {
+        let (Some(host), Some(port)) = (dns::url_host(url), dns::url_port(url)) else {
+            return Ok(());
+        };
+        let Some(ip) = vars::parse_ipv4(host).or_else(|| self.resolved.get(host).copied()) else {
+            return Ok(());
+        };
+        if self.probe_timeout.is_zero() || !self.probed.insert((ip, port)) {
+            return Ok(());
+        }
+        // Without a TCP4 stack to probe with, let the request find out.
+        let Ok(status) = tcp::probe(self.nic, ip, port, self.probe_timeout) else {
+            return Ok(());
+        };
+        let why = match status {
+            Status::SUCCESS => return Ok(()),
+            tcp::CONNECTION_REFUSED | tcp::CONNECTION_RESET | tcp::PORT_UNREACHABLE => {
+                "connection refused"
+            }
+            Status::TIMEOUT => "timeout",
+            tcp::NETWORK_UNREACHABLE | tcp::HOST_UNREACHABLE => "no route",
+            _ => "connection failed",
+        };
+        crate::println!(
+            "  {}:{}: server unreachable: {} ({:?})",
+            host,
+            port,
+            why,
+            status
+        );
+        self.probed.remove(&(ip, port));
+        Err(uefi::Error::from(status))
+    }
+
+    /// [`Self::fetch`] `url` with the session headers, by the address DoH
+    /// gave for its host if any, with the host name kept in `Host`. The
+    /// firmware still checks the TLS certificate against the address.
+    fn fetch_routed(
+        &mut self,
+        url: &str,
+        max: Option<usize>,
+        deadline: &Deadline,
+    ) -> uefi::Result<(http::StatusLine, Option<usize>, Vec<u8>)> {
+        let mut headers = self.headers.clone();
+        let direct = dns::url_host(url)
+            .and_then(|host| self.resolved.get(host))
+            .and_then(|&ip| dns::with_ip(url, ip));
+        match direct {
+            Some(direct) => {
+                headers.push((String::from("Host"), String::from(http::host_of(url))));
+                self.fetch(&direct, &headers, max, deadline)
+            }
+            None => self.fetch(url, &headers, max, deadline),
+        }
+    }
+
+    /// Look `host` up through `[network] doh`, for when firmware DNS failed
+    /// on it. The query carries none of the session headers.
+    fn resolve_doh(&mut self, host: &str, deadline: &Deadline) {
+        let Some(doh) = self.doh.clone() else {
+            return;
+        };
+        if self.resolved.contains_key(host) || vars::parse_ipv4(host).is_some() {
+            return;
+        }
+        let Some(query) = dns::query(host, 0) else {
+            return;
+        };
+        let sep = if doh.contains('?') { '&' } else { '?' };
+        let url = format!("{}{}dns={}", doh, sep, dns::base64url(&query));
+        let headers = [(
+            String::from("Accept"),
+            String::from("application/dns-message"),
+        )];
+        crate::println!("  Resolving {} via {}...", host, doh);
+        match self.fetch(&url, &headers, Some(DOH_MAX_RESPONSE), deadline) {
+            Ok((status, _, body)) if status.code == 200 => match dns::answer(&body, 0) {
+                Some(ip) => {
+                    crate::println!("  {} is {}", host, net::ipv4_to_string(ip));
+                    self.resolved.insert(String::from(host), ip);
+                }
+                None => crate::println!("  DoH: no address for {}", host),
+            },
+            Ok((status, _, _)) => crate::println!("  DoH: {}", status),
+            Err(e) => crate::println!("  DoH: {:?}", e.status()),
+        }
+    }
+
+    /// GET `url` over the existing connection; on a transport failure,
+    /// reconnect once, resolving the host over DoH when configured, and
+    /// retry before giving up. Non-2xx responses and
+    /// truncated bodies are reported as errors, as is a body exceeding `max`
+    /// bytes (checked while streaming, without retrying).
+    fn get(
+        &mut self,
+        url: &str,
+        max: Option<usize>,
+        deadline: &Deadline,
+    ) -> error::Result<Vec<u8>> {
+        if self.renew.due() {
+            crate::println!("Renewing DHCP lease...");
+            if net::renew(&mut self.lease)
+                && let Some(s) = self.lease.lease_secs
+            {
+                crate::println!("  Lease:   {}s", s);
+            }
+            self.renew = net::RenewTimer::start(&self.lease);
+        }
+        self.probe(url)?;
+        let (status, expected, data) = match self.fetch_routed(url, max, deadline) {
+            Ok(r) => r,
+            Err(e)
+                if deadline.expired()
+                    || matches!(
+                        e.status(),
+                        Status::BAD_BUFFER_SIZE | Status::OUT_OF_RESOURCES
+                    ) =>
+            {
+                return Err(e.into());
+            }
+            Err(e) => {
+                crate::println!("  Request failed ({:?}), reconnecting...", e.status());
+                self.client = new_http_client(self.nic)?;
+                if let Some(host) = dns::url_host(url) {
+                    self.resolve_doh(host, deadline);
+                }
+                self.fetch_routed(url, max, deadline)?
+            }
+        };
+
+        if !status.is_success() {
+            crate::println!("  {}: {}", url, status);
+            return Err(AlpheratzError::Http {
+                url: String::from(url),
+                status,
+            });
+        }
+        if let Some(len) = expected
+            && len != data.len()
+        {
+            crate::println!(
+                "  {}: received {} bytes, Content-Length {}",
+                url,
+                data.len(),
+                len
+            );
+            return Err(AlpheratzError::Uefi(Status::END_OF_FILE));
+        }
+        Ok(data)
+    }
+}
+
+/// Bring up IPv4 and an HTTP client on a single NIC.
+#[cfg(feature = "network")]
+fn open_http(
+    cfg: &Config,
+    nic: uefi::Handle,
+    headers: &[(String, String)],
+    deadline: &Deadline,
+) -> error::Result<HttpSession> {
+    let (nic, lease) = net::bring_up_ipv4(cfg, nic, deadline)?;
+    http_session(cfg, nic, lease, headers)
+}
+
+/// Create the HTTP client on `nic`, which already holds `lease`.
+#[cfg(feature = "network")]
+fn http_session(
+    cfg: &Config,
+    nic: uefi::Handle,
+    lease: net::Lease,
+    headers: &[(String, String)],
+) -> error::Result<HttpSession> {
+    crate::println!("Creating HTTP client...");
+    let client = new_http_client(nic).map_err(|e| AlpheratzError::network(Phase::Http, e))?;
+    Ok(HttpSession {
+        nic,
+        client,
+        headers: headers.to_vec(),
+        renew: net::RenewTimer::start(&lease),
+        lease,
+        doh: cfg.network.as_ref().and_then(|n| n.doh.clone()),
+        resolved: BTreeMap::new(),
+        probe_timeout: Duration::from_secs(
+            cfg.network
+                .as_ref()
+                .and_then(|n| n.probe_timeout_secs)
+                .unwrap_or(DEFAULT_PROBE_TIMEOUT_SECS),
+        ),
+        probed: BTreeSet::new(),
+    })
+}
+
+/// Try every candidate NIC in order and return the first working HTTP
+/// client. Fails only when every interface has failed. Where DHCP can run
+/// on all of them at once, the first to get a lease is used instead.
+#[cfg(feature = "network")]
+fn open_http_any(
+    cfg: &Config,
+    headers: &[(String, String)],
+    deadline: &Deadline,
+) -> error::Result<HttpSession> {
+    net::sync_clock(cfg);
+
+    let nics = net::candidate_nic_handles(cfg)?;
+    if let Some((nic, lease)) = race(cfg, &nics, deadline)? {
+        return http_session(cfg, nic, lease, headers);
+    }
+    let mut last_err = AlpheratzError::Uefi(Status::NOT_FOUND);
+
+    for (i, &nic) in nics.iter().enumerate() {
+        match open_http(cfg, nic, headers, deadline) {
+            Ok(h) => return Ok(h),
+            Err(e) if deadline.expired() => return Err(e),
+            Err(e) => {
+                if i + 1 < nics.len() {
+                    crate::println!("  Interface failed, trying next...");
+                }
+                last_err = e;
+            }
+        }
+    }
+
+    Err(last_err)
+}
+
+/// Download `url`, with variables expanded, outside of any entry: for the
+/// loader's own maintenance rather than a boot.
+#[cfg(feature = "network")]
+pub fn fetch(cfg: &Config, url: &str, max: usize) -> error::Result<Vec<u8>> {
+    let identity = cfg.identity.clone();
+    let headers = request_headers(cfg, None, identity.as_ref());
+    let deadline = Deadline::after(cfg.resolve_timeout);
+    let mut session = open_http_any(cfg, &headers, &deadline)?;
+    let url = expand_vars(url, identity.as_ref(), Some(&session.lease));
+    crate::println!("Downloading {}...", url);
+    session.get(&url, Some(max), &deadline)
+}
+
+/// Largest iPXE script [`import_ipxe`] reads.
+#[cfg(feature = "network")]
+const IPXE_SCRIPT_MAX: usize = 1024 * 1024;
+
+/// Fetch each of `ipxe_scripts` and append the entries it boots, see
+/// [`ipxe`]. A script that cannot be fetched is reported and left out.
+#[cfg(feature = "network")]
+pub fn import_ipxe(cfg: &mut Config) {
+    if cfg.ipxe_scripts.is_empty() {
+        return;
+    }
+    let identity = cfg.identity.clone();
+    let headers = request_headers(cfg, None, identity.as_ref());
+    let deadline = Deadline::after(cfg.resolve_timeout);
+    let mut session = match open_http_any(cfg, &headers, &deadline) {
+        Ok(session) => session,
+        Err(e) => {
+            crate::println!("Cannot import iPXE scripts: {}", e);
+            return;
+        }
+    };
+    for url in cfg.ipxe_scripts.clone() {
+        let url = expand_vars(&url, identity.as_ref(), Some(&session.lease));
+        crate::println!("Importing {}...", url);
+        let script = match session.get(&url, Some(IPXE_SCRIPT_MAX), &deadline) {
+            Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+            Err(e) => {
+                crate::println!("  {}", e);
+                continue;
+            }
+        };
+        let import = ipxe::parse(&script, &url);
+        for line in &import.skipped {
+            crate::println!("  Skipped: {}", line);
+        }
+        crate::println!("  {} entries", import.entries.len());
+        cfg.entry.extend(import.entries);
+    }
+}
+
+/// Largest `remote_menu` document [`import_remote_menu`] reads.
+#[cfg(feature = "network")]
+const REMOTE_MENU_MAX: usize = 1024 * 1024;
+
+/// Where the last `remote_menu` document that parsed is kept.
+#[cfg(feature = "network")]
+const REMOTE_MENU_CACHE: &str = "\\EFI\\alpheratz\\remote-menu.toml";
+
+/// Fetch the `remote_menu` document and merge its entries under its
+/// submenu, keeping a copy on the ESP. When it cannot be fetched or does
+/// not parse, the copy kept last time is merged instead.
+#[cfg(feature = "network")]
+pub fn import_remote_menu(cfg: &mut Config) {
+    let Some(remote) = cfg.remote_menu.clone() else {
+        return;
+    };
+    let identity = cfg.identity.clone();
+    let headers = request_headers(cfg, None, identity.as_ref());
+    let deadline = Deadline::after(cfg.resolve_timeout);
+    let fetched = open_http_any(cfg, &headers, &deadline).and_then(|mut session| {
+        let url = expand_vars(&remote.url, identity.as_ref(), Some(&session.lease));
+        crate::println!("Fetching menu {}...", url);
+        session.get(&url, Some(REMOTE_MENU_MAX), &deadline)
+    });
+    let mut root = fsutil::open_esp_root().ok();
+    match fetched.map(|data| String::from_utf8_lossy(&data).into_owned()) {
+        Ok(text) => match cfg.merge_remote(&text, &remote.title) {
+            Ok(added) => {
+                crate::println!("  {} entries", added);
+                if let Some(root) = root.as_mut()
+                    && let Err(e) =
+                        fsutil::write_file_atomic(root, REMOTE_MENU_CACHE, text.as_bytes())
+                {
+                    crate::println!("  Cannot keep a copy: {:?}", e.status());
+                }
+                return;
+            }
+            Err(e) => crate::println!("  Invalid menu: {}", e.message()),
+        },
+        Err(e) => crate::println!("Cannot fetch remote menu: {}", e),
+    }
+
+    let Some(root) = root.as_mut() else {
+        return;
+    };
+    fsutil::recover_atomic(root, REMOTE_MENU_CACHE);
+    let Ok(data) = fsutil::read_file_max(root, REMOTE_MENU_CACHE, Some(REMOTE_MENU_MAX)) else {
+        return;
+    };
+    let text = String::from_utf8_lossy(&data);
+    if let Ok(added) = cfg.merge_remote(&text, &remote.title) {
+        crate::println!("  Using the copy kept last time: {} entries", added);
+    }
+}
+
+/// Race DHCP across `nics` when [`net::can_race`] allows it. `None` means
+/// trying them one at a time instead, as when the firmware has no IPv4
+/// stack on the NIC handles themselves.
+#[cfg(feature = "network")]
+fn race(
+    cfg: &Config,
+    nics: &[uefi::Handle],
+    deadline: &Deadline,
+) -> error::Result<Option<(uefi::Handle, net::Lease)>> {
+    if !net::can_race(cfg, nics) {
+        return Ok(None);
+    }
+    match net::race_dhcp(cfg, nics, deadline) {
+        Ok(won) => Ok(Some(won)),
+        Err(AlpheratzError::Network {
+            phase: Phase::Stack,
+            ..
+        }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Bring up IPv4 on the first working NIC without an HTTP client, for
+/// entries that need the lease but download nothing.
+#[cfg(feature = "network")]
+fn lease_any(cfg: &Config, deadline: &Deadline) -> error::Result<net::Lease> {
+    let nics = net::candidate_nic_handles(cfg)?;
+    if let Some((_, lease)) = race(cfg, &nics, deadline)? {
+        return Ok(lease);
+    }
+    let mut last_err = AlpheratzError::Uefi(Status::NOT_FOUND);
+
+    for (i, &nic) in nics.iter().enumerate() {
+        match net::bring_up_ipv4(cfg, nic, deadline) {
+            Ok((_, lease)) => return Ok(lease),
+            Err(e) if deadline.expired() => return Err(e),
+            Err(e) => {
+                if i + 1 < nics.len() {
+                    crate::println!("  Interface failed, trying next...");
+                }
+                last_err = e;
+            }
+        }
+    }
+
+    Err(last_err)
+}
+
+/// Compose `root=/dev/nfs nfsroot=… ip=…` from `nfs` and the DHCP lease, so
+/// the initramfs reuses the address instead of running DHCP again.
+#[cfg(feature = "network")]
+fn nfsroot_cmdline(nfs: &NfsRoot, identity: Option<&Identity>, lease: &net::Lease) -> String {
+    let addr = |a: Option<[u8; 4]>| a.map(net::ipv4_to_string).unwrap_or_default();
+    let server = nfs
+        .server
+        .as_deref()
+        .map(|s| expand_vars(s, identity, Some(lease)))
+        .or_else(|| lease.dhcp_server.map(net::ipv4_to_string));
+    if server.is_none() {
+        crate::println!("  nfsroot: no server configured and none learned from DHCP");
+    }
+
+    let mut out = String::from("root=/dev/nfs nfsroot=");
+    if let Some(server) = server {
+        out.push_str(&server);
+        out.push(':');
+    }
+    out.push_str(&expand_vars(&nfs.path, identity, Some(lease)));
+    if let Some(opts) = nfs.options.as_deref() {
+        out.push(',');
+        out.push_str(opts);
+    }
+    out.push_str(&format!(
+        " ip={}::{}:{}:{}:{}:none rw",
+        addr(lease.ip),
+        addr(lease.gateway),
+        addr(lease.netmask),
+        identity.and_then(|i| i.hostname.as_deref()).unwrap_or(""),
+        nfs.device.as_deref().unwrap_or(""),
+    ));
     out
 }
 
+/// Resolve the AES key for `entry`: the entry's `decryption_key`, else the
+/// global one.
+fn decryption_key(cfg: &Config, entry: &Entry) -> uefi::Result<Vec<u8>> {
+    resolve_key(
+        entry
+            .decryption_key
+            .as_deref()
+            .or(cfg.decryption_key.as_deref()),
+    )
+}
+
+/// The AES key `raw` gives: hex, or `@prompt` to type it at the console.
+fn resolve_key(raw: Option<&str>) -> uefi::Result<Vec<u8>> {
+    let raw = raw.ok_or_else(|| {
+        crate::println!("  Encrypted file but no decryption_key configured");
+        uefi::Error::from(Status::SECURITY_VIOLATION)
+    })?;
+
+    let hex = if raw == "@prompt" {
+        menu::read_secret("Decryption key (hex): ").ok_or(uefi::Error::from(Status::ABORTED))?
+    } else {
+        String::from(raw)
+    };
+
+    parse_hex(&hex).ok_or_else(|| {
+        crate::println!("  decryption_key is not valid hex");
+        uefi::Error::from(Status::INVALID_PARAMETER)
+    })
+}
+
+/// Read the `driver_manifest` at `path` and check its detached PKCS#7
+/// signature, `<path>.p7s`, against the Secure Boot `db`.
+#[cfg(feature = "drivers")]
+fn driver_manifest(path: &str) -> uefi::Result<Manifest> {
+    let mut root = fsutil::open_esp_root()?;
+    let path = fsutil::normalize_path(path);
+    let text = fsutil::read_file(&mut root, &path)?;
+    let signature = fsutil::read_file(&mut root, &format!("{}.p7s", path))?;
+    pkcs7::verify_detached(&text, &signature).inspect_err(|e| {
+        crate::println!("  {}.p7s: signature not accepted: {:?}", path, e.status())
+    })?;
+    let text = core::str::from_utf8(&text).map_err(|_| uefi::Error::from(Status::LOAD_ERROR))?;
+    Manifest::parse(text).map_err(|line| {
+        crate::println!("  line {} is not \"<sha256>  <path>\"", line);
+        uefi::Error::from(Status::LOAD_ERROR)
+    })
+}
+
+/// Load the configured `drivers`, only those in `driver_manifest` when one is
+/// set. A manifest that cannot be opened loads none rather than all.
+#[cfg(feature = "drivers")]
+fn load_drivers(cfg: &Config) {
+    let manifest = match cfg.driver_manifest.as_deref() {
+        Some(path) => match driver_manifest(path) {
+            Ok(m) => Some(m),
+            Err(e) => {
+                crate::println!("Drivers not loaded: {}: {:?}", path, e.status());
+                return;
+            }
+        },
+        None => None,
+    };
+    let _ = fsutil::load_drivers_from_config(cfg, manifest.as_ref());
+}
+
+fn report_error(source: &str, status: Status, max: Option<usize>) {
+    match (status, max) {
+        (Status::BAD_BUFFER_SIZE, Some(m)) => {
+            crate::println!("  {}: exceeds size limit of {} bytes", source, m)
+        }
+        _ => crate::println!("  {}: {:?}", source, status),
+    }
+}
+
+/// Refuse entries that need the network in a build without it, naming the
+/// first setting responsible.
+#[cfg(not(feature = "network"))]
+fn require_no_network(cfg: &Config, entry: &Entry) -> error::Result<()> {
+    let reason = if entry.uses_iscsi() && cfg.storage.as_ref().is_some_and(|s| s.iscsi.is_some()) {
+        Some("[storage.iscsi]")
+    } else if entry.nfsroot.is_some() {
+        Some("nfsroot")
+    } else if entry
+        .files
+        .iter()
+        .any(|f| matches!(f.search, SearchMethod::Https))
+    {
+        Some("search = \"https\"")
+    } else if entry.files.iter().any(|f| f.search == SearchMethod::Tftp) {
+        Some("search = \"tftp\"")
+    } else {
+        None
+    };
+    match reason {
+        Some(r) => Err(AlpheratzError::Config(format!(
+            "{} needs network support, which this build lacks",
+            r
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Parse an Android boot image and its optional vendor_boot companion.
+fn unpack_android<'a>(
+    boot: &'a [u8],
+    vendor: Option<&'a [u8]>,
+) -> uefi::Result<android::Combined<'a>> {
+    let fail = |what: &str, e: android::Error| {
+        crate::println!("  {}: {}", what, e);
+        uefi::Error::from(Status::LOAD_ERROR)
+    };
+    let boot = android::parse_boot(boot).map_err(|e| fail("boot.img", e))?;
+    let vendor = vendor
+        .map(android::parse_vendor_boot)
+        .transpose()
+        .map_err(|e| fail("vendor_boot.img", e))?;
+    if boot.header_version >= 3 && vendor.is_none() {
+        crate::println!(
+            "  boot.img v{} has no vendor-boot; using its ramdisk alone.",
+            boot.header_version
+        );
+    }
+    crate::println!("  Android boot image v{}", boot.header_version);
+    Ok(android::combine(&boot, vendor.as_ref()))
+}
+
 /// All resolved boot data for a single entry.
 pub struct ResolvedFiles {
     pub kernel: Option<Vec<u8>>,
     pub initrd: Option<Vec<u8>>,
     pub cmdline: Option<String>,
+    /// Device tree to install for the kernel, from a FIT image.
+    pub dtb: Option<Vec<u8>>,
+    /// `System.map` for Canicula kernels.
+    pub symbols: Option<Vec<u8>>,
+}
+
+/// Check `data` read from `source` against its `sha256` pin, if any.
+fn verify_pin(source: &str, pin: Option<&str>, data: &[u8]) -> error::Result<()> {
+    let Some(pin) = pin else {
+        return Ok(());
+    };
+    let got = sha256::to_hex(&sha256::digest(data));
+    if got.eq_ignore_ascii_case(pin) {
+        return Ok(());
+    }
+    Err(AlpheratzError::Verify {
+        source: String::from(source),
+        expected: String::from(pin),
+        got,
+    })
+}
+
+/// Download `url` in full and check it against `pin`.
+#[cfg(feature = "network")]
+fn download(
+    http: &mut HttpSession,
+    url: &str,
+    pin: Option<&str>,
+    max: Option<usize>,
+    deadline: &Deadline,
+) -> error::Result<Vec<u8>> {
+    crate::println!("Downloading {}...", url);
+    let data = http
+        .get(url, max, deadline)
+        .inspect_err(|e| report_error(url, e.status(), max))?;
+    crate::println!("  {} bytes", data.len());
+    verify_pin(url, pin, &data)?;
+    Ok(data)
+}
+
+/// Rebuild the file pinned to `digest` by fetching `delta` (with `${from}`
+/// expanded) and patching the copy last stored for `url`. `None` when there
+/// is no such copy or the delta fails; the caller then downloads in full.
+#[cfg(feature = "network")]
+fn fetch_delta(
+    http: &mut HttpSession,
+    root: &mut Directory,
+    url: &str,
+    delta: &str,
+    digest: &str,
+    max: Option<usize>,
+    deadline: &Deadline,
+) -> Option<Vec<u8>> {
+    let from = store::previous(root, url)?;
+    let base = store::get(root, &from, None)?;
+    let delta_url = delta.replace("${from}", &from);
+    crate::println!("Downloading delta {}...", delta_url);
+    let patch = match http.get(&delta_url, max, deadline) {
+        Ok(p) => p,
+        Err(e) => {
+            report_error(&delta_url, e.status(), max);
+            return None;
+        }
+    };
+    crate::println!("  {} bytes", patch.len());
+    match bsdiff::apply(&base, &patch, max.unwrap_or(usize::MAX)) {
+        Some(data) if sha256::to_hex(&sha256::digest(&data)) == digest => Some(data),
+        _ => {
+            crate::println!("  Delta did not produce {}; downloading in full", digest);
+            None
+        }
+    }
+}
+
+/// Describe what [`resolve_all`] would read for `entry` without reading
+/// anything: expanded paths and URLs, pins, and the command line as far as
+/// it is known before the files are. DHCP variables stay unexpanded.
+pub fn plan(cfg: &Config, entry: &Entry) -> Vec<String> {
+    let identity = cfg.identity_for(entry);
+    let origin = loader_origin();
+    let expand = |s: &str| {
+        let out = expand_vars(s, identity.as_ref(), None);
+        match &origin {
+            Some(origin) => out.replace("${origin}", origin),
+            None => out,
+        }
+    };
+    let mut lines = Vec::new();
+    let mut cmdline: Option<String> = None;
+    for f in &entry.files {
+        let kind = validate::type_name(f.file_type);
+        match f.search {
+            SearchMethod::Esp => {
+                let path = expand(f.file.as_deref().unwrap_or(""));
+                lines.push(format!("{}: read {}", kind, path));
+            }
+            SearchMethod::Https => {
+                let mut url = expand(f.file.as_deref().unwrap_or(""));
+                if let Some(origin) = origin.as_deref().filter(|_| vars::is_relative(&url)) {
+                    url = vars::resolve(&url, origin);
+                }
+                lines.push(format!("{}: download {}", kind, url));
+                if let (Some(_), Some(_)) = (&f.sha256, &cfg.store) {
+                    lines.push(String::from("  (a stored copy is used if there is one)"));
+                }
+                if let Some(delta) = &f.delta {
+                    lines.push(format!("  delta from {}", expand(delta)));
+                }
+            }
+            SearchMethod::Tftp => {
+                let path = expand(f.file.as_deref().unwrap_or(""));
+                lines.push(format!("{}: fetch {} over TFTP", kind, path));
+            }
+            SearchMethod::Inline => {
+                let content = expand(f.content.as_deref().unwrap_or(""));
+                lines.push(format!("{}: inline, {} bytes", kind, content.len()));
+                if f.file_type == config::FileType::Cmdline {
+                    cmdline = Some(content);
+                }
+            }
+        }
+        if f.file_type == config::FileType::Cmdline && f.search != SearchMethod::Inline {
+            cmdline = Some(String::from("<from file>"));
+        }
+        if let Some(pin) = &f.sha256 {
+            lines.push(format!("  sha256 {}", pin));
+        }
+        if let Some(max) = f.max_size {
+            lines.push(format!("  at most {} bytes", max));
+        }
+        if f.encrypted {
+            lines.push(String::from("  encrypted"));
+        }
+    }
+
+    // In the order resolve_all prepends them.
+    let mut composed = Vec::new();
+    if entry.nfsroot.is_some() {
+        composed.push(String::from("<nfsroot from DHCP>"));
+    }
+    if entry
+        .files
+        .iter()
+        .any(|f| f.file_type == config::FileType::AndroidBoot)
+    {
+        composed.push(String::from("<boot image cmdline>"));
+    }
+    composed.extend(cmdline.filter(|c| !c.is_empty()));
+    let mut cmdline = (!composed.is_empty()).then(|| composed.join(" "));
+    if entry.protocol == Some(config::Protocol::Linux) {
+        cmdline = console::inject(cfg, entry, cmdline);
+    }
+    lines.push(format!(
+        "cmdline: {}",
+        cmdline.as_deref().unwrap_or("(none)")
+    ));
+    if lines.iter().any(|l| l.contains("${")) {
+        lines.push(String::from(
+            "Remaining ${...} variables are expanded once the network is up.",
+        ));
+    }
+    lines
+}
+
+/// What the files of an entry are read with: the configuration, the
+/// variables they expand, the resolve deadline and the ESP, which both
+/// `esp` and the `[store]` cache of `https` read from.
+struct Context<'a> {
+    #[cfg(feature = "network")]
+    cfg: &'a Config,
+    identity: Option<&'a Identity>,
+    lease: Option<&'a vars::Lease>,
+    #[cfg(feature = "network")]
+    deadline: &'a Deadline,
+    esp_root: Option<Directory>,
+    /// `${origin}`, when the loader came over UEFI HTTP Boot.
+    origin: Option<String>,
+    /// `${pxe_server}`, when the loader was chainloaded over PXE.
+    pxe_server: Option<[u8; 4]>,
+}
+
+impl Context<'_> {
+    fn expand(&self, s: &str) -> String {
+        let mut out = expand_vars(s, self.identity, self.lease);
+        if let Some(origin) = &self.origin {
+            out = out.replace("${origin}", origin);
+        }
+        if let Some(server) = self.pxe_server {
+            out = out.replace("${pxe_server}", &vars::ipv4_to_string(server));
+        }
+        out
+    }
+
+    /// Expand the URL `raw`, resolving it against `${origin}` when relative.
+    #[cfg(feature = "network")]
+    fn url(&self, raw: &str) -> error::Result<String> {
+        let url = self.expand(raw);
+        if !vars::is_relative(&url) {
+            return Ok(url);
+        }
+        match &self.origin {
+            Some(origin) => Ok(vars::resolve(&url, origin)),
+            None => Err(AlpheratzError::Config(format!(
+                "\"{}\" is relative, but Alpheratz was not loaded over HTTP",
+                url
+            ))),
+        }
+    }
+}
+
+/// The directory URL Alpheratz was served from, for `${origin}`.
+fn loader_origin() -> Option<String> {
+    let uri = fsutil::loader_uri()?;
+    vars::origin(&uri).map(String::from)
+}
+
+/// Where the bytes of a file come from. Each `search` method is one
+/// implementation in a module of its own.
+trait Source {
+    /// The contents of `file`, at most `max` bytes, or `None` when the file
+    /// names nothing to read.
+    fn fetch(
+        &mut self,
+        cx: &mut Context,
+        file: &BootFile,
+        max: Option<usize>,
+    ) -> error::Result<Option<Vec<u8>>>;
+
+    /// An older copy of `file` kept from an earlier boot, offered when
+    /// fetching it failed.
+    fn cached(
+        &mut self,
+        _cx: &mut Context,
+        _file: &BootFile,
+        _max: Option<usize>,
+    ) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// The sources available to an entry, by `search` method.
+#[derive(Default)]
+struct Sources {
+    sources: Vec<(SearchMethod, Box<dyn Source>)>,
+}
+
+impl Sources {
+    fn register(&mut self, method: SearchMethod, source: Box<dyn Source>) {
+        self.sources.retain(|(m, _)| *m != method);
+        self.sources.push((method, source));
+    }
+
+    fn get(&mut self, method: SearchMethod) -> Option<&mut dyn Source> {
+        for (m, source) in &mut self.sources {
+            if *m == method {
+                return Some(source.as_mut());
+            }
+        }
+        None
+    }
 }
 
 /// Resolve every file listed in `entry` — reading from ESP, downloading via
 /// HTTPS, or extracting inline content — and return the combined result.
-pub fn resolve_all(cfg: &Config, entry: &Entry) -> uefi::Result<ResolvedFiles> {
-    let needs_https = entry.files.iter().any(|f| matches!(f.search, SearchMethod::Https));
-    let needs_esp = entry.files.iter().any(|f| matches!(f.search, SearchMethod::Esp));
+/// When `interactive`, a file that fails can be retried, skipped or taken
+/// from the cache instead of failing the entry. Running out of
+/// `resolve_timeout` fails with [`AlpheratzError::Deadline`].
+pub fn resolve_all(cfg: &Config, entry: &Entry, interactive: bool) -> error::Result<ResolvedFiles> {
+    let deadline = Deadline::after(cfg.resolve_timeout);
+    resolve_within(cfg, entry, interactive, &deadline).map_err(|e| {
+        if deadline.expired() {
+            AlpheratzError::Deadline
+        } else {
+            e
+        }
+    })
+}
+
+fn resolve_within(
+    cfg: &Config,
+    entry: &Entry,
+    interactive: bool,
+    deadline: &Deadline,
+) -> error::Result<ResolvedFiles> {
+    #[cfg(not(feature = "network"))]
+    require_no_network(cfg, entry)?;
+
+    let identity = cfg.identity_for(entry);
+
+    #[cfg(feature = "network")]
+    if entry.uses_iscsi() && cfg.storage.as_ref().is_some_and(|s| s.iscsi.is_some()) {
+        iscsi::attach(cfg, deadline)?;
+    }
+
+    #[cfg(feature = "network")]
+    let pxe = crate::pxe::Pxe::find();
+    #[cfg(feature = "network")]
+    if pxe.is_none() && entry.files.iter().any(|f| f.search == SearchMethod::Tftp) {
+        return Err(AlpheratzError::Config(String::from(
+            "search = \"tftp\" needs Alpheratz to be chainloaded over PXE",
+        )));
+    }
+
+    #[cfg(feature = "network")]
+    let needs_https = entry
+        .files
+        .iter()
+        .any(|f| matches!(f.search, SearchMethod::Https));
+    #[cfg(feature = "network")]
+    let use_store = cfg.store.is_some()
+        && entry
+            .files
+            .iter()
+            .any(|f| f.search == SearchMethod::Https && f.sha256.is_some());
+    #[cfg(not(feature = "network"))]
+    let use_store = false;
+    let needs_esp = use_store
+        || entry
+            .files
+            .iter()
+            .any(|f| matches!(f.search, SearchMethod::Esp));
 
-    let mut esp_root = if needs_esp {
+    let esp_root = if needs_esp {
         Some(fsutil::open_esp_root()?)
     } else {
         None
     };
 
-    let mut http: Option<HttpHelper> = if needs_https {
-        let _ = fsutil::load_drivers_from_config(cfg);
-        let nic = net::select_nic_handle(cfg)?;
-        net::bring_up_ipv4(cfg, nic)?;
-
-        uefi::println!("Creating HTTP client...");
-        let mut h = HttpHelper::new(nic).map_err(|e| {
-            uefi::println!("  HttpHelper::new failed: {:?}", e.status());
-            e
-        })?;
-        h.configure().map_err(|e| {
-            uefi::println!("  http.configure failed: {:?}", e.status());
-            e
-        })?;
-        Some(h)
+    #[cfg(feature = "network")]
+    let http: Option<HttpSession> = if needs_https {
+        #[cfg(feature = "drivers")]
+        load_drivers(cfg);
+        Some(open_http_any(
+            cfg,
+            &request_headers(cfg, Some(entry), identity.as_ref()),
+            deadline,
+        )?)
     } else {
         None
     };
 
+    #[cfg(feature = "network")]
+    let mut lease = http.as_ref().map(|h| h.lease.clone());
+    #[cfg(feature = "network")]
+    if lease.is_none() && entry.nfsroot.is_some() {
+        lease = Some(lease_any(cfg, deadline)?);
+    }
+    #[cfg(not(feature = "network"))]
+    let lease: Option<vars::Lease> = None;
+
     let mut kernel: Option<Vec<u8>> = None;
     let mut initrd_parts: Vec<Vec<u8>> = Vec::new();
     let mut cmdline: Option<String> = None;
+    let mut dtb: Option<Vec<u8>> = None;
+    let mut android_boot: Option<Vec<u8>> = None;
+    let mut vendor_boot: Option<Vec<u8>> = None;
+    let mut symbols: Option<Vec<u8>> = None;
+    let mut total: usize = 0;
+    let mut key: Option<Vec<u8>> = None;
 
-    for f in &entry.files {
-        let data = match f.search {
-            SearchMethod::Esp => {
-                let path = f.file.as_deref().unwrap_or("");
-                if path.is_empty() {
-                    continue;
-                }
-                let path = expand_vars(path);
-                uefi::println!("Reading {}...", path);
-                let root = esp_root.as_mut().unwrap();
-                let data = fsutil::read_file(root, &path)?;
-                uefi::println!("  {} bytes", data.len());
-                data
+    let mut sources = Sources::default();
+    sources.register(SearchMethod::Esp, Box::new(esp::Esp));
+    sources.register(SearchMethod::Inline, Box::new(inline::Inline));
+    #[cfg(feature = "network")]
+    if let Some(session) = http {
+        sources.register(SearchMethod::Https, Box::new(https::Https::new(session)));
+    }
+    #[cfg(feature = "network")]
+    let pxe_server = pxe.as_ref().map(|p| p.boot.server);
+    #[cfg(not(feature = "network"))]
+    let pxe_server = None;
+    #[cfg(feature = "network")]
+    if let Some(pxe) = pxe {
+        sources.register(SearchMethod::Tftp, Box::new(tftp::Tftp::new(pxe)));
+    }
+    let mut cx = Context {
+        #[cfg(feature = "network")]
+        cfg,
+        identity: identity.as_ref(),
+        lease: lease.as_ref(),
+        #[cfg(feature = "network")]
+        deadline,
+        esp_root,
+        origin: loader_origin(),
+        pxe_server,
+    };
+
+    for (i, f) in entry.files.iter().enumerate() {
+        deadline.check()?;
+        splash::progress(Stage::Download, i, entry.files.len());
+
+        let remaining = entry.max_size.map(|m| m.saturating_sub(total));
+        let max = match (f.max_size, remaining) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        let Some(source) = sources.get(f.search) else {
+            return Err(AlpheratzError::Config(format!(
+                "search = {:?} is not available in this build",
+                f.search
+            )));
+        };
+        let data = loop {
+            let e = match source.fetch(&mut cx, f, max) {
+                Ok(data) => break data,
+                Err(e) if !interactive || deadline.expired() => return Err(e),
+                Err(e) => e,
+            };
+            splash::stop();
+            console::leave_quiet();
+            crate::println!("{} failed: {}", validate::type_name(f.file_type), e);
+            let cached = source.cached(&mut cx, f, max);
+            let mut options = vec![('r', "retry")];
+            if f.file_type != config::FileType::Kernel {
+                options.push(('s', "skip this file"));
             }
-            SearchMethod::Https => {
-                let raw_url = f.file.as_deref().unwrap_or("");
-                if raw_url.is_empty() {
-                    continue;
-                }
-                let url = expand_vars(raw_url);
-                uefi::println!("Downloading {}...", url);
-                let h = http.as_mut().unwrap();
-                h.request_get(&url)?;
-                let rsp = h.response_first(true)?;
-                let mut data = rsp.body;
-                loop {
-                    let more = h.response_more()?;
-                    if more.is_empty() {
-                        break;
-                    }
-                    data.extend_from_slice(&more);
-                }
-                uefi::println!("  {} bytes", data.len());
-                data
+            if cached.is_some() {
+                options.push(('c', "use cached copy"));
             }
-            SearchMethod::Inline => {
-                if let Some(content) = &f.content {
-                    Vec::from(content.as_bytes())
-                } else {
-                    continue;
+            options.push(('a', "abort to menu"));
+            match menu::choose(&options) {
+                Some('r') => {}
+                Some('s') => break None,
+                Some('c') => {
+                    crate::println!("  Using the cached copy");
+                    break cached;
                 }
+                _ => return Err(e),
             }
         };
+        let Some(data) = data else {
+            continue;
+        };
+
+        let data = if f.encrypted {
+            if key.is_none() {
+                key = Some(decryption_key(cfg, entry)?);
+            }
+            aes_gcm::decrypt(key.as_deref().unwrap(), &data).map_err(|e| {
+                crate::println!("  Decryption failed: {:?}", e);
+                uefi::Error::from(Status::SECURITY_VIOLATION)
+            })?
+        } else {
+            data
+        };
+
+        match sniff::check(entry.protocol, f.file_type, &data) {
+            Some((validate::Severity::Error, message)) => {
+                return Err(AlpheratzError::Config(message));
+            }
+            Some((validate::Severity::Warning, message)) => {
+                crate::println!("  Warning: {}.", message);
+            }
+            None => {}
+        }
+
+        total += data.len();
 
         match f.file_type {
             config::FileType::Kernel => kernel = Some(data),
             config::FileType::Initrd => initrd_parts.push(data),
-            config::FileType::Cmdline => {
-                if let Ok(s) = core::str::from_utf8(&data) {
-                    cmdline = Some(String::from(s.trim_end_matches('\n')));
+            config::FileType::Cmdline => match core::str::from_utf8(&data) {
+                Ok(s) => cmdline = Some(String::from(s.trim_end_matches('\n'))),
+                // Canicula rejects a non-UTF-8 cmdline; other protocols ignore it as before.
+                Err(_) if entry.protocol == Some(config::Protocol::Canicula) => {
+                    return Err(AlpheratzError::Config(String::from("cmdline is not UTF-8")));
                 }
+                Err(_) => {}
+            },
+            config::FileType::Fit => {
+                let unpacked = fit::unpack(&data)?;
+                kernel = Some(unpacked.kernel);
+                initrd_parts.extend(unpacked.ramdisk);
+                dtb = unpacked.fdt;
             }
+            config::FileType::AndroidBoot => android_boot = Some(data),
+            config::FileType::VendorBoot => vendor_boot = Some(data),
+            config::FileType::Symbols => symbols = Some(data),
         }
     }
 
+    if let Some(boot) = &android_boot {
+        let unpacked = unpack_android(boot, vendor_boot.as_deref())?;
+        kernel = Some(Vec::from(unpacked.kernel));
+        // Bootconfig must stay at the very end of the initrd.
+        initrd_parts.push(unpacked.ramdisk);
+        cmdline = Some(match cmdline {
+            Some(cl) if !cl.is_empty() => format!("{} {}", unpacked.cmdline, cl),
+            _ => unpacked.cmdline,
+        });
+        if let Some(fdt) = unpacked.dtb {
+            dtb = Some(Vec::from(fdt));
+        }
+    } else if vendor_boot.is_some() {
+        return Err(AlpheratzError::Config(String::from(
+            "vendor-boot requires an android-boot file in the same entry",
+        )));
+    }
+
     let initrd = if initrd_parts.is_empty() {
         None
     } else if initrd_parts.len() == 1 {
@@ -139,9 +1270,29 @@ pub fn resolve_all(cfg: &Config, entry: &Entry) -> uefi::Result<ResolvedFiles> {
         Some(combined)
     };
 
+    #[cfg(feature = "network")]
+    if let (Some(nfs), Some(lease)) = (&entry.nfsroot, &lease) {
+        let nfs = nfsroot_cmdline(nfs, identity.as_ref(), lease);
+        cmdline = Some(match cmdline {
+            Some(cl) if !cl.is_empty() => format!("{} {}", nfs, cl),
+            _ => nfs,
+        });
+    }
+
+    if symbols.is_none() && entry.symbols {
+        symbols = kernel
+            .as_deref()
+            .and_then(alpheratz_core::symbols::from_elf);
+        if symbols.is_none() {
+            crate::println!("  Kernel has no ELF symbol table.");
+        }
+    }
+
     Ok(ResolvedFiles {
         kernel,
         initrd,
         cmdline,
+        dtb,
+        symbols,
     })
 }
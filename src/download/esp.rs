@@ -0,0 +1,42 @@
+//! `search = "esp"`: files on the ESP, or behind a `devpath:` device path.
+
+use alloc::vec::Vec;
+
+use uefi::prelude::*;
+
+use super::{Context, Source, report_error, verify_pin};
+use crate::config::BootFile;
+use crate::error;
+use crate::fsutil;
+
+pub struct Esp;
+
+impl Source for Esp {
+    fn fetch(
+        &mut self,
+        cx: &mut Context,
+        file: &BootFile,
+        max: Option<usize>,
+    ) -> error::Result<Option<Vec<u8>>> {
+        let path = file.file.as_deref().unwrap_or("");
+        if path.is_empty() {
+            return Ok(None);
+        }
+        let path = cx.expand(path);
+        crate::println!("Reading {}...", path);
+        let root = cx.esp_root.as_mut().unwrap();
+        let data = match path.strip_prefix(fsutil::DEVPATH_PREFIX) {
+            Some(dp) => fsutil::read_devpath_file(dp, max),
+            None => fsutil::read_file_max(root, &path, max),
+        }
+        .inspect_err(|e| {
+            report_error(&path, e.status(), max);
+            if e.status() == Status::NOT_FOUND && !path.starts_with(fsutil::DEVPATH_PREFIX) {
+                fsutil::print_nearest_listing(root, &path);
+            }
+        })?;
+        crate::println!("  {} bytes", data.len());
+        verify_pin(&path, file.sha256.as_deref(), &data)?;
+        Ok(Some(data))
+    }
+}
@@ -0,0 +1,76 @@
+//! `search = "https"`: downloads over the entry's HTTP session, served from
+//! or saved to the `[store]` cache when the file is pinned, and rebuilt from
+//! a `delta` when one is configured.
+
+use alloc::vec::Vec;
+
+use super::{Context, HttpSession, Source, download, fetch_delta};
+use crate::config::BootFile;
+use crate::error;
+use crate::store;
+
+pub struct Https {
+    session: HttpSession,
+}
+
+impl Https {
+    pub fn new(session: HttpSession) -> Self {
+        Https { session }
+    }
+}
+
+impl Source for Https {
+    fn fetch(
+        &mut self,
+        cx: &mut Context,
+        file: &BootFile,
+        max: Option<usize>,
+    ) -> error::Result<Option<Vec<u8>>> {
+        let raw_url = file.file.as_deref().unwrap_or("");
+        if raw_url.is_empty() {
+            return Ok(None);
+        }
+        let url = cx.url(raw_url)?;
+        let digest = file.sha256.as_deref().map(str::to_ascii_lowercase);
+        // resolve_all opens the ESP whenever a pinned file may be stored.
+        let stored = match (&digest, &cx.cfg.store) {
+            (Some(d), Some(_)) => store::get(cx.esp_root.as_mut().unwrap(), d, max),
+            _ => None,
+        };
+        if let Some(data) = stored {
+            crate::println!("Using stored copy of {}", url);
+            crate::println!("  {} bytes", data.len());
+            return Ok(Some(data));
+        }
+
+        let patched = match (&digest, &file.delta, &cx.cfg.store) {
+            (Some(d), Some(delta), Some(_)) => {
+                let delta = cx.url(delta)?;
+                let root = cx.esp_root.as_mut().unwrap();
+                fetch_delta(&mut self.session, root, &url, &delta, d, max, cx.deadline)
+            }
+            _ => None,
+        };
+        let data = match patched {
+            Some(data) => data,
+            None => download(&mut self.session, &url, digest.as_deref(), max, cx.deadline)?,
+        };
+        if let (Some(d), Some(s)) = (&digest, &cx.cfg.store) {
+            let root = cx.esp_root.as_mut().unwrap();
+            let saved = store::put(root, d, &data, s.max_size)
+                .and_then(|()| store::remember(root, &url, d));
+            if let Err(e) = saved {
+                crate::println!("  Could not store {}: {:?}", d, e.status());
+            }
+        }
+        Ok(Some(data))
+    }
+
+    fn cached(&mut self, cx: &mut Context, file: &BootFile, max: Option<usize>) -> Option<Vec<u8>> {
+        cx.cfg.store.as_ref()?;
+        let url = cx.url(file.file.as_deref()?).ok()?;
+        let root = cx.esp_root.as_mut()?;
+        let digest = store::previous(root, &url)?;
+        store::get(root, &digest, max)
+    }
+}
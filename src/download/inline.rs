@@ -0,0 +1,23 @@
+//! `search = "inline"`: the entry's own `content`, with variables expanded.
+
+use alloc::vec::Vec;
+
+use super::{Context, Source};
+use crate::config::BootFile;
+use crate::error;
+
+pub struct Inline;
+
+impl Source for Inline {
+    fn fetch(
+        &mut self,
+        cx: &mut Context,
+        file: &BootFile,
+        _max: Option<usize>,
+    ) -> error::Result<Option<Vec<u8>>> {
+        Ok(file
+            .content
+            .as_deref()
+            .map(|content| Vec::from(cx.expand(content).as_bytes())))
+    }
+}
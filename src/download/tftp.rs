@@ -0,0 +1,56 @@
+//! `search = "tftp"`: files on the TFTP server, read through the PXE stack
+//! that chainloaded the loader. Relative paths start from the directory of
+//! the boot file it was sent.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use alpheratz_core::pxe;
+use alpheratz_core::vars;
+
+use super::{Context, Source, report_error, verify_pin};
+use crate::config::BootFile;
+use crate::error::{self, AlpheratzError};
+use crate::pxe::Pxe;
+
+pub struct Tftp {
+    pxe: Pxe,
+}
+
+impl Tftp {
+    pub fn new(pxe: Pxe) -> Self {
+        Tftp { pxe }
+    }
+}
+
+impl Source for Tftp {
+    fn fetch(
+        &mut self,
+        cx: &mut Context,
+        file: &BootFile,
+        max: Option<usize>,
+    ) -> error::Result<Option<Vec<u8>>> {
+        let raw = file.file.as_deref().unwrap_or("");
+        if raw.is_empty() {
+            return Ok(None);
+        }
+        let mut url = cx.expand(raw);
+        if vars::is_relative(&url) {
+            url = vars::resolve(&url, &self.pxe.boot.origin());
+        }
+        let Some((server, path)) = pxe::split_url(&url) else {
+            return Err(AlpheratzError::Config(format!(
+                "\"{}\" is not a tftp://<IPv4 address>/ URL",
+                url
+            )));
+        };
+        crate::println!("Reading {}...", url);
+        let data = self.pxe.read(server, path, max).map_err(|e| {
+            report_error(&url, e.status(), max);
+            AlpheratzError::fs(&url, e)
+        })?;
+        crate::println!("  {} bytes", data.len());
+        verify_pin(&url, file.sha256.as_deref(), &data)?;
+        Ok(Some(data))
+    }
+}
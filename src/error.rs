@@ -0,0 +1,167 @@
+//! Why an entry failed to boot, kept specific enough to tell the user what
+//! to fix. Modules whose failures are worth telling apart return
+//! [`Result`]; the rest stay on `uefi::Result`, and `?` converts either way.
+
+use alloc::string::String;
+use core::fmt;
+
+use uefi::Status;
+
+use crate::config::Protocol;
+
+pub type Result<T> = core::result::Result<T, AlpheratzError>;
+
+/// The step of bringing up the network that failed.
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// No IPv4 stack appeared on the interface.
+    Stack,
+    Wifi,
+    Link,
+    Vlan,
+    Dhcp,
+    Static,
+    /// Creating the HTTP client on top of IPv4.
+    Http,
+}
+
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub enum AlpheratzError {
+    /// The configuration asks for something that cannot work.
+    Config(String),
+    Network {
+        phase: Phase,
+        status: Status,
+    },
+    /// The server answered `url` with a non-2xx `status`.
+    #[cfg(feature = "network")]
+    Http {
+        url: String,
+        status: crate::http::StatusLine,
+    },
+    Fs {
+        path: String,
+        status: Status,
+    },
+    /// A file's sha256 is not the one it is pinned to.
+    Verify {
+        source: String,
+        expected: String,
+        got: String,
+    },
+    /// Resolving the entry ran past `resolve_timeout`.
+    Deadline,
+    /// The loader for `protocol` gave up on the kernel.
+    Boot {
+        protocol: Protocol,
+        status: Status,
+    },
+    /// Anything else, by the UEFI status it failed with.
+    Uefi(Status),
+}
+
+impl AlpheratzError {
+    pub fn fs(path: &str, e: uefi::Error) -> Self {
+        AlpheratzError::Fs {
+            path: String::from(path),
+            status: e.status(),
+        }
+    }
+
+    #[cfg_attr(not(feature = "network"), allow(dead_code))]
+    pub fn network(phase: Phase, e: uefi::Error) -> Self {
+        AlpheratzError::Network {
+            phase,
+            status: e.status(),
+        }
+    }
+
+    /// The UEFI status closest to this error, for callers and policies
+    /// that only look at that.
+    pub fn status(&self) -> Status {
+        match self {
+            AlpheratzError::Config(_) => Status::INVALID_PARAMETER,
+            #[cfg(feature = "network")]
+            AlpheratzError::Http { .. } => Status::PROTOCOL_ERROR,
+            AlpheratzError::Verify { .. } => Status::SECURITY_VIOLATION,
+            AlpheratzError::Deadline => Status::TIMEOUT,
+            AlpheratzError::Network { status, .. }
+            | AlpheratzError::Fs { status, .. }
+            | AlpheratzError::Boot { status, .. }
+            | AlpheratzError::Uefi(status) => *status,
+        }
+    }
+}
+
+impl From<uefi::Error> for AlpheratzError {
+    fn from(e: uefi::Error) -> Self {
+        AlpheratzError::Uefi(e.status())
+    }
+}
+
+impl From<AlpheratzError> for uefi::Error {
+    fn from(e: AlpheratzError) -> Self {
+        uefi::Error::from(e.status())
+    }
+}
+
+impl fmt::Display for AlpheratzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlpheratzError::Config(reason) => write!(f, "configuration: {}", reason),
+            AlpheratzError::Network { phase, status } => {
+                let hint = match phase {
+                    Phase::Stack => "the firmware started no IPv4 stack on the interface",
+                    Phase::Wifi => "check [network.wifi]",
+                    Phase::Link => "no link; check the cable or the switch port",
+                    Phase::Vlan => "check [network] vlan",
+                    Phase::Dhcp => "no DHCP lease; is a DHCP server reachable?",
+                    Phase::Static => "check the [network] static address settings",
+                    Phase::Http => "the firmware may lack HTTP boot support",
+                };
+                write!(f, "network setup failed ({:?}): {}", status, hint)
+            }
+            #[cfg(feature = "network")]
+            AlpheratzError::Http { url, status } => {
+                write!(f, "{}: {}", url, status)?;
+                match status.code {
+                    401 | 403 => f.write_str("; check the [identity] token"),
+                    404 | 410 => f.write_str("; not on the server"),
+                    500..=599 => f.write_str("; server error"),
+                    _ => Ok(()),
+                }
+            }
+            AlpheratzError::Fs { path, status } => {
+                write!(f, "{}: ", path)?;
+                match *status {
+                    Status::NOT_FOUND => f.write_str("not found"),
+                    Status::BAD_BUFFER_SIZE => f.write_str("larger than max_size allows"),
+                    Status::OUT_OF_RESOURCES => f.write_str("not enough memory to read it"),
+                    s => write!(f, "{:?}", s),
+                }
+            }
+            AlpheratzError::Verify {
+                source,
+                expected,
+                got,
+            } => write!(f, "{}: sha256 is {}, expected {}", source, got, expected),
+            AlpheratzError::Deadline => f.write_str("resolve_timeout exceeded"),
+            AlpheratzError::Boot { protocol, status } => {
+                write!(f, "{} boot failed: {:?}", protocol, status)?;
+                match *status {
+                    Status::SECURITY_VIOLATION | Status::ACCESS_DENIED => {
+                        f.write_str("; rejected by Secure Boot")
+                    }
+                    Status::LOAD_ERROR | Status::UNSUPPORTED => {
+                        f.write_str("; the kernel does not suit this protocol")
+                    }
+                    _ => Ok(()),
+                }
+            }
+            AlpheratzError::Uefi(Status::TIMEOUT) => f.write_str("timed out"),
+            AlpheratzError::Uefi(status) => write!(f, "{:?}", status),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! The loader's fallback copy. Before a self-update replaces the loader,
+//! [`arm`] keeps the running image as `alpheratz-prev.efi` beside it, gives
+//! that copy a boot option placed just ahead of the loader's own in
+//! `BootOrder`, and sets `BootNext` so the new loader is started once. The
+//! new loader marks a successful start by putting its option first again
+//! ([`check`]). If it never gets that far, firmware falls through to the
+//! previous copy on the next boot, which restores itself in the loader's
+//! place.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use alpheratz_core::load_option;
+use uefi::prelude::*;
+use uefi::runtime::{VariableAttributes, VariableVendor};
+use uefi::{CStr16, CString16, guid};
+
+use crate::fsutil;
+
+/// Vendor of the variables Alpheratz keeps for itself.
+const VENDOR: VariableVendor = VariableVendor(guid!("6c1a0f7e-3b52-4d8e-9a41-2f0d7c5e8b93"));
+
+/// Set while an update has not been confirmed; holds the ESP path of the
+/// loader that was replaced.
+const PENDING: &CStr16 = cstr16!("AlpheratzUpdatePending");
+
+pub const PREV_NAME: &str = "alpheratz-prev.efi";
+
+const PREV_DESCRIPTION: &str = "Alpheratz (previous)";
+
+fn attributes() -> VariableAttributes {
+    VariableAttributes::NON_VOLATILE
+        | VariableAttributes::BOOTSERVICE_ACCESS
+        | VariableAttributes::RUNTIME_ACCESS
+}
+
+fn global(name: &str) -> Option<Box<[u8]>> {
+    let name = CString16::try_from(name).ok()?;
+    let (data, _) =
+        uefi::runtime::get_variable_boxed(&name, &VariableVendor::GLOBAL_VARIABLE).ok()?;
+    Some(data)
+}
+
+fn set_global(name: &str, data: &[u8]) -> uefi::Result<()> {
+    let name =
+        CString16::try_from(name).map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    uefi::runtime::set_variable(&name, &VariableVendor::GLOBAL_VARIABLE, attributes(), data)
+}
+
+fn boot_order() -> Vec<u16> {
+    global("BootOrder")
+        .map(|d| load_option::parse_order(&d))
+        .unwrap_or_default()
+}
+
+/// The boot option firmware started this boot with.
+fn boot_current() -> Option<u16> {
+    let data = global("BootCurrent")?;
+    Some(u16::from_le_bytes([*data.first()?, *data.get(1)?]))
+}
+
+fn is_fallback(option: u16) -> bool {
+    global(&load_option::variable_name(option))
+        .and_then(|o| load_option::description(&o))
+        .is_some_and(|d| d == PREV_DESCRIPTION)
+}
+
+/// Where the fallback copy of the loader at `main` is kept.
+fn prev_path(main: &str) -> String {
+    let dir = &main[..main.rfind('\\').unwrap_or(0)];
+    alloc::format!("{}\\{}", dir, PREV_NAME)
+}
+
+/// Point the fallback copy's boot option at `prev`, reusing the one in
+/// `order` left by an earlier update or taking the first unused number.
+fn fallback_option(order: &[u16], prev: &str) -> uefi::Result<u16> {
+    let number = match order.iter().copied().find(|&n| is_fallback(n)) {
+        Some(n) => n,
+        None => (0..=u16::MAX)
+            .find(|&n| global(&load_option::variable_name(n)).is_none())
+            .ok_or_else(|| uefi::Error::from(Status::OUT_OF_RESOURCES))?,
+    };
+    let path = fsutil::file_device_path(prev)?;
+    let option = load_option::encode(PREV_DESCRIPTION, path.as_bytes());
+    set_global(&load_option::variable_name(number), &option)?;
+    Ok(number)
+}
+
+/// Keep the loader at `main` as the fallback copy and have firmware start
+/// whatever replaces it once, falling back to the copy after that until
+/// [`check`] confirms the start. Call before replacing the file.
+pub fn arm(main: &str) -> uefi::Result<()> {
+    let prev = prev_path(main);
+    let mut root = fsutil::open_esp_root()?;
+    let current = fsutil::read_file(&mut root, main)?;
+    fsutil::write_file_atomic(&mut root, &prev, &current)?;
+
+    match boot_current() {
+        Some(current) => {
+            let order = boot_order();
+            let fallback = fallback_option(&order, &prev)?;
+            let order = load_option::place(&order, current, fallback, true);
+            set_global("BootOrder", &load_option::encode_order(&order))?;
+            set_global("BootNext", &current.to_le_bytes())?;
+        }
+        None => crate::println!(
+            "  Not started from a boot option; firmware cannot fall back to {}",
+            prev
+        ),
+    }
+    uefi::runtime::set_variable(PENDING, &VENDOR, attributes(), main.as_bytes())
+}
+
+/// Put the loader's own boot option back ahead of the fallback copy's.
+fn demote_fallback() {
+    let order = boot_order();
+    let Some(at) = order.iter().position(|&n| is_fallback(n)) else {
+        return;
+    };
+    let Some(&main) = order.get(at + 1) else {
+        return;
+    };
+    let order = load_option::place(&order, main, order[at], false);
+    let _ = set_global("BootOrder", &load_option::encode_order(&order));
+}
+
+/// Settle an update [`arm`] left pending. Started as the new loader, this
+/// marks the start as successful; started as the fallback copy, because the
+/// new loader did not get this far, it puts the copy back in its place.
+pub fn check() {
+    let Ok((pending, _)) = uefi::runtime::get_variable_boxed(PENDING, &VENDOR) else {
+        return;
+    };
+    let _ = uefi::runtime::delete_variable(PENDING, &VENDOR);
+    demote_fallback();
+
+    let Ok(main) = core::str::from_utf8(&pending) else {
+        return;
+    };
+    let prev = prev_path(main);
+    let running = fsutil::loader_path().unwrap_or_default();
+    if !running.eq_ignore_ascii_case(&prev) {
+        return;
+    }
+    crate::println!("The updated loader did not start; restoring the previous copy.");
+    let restored = fsutil::open_esp_root().and_then(|mut root| {
+        let image = fsutil::read_file(&mut root, &prev)?;
+        fsutil::write_file_atomic(&mut root, main, &image)
+    });
+    if let Err(e) = restored {
+        crate::println!("  Cannot restore {}: {:?}", main, e.status());
+    }
+}
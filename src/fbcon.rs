@@ -0,0 +1,387 @@
+//! Text on the GOP framebuffer after exit_boot_services(), so late
+//! failures are visible without a serial console, and the large-text menu
+//! before it. Glyphs are 5×7, drawn with doubled rows in 8×16 cells, or
+//! scaled twice that for [`Large`].
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::proto::console::gop::{GraphicsOutput, PixelFormat};
+use uefi::proto::console::text::Color;
+
+use crate::render::{self, Renderer};
+use crate::serial;
+
+const CELL_W: usize = 8;
+const CELL_H: usize = 16;
+
+/// Framebuffer base; 0 until [`capture`] found a usable one.
+static BASE: AtomicU64 = AtomicU64::new(0);
+static WIDTH: AtomicUsize = AtomicUsize::new(0);
+static HEIGHT: AtomicUsize = AtomicUsize::new(0);
+/// Pixels per scan line.
+static STRIDE: AtomicUsize = AtomicUsize::new(0);
+static BGR: AtomicBool = AtomicBool::new(true);
+static COL: AtomicUsize = AtomicUsize::new(0);
+static ROW: AtomicUsize = AtomicUsize::new(0);
+
+/// Rows of 5 pixels, most significant of the low 5 bits leftmost, for
+/// ' ' to '~'.
+#[rustfmt::skip]
+static GLYPHS: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // '#'
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // '&'
+    [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // '0'
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // '1'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // '2'
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // '3'
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // '4'
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // '5'
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // '6'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // '8'
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // '@'
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'A'
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // 'B'
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // 'C'
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // 'D'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // 'E'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // 'F'
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // 'G'
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'H'
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // 'L'
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'O'
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // 'P'
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // 'Q'
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // 'R'
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // 'S'
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // 'W'
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // 'Y'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // 'Z'
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ']'
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // '_'
+    [0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E], // 'b'
+    [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E], // 'c'
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F], // 'd'
+    [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E], // 'e'
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08], // 'f'
+    [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+    [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'l'
+    [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+    [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E], // 'o'
+    [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+    [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E], // 's'
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A], // 'w'
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E], // 'y'
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
+
+/// Remember the current GOP framebuffer for use once boot services are
+/// gone (through [`crate::late::prepare`]), or for [`Large`].
+pub fn capture() {
+    let Ok(handle) = boot::get_handle_for_protocol::<GraphicsOutput>() else {
+        return;
+    };
+    let gop = unsafe {
+        boot::open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    };
+    let Ok(mut gop) = gop else {
+        return;
+    };
+    let info = gop.current_mode_info();
+    let bgr = match info.pixel_format() {
+        PixelFormat::Bgr => true,
+        PixelFormat::Rgb => false,
+        _ => return,
+    };
+    let (width, height) = info.resolution();
+    WIDTH.store(width, Ordering::Relaxed);
+    HEIGHT.store(height, Ordering::Relaxed);
+    STRIDE.store(info.stride(), Ordering::Relaxed);
+    BGR.store(bgr, Ordering::Relaxed);
+    COL.store(0, Ordering::Relaxed);
+    ROW.store(0, Ordering::Relaxed);
+    BASE.store(gop.frame_buffer().as_mut_ptr() as u64, Ordering::Release);
+}
+
+fn pixel(r: u8, g: u8, b: u8) -> u32 {
+    if BGR.load(Ordering::Relaxed) {
+        u32::from_le_bytes([b, g, r, 0])
+    } else {
+        u32::from_le_bytes([r, g, b, 0])
+    }
+}
+
+/// Whether [`capture`] found a framebuffer.
+pub fn captured() -> bool {
+    BASE.load(Ordering::Acquire) != 0
+}
+
+/// Text in one colour on one background, with cells `scale` times 8×16.
+struct Screen {
+    base: *mut u32,
+    stride: usize,
+    cols: usize,
+    rows: usize,
+    fg: u32,
+    bg: u32,
+    scale: usize,
+}
+
+impl Screen {
+    fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, color: u32) {
+        for yy in y..y + h {
+            for xx in x..x + w {
+                unsafe { self.base.add(yy * self.stride + xx).write_volatile(color) };
+            }
+        }
+    }
+
+    fn glyph(&mut self, col: usize, row: usize, c: u8) {
+        if col >= self.cols || row >= self.rows {
+            return;
+        }
+        let index = if (0x20..0x7F).contains(&c) {
+            c - 0x20
+        } else {
+            b'?' - 0x20
+        };
+        let k = self.scale;
+        let (x0, y0) = (col * CELL_W * k, row * CELL_H * k);
+        self.fill(x0, y0, CELL_W * k, CELL_H * k, self.bg);
+        for (r, bits) in GLYPHS[index as usize].iter().enumerate() {
+            for dx in 0..5 {
+                if bits & (0x10 >> dx) != 0 {
+                    let (x, y) = (x0 + (1 + dx) * k, y0 + (1 + 2 * r) * k);
+                    self.fill(x, y, k, 2 * k, self.fg);
+                }
+            }
+        }
+    }
+
+    fn scroll(&mut self) {
+        let cell_h = CELL_H * self.scale;
+        let line = cell_h * self.stride;
+        let text = (self.rows - 1) * line;
+        unsafe { core::ptr::copy(self.base.add(line), self.base, text) };
+        let width = self.cols * CELL_W * self.scale;
+        self.fill(0, (self.rows - 1) * cell_h, width, cell_h, self.bg);
+    }
+
+    fn newline(&mut self, row: &mut usize) {
+        if *row + 1 < self.rows {
+            *row += 1;
+        } else {
+            self.scroll();
+        }
+    }
+}
+
+impl Write for Screen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut col = COL.load(Ordering::Relaxed);
+        let mut row = ROW.load(Ordering::Relaxed);
+        for c in s.bytes() {
+            match c {
+                b'\n' => {
+                    col = 0;
+                    self.newline(&mut row);
+                }
+                b'\r' => col = 0,
+                _ => {
+                    if col == self.cols {
+                        col = 0;
+                        self.newline(&mut row);
+                    }
+                    self.glyph(col, row, c);
+                    col += 1;
+                }
+            }
+        }
+        COL.store(col, Ordering::Relaxed);
+        ROW.store(row, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn screen(fg: u32, bg: u32, scale: usize) -> Option<Screen> {
+    let base = BASE.load(Ordering::Acquire);
+    let cols = WIDTH.load(Ordering::Relaxed) / (CELL_W * scale);
+    let rows = HEIGHT.load(Ordering::Relaxed) / (CELL_H * scale);
+    (base != 0 && cols > 0 && rows > 0).then(|| Screen {
+        base: base as *mut u32,
+        stride: STRIDE.load(Ordering::Relaxed),
+        cols,
+        rows,
+        fg,
+        bg,
+        scale,
+    })
+}
+
+/// The text console's 16 colours.
+fn rgb(c: Color) -> u32 {
+    match c {
+        Color::Black => pixel(0x00, 0x00, 0x00),
+        Color::Blue => pixel(0x00, 0x00, 0xAA),
+        Color::Green => pixel(0x00, 0xAA, 0x00),
+        Color::Cyan => pixel(0x00, 0xAA, 0xAA),
+        Color::Red => pixel(0xAA, 0x00, 0x00),
+        Color::Magenta => pixel(0xAA, 0x00, 0xAA),
+        Color::Brown => pixel(0xAA, 0x55, 0x00),
+        Color::LightGray => pixel(0xAA, 0xAA, 0xAA),
+        Color::DarkGray => pixel(0x55, 0x55, 0x55),
+        Color::LightBlue => pixel(0x55, 0x55, 0xFF),
+        Color::LightGreen => pixel(0x55, 0xFF, 0x55),
+        Color::LightCyan => pixel(0x55, 0xFF, 0xFF),
+        Color::LightRed => pixel(0xFF, 0x55, 0x55),
+        Color::LightMagenta => pixel(0xFF, 0x55, 0xFF),
+        Color::Yellow => pixel(0xFF, 0xFF, 0x55),
+        Color::White => pixel(0xFF, 0xFF, 0xFF),
+    }
+}
+
+/// The menu on the framebuffer in double-size cells, for the large-text
+/// theme. Only usable once [`capture`] has run.
+pub struct Large {
+    fg: u32,
+    bg: u32,
+}
+
+impl Default for Large {
+    fn default() -> Self {
+        Large {
+            fg: rgb(Color::White),
+            bg: rgb(Color::Black),
+        }
+    }
+}
+
+impl Large {
+    const SCALE: usize = 2;
+
+    fn screen(&self) -> Option<Screen> {
+        screen(self.fg, self.bg, Self::SCALE)
+    }
+}
+
+impl Write for Large {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.screen() {
+            Some(mut screen) => screen.write_str(s),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Renderer for Large {
+    fn clear(&mut self) {
+        let (width, height) = (
+            WIDTH.load(Ordering::Relaxed),
+            HEIGHT.load(Ordering::Relaxed),
+        );
+        if let Some(mut screen) = screen(self.fg, rgb(Color::Black), 1) {
+            screen.fill(0, 0, width, height, rgb(Color::Black));
+        }
+        COL.store(0, Ordering::Relaxed);
+        ROW.store(0, Ordering::Relaxed);
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        COL.store(col, Ordering::Relaxed);
+        ROW.store(row, Ordering::Relaxed);
+    }
+
+    fn set_color(&mut self, fg: Color, bg: Color) {
+        let (fg, bg) = render::palette(fg, bg);
+        self.fg = rgb(fg);
+        self.bg = rgb(bg);
+    }
+
+    fn columns(&mut self) -> usize {
+        self.screen().map_or(0, |s| s.cols)
+    }
+}
+
+struct Serial;
+
+impl Write for Serial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                serial::serial_str("\r\n");
+            }
+            serial::serial_str(line);
+        }
+        Ok(())
+    }
+}
+
+/// Report a failure on serial and, once [`capture`] has run, in red on
+/// the framebuffer.
+pub fn error(args: fmt::Arguments) {
+    let _ = Serial.write_fmt(args);
+    let _ = Serial.write_str("\n");
+    if let Some(mut screen) = screen(rgb(Color::LightRed), rgb(Color::Black), 1) {
+        let _ = screen.write_fmt(args);
+        let _ = screen.write_str("\n");
+    }
+}
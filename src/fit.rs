@@ -0,0 +1,101 @@
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use alpheratz_core::{fdt, fit};
+use uefi::prelude::*;
+use uefi::{Guid, guid};
+
+use crate::{sha1, sha256};
+
+/// EFI_DTB_TABLE_GUID: configuration table holding the device tree the
+/// firmware hands to the OS.
+pub const DTB_TABLE_GUID: Guid = guid!("b1b621d5-f19c-41a5-830b-d9152c69aae0");
+
+/// Images of the selected FIT configuration.
+pub struct Unpacked {
+    pub kernel: Vec<u8>,
+    pub ramdisk: Option<Vec<u8>>,
+    pub fdt: Option<Vec<u8>>,
+}
+
+/// Root `compatible` strings of the firmware's device tree, most specific
+/// first; empty on ACPI-only machines.
+fn board_compatible() -> Vec<String> {
+    let ptr = uefi::system::with_config_table(|entries| {
+        entries
+            .iter()
+            .find(|e| e.guid == DTB_TABLE_GUID)
+            .map(|e| e.address as *const u8)
+    });
+    let Some(ptr) = ptr else {
+        return Vec::new();
+    };
+    let dtb = unsafe {
+        let Ok(size) = fdt::total_size(core::slice::from_raw_parts(ptr, 8)) else {
+            return Vec::new();
+        };
+        core::slice::from_raw_parts(ptr, size)
+    };
+    fdt::parse(dtb)
+        .map(|root| root.prop_strs("compatible").map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Check every hash node of `image`. An algorithm we cannot compute fails
+/// the image rather than being skipped.
+fn verify(image: &fit::Image) -> uefi::Result<()> {
+    for hash in &image.hashes {
+        let ok = match hash.algo {
+            "crc32" => hash.value == crc32(image.data).to_be_bytes(),
+            "sha1" => hash.value == sha1::digest(image.data),
+            "sha256" => hash.value == sha256::digest(image.data),
+            other => {
+                crate::println!("  {}: unsupported hash algorithm {}", image.name, other);
+                return Err(uefi::Error::from(Status::UNSUPPORTED));
+            }
+        };
+        if !ok {
+            crate::println!("  {}: {} mismatch", image.name, hash.algo);
+            return Err(uefi::Error::from(Status::CRC_ERROR));
+        }
+        crate::println!("  {}: {} OK", image.name, hash.algo);
+    }
+    Ok(())
+}
+
+/// Pick the configuration of the FIT in `data` that matches this board,
+/// verify its images and copy them out.
+pub fn unpack(data: &[u8]) -> uefi::Result<Unpacked> {
+    let board = board_compatible();
+    let board: Vec<&str> = board.iter().map(String::as_str).collect();
+    let sel = fit::select(data, &board).map_err(|e| {
+        crate::println!("  FIT: {}", e);
+        uefi::Error::from(Status::LOAD_ERROR)
+    })?;
+    crate::println!("  FIT configuration: {}", sel.config);
+
+    for image in [Some(&sel.kernel), sel.ramdisk.as_ref(), sel.fdt.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        verify(image)?;
+    }
+    Ok(Unpacked {
+        kernel: Vec::from(sel.kernel.data),
+        ramdisk: sel.ramdisk.map(|i| Vec::from(i.data)),
+        fdt: sel.fdt.map(|i| Vec::from(i.data)),
+    })
+}
@@ -5,13 +5,25 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
 
-use uefi::boot::{self, LoadImageSource};
+use uefi::boot::{self, LoadImageSource, OpenProtocolAttributes, OpenProtocolParams};
 use uefi::prelude::*;
+use uefi::proto::device_path::text::{AllowShortcuts, DevicePathFromText, DisplayOnly};
+use uefi::proto::device_path::{DevicePath, DeviceSubType, DeviceType, PoolDevicePath};
 use uefi::proto::loaded_image::LoadedImage;
-use uefi::proto::media::file::{Directory, File, FileAttribute, FileInfo, FileMode, FileType};
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileInfo, FileMode, RegularFile};
 use uefi::proto::media::fs::SimpleFileSystem;
 
-use crate::config::Config;
+#[cfg(feature = "drivers")]
+use alpheratz_core::manifest::Manifest;
+#[cfg(feature = "drivers")]
+use alpheratz_core::uki;
+
+#[cfg(feature = "drivers")]
+use crate::config::{Config, Connect, Driver};
+use crate::error::{self, AlpheratzError};
+use crate::memcheck;
+#[cfg(feature = "drivers")]
+use crate::{pci, secureboot, sha256};
 
 pub fn open_esp_root() -> uefi::Result<Directory> {
     let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())?;
@@ -23,8 +35,116 @@ pub fn open_esp_root() -> uefi::Result<Directory> {
     sfs.open_volume()
 }
 
+/// ESP path of the loader image (e.g. `\EFI\BOOT\BOOTX64.EFI`), when it
+/// was loaded from a file.
+pub fn loader_path() -> Option<String> {
+    let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle()).ok()?;
+    let text = loaded_image
+        .file_path()?
+        .to_string(DisplayOnly(false), AllowShortcuts(false))
+        .ok()?;
+    let text = String::from(&*text);
+    let start = text.find('\\')?;
+    Some(normalize_path(&text[start..]))
+}
+
+/// Directory holding the loader image (e.g. `\EFI\BOOT`), or the volume
+/// root when it cannot be determined.
+fn loader_dir() -> String {
+    let dir = loader_path().and_then(|path| Some(String::from(&path[..path.rfind('\\')?])));
+    dir.unwrap_or_default()
+}
+
+/// The URI the loader was downloaded from when UEFI HTTP Boot delivered it,
+/// read from the URI node of its device's path.
+pub fn loader_uri() -> Option<String> {
+    let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle()).ok()?;
+    let dp = unsafe {
+        boot::open_protocol::<DevicePath>(
+            OpenProtocolParams {
+                handle: loaded_image.device()?,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+    let node = dp
+        .node_iter()
+        .filter(|n| {
+            n.device_type() == DeviceType::MESSAGING && n.sub_type() == DeviceSubType::MESSAGING_URI
+        })
+        .last()?;
+    let uri = core::str::from_utf8(node.data()).ok()?;
+    let uri = uri.trim_end_matches('\0');
+    (!uri.is_empty()).then(|| String::from(uri))
+}
+
+/// Turn a config path into an absolute FAT path: `/` becomes `\`, relative
+/// paths are taken from the loader's directory, empty and `.` components are
+/// dropped and `..` climbs one level.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    let base = if path.starts_with('\\') {
+        String::new()
+    } else {
+        loader_dir()
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in base.split('\\').chain(path.split('\\')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+
+    let mut out = String::new();
+    for p in parts {
+        out.push('\\');
+        out.push_str(p);
+    }
+    if out.is_empty() {
+        out.push('\\');
+    }
+    out
+}
+
+/// Whether `path` names an existing file or directory.
+pub fn exists(root: &mut Directory, path: &str) -> bool {
+    uefi::CString16::try_from(normalize_path(path).as_str()).is_ok_and(|p| {
+        root.open(&p, FileMode::Read, FileAttribute::empty())
+            .is_ok()
+    })
+}
+
 pub fn read_file(root: &mut Directory, path: &str) -> uefi::Result<Vec<u8>> {
-    let path16 = uefi::CString16::try_from(path)
+    Ok(read_file_max(root, path, None)?)
+}
+
+/// Bytes requested per `read` call; some firmware fails or stalls on a
+/// single read of hundreds of MiB.
+const READ_CHUNK: usize = 4 * 1024 * 1024;
+
+/// Files at least this large get a progress line while reading.
+const PROGRESS_THRESHOLD: usize = 4 * READ_CHUNK;
+
+/// Like [`read_file`], but refuses files larger than `max` bytes before
+/// allocating anything.
+pub fn read_file_max(
+    root: &mut Directory,
+    path: &str,
+    max: Option<usize>,
+) -> error::Result<Vec<u8>> {
+    read_max(root, path, max).map_err(|e| AlpheratzError::fs(path, e))
+}
+
+fn read_max(root: &mut Directory, path: &str, max: Option<usize>) -> uefi::Result<Vec<u8>> {
+    let path16 = uefi::CString16::try_from(normalize_path(path).as_str())
         .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
 
     let handle = root.open(path16.as_ref(), FileMode::Read, FileAttribute::empty())?;
@@ -34,12 +154,311 @@ pub fn read_file(root: &mut Directory, path: &str) -> uefi::Result<Vec<u8>> {
 
     let info = file.get_boxed_info::<FileInfo>()?;
     let size = info.file_size() as usize;
+    if max.is_some_and(|m| size > m) {
+        return Err(uefi::Error::from(Status::BAD_BUFFER_SIZE));
+    }
+    memcheck::ensure_available(size)?;
     let mut buf = Vec::with_capacity(size);
     buf.resize(size, 0);
-    file.read(&mut buf)?;
+
+    let show_progress = size >= PROGRESS_THRESHOLD;
+    let mut done = 0;
+    while done < size {
+        let end = (done + READ_CHUNK).min(size);
+        let n = file.read(&mut buf[done..end])?;
+        if n == 0 {
+            break;
+        }
+        done += n;
+        if show_progress {
+            crate::print!("\r  {} / {} KiB", done / 1024, size / 1024);
+        }
+    }
+    if show_progress {
+        crate::println!();
+    }
+
+    if done != size {
+        crate::println!("  Short read: {} of {} bytes", done, size);
+        return Err(uefi::Error::from(Status::END_OF_FILE));
+    }
     Ok(buf)
 }
 
+/// Most entries printed by [`print_nearest_listing`].
+const LISTING_LIMIT: usize = 40;
+
+/// After a NOT_FOUND on `path`, print the contents of its closest ancestor
+/// directory that does exist, so typos and case mistakes are easy to spot.
+pub fn print_nearest_listing(root: &mut Directory, path: &str) {
+    let path = normalize_path(path);
+    let mut dir = path.as_str();
+    loop {
+        dir = match dir.rfind('\\') {
+            Some(0) => "\\",
+            Some(i) => &dir[..i],
+            None => return,
+        };
+        let Ok(dir16) = uefi::CString16::try_from(dir) else {
+            return;
+        };
+        let opened = root
+            .open(dir16.as_ref(), FileMode::Read, FileAttribute::empty())
+            .ok()
+            .and_then(|h| h.into_directory());
+        if let Some(mut d) = opened {
+            crate::println!("  Contents of {}:", dir);
+            let mut shown = 0;
+            let mut more = 0;
+            while let Ok(Some(info)) = d.read_entry_boxed() {
+                let name = String::from(info.file_name());
+                if name == "." || name == ".." {
+                    continue;
+                }
+                if shown == LISTING_LIMIT {
+                    more += 1;
+                    continue;
+                }
+                let suffix = if info.is_directory() { "\\" } else { "" };
+                crate::println!("    {}{}", name, suffix);
+                shown += 1;
+            }
+            if shown == 0 {
+                crate::println!("    (empty)");
+            }
+            if more > 0 {
+                crate::println!("    ... and {} more", more);
+            }
+            return;
+        }
+        if dir == "\\" {
+            return;
+        }
+    }
+}
+
+/// Prefix marking a `file` given as a full UEFI device path in text form.
+pub const DEVPATH_PREFIX: &str = "devpath:";
+
+/// Read a file named by a textual device path such as
+/// `PciRoot(0x0)/Pci(0x1,0x1)/HD(1,GPT,…)/\EFI\vmlinuz`: everything before
+/// the first `\` selects the partition through LocateDevicePath, the rest is
+/// the path on its file system.
+pub fn read_devpath_file(text: &str, max: Option<usize>) -> error::Result<Vec<u8>> {
+    read_devpath(text, max).map_err(|e| AlpheratzError::fs(text, e))
+}
+
+fn read_devpath(text: &str, max: Option<usize>) -> uefi::Result<Vec<u8>> {
+    let split = text
+        .find('\\')
+        .ok_or_else(|| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let (device, path) = (text[..split].trim_end_matches('/'), &text[split..]);
+
+    let device16 = uefi::CString16::try_from(device)
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let from_text_handle = boot::get_handle_for_protocol::<DevicePathFromText>()?;
+    let from_text = boot::open_protocol_exclusive::<DevicePathFromText>(from_text_handle)?;
+    let dp = from_text.convert_text_to_device_path(&device16)?;
+    let mut remaining: &DevicePath = &dp;
+    let handle = boot::locate_device_path::<SimpleFileSystem>(&mut remaining)?;
+
+    let mut sfs = boot::open_protocol_exclusive::<SimpleFileSystem>(handle)?;
+    let mut root = sfs.open_volume()?;
+    read_max(&mut root, path, max)
+}
+
+/// Text of the device path to `path` on the volume the loader came from.
+fn esp_device_path_text(path: &str) -> uefi::Result<String> {
+    let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())?;
+    let device = loaded_image
+        .device()
+        .ok_or_else(|| uefi::Error::from(Status::NOT_FOUND))?;
+    let dp = unsafe {
+        boot::open_protocol::<DevicePath>(
+            OpenProtocolParams {
+                handle: device,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }?;
+    let device = dp
+        .to_string(DisplayOnly(false), AllowShortcuts(false))
+        .map_err(|_| uefi::Error::from(Status::OUT_OF_RESOURCES))?;
+    let mut text = String::from(&*device);
+    text.push('/');
+    text.push_str(&normalize_path(path));
+    Ok(text)
+}
+
+fn device_path_from_text(text: &str) -> uefi::Result<PoolDevicePath> {
+    let text16 = uefi::CString16::try_from(text)
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let from_text_handle = boot::get_handle_for_protocol::<DevicePathFromText>()?;
+    let from_text = boot::open_protocol_exclusive::<DevicePathFromText>(from_text_handle)?;
+    from_text.convert_text_to_device_path(&text16)
+}
+
+/// Device path making a chainloaded image look as if it was loaded from the
+/// file `path`: either a `devpath:` text path, or an ESP path. The image
+/// finds its own files relative to the directory holding `path`.
+pub fn image_device_path(path: &str) -> uefi::Result<PoolDevicePath> {
+    let text = match path.strip_prefix(DEVPATH_PREFIX) {
+        Some(dp) => String::from(dp),
+        None => esp_device_path_text(path)?,
+    };
+    device_path_from_text(&text)
+}
+
+/// Full device path of the file at ESP `path`, as boot options name it.
+pub fn file_device_path(path: &str) -> uefi::Result<PoolDevicePath> {
+    device_path_from_text(&esp_device_path_text(path)?)
+}
+
+/// Create every missing directory leading up to `path` (already normalized).
+fn create_parent_dirs(root: &mut Directory, path: &str) -> uefi::Result<()> {
+    let Some(end) = path.rfind('\\').filter(|&e| e > 0) else {
+        return Ok(());
+    };
+    for (i, _) in path[..end].match_indices('\\').skip(1).chain([(end, "")]) {
+        let dir16 = uefi::CString16::try_from(&path[..i])
+            .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+        root.open(
+            dir16.as_ref(),
+            FileMode::CreateReadWrite,
+            FileAttribute::DIRECTORY,
+        )?;
+    }
+    Ok(())
+}
+
+/// Open `path` for writing, creating it and any missing parent directories.
+fn open_for_write(root: &mut Directory, path: &str) -> uefi::Result<RegularFile> {
+    let path = normalize_path(path);
+    create_parent_dirs(root, &path)?;
+
+    let path16 = uefi::CString16::try_from(path.as_str())
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let handle = root.open(
+        path16.as_ref(),
+        FileMode::CreateReadWrite,
+        FileAttribute::empty(),
+    )?;
+    handle
+        .into_regular_file()
+        .ok_or_else(|| uefi::Error::from(Status::INVALID_PARAMETER))
+}
+
+/// Replace the contents of the file at `path` with `data`, creating it and
+/// its parent directories as needed.
+pub fn write_file(root: &mut Directory, path: &str, data: &[u8]) -> uefi::Result<()> {
+    // FAT has no in-place truncate through this API: delete and recreate.
+    open_for_write(root, path)?.delete()?;
+
+    let mut file = open_for_write(root, path)?;
+    file.write(data)
+        .map_err(|e| uefi::Error::from(e.status()))?;
+    file.flush()
+}
+
+/// Append `data` to the file at `path`, creating it and its parent
+/// directories if they do not exist.
+pub fn append_file(root: &mut Directory, path: &str, data: &[u8]) -> uefi::Result<()> {
+    let mut file = open_for_write(root, path)?;
+
+    file.set_position(RegularFile::END_OF_FILE)?;
+    file.write(data)
+        .map_err(|e| uefi::Error::from(e.status()))?;
+    file.flush()
+}
+
+/// Rename the file at `path` (normalized) to `new_name` in the same
+/// directory. FAT refuses to rename over an existing file.
+fn rename(root: &mut Directory, path: &str, new_name: &str) -> uefi::Result<()> {
+    let path16 = uefi::CString16::try_from(path)
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let name16 = uefi::CString16::try_from(new_name)
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let mut file = root.open(path16.as_ref(), FileMode::ReadWrite, FileAttribute::empty())?;
+    let info = file.get_boxed_info::<FileInfo>()?;
+    let mut storage = alloc::vec![0u8; core::mem::size_of_val(&*info) + 2 * new_name.len() + 16];
+    let renamed = FileInfo::new(
+        &mut storage,
+        info.file_size(),
+        info.physical_size(),
+        *info.create_time(),
+        *info.last_access_time(),
+        *info.modification_time(),
+        info.attribute(),
+        &name16,
+    )
+    .map_err(|_| uefi::Error::from(Status::BUFFER_TOO_SMALL))?;
+    file.set_info(&*renamed)
+}
+
+fn remove_file(root: &mut Directory, path: &str) -> uefi::Result<()> {
+    let path16 = uefi::CString16::try_from(path)
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    root.open(path16.as_ref(), FileMode::ReadWrite, FileAttribute::empty())?
+        .delete()
+}
+
+/// Sibling names used while [`write_file_atomic`] replaces `path`: the
+/// fully written new contents, and the previous file while it is swapped.
+fn atomic_names(path: &str) -> (String, String, &str) {
+    let name = &path[path.rfind('\\').map_or(0, |i| i + 1)..];
+    let mut new = String::from(path);
+    new.push_str(".new");
+    let mut old = String::from(path);
+    old.push_str(".old");
+    (new, old, name)
+}
+
+/// Replace the file at `path` so that a power cut at any point leaves
+/// either the old or the new contents: write and flush `<path>.new`, move
+/// the current file aside to `<path>.old`, rename the new one into place,
+/// then drop the old. [`recover_atomic`] finishes an interrupted swap.
+pub fn write_file_atomic(root: &mut Directory, path: &str, data: &[u8]) -> uefi::Result<()> {
+    let path = normalize_path(path);
+    let (new, old, name) = atomic_names(&path);
+    write_file(root, &new, data)?;
+
+    if exists(root, &path) {
+        if exists(root, &old) {
+            remove_file(root, &old)?;
+        }
+        let old_name = &old[old.rfind('\\').map_or(0, |i| i + 1)..];
+        rename(root, &path, old_name)?;
+    }
+    rename(root, &new, name)?;
+    let _ = remove_file(root, &old);
+    Ok(())
+}
+
+/// Complete a [`write_file_atomic`] cut short after the old file was moved
+/// aside. An existing `<path>.old` shows `<path>.new` had been flushed, so
+/// the new file wins over it. Without one, `<path>.new` may be a first
+/// write cut short and is deleted. Does nothing while `path` exists.
+pub fn recover_atomic(root: &mut Directory, path: &str) {
+    let path = normalize_path(path);
+    if exists(root, &path) {
+        return;
+    }
+    let (new, old, name) = atomic_names(&path);
+    if !exists(root, &old) {
+        if exists(root, &new) {
+            let _ = remove_file(root, &new);
+        }
+        return;
+    }
+    for candidate in [new, old] {
+        if exists(root, &candidate) && rename(root, &candidate, name).is_ok() {
+            return;
+        }
+    }
+}
+
 fn path_join(dir: &str, file: &str) -> String {
     if dir.ends_with('\\') {
         let mut s = String::from(dir);
@@ -53,15 +472,120 @@ fn path_join(dir: &str, file: &str) -> String {
     }
 }
 
-pub fn load_drivers_from_config(cfg: &Config) -> uefi::Result<()> {
+/// Why a configured driver was not loaded.
+#[cfg(feature = "drivers")]
+enum Skip {
+    Unreadable(Status),
+    NotInManifest,
+    DigestMismatch,
+    Unsigned,
+    /// `load_image` refused the image's signature.
+    Rejected(Status),
+    Failed(Status),
+}
+
+#[cfg(feature = "drivers")]
+impl core::fmt::Display for Skip {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Skip::Unreadable(s) => write!(f, "cannot be read ({:?})", s),
+            Skip::NotInManifest => f.write_str("not listed in driver_manifest"),
+            Skip::DigestMismatch => f.write_str("sha256 differs from driver_manifest"),
+            Skip::Unsigned => f.write_str("unsigned, and Secure Boot is enabled"),
+            Skip::Rejected(s) => write!(f, "signature rejected by firmware ({:?})", s),
+            Skip::Failed(s) => write!(f, "failed to start ({:?})", s),
+        }
+    }
+}
+
+/// Check the driver at `path` against `manifest` and, under Secure Boot,
+/// for an Authenticode signature before handing it to `load_image`, which
+/// does the actual signature verification. Returns the started image.
+#[cfg(feature = "drivers")]
+fn load_driver(
+    root: &mut Directory,
+    path: &str,
+    manifest: Option<&Manifest>,
+    secure_boot: bool,
+) -> Result<Handle, Skip> {
+    let image = read_file(root, path).map_err(|e| Skip::Unreadable(e.status()))?;
+    if let Some(manifest) = manifest {
+        let listed = manifest.digest(path).ok_or(Skip::NotInManifest)?;
+        if sha256::to_hex(&sha256::digest(&image)) != listed {
+            return Err(Skip::DigestMismatch);
+        }
+    }
+    if secure_boot && !uki::is_signed(&image) {
+        return Err(Skip::Unsigned);
+    }
+    let h = boot::load_image(
+        boot::image_handle(),
+        LoadImageSource::FromBuffer {
+            buffer: &image,
+            file_path: None,
+        },
+    )
+    .map_err(|e| match e.status() {
+        s @ (Status::SECURITY_VIOLATION | Status::ACCESS_DENIED) => Skip::Rejected(s),
+        s => Skip::Failed(s),
+    })?;
+    boot::start_image(h).map_err(|e| Skip::Failed(e.status()))?;
+    Ok(h)
+}
+
+/// Connect the started driver `image` to the controllers `connect` names,
+/// so only those are probed, and by that driver alone.
+#[cfg(feature = "drivers")]
+fn connect_driver(image: Handle, connect: Connect) {
+    let controllers = match connect {
+        Connect::None => return,
+        Connect::Nic => pci::network_controllers(),
+        Connect::All => boot::locate_handle_buffer(boot::SearchType::AllHandles)
+            .map(|h| h.to_vec())
+            .unwrap_or_default(),
+    };
+    for h in controllers {
+        let _ = boot::connect_controller(h, Some(image), None, true);
+    }
+}
+
+/// The `.efi` files in directory `dir`, sorted by name so they load in the
+/// same order every boot.
+#[cfg(feature = "drivers")]
+fn driver_files(dir: &mut Directory, path: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let _ = dir.reset_entry_readout();
+    while let Ok(Some(info)) = dir.read_entry_boxed() {
+        if info.is_directory() {
+            continue;
+        }
+        let name = String::from(info.file_name());
+        if name.to_ascii_lowercase().ends_with(".efi") {
+            names.push(name);
+        }
+    }
+    names.sort_by_key(|n| n.to_ascii_lowercase());
+    names.iter().map(|n| path_join(path, n)).collect()
+}
+
+/// Load, start and connect the drivers named by `drivers` in ascending
+/// `order`, printing the ones skipped and why. With a `manifest`, only
+/// drivers it lists with a matching digest are loaded.
+#[cfg(feature = "drivers")]
+pub fn load_drivers_from_config(cfg: &Config, manifest: Option<&Manifest>) -> uefi::Result<()> {
+    use uefi::proto::media::file::FileType;
+
     if cfg.drivers.is_empty() {
         return Ok(());
     }
 
     let mut root = open_esp_root()?;
+    let secure_boot = secureboot::state() == secureboot::State::Enabled;
+    let mut drivers: Vec<&Driver> = cfg.drivers.iter().collect();
+    drivers.sort_by_key(|d| d.order);
 
-    for p in &cfg.drivers {
-        // Treat configured path as either a single driver .efi file or a directory containing drivers.
+    for driver in drivers {
+        let p = &normalize_path(&driver.path);
         let p16 = match uefi::CString16::try_from(p.as_str()) {
             Ok(v) => v,
             Err(_) => continue,
@@ -70,32 +594,17 @@ pub fn load_drivers_from_config(cfg: &Config) -> uefi::Result<()> {
             Ok(h) => h,
             Err(_) => continue,
         };
+        let paths = match handle.into_type() {
+            Ok(FileType::Dir(mut dir)) => driver_files(&mut dir, p),
+            Ok(FileType::Regular(_)) => alloc::vec![p.clone()],
+            Err(_) => continue,
+        };
 
-        match handle.into_type() {
-            Ok(FileType::Dir(mut dir)) => {
-                let _ = dir.reset_entry_readout();
-                while let Ok(Some(info)) = dir.read_entry_boxed() {
-                    if info.is_directory() {
-                        continue;
-                    }
-                    let name = String::from(info.file_name());
-                    // Skip "." and ".." if present.
-                    if name == "." || name == ".." {
-                        continue;
-                    }
-                    // Only attempt to load *.efi files.
-                    let name_lc = name.to_ascii_lowercase();
-                    if !name_lc.ends_with(".efi") {
-                        continue;
-                    }
-                    let full = path_join(p, &name);
-                    let _ = load_and_start_image(&mut root, &full);
-                }
-            }
-            Ok(FileType::Regular(_)) => {
-                let _ = load_and_start_image(&mut root, p);
+        for path in &paths {
+            match load_driver(&mut root, path, manifest, secure_boot) {
+                Ok(image) => connect_driver(image, driver.connect),
+                Err(skip) => crate::println!("Driver {} skipped: {}", path, skip),
             }
-            Err(_) => {}
         }
     }
 
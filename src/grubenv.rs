@@ -0,0 +1,72 @@
+//! `default = "@saved"` and boot counting shared with GRUB through its
+//! environment block, see [`alpheratz_core::grubenv`].
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use alpheratz_core::grubenv::Env;
+
+use crate::config::{self, Config};
+use crate::fsutil;
+
+/// Largest block read; GRUB's are normally 1 KiB.
+const MAX_SIZE: usize = 64 * 1024;
+
+/// Resolve `default = "@saved"` from the `[grubenv]` block. With `write`,
+/// also honour and consume a one-shot `next_entry`, count this boot against
+/// `boot_counter` (falling back to the second entry when it runs out) and
+/// write the block back at its original size.
+pub fn apply(cfg: &mut Config) {
+    let Some(settings) = cfg.grubenv.clone() else {
+        return;
+    };
+    let path = settings.path.as_str();
+    let Ok(mut root) = fsutil::open_esp_root() else {
+        return;
+    };
+    fsutil::recover_atomic(&mut root, path);
+    let data = match fsutil::read_file_max(&mut root, path, Some(MAX_SIZE)) {
+        Ok(data) => data,
+        Err(e) => {
+            crate::println!("grubenv: {}", e);
+            return;
+        }
+    };
+    let Some(mut env) = Env::parse(&data) else {
+        crate::println!("grubenv: {} is not a GRUB environment block", path);
+        return;
+    };
+
+    // Without write-back a one-shot `next_entry` would stick for every boot.
+    if !settings.write && env.get("next_entry").is_some() {
+        env.set("next_entry", "");
+    }
+    if let config::Default::Saved(_) = cfg.default {
+        let names: Vec<&str> = cfg.entry.iter().map(|e| e.name.as_str()).collect();
+        if let Some(index) = env.saved_index(&names) {
+            cfg.default = config::Default::Index(index);
+        }
+    }
+    if !settings.write {
+        return;
+    }
+
+    if env.get("next_entry").is_some() {
+        env.set("next_entry", "");
+    }
+    if env.count_boot() && cfg.entry.len() > 1 {
+        crate::println!(
+            "grubenv: boot_counter ran out; defaulting to \"{}\"",
+            cfg.entry[1].name
+        );
+        cfg.default = config::Default::Index(1);
+    }
+    let Some(block) = env.to_block(data.len()) else {
+        crate::println!("grubenv: {} is too full to update", path);
+        return;
+    };
+    if let Err(e) = fsutil::write_file_atomic(&mut root, path, &block) {
+        crate::println!("grubenv: cannot write {}: {:?}", path, e.status());
+    }
+}
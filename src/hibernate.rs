@@ -0,0 +1,194 @@
+//! Look for a hibernated system before booting another one over it, see
+//! `hibernation` in example.toml: Windows waiting to resume according to its
+//! BCD store, or a Linux hibernation image in a swap partition.
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use alpheratz_core::hibernate;
+use alpheratz_core::validate::{Issue, Severity};
+use uefi::Identify;
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol};
+use uefi::prelude::*;
+use uefi::proto::ProtocolPointer;
+use uefi::proto::device_path::{DevicePath, DeviceSubType, DeviceType};
+use uefi::proto::media::block::BlockIO;
+use uefi::proto::media::disk::DiskIo;
+
+use crate::bcd;
+use crate::config::{self, Config, Entry, FileType, Hibernation, SearchMethod};
+use crate::console;
+use crate::fsutil;
+use crate::menu;
+use crate::splash;
+
+/// Where Windows keeps its store when no entry names `bootmgfw.efi`.
+const DEFAULT_STORE: &str = "\\EFI\\Microsoft\\Boot\\BCD";
+
+/// A system found hibernated.
+pub struct Hibernated {
+    /// What is hibernated, for messages.
+    what: String,
+    /// The entry that resumes it, if one was recognised.
+    entry: Option<usize>,
+}
+
+fn get<P: ProtocolPointer + ?Sized>(handle: Handle) -> Option<ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()
+}
+
+/// Windows, if a BCD store an entry chainloads (or the default one) has
+/// the boot manager set to resume it.
+fn windows(cfg: &Config) -> Option<Hibernated> {
+    let mut root = fsutil::open_esp_root().ok()?;
+    let mut resumes = |path: &str| {
+        fsutil::read_file_max(&mut root, path, Some(bcd::BCD_MAX))
+            .is_ok_and(|data| alpheratz_core::bcd::attempts_resume(&data))
+    };
+    let stores: Vec<(usize, String)> = cfg
+        .entry
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| Some((i, bcd::store_path(e)?)))
+        .collect();
+    let entry = match stores.iter().find(|(_, path)| resumes(path)) {
+        Some(&(i, _)) => Some(i),
+        None if stores.is_empty() && resumes(DEFAULT_STORE) => None,
+        None => return None,
+    };
+    Some(Hibernated {
+        what: String::from("Windows"),
+        entry,
+    })
+}
+
+/// The start of a partition, enough to find a swap header in.
+fn partition_header(handle: Handle) -> Option<Vec<u8>> {
+    let block = get::<BlockIO>(handle)?;
+    let media = block.media();
+    if !media.is_logical_partition() || !media.is_media_present() {
+        return None;
+    }
+    let size = (media.last_block() + 1).saturating_mul(u64::from(media.block_size()));
+    let mut header = vec![0; hibernate::HEADER_MAX.min(size as usize)];
+    get::<DiskIo>(handle)?
+        .read_disk(media.media_id(), 0, &mut header)
+        .ok()?;
+    Some(header)
+}
+
+/// The partition's `PARTUUID=`: its GPT partition GUID, or the MBR disk
+/// signature and partition number.
+fn partuuid(handle: Handle) -> Option<String> {
+    let path = get::<DevicePath>(handle)?;
+    let node = path.node_iter().find(|n| {
+        n.device_type() == DeviceType::MEDIA && n.sub_type() == DeviceSubType::MEDIA_HARD_DRIVE
+    })?;
+    // PartitionNumber(4) PartitionStart(8) PartitionSize(8) Signature(16)
+    // MBRType(1) SignatureType(1)
+    let data = node.data();
+    match data.get(37)? {
+        1 => {
+            let signature = u32::from_le_bytes(data.get(20..24)?.try_into().ok()?);
+            let number = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+            Some(format!("{:08x}-{:02x}", signature, number))
+        }
+        2 => Some(uefi::Guid::from_bytes(data.get(20..36)?.try_into().ok()?).to_string()),
+        _ => None,
+    }
+}
+
+/// An inline cmdline, which is where `resume=` can be read before booting.
+fn cmdline(entry: &Entry) -> Option<&str> {
+    entry
+        .files
+        .iter()
+        .find(|f| f.file_type == FileType::Cmdline && f.search == SearchMethod::Inline)?
+        .content
+        .as_deref()
+}
+
+/// Linux, if a swap partition holds a hibernation image; its entry is the
+/// one whose `resume=` names that partition.
+fn linux(cfg: &Config) -> Option<Hibernated> {
+    let handles = boot::locate_handle_buffer(boot::SearchType::ByProtocol(&BlockIO::GUID)).ok()?;
+    handles.iter().find_map(|&handle| {
+        let swap = hibernate::swap(&partition_header(handle)?).filter(|s| s.suspended)?;
+        let partuuid = partuuid(handle);
+        let entry = cfg.entry.iter().position(|e| {
+            cmdline(e)
+                .and_then(hibernate::resume_device)
+                .is_some_and(|d| hibernate::names(d, &swap.uuid, partuuid.as_deref()))
+        });
+        Some(Hibernated {
+            what: format!("Linux (swap {})", swap.uuid),
+            entry,
+        })
+    })
+}
+
+/// Look for a hibernated system unless `hibernation = "off"`, reporting it
+/// as an issue on the menu. With `"select"`, its entry becomes the default.
+pub fn check(cfg: &mut Config, issues: &mut Vec<Issue>) -> Option<Hibernated> {
+    if cfg.hibernation == Hibernation::Off {
+        return None;
+    }
+    let hibernated = windows(cfg).or_else(|| linux(cfg))?;
+    issues.push(Issue {
+        severity: Severity::Warning,
+        entry: hibernated.entry.map(|i| cfg.entry[i].name.clone()),
+        message: format!(
+            "{} is hibernated; booting another system could corrupt its file systems",
+            hibernated.what
+        ),
+    });
+    if cfg.hibernation == Hibernation::Select
+        && let Some(index) = hibernated.entry
+    {
+        cfg.default = config::Default::Index(index);
+    }
+    Some(hibernated)
+}
+
+/// The entry to boot in place of entry `index`, which is asked about unless
+/// it resumes the hibernated system: `index` itself, the entry that does
+/// resume it, or `None` to go back to the menu.
+pub fn confirm(cfg: &Config, hibernated: &Hibernated, index: usize) -> Option<usize> {
+    if hibernated.entry == Some(index) {
+        return Some(index);
+    }
+    splash::stop();
+    console::leave_quiet();
+    crate::println!(
+        "{} is hibernated; booting \"{}\" could corrupt its file systems.",
+        hibernated.what,
+        cfg.entry[index].name
+    );
+    let resume = hibernated
+        .entry
+        .map(|i| format!("resume \"{}\"", cfg.entry[i].name));
+    let mut options = vec![('b', "boot anyway")];
+    if let Some(resume) = &resume {
+        options.push(('r', resume.as_str()));
+    }
+    options.push(('m', "back to menu"));
+    match menu::choose(&options) {
+        Some('b') => Some(index),
+        Some('r') => hibernated.entry,
+        _ => None,
+    }
+}
@@ -0,0 +1,386 @@
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr::{self, NonNull};
+use core::time::Duration;
+
+use uefi::boot::{self, EventType, OpenProtocolAttributes, OpenProtocolParams, Tpl};
+use uefi::prelude::*;
+use uefi::proto::unsafe_protocol;
+use uefi::{CString16, Event};
+use uefi_raw::protocol::network::http::HttpStatusCode;
+
+/// Per-request timeout handed to the HTTP driver and used while polling.
+const TIMEOUT_MS: u32 = 30_000;
+
+const HTTP_VERSION_11: u32 = 1;
+const HTTP_METHOD_GET: u32 = 0;
+
+/// EFI_HTTP_SERVICE_BINDING_PROTOCOL
+#[repr(C)]
+#[unsafe_protocol("bdc8e6af-d9bc-4379-a72a-e0c4e75dae1c")]
+struct HttpServiceBinding {
+    create_child: unsafe extern "efiapi" fn(this: *mut Self, child: *mut *mut c_void) -> Status,
+    destroy_child: unsafe extern "efiapi" fn(this: *mut Self, child: *mut c_void) -> Status,
+}
+
+#[repr(C)]
+struct AccessPointV4 {
+    use_default_address: bool,
+    local_address: [u8; 4],
+    local_subnet: [u8; 4],
+    local_port: u16,
+}
+
+#[repr(C)]
+struct ConfigData {
+    http_version: u32,
+    timeout_ms: u32,
+    local_address_is_ipv6: bool,
+    access_point: *const AccessPointV4,
+}
+
+#[repr(C)]
+struct RequestData {
+    method: u32,
+    url: *const u16,
+}
+
+#[repr(C)]
+struct ResponseData {
+    status_code: HttpStatusCode,
+}
+
+#[repr(C)]
+struct Header {
+    field_name: *const u8,
+    field_value: *const u8,
+}
+
+#[repr(C)]
+struct Message {
+    data: *mut c_void,
+    header_count: usize,
+    headers: *mut Header,
+    body_length: usize,
+    body: *mut c_void,
+}
+
+#[repr(C)]
+struct Token {
+    event: *mut c_void,
+    status: Status,
+    message: *mut Message,
+}
+
+/// EFI_HTTP_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("7a59b29b-910b-4171-8242-a85a0df25b5b")]
+struct Http {
+    get_mode_data: unsafe extern "efiapi" fn(this: *mut Self, data: *mut ConfigData) -> Status,
+    configure: unsafe extern "efiapi" fn(this: *mut Self, data: *const ConfigData) -> Status,
+    request: unsafe extern "efiapi" fn(this: *mut Self, token: *mut Token) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: *mut Self, token: *mut Token) -> Status,
+    response: unsafe extern "efiapi" fn(this: *mut Self, token: *mut Token) -> Status,
+    poll: unsafe extern "efiapi" fn(this: *mut Self) -> Status,
+}
+
+/// Numeric code and reason phrase of a response, as the firmware reports
+/// them; code 0 for a status EFI_HTTP_STATUS_CODE has no name for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusLine {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl StatusLine {
+    /// Split an `HttpStatusCode` such as `STATUS_404_NOT_FOUND` into its
+    /// numeric code and reason phrase.
+    fn from_efi(status: HttpStatusCode) -> Self {
+        let name = format!("{:?}", status);
+        let rest = name.strip_prefix("STATUS_").unwrap_or(&name);
+        match rest.split_once('_') {
+            Some((code, reason)) => StatusLine {
+                code: code.parse().unwrap_or(0),
+                reason: reason.replace('_', " "),
+            },
+            None => StatusLine {
+                code: 0,
+                reason: String::from(rest),
+            },
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code)
+    }
+}
+
+impl fmt::Display for StatusLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {} {}", self.code, self.reason)
+    }
+}
+
+/// Status line and headers of an HTTP response.
+pub struct Response {
+    pub status: StatusLine,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn content_length(&self) -> Option<usize> {
+        self.header("content-length")
+            .and_then(|v| v.trim().parse().ok())
+    }
+}
+
+/// A minimal client on top of EFI_HTTP_PROTOCOL that, unlike the uefi-rs
+/// helper, lets callers add their own request headers.
+pub struct HttpClient {
+    service: Handle,
+    child: Handle,
+    http: Option<boot::ScopedProtocol<Http>>,
+    event: Event,
+}
+
+unsafe fn open_get<P: uefi::proto::ProtocolPointer + ?Sized>(
+    handle: Handle,
+) -> uefi::Result<boot::ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+fn c_string(s: &str) -> Vec<u8> {
+    let mut v = Vec::with_capacity(s.len() + 1);
+    v.extend_from_slice(s.as_bytes());
+    v.push(0);
+    v
+}
+
+unsafe fn read_c_string(p: *const u8) -> String {
+    if p.is_null() {
+        return String::new();
+    }
+    let s = unsafe { core::ffi::CStr::from_ptr(p as *const core::ffi::c_char) };
+    String::from(s.to_str().unwrap_or(""))
+}
+
+/// The `host[:port]` part of `url`, as sent in `Host`.
+pub fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+impl HttpClient {
+    /// Create an HTTP child on `nic` and configure it for IPv4 with the
+    /// default (DHCP-assigned) address.
+    pub fn new(nic: Handle) -> uefi::Result<Self> {
+        let mut sb = unsafe { open_get::<HttpServiceBinding>(nic)? };
+        let this: *mut HttpServiceBinding = &mut *sb;
+        let mut child: *mut c_void = ptr::null_mut();
+        unsafe { (sb.create_child)(this, &mut child) }.to_result()?;
+        drop(sb);
+        let child =
+            unsafe { Handle::from_ptr(child) }.ok_or(uefi::Error::from(Status::NOT_FOUND))?;
+
+        let http = unsafe { open_get::<Http>(child)? };
+        let event = unsafe { boot::create_event(EventType::empty(), Tpl::CALLBACK, None, None)? };
+
+        let mut client = HttpClient {
+            service: nic,
+            child,
+            http: Some(http),
+            event,
+        };
+        client.configure()?;
+        Ok(client)
+    }
+
+    fn proto(&mut self) -> &mut Http {
+        self.http.as_mut().unwrap()
+    }
+
+    fn configure(&mut self) -> uefi::Result<()> {
+        let ap = AccessPointV4 {
+            use_default_address: true,
+            local_address: [0; 4],
+            local_subnet: [0; 4],
+            local_port: 0,
+        };
+        let data = ConfigData {
+            http_version: HTTP_VERSION_11,
+            timeout_ms: TIMEOUT_MS,
+            local_address_is_ipv6: false,
+            access_point: &ap,
+        };
+        let http = self.proto();
+        let configure = http.configure;
+        unsafe { configure(http, &data) }.to_result()
+    }
+
+    /// Submit a request (or response) token and wait for the driver to complete it.
+    fn run(&mut self, response: bool, message: &mut Message) -> uefi::Result<()> {
+        let mut token = Token {
+            event: self.event.as_ptr(),
+            status: Status::NOT_READY,
+            message,
+        };
+        let event = unsafe { self.event.unsafe_clone() };
+        let http = self.proto();
+        let (op, poll, cancel) = (
+            if response {
+                http.response
+            } else {
+                http.request
+            },
+            http.poll,
+            http.cancel,
+        );
+        let this: *mut Http = http;
+        unsafe { op(this, &mut token) }.to_result()?;
+
+        let mut waited = 0u32;
+        loop {
+            let _ = unsafe { poll(this) };
+            if boot::check_event(unsafe { event.unsafe_clone() }).unwrap_or(false) {
+                break;
+            }
+            if waited >= TIMEOUT_MS {
+                let _ = unsafe { cancel(this, &mut token) };
+                return Err(uefi::Error::from(Status::TIMEOUT));
+            }
+            boot::stall(Duration::from_millis(1));
+            waited += 1;
+        }
+
+        unsafe { ptr::read_volatile(&token.status) }.to_result()
+    }
+
+    /// Send a GET for `url` with `extra` headers and return the status line,
+    /// headers and the first part of the body. A `Host` or `Accept` in
+    /// `extra` replaces the default one.
+    pub fn get(&mut self, url: &str, extra: &[(String, String)]) -> uefi::Result<Response> {
+        let url16 =
+            CString16::try_from(url).map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+
+        let mut owned: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        let given = |name: &str| extra.iter().any(|(k, _)| k.eq_ignore_ascii_case(name));
+        if !given("Host") {
+            owned.push((c_string("Host"), c_string(host_of(url))));
+        }
+        if !given("Accept") {
+            owned.push((c_string("Accept"), c_string("*/*")));
+        }
+        for (k, v) in extra {
+            owned.push((c_string(k), c_string(v)));
+        }
+        let mut headers: Vec<Header> = owned
+            .iter()
+            .map(|(k, v)| Header {
+                field_name: k.as_ptr(),
+                field_value: v.as_ptr(),
+            })
+            .collect();
+
+        let mut request = RequestData {
+            method: HTTP_METHOD_GET,
+            url: url16.as_ptr() as *const u16,
+        };
+        let mut message = Message {
+            data: &mut request as *mut RequestData as *mut c_void,
+            header_count: headers.len(),
+            headers: headers.as_mut_ptr(),
+            body_length: 0,
+            body: ptr::null_mut(),
+        };
+        self.run(false, &mut message)?;
+
+        let mut response = ResponseData {
+            status_code: HttpStatusCode::STATUS_UNSUPPORTED,
+        };
+        let mut body = alloc::vec![0u8; 64 * 1024];
+        let mut message = Message {
+            data: &mut response as *mut ResponseData as *mut c_void,
+            header_count: 0,
+            headers: ptr::null_mut(),
+            body_length: body.len(),
+            body: body.as_mut_ptr() as *mut c_void,
+        };
+        self.run(true, &mut message)?;
+
+        // The driver allocates the header array and each name and value in
+        // it from pool; all of it is ours to free.
+        let mut parsed = Vec::with_capacity(message.header_count);
+        if !message.headers.is_null() {
+            for i in 0..message.header_count {
+                let h = unsafe { &*message.headers.add(i) };
+                unsafe {
+                    parsed.push((read_c_string(h.field_name), read_c_string(h.field_value)));
+                }
+                for p in [h.field_name, h.field_value] {
+                    if let Some(p) = NonNull::new(p as *mut u8) {
+                        let _ = unsafe { boot::free_pool(p) };
+                    }
+                }
+            }
+            if let Some(p) = NonNull::new(message.headers as *mut u8) {
+                let _ = unsafe { boot::free_pool(p) };
+            }
+        }
+        body.truncate(message.body_length);
+
+        Ok(Response {
+            status: StatusLine::from_efi(response.status_code),
+            headers: parsed,
+            body,
+        })
+    }
+
+    /// Read the next part of the current response body into `buf`. Returns
+    /// 0 once the driver has nothing more to hand out.
+    pub fn read_body(&mut self, buf: &mut [u8]) -> uefi::Result<usize> {
+        let mut message = Message {
+            data: ptr::null_mut(),
+            header_count: 0,
+            headers: ptr::null_mut(),
+            body_length: buf.len(),
+            body: buf.as_mut_ptr() as *mut c_void,
+        };
+        self.run(true, &mut message)?;
+        Ok(message.body_length)
+    }
+}
+
+impl Drop for HttpClient {
+    fn drop(&mut self) {
+        let _ = boot::close_event(unsafe { self.event.unsafe_clone() });
+        drop(self.http.take());
+        if let Ok(mut sb) = unsafe { open_get::<HttpServiceBinding>(self.service) } {
+            let this: *mut HttpServiceBinding = &mut *sb;
+            let _ = unsafe { (sb.destroy_child)(this, self.child.as_ptr()) };
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! `--ifinfo` mode: list every network interface with its MAC, link state
+//! and the network stack protocols that appear on it once connected, e.g.
+//! `alpheratz.efi --ifinfo` from the UEFI shell.
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::prelude::*;
+use uefi::proto::device_path::DevicePath;
+use uefi::proto::device_path::text::{AllowShortcuts, DisplayOnly};
+use uefi::{Guid, guid};
+
+use crate::{check, net, wifi};
+
+/// The layers `bring_up_ipv4` waits for, bottom up, by the protocol each
+/// installs on the NIC or a child of it.
+const LAYERS: &[(&str, Guid)] = &[
+    ("MNP", guid!("f36ff770-a7e1-42cf-9ed2-56f0f271f44c")),
+    ("ARP", guid!("f44c00ee-1f2c-4a00-aa09-1c9f3e0800a3")),
+    ("IP4", guid!("c51711e7-b4bf-404a-bfb8-0a048ef1ffe4")),
+    ("IP4Config2", guid!("5b446ed1-e30b-4faa-871a-3654eca36080")),
+    ("DHCP4", guid!("9d9a39d8-bd42-4a73-a4d5-8ee94be11380")),
+    ("TCP4", guid!("00720665-67eb-4a99-baf7-d3c33a1c7cd6")),
+    ("DNS4", guid!("b625b186-e063-44f7-8905-6a74dc6f52b4")),
+    ("HTTP", guid!("bdc8e6af-d9bc-4379-a72a-e0c4e75dae1c")),
+];
+
+/// Whether `--ifinfo` was passed in the image's load options.
+pub fn requested() -> bool {
+    check::has_option("--ifinfo")
+}
+
+fn path_text(handle: Handle) -> Option<String> {
+    let dp = unsafe {
+        boot::open_protocol::<DevicePath>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+    let text = dp
+        .to_string(DisplayOnly(false), AllowShortcuts(false))
+        .ok()?;
+    Some(String::from(&*text))
+}
+
+/// The [`LAYERS`] present on `nic` itself or on a handle below it in the
+/// device path (VLAN children).
+fn layers(nic: Handle, nic_path: Option<&str>) -> Vec<&'static str> {
+    let below = |h: Handle| {
+        h == nic
+            || nic_path.is_some_and(|parent| {
+                path_text(h).is_some_and(|p| {
+                    p.strip_prefix(parent)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                })
+            })
+    };
+    LAYERS
+        .iter()
+        .filter(|(_, guid)| {
+            boot::locate_handle_buffer(boot::SearchType::ByProtocol(guid))
+                .is_ok_and(|handles| handles.iter().any(|&h| below(h)))
+        })
+        .map(|&(name, _)| name)
+        .collect()
+}
+
+fn print_layers(label: &str, names: &[&str]) {
+    let mut line = String::new();
+    for name in names {
+        line.push(' ');
+        line.push_str(name);
+    }
+    if line.is_empty() {
+        line.push_str(" none");
+    }
+    crate::println!("  {}:{}", label, line);
+}
+
+/// Describe each SNP handle, connect it, and report which layers it
+/// gained. Link speed is not part of SNP, so it is not shown.
+pub fn run() -> Status {
+    let handles = match net::locate_snp_handles() {
+        Ok(h) if !h.is_empty() => h,
+        _ => {
+            crate::println!("No network interfaces (SimpleNetwork) found");
+            return Status::NOT_FOUND;
+        }
+    };
+
+    for (i, &nic) in handles.iter().enumerate() {
+        let Ok(snp) = (unsafe { net::open_snp_readonly(nic) }) else {
+            crate::println!("NIC {}: SimpleNetwork cannot be opened", i);
+            continue;
+        };
+        let mode = snp.mode();
+        let media = if !bool::from(mode.media_present_supported) {
+            "link unknown"
+        } else if bool::from(mode.media_present) {
+            "link up"
+        } else {
+            "no link"
+        };
+        let kind = if wifi::is_wireless(nic) {
+            "wireless"
+        } else {
+            "wired"
+        };
+        crate::println!(
+            "NIC {}: {} {}, {}, MTU {}, state {:?}",
+            i,
+            net::mac_to_string(net::snp_mac6(&snp)),
+            kind,
+            media,
+            mode.max_packet_size,
+            mode.state
+        );
+        drop(snp);
+
+        let nic_path = path_text(nic);
+        if let Some(p) = &nic_path {
+            crate::println!("  path: {}", p);
+        }
+        let before = layers(nic, nic_path.as_deref());
+        print_layers("before connect", &before);
+        if let Err(e) = boot::connect_controller(nic, None, None, true) {
+            crate::println!("  connect: {:?}", e.status());
+        }
+        let after = layers(nic, nic_path.as_deref());
+        print_layers("after connect", &after);
+        let gained: Vec<&str> = after
+            .iter()
+            .copied()
+            .filter(|name| !before.contains(name))
+            .collect();
+        print_layers("gained", &gained);
+    }
+    Status::SUCCESS
+}
@@ -0,0 +1,256 @@
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::ffi::c_void;
+use core::fmt::Write;
+use core::time::Duration;
+
+use uefi::Identify;
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::prelude::*;
+use uefi::proto::device_path::{DevicePath, DeviceSubType, DeviceType};
+use uefi::proto::media::block::BlockIO;
+use uefi::proto::unsafe_protocol;
+use uefi::{CStr16, CString16};
+
+use crate::config::{Config, Iscsi};
+use crate::deadline::Deadline;
+
+const ATTACH_TIMEOUT_SECS: u64 = 30;
+
+/// EFI_ISCSI_INITIATOR_NAME_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("59324945-ec44-4c0d-b1cd-9db139df070c")]
+struct IscsiInitiatorName {
+    get:
+        unsafe extern "efiapi" fn(this: *mut Self, size: *mut usize, buffer: *mut c_void) -> Status,
+    set:
+        unsafe extern "efiapi" fn(this: *mut Self, size: *mut usize, buffer: *mut c_void) -> Status,
+}
+
+/// EFI_CONFIG_KEYWORD_HANDLER_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("0a8badd5-03b8-4d19-b128-7b8f0edaa596")]
+struct KeywordHandler {
+    set_data: unsafe extern "efiapi" fn(
+        this: *mut Self,
+        keyword_string: *const u16,
+        progress: *mut *const u16,
+        progress_err: *mut u32,
+    ) -> Status,
+    get_data: unsafe extern "efiapi" fn(
+        this: *mut Self,
+        namespace_id: *const u16,
+        keyword_string: *const u16,
+        progress: *mut *const u16,
+        progress_err: *mut u32,
+        results: *mut *mut u16,
+    ) -> Status,
+}
+
+/// The attempt `configure` fills in; IScsiDxe numbers them from 1.
+const ATTEMPT: u8 = 1;
+
+// Values of the IScsiDxe attempt questions (IScsiConfigNVDataStruc.h).
+const ENABLED: u8 = 1;
+const IP_MODE_IP4: u8 = 0;
+const AUTH_NONE: u8 = 0;
+const AUTH_CHAP: u8 = 1;
+const CHAP_UNI: u8 = 0;
+const CHAP_MUTUAL: u8 = 1;
+
+/// A keyword value holding the UTF-16 string `text`, NUL included.
+fn text_value(text: &str) -> String {
+    let mut out = String::new();
+    for unit in text.encode_utf16().chain([0]) {
+        let _ = write!(out, "{:04x}", unit);
+    }
+    out
+}
+
+/// A keyword value holding the `width`-byte number `n`.
+fn number_value(n: u64, width: usize) -> String {
+    format!("{:0w$x}", n, w = width * 2)
+}
+
+fn set_keyword(handler: &mut KeywordHandler, keyword: &str, value: &str) -> uefi::Result<()> {
+    let request = format!("NAMESPACE=x-UEFI-ns&KEYWORD={}&VALUE={}", keyword, value);
+    let request = CString16::try_from(request.as_str())
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let request: &CStr16 = &request;
+    let mut progress = core::ptr::null();
+    let mut progress_err = 0;
+    let this: *mut KeywordHandler = handler;
+    let status = unsafe {
+        (handler.set_data)(
+            this,
+            request.as_ptr().cast(),
+            &mut progress,
+            &mut progress_err,
+        )
+    };
+    status.to_result()
+}
+
+/// Fill in the firmware's first iSCSI attempt from `iscsi` — target,
+/// portal, LUN and CHAP — through the `x-UEFI-ns` keywords IScsiDxe
+/// publishes. The initiator address comes from DHCP.
+fn configure(iscsi: &Iscsi, (ip, port): ([u8; 4], u16)) -> uefi::Result<()> {
+    let handle = boot::get_handle_for_protocol::<KeywordHandler>()?;
+    let mut handler = boot::open_protocol_exclusive::<KeywordHandler>(handle)?;
+
+    let attempt = |name: &str| format!("{}:{}", name, ATTEMPT);
+    let lun = iscsi.lun_bytes();
+    let lun = format!("{:x}", u16::from_be_bytes([lun[0], lun[1]]));
+    let ip = format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]);
+
+    let _ = set_keyword(&mut handler, "iSCSIAddAttempts", &text_value("1"));
+    let mut settings = Vec::from([
+        (attempt("iSCSIBootEnable"), number_value(ENABLED.into(), 1)),
+        (
+            attempt("iSCSIIpAddressType"),
+            number_value(IP_MODE_IP4.into(), 1),
+        ),
+        (attempt("iSCSIInitiatorInfoViaDHCP"), number_value(1, 1)),
+        (attempt("iSCSITargetInfoViaDHCP"), number_value(0, 1)),
+        (attempt("iSCSITargetName"), text_value(&iscsi.target)),
+        (attempt("iSCSITargetIpAddress"), text_value(&ip)),
+        (attempt("iSCSITargetTcpPort"), number_value(port.into(), 2)),
+        (attempt("iSCSIBootLUN"), text_value(&lun)),
+    ]);
+    match (&iscsi.chap_user, &iscsi.chap_secret) {
+        (Some(user), Some(secret)) => {
+            let mutual = iscsi.reverse_chap_user.is_some() && iscsi.reverse_chap_secret.is_some();
+            let chap_type = if mutual { CHAP_MUTUAL } else { CHAP_UNI };
+            settings.push((
+                attempt("iSCSIAuthenticationMethod"),
+                number_value(AUTH_CHAP.into(), 1),
+            ));
+            settings.push((attempt("iSCSIChapType"), number_value(chap_type.into(), 1)));
+            settings.push((attempt("iSCSIChapUsername"), text_value(user)));
+            settings.push((attempt("iSCSIChapSecret"), text_value(secret)));
+            if let (true, Some(user), Some(secret)) =
+                (mutual, &iscsi.reverse_chap_user, &iscsi.reverse_chap_secret)
+            {
+                settings.push((attempt("iSCSIReverseChapUsername"), text_value(user)));
+                settings.push((attempt("iSCSIReverseChapSecret"), text_value(secret)));
+            }
+        }
+        _ => settings.push((
+            attempt("iSCSIAuthenticationMethod"),
+            number_value(AUTH_NONE.into(), 1),
+        )),
+    }
+
+    for (keyword, value) in &settings {
+        set_keyword(&mut handler, keyword, value).inspect_err(|e| {
+            crate::println!("  Setting {} failed: {:?}", keyword, e.status());
+        })?;
+    }
+    Ok(())
+}
+
+fn set_initiator_name(name: &str) -> uefi::Result<()> {
+    let handle = boot::get_handle_for_protocol::<IscsiInitiatorName>()?;
+    let mut proto = boot::open_protocol_exclusive::<IscsiInitiatorName>(handle)?;
+    let this: *mut IscsiInitiatorName = &mut *proto;
+
+    let mut buf = Vec::from(name.as_bytes());
+    buf.push(0);
+    let mut size = buf.len();
+    let status = unsafe { (proto.set)(this, &mut size, buf.as_mut_ptr() as *mut c_void) };
+    status.to_result()
+}
+
+/// Target IQN and raw LUN bytes of the first iSCSI node in `handle`'s
+/// device path.
+fn iscsi_node(handle: Handle) -> Option<(String, [u8; 8])> {
+    let dp = unsafe {
+        boot::open_protocol::<DevicePath>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+
+    let node = dp.node_iter().find(|n| {
+        n.device_type() == DeviceType::MESSAGING && n.sub_type() == DeviceSubType::MESSAGING_ISCSI
+    })?;
+
+    // NetworkProtocol(2) LoginOption(2) Lun(8) TargetPortalGroupTag(2) TargetName(…)
+    let data = node.data();
+    let lun: [u8; 8] = data.get(4..12)?.try_into().ok()?;
+    let name = data.get(14..)?;
+    let name = name.split(|&b| b == 0).next().unwrap_or(&[]);
+    let name = core::str::from_utf8(name).ok()?;
+    Some((String::from(name), lun))
+}
+
+fn find_lun(iscsi: &Iscsi) -> Option<Handle> {
+    let handles = boot::locate_handle_buffer(boot::SearchType::ByProtocol(&BlockIO::GUID)).ok()?;
+    let want = iscsi.lun_bytes();
+    handles
+        .iter()
+        .copied()
+        .find(|&h| iscsi_node(h).is_some_and(|(target, lun)| target == iscsi.target && lun == want))
+}
+
+/// Configure the firmware iSCSI initiator and wait for the LUN described by
+/// `[storage.iscsi]` to show up as a block device.
+///
+/// With a `portal`, the firmware's iSCSI attempt is written from the
+/// section first; without one it must have been provisioned in firmware
+/// setup, and only the initiator name is set before the driver is kicked.
+/// Gives up early once `deadline` has passed.
+pub fn attach(cfg: &Config, deadline: &Deadline) -> uefi::Result<Handle> {
+    let Some(iscsi) = cfg.storage.as_ref().and_then(|s| s.iscsi.as_ref()) else {
+        return Err(uefi::Error::from(Status::NOT_FOUND));
+    };
+
+    if let Some(h) = find_lun(iscsi) {
+        return Ok(h);
+    }
+
+    crate::println!(
+        "Attaching iSCSI {} LUN {}...",
+        iscsi.target,
+        iscsi.lun.unwrap_or(0)
+    );
+
+    if let Some(name) = iscsi.initiator.as_deref()
+        && let Err(e) = set_initiator_name(name)
+    {
+        crate::println!("  Setting initiator name failed: {:?}", e.status());
+    }
+    if let Some(portal) = iscsi.portal_address()
+        && let Err(e) = configure(iscsi, portal)
+    {
+        crate::println!("  Configuring the iSCSI attempt failed: {:?}", e.status());
+    }
+
+    for _ in 0..ATTACH_TIMEOUT_SECS {
+        deadline.check()?;
+        if let Ok(all) = boot::locate_handle_buffer(boot::SearchType::AllHandles) {
+            for &h in all.iter() {
+                let _ = boot::connect_controller(h, None, None, true);
+            }
+        }
+        if let Some(h) = find_lun(iscsi) {
+            crate::println!("  iSCSI LUN attached.");
+            return Ok(h);
+        }
+        boot::stall(Duration::from_secs(1));
+    }
+
+    crate::println!("  iSCSI LUN not found; check the firmware iSCSI attempt settings.");
+    Err(uefi::Error::from(Status::NOT_FOUND))
+}
@@ -0,0 +1,123 @@
+//! Keystrokes with modifier state. Reads through
+//! EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL on the console-in handle where firmware
+//! provides it and plain ConIn elsewhere, and folds the different ways
+//! firmware reports Ctrl+letter into one form.
+
+use core::ffi::c_void;
+
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::prelude::*;
+use uefi::proto::console::text::{Key, ScanCode};
+use uefi::proto::unsafe_protocol;
+use uefi::{Char16, Event};
+
+const SHIFT_STATE_VALID: u32 = 0x8000_0000;
+const CONTROL_PRESSED: u32 = 0x0000_000C;
+
+#[repr(C)]
+#[derive(Default)]
+struct KeyData {
+    scan_code: u16,
+    unicode_char: u16,
+    shift_state: u32,
+    toggle_state: u8,
+}
+
+/// EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("dd9e7534-7762-4698-8c14-f58517a625aa")]
+struct InputEx {
+    reset: unsafe extern "efiapi" fn(this: *mut Self, extended: bool) -> Status,
+    read_key_stroke_ex: unsafe extern "efiapi" fn(this: *mut Self, key: *mut KeyData) -> Status,
+    wait_for_key_ex: *mut c_void,
+    set_state: *const c_void,
+    register_key_notify: *const c_void,
+    unregister_key_notify: *const c_void,
+}
+
+/// A key together with whether Ctrl was held, when firmware reports it.
+pub struct KeyPress {
+    pub key: Key,
+    pub ctrl: bool,
+}
+
+impl KeyPress {
+    fn plain(key: Key) -> Self {
+        KeyPress { key, ctrl: false }
+    }
+
+    /// The letter of a Ctrl+letter combination, lowercase. Firmware sends
+    /// either the letter with the Ctrl state or the ASCII control code; the
+    /// codes for Backspace, Tab and Enter are left alone.
+    pub fn ctrl_letter(&self) -> Option<char> {
+        let Key::Printable(c) = self.key else {
+            return None;
+        };
+        let c = char::from(c);
+        match c as u32 {
+            0x08 | 0x09 | 0x0A | 0x0D => None,
+            code @ 0x01..=0x1A => char::from_u32(u32::from(b'a') + code - 1),
+            _ if self.ctrl && c.is_ascii_alphabetic() => Some(c.to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+
+    pub fn is_enter(&self) -> bool {
+        matches!(self.key, Key::Printable(c) if u16::from(c) == 0x000D)
+    }
+}
+
+fn input_ex() -> Option<boot::ScopedProtocol<InputEx>> {
+    let st = uefi::table::system_table_raw()?;
+    let handle = unsafe { Handle::from_ptr(st.as_ref().stdin_handle) }?;
+    unsafe {
+        boot::open_protocol::<InputEx>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()
+}
+
+/// Event signalled when a key is waiting, for `wait_for_event`.
+pub fn wait_event() -> Option<Event> {
+    if let Some(ex) = input_ex() {
+        return unsafe { Event::from_ptr(ex.wait_for_key_ex) };
+    }
+    uefi::system::with_stdin(|stdin| stdin.wait_for_key_event())
+}
+
+/// Take the next keystroke, if one is waiting.
+pub fn read() -> Option<KeyPress> {
+    let Some(mut ex) = input_ex() else {
+        return match uefi::system::with_stdin(|stdin| stdin.read_key()) {
+            Ok(Some(key)) => Some(KeyPress::plain(key)),
+            _ => None,
+        };
+    };
+
+    let mut data = KeyData::default();
+    let this: *mut InputEx = &mut *ex;
+    if unsafe { (ex.read_key_stroke_ex)(this, &mut data) } != Status::SUCCESS {
+        return None;
+    }
+    let key = if data.scan_code == 0 {
+        Key::Printable(Char16::try_from(data.unicode_char).ok()?)
+    } else {
+        Key::Special(ScanCode(data.scan_code))
+    };
+    let state = if data.shift_state & SHIFT_STATE_VALID != 0 {
+        data.shift_state
+    } else {
+        0
+    };
+    Some(KeyPress {
+        key,
+        ctrl: state & CONTROL_PRESSED != 0,
+    })
+}
@@ -0,0 +1,60 @@
+//! Failures after exit_boot_services(): report them on serial and screen,
+//! then reset instead of hanging with nothing to show for it.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use uefi_raw::Status;
+use uefi_raw::table::runtime::ResetType;
+
+use crate::fbcon;
+
+type ResetSystem = unsafe extern "efiapi" fn(ResetType, Status, usize, *const u8) -> !;
+
+/// `ResetSystem` from the runtime services table; 0 until [`prepare`].
+static RESET: AtomicUsize = AtomicUsize::new(0);
+
+/// Spin iterations between the report and the reset, a few seconds on
+/// current hardware, so the message can be read.
+const DELAY_SPINS: u64 = 2_000_000_000;
+
+/// Save what a late failure needs. Call right before exit_boot_services().
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+pub fn prepare() {
+    fbcon::capture();
+    if let Some(st) = uefi::table::system_table_raw() {
+        let rt = unsafe { st.as_ref().runtime_services };
+        if !rt.is_null() {
+            let reset = unsafe { (*rt).reset_system };
+            RESET.store(reset as usize, Ordering::Release);
+        }
+    }
+}
+
+/// Whether [`prepare`] has run, i.e. boot services may be gone.
+pub fn prepared() -> bool {
+    RESET.load(Ordering::Acquire) != 0
+}
+
+/// Report `args`, wait a moment and warm-reset the machine.
+pub fn fail(args: fmt::Arguments) -> ! {
+    fbcon::error(args);
+    fbcon::error(format_args!("Resetting..."));
+    for _ in 0..DELAY_SPINS {
+        core::hint::spin_loop();
+    }
+
+    let reset = RESET.load(Ordering::Acquire);
+    if reset != 0 {
+        let reset: ResetSystem = unsafe { core::mem::transmute(reset) };
+        unsafe { reset(ResetType::WARM, Status::ABORTED, 0, core::ptr::null()) };
+    }
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        // Reset control register: request a warm CPU reset.
+        core::arch::asm!("out dx, al", in("dx") 0xCF9u16, in("al") 0x06u8);
+    }
+    loop {
+        core::hint::spin_loop();
+    }
+}
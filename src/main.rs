@@ -3,110 +3,340 @@
 
 extern crate alloc;
 
+mod audit;
+mod bcd;
+mod beep;
 mod boot;
-mod config;
+mod check;
+mod console;
+mod deadline;
 mod download;
+mod error;
+// Only self-updates arm the fallback copy.
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+mod fallback;
+mod fbcon;
+mod fit;
 mod fsutil;
+mod grubenv;
+mod hibernate;
+#[cfg(feature = "network")]
+mod http;
+#[cfg(feature = "network")]
+mod ifinfo;
+#[cfg(feature = "network")]
+mod iscsi;
+mod keyboard;
+mod late;
+mod memcheck;
 mod menu;
+#[cfg(feature = "network")]
 mod net;
+#[cfg(feature = "canicula")]
 mod page_table;
+#[cfg(feature = "drivers")]
+mod pci;
+#[cfg(feature = "drivers")]
+mod pkcs7;
+#[cfg(feature = "network")]
+mod pxe;
+mod render;
+mod secureboot;
 mod serial;
-
+mod setup;
+mod setvar;
+mod smbios;
+mod splash;
+#[cfg(feature = "network")]
+mod store;
+#[cfg(feature = "network")]
+mod tcp;
+#[cfg(feature = "canicula")]
+mod timer;
+#[cfg(feature = "network")]
+mod update;
+mod video;
+#[cfg(feature = "network")]
+mod wifi;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec;
+use alloc::vec::Vec;
+use alpheratz_core::config::{self, OnError};
+use alpheratz_core::loader_info;
+use alpheratz_core::{aes_gcm, sha1, sha256};
+use alpheratz_core::validate::{self, Issue, Severity};
 use core::panic::PanicInfo;
 use uefi::prelude::*;
-use uefi::proto::media::file::{File, FileAttribute, FileInfo, FileMode};
-use uefi::proto::media::fs::SimpleFileSystem;
 
 pub const PAGE_SIZE: usize = 4096;
 
-const CONFIG_PATH: &uefi::CStr16 = cstr16!("\\EFI\\BOOT\\bootloader.toml");
-
-fn load_config() -> config::Config {
-    let result = (|| -> Option<config::Config> {
-        let loaded_image = uefi::boot::open_protocol_exclusive::<
-            uefi::proto::loaded_image::LoadedImage,
-        >(uefi::boot::image_handle())
-        .ok()?;
-        let device = loaded_image.device()?;
-
-        let mut sfs = uefi::boot::open_protocol_exclusive::<SimpleFileSystem>(device).ok()?;
-        let mut root = sfs.open_volume().ok()?;
-        let handle = root
-            .open(CONFIG_PATH, FileMode::Read, FileAttribute::empty())
-            .ok()?;
-        let mut file = handle.into_regular_file()?;
-
-        let info = file.get_boxed_info::<FileInfo>().ok()?;
-        let size = info.file_size() as usize;
-        let mut buf = vec![0u8; size];
-        file.read(&mut buf).ok()?;
-
-        let text = core::str::from_utf8(&buf).ok()?;
-        config::Config::from_str(text).ok()
-    })();
-
-    result.unwrap_or_default()
+const CONFIG_PATH: &str = "\\EFI\\BOOT\\bootloader.toml";
+
+/// Read `CONFIG_PATH` from the volume Alpheratz was loaded from.
+fn read_config() -> uefi::Result<String> {
+    let mut root = fsutil::open_esp_root()?;
+    fsutil::recover_atomic(&mut root, CONFIG_PATH);
+    let buf = fsutil::read_file(&mut root, CONFIG_PATH)?;
+    String::from_utf8(buf).map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))
+}
+
+/// Read the configuration, running the setup wizard if there is none, along
+/// with the problems found in it. An unreadable or invalid file falls back
+/// to the defaults.
+fn load_config() -> (config::Config, Vec<Issue>) {
+    match read_config() {
+        Ok(text) => match config::Config::from_str(&text) {
+            Ok(mut cfg) => {
+                #[cfg(feature = "network")]
+                download::import_ipxe(&mut cfg);
+                #[cfg(feature = "network")]
+                download::import_remote_menu(&mut cfg);
+                bcd::name_entries(&mut cfg);
+                let issues = validate::validate(&cfg);
+                cfg.retain_matching(&smbios::machine());
+                cfg.retain_runnable();
+                cfg.disambiguate_names();
+                grubenv::apply(&mut cfg);
+                (cfg, issues)
+            }
+            Err(e) => (
+                config::Config::default(),
+                vec![validate::syntax_error(&text, &e)],
+            ),
+        },
+        Err(e) if e.status() == Status::NOT_FOUND => {
+            (setup::run(CONFIG_PATH).unwrap_or_default(), Vec::new())
+        }
+        Err(e) => (
+            config::Config::default(),
+            vec![Issue {
+                severity: Severity::Error,
+                entry: None,
+                message: format!("cannot read {}: {:?}", CONFIG_PATH, e.status()),
+            }],
+        ),
+    }
 }
 
 #[entry]
 fn main() -> Status {
-    let cfg = load_config();
+    if check::requested() {
+        return check::run(read_config());
+    }
+    #[cfg(feature = "network")]
+    if ifinfo::requested() {
+        return ifinfo::run();
+    }
+
+    fallback::check();
+    let (mut cfg, mut issues) = load_config();
+    let hibernated = hibernate::check(&mut cfg, &mut issues);
+    video::apply(cfg.video);
+    render::configure(&cfg.theme);
+    beep::enable(cfg.beep);
+
+    let mut fallback: Option<menu::Choice> = None;
 
     loop {
-        let selected = menu::show(&cfg);
+        let choice = fallback.take().unwrap_or_else(|| menu::show(&cfg, &issues));
+        let entry = &cfg.entry[choice.index];
 
-        uefi::println!(
-            "Selected: [{}] {}",
-            cfg.entry[selected].protocol,
-            cfg.entry[selected].name,
-        );
+        if entry.action == Some(config::Action::SelfUpdate) {
+            if menu::check_password(entry) {
+                #[cfg(feature = "network")]
+                if let Err(e) = update::run(&cfg) {
+                    crate::println!("Self-update failed: {}", e);
+                }
+                #[cfg(not(feature = "network"))]
+                crate::println!("Self-update needs a loader built with networking.");
+                fallback = on_failure(&cfg, &choice, OnError::Menu);
+            }
+            continue;
+        }
+
+        let Some(protocol) = entry.protocol else {
+            crate::println!(
+                "Entry \"{}\" has neither a protocol nor an action.",
+                entry.name
+            );
+            fallback = on_failure(&cfg, &choice, entry.on_error.unwrap_or_default());
+            continue;
+        };
+
+        crate::println!("Selected: [{}] {}", protocol, entry.name);
+
+        if let Some(hibernated) = &hibernated {
+            match hibernate::confirm(&cfg, hibernated, choice.index) {
+                Some(index) if index == choice.index => {}
+                Some(index) => {
+                    fallback = Some(menu::Choice {
+                        index,
+                        auto: false,
+                        extra_cmdline: None,
+                    });
+                    continue;
+                }
+                None => continue,
+            }
+        }
+
+        if !menu::check_password(entry) {
+            continue;
+        }
 
-        let entry = &cfg.entry[selected];
-        let resolved = match download::resolve_all(&cfg, entry) {
+        if entry.quiet {
+            console::enter_quiet();
+            if cfg.theme.splash {
+                splash::start(&cfg.theme);
+            }
+        } else {
+            console::leave_quiet();
+            splash::stop();
+        }
+
+        let mut resolved = match download::resolve_all(&cfg, entry, !choice.auto) {
             Ok(r) => r,
             Err(e) => {
-                uefi::println!("Failed to load files: {:?}", e.status());
-                uefi::println!("Press any key to return to menu...");
-                wait_for_key();
+                let past_deadline = matches!(e, error::AlpheratzError::Deadline);
+                let policy = match entry.on_error {
+                    Some(policy) => policy,
+                    None if past_deadline && choice.auto => OnError::Next,
+                    None => OnError::Menu,
+                };
+                if past_deadline {
+                    crate::println!("Resolving \"{}\" timed out.", entry.name);
+                } else {
+                    crate::println!("Failed to load files: {}", e);
+                }
+                fallback = on_failure(&cfg, &choice, policy);
                 continue;
             }
         };
 
+        if let Some(extra) = &choice.extra_cmdline {
+            resolved.cmdline = Some(match resolved.cmdline.take() {
+                Some(cl) if !cl.is_empty() => format!("{} {}", cl, extra),
+                _ => extra.clone(),
+            });
+        }
+        if protocol == config::Protocol::Linux {
+            resolved.cmdline = console::inject(&cfg, entry, resolved.cmdline.take());
+        }
+        if protocol == config::Protocol::Canicula
+            && let Some(Err(reason)) = resolved.cmdline.as_deref().map(loader_info::check_cmdline)
+        {
+            crate::println!("Cannot boot \"{}\": {}", entry.name, reason);
+            fallback = on_failure(&cfg, &choice, entry.on_error.unwrap_or_default());
+            continue;
+        }
+
         let Some(kernel) = resolved.kernel.as_deref() else {
-            uefi::println!("No kernel found in entry.");
-            uefi::println!("Press any key to return to menu...");
-            wait_for_key();
+            crate::println!("No kernel found in entry.");
+            fallback = on_failure(&cfg, &choice, entry.on_error.unwrap_or_default());
             continue;
         };
 
-        match entry.protocol {
-            config::Protocol::Linux => {
-                let _ = boot::boot_linux(
-                    kernel,
-                    resolved.initrd.as_deref(),
-                    resolved.cmdline.as_deref(),
-                );
-            }
-            config::Protocol::Canicula => {
-                let _ = boot::boot_canicula(kernel, resolved.cmdline.as_deref());
-            }
+        splash::stage(splash::Stage::Verify);
+        if let Err(reason) = secureboot::check(&cfg, protocol, kernel) {
+            crate::println!("Refusing to boot: {}", reason);
+            fallback = on_failure(&cfg, &choice, entry.on_error.unwrap_or_default());
+            continue;
         }
 
-        return Status::SUCCESS;
+        splash::stage(splash::Stage::Load);
+        audit::log_boot(&cfg, entry, &resolved);
+        setvar::apply(entry);
+        let env = (!entry.env.is_empty()).then(|| alpheratz_core::env::serialize(&entry.env));
+        let handoff = boot::Handoff {
+            env: env.as_deref(),
+            symbols: resolved.symbols.as_deref(),
+            debug_halt: entry.debug_halt,
+            original_rsdp: entry.original_rsdp,
+            memory: cfg.memory,
+        };
+
+        let booted = boot::boot(
+            protocol,
+            kernel,
+            resolved.initrd.as_deref(),
+            resolved.cmdline.as_deref(),
+            resolved.dtb.as_deref(),
+            entry.image_path().as_deref(),
+            handoff,
+        );
+        let Err(e) = booted else {
+            return Status::SUCCESS;
+        };
+
+        // Release the kernel and initrd before the next attempt resolves
+        // fresh copies, or a retry can run out of memory.
+        drop(resolved);
+        crate::println!("Boot failed: {}", e);
+        fallback = on_failure(&cfg, &choice, entry.on_error.unwrap_or_default());
+    }
+}
+
+/// Carry out `policy` after the entry picked by `choice` failed. Returns
+/// the entry to try next, if any.
+fn on_failure(
+    cfg: &config::Config,
+    choice: &menu::Choice,
+    policy: OnError,
+) -> Option<menu::Choice> {
+    beep::cue(beep::Cue::Error);
+    match policy {
+        OnError::Menu => {}
+        OnError::Next => {
+            // Nobody is there to type the password of an unattended fallback.
+            let next = (choice.index + 1..cfg.entry.len()).find(|&i| {
+                let e = &cfg.entry[i];
+                e.protocol.is_some() && !(choice.auto && e.password_hash.is_some())
+            });
+            if let Some(index) = next {
+                crate::println!("Falling back to \"{}\"...", cfg.entry[index].name);
+                return Some(menu::Choice {
+                    index,
+                    auto: choice.auto,
+                    extra_cmdline: None,
+                });
+            }
+        }
+        OnError::Reboot | OnError::Shutdown => {
+            let (what, action) = if policy == OnError::Reboot {
+                ("Rebooting", config::Action::Reboot)
+            } else {
+                ("Shutting down", config::Action::Shutdown)
+            };
+            crate::println!("{} in 3 seconds...", what);
+            uefi::boot::stall(core::time::Duration::from_secs(3));
+            menu::run_action(action);
+        }
     }
+    crate::println!("Press any key to return to menu...");
+    wait_for_key();
+    None
 }
 
 fn wait_for_key() {
+    splash::stop();
+    if console::leave_quiet() {
+        crate::println!("Boot failed; details were logged to the serial console.");
+        crate::println!("Press any key to return to menu...");
+    }
     loop {
         uefi::boot::stall(core::time::Duration::from_millis(100));
-        if let Ok(Some(_)) = uefi::system::with_stdin(|stdin| stdin.read_key()) {
+        if console::key_pressed() {
             return;
         }
     }
 }
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(info: &PanicInfo) -> ! {
+    if late::prepared() {
+        late::fail(format_args!("alpheratz panicked: {}", info.message()));
+    }
+    fbcon::error(format_args!("alpheratz panicked: {}", info.message()));
     loop {}
 }
@@ -0,0 +1,34 @@
+use uefi::boot::{self, MemoryType};
+use uefi::mem::memory_map::MemoryMap;
+use uefi::prelude::*;
+
+use crate::PAGE_SIZE;
+
+const MIB: usize = 1024 * 1024;
+
+/// Size in bytes of the largest free (conventional) region in the UEFI
+/// memory map.
+fn largest_free_region() -> Option<usize> {
+    let map = boot::memory_map(MemoryType::LOADER_DATA).ok()?;
+    map.entries()
+        .filter(|d| d.ty == MemoryType::CONVENTIONAL)
+        .map(|d| d.page_count as usize * PAGE_SIZE)
+        .max()
+}
+
+/// Fail early when a buffer of `need` bytes cannot fit in any free region,
+/// rather than running out of memory halfway through a transfer.
+pub fn ensure_available(need: usize) -> uefi::Result<()> {
+    let Some(largest) = largest_free_region() else {
+        return Ok(());
+    };
+    if need > largest {
+        crate::println!(
+            "  Not enough memory: need {} MiB, largest free region is {} MiB",
+            need.div_ceil(MIB),
+            largest / MIB
+        );
+        return Err(uefi::Error::from(Status::OUT_OF_RESOURCES));
+    }
+    Ok(())
+}
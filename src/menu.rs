@@ -1,116 +1,543 @@
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
 use core::fmt::Write;
 use core::time::Duration;
 
+use uefi::Event;
+use uefi::boot::{self, EventType, TimerTrigger, Tpl};
 use uefi::prelude::*;
 use uefi::proto::console::text::{Color, Key, ScanCode};
 use uefi::runtime::{ResetType, VariableAttributes, VariableVendor};
 
-use crate::config::Config;
+use alpheratz_core::validate::{Issue, Severity};
+
+use crate::beep::{self, Cue};
+use crate::config::{Action, Config, Entry};
+use crate::download;
+use crate::fsutil;
+use crate::keyboard::{self, KeyPress};
+use crate::render::{self, Renderer};
+use crate::secureboot;
+use crate::sha256;
+
+/// Countdown timer period in 100 ns units (one second).
+const COUNTDOWN_TICK_100NS: u64 = 10_000_000;
+
+/// Polling interval when no countdown timer event can be created.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Most config problems listed under the menu; `--check` shows them all.
+const FOOTER_ISSUES: usize = 4;
+
+/// Trailing audit log lines shown by Ctrl+L.
+const LOG_LINES: usize = 20;
+
+/// State of the auto-boot countdown. Navigation pauses it and it re-arms
+/// after `timeout_resume_secs` of inactivity; Esc cancels it for good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Countdown {
+    Off,
+    Running(usize),
+    Paused { idle: usize },
+    Cancelled,
+}
 
 enum Selection {
     Entry(usize),
+    /// The submenu holding `cfg.entry[i]`, its first entry.
+    Submenu(usize),
     Firmware,
     Shutdown,
 }
 
+/// Entries shown on the top-level menu: those outside any submenu, and the
+/// first of each submenu standing in for it.
+fn top_level(cfg: &Config) -> impl Iterator<Item = usize> + '_ {
+    (0..cfg.entry.len()).filter(|&i| match &cfg.entry[i].submenu {
+        None => true,
+        Some(name) => !cfg.entry[..i]
+            .iter()
+            .any(|e| e.submenu.as_ref() == Some(name)),
+    })
+}
+
+/// Number of top-level items before the firmware/shutdown items.
+fn entry_items(cfg: &Config) -> usize {
+    top_level(cfg).count()
+}
+
 fn total_items(cfg: &Config) -> usize {
-    cfg.entry.len() + cfg.firmware as usize + cfg.shutdown as usize
+    entry_items(cfg) + cfg.firmware as usize + cfg.shutdown as usize
+}
+
+/// Menu index of the item leading to `cfg.entry[entry]`.
+fn item_for_entry(cfg: &Config, entry: usize) -> usize {
+    let lead = match cfg.entry.get(entry).and_then(|e| e.submenu.as_ref()) {
+        Some(name) => cfg
+            .entry
+            .iter()
+            .position(|e| e.submenu.as_ref() == Some(name))
+            .unwrap_or(entry),
+        None => entry,
+    };
+    top_level(cfg).position(|i| i == lead).unwrap_or(0)
+}
+
+/// Nearest valid menu index to `idx`. The menu is never empty when this
+/// is used.
+fn clamp_selection(cfg: &Config, idx: usize) -> usize {
+    idx.min(total_items(cfg).saturating_sub(1))
 }
 
+/// What menu index `idx` stands for, computed from the current `firmware`
+/// and `shutdown` flags. Out-of-range indices select the last item.
 fn index_to_selection(cfg: &Config, idx: usize) -> Selection {
-    if idx < cfg.entry.len() {
-        return Selection::Entry(idx);
+    let idx = clamp_selection(cfg, idx);
+    if let Some(i) = top_level(cfg).nth(idx) {
+        return match cfg.entry[i].submenu {
+            Some(_) => Selection::Submenu(i),
+            None => Selection::Entry(i),
+        };
     }
-    let extra = idx - cfg.entry.len();
+    let extra = idx - entry_items(cfg);
     if cfg.firmware && extra == 0 {
         return Selection::Firmware;
     }
     Selection::Shutdown
 }
 
-/// Display the boot menu and return the index of the selected boot entry.
+/// A boot entry picked from the menu.
+pub struct Choice {
+    pub index: usize,
+    /// Chosen by the countdown expiring rather than by the user.
+    pub auto: bool,
+    /// Parameters typed in with Ctrl+E, appended to the command line for
+    /// this boot only.
+    pub extra_cmdline: Option<String>,
+}
+
+/// Display the boot menu and return the selected boot entry.
 ///
-/// Firmware / Shutdown selections never return — they call `uefi::runtime::reset`.
-pub fn show(cfg: &Config) -> usize {
+/// Firmware / Shutdown selections and `action` entries never return — they
+/// call `uefi::runtime::reset`.
+pub fn show(cfg: &Config, issues: &[Issue]) -> Choice {
     let total = total_items(cfg);
     if total == 0 {
         uefi::system::with_stdout(|out| {
             let _ = write!(out, "No boot entries found in config.\r\n");
+            for issue in issues {
+                let _ = write!(out, "{}\r\n", issue);
+            }
         });
         loop {
             uefi::boot::stall(Duration::from_secs(1));
         }
     }
 
-    let mut selected = cfg.default_entry_index().min(total - 1);
-    let mut timeout: Option<usize> = if cfg.timeout > 0 {
-        Some(cfg.timeout)
+    let mut selected = clamp_selection(cfg, item_for_entry(cfg, cfg.default_entry_index()));
+    let mut timeout = if cfg.timeout > 0 {
+        Countdown::Running(cfg.timeout)
     } else {
-        None
+        Countdown::Off
     };
-    let mut tick_count: usize = 0;
 
     uefi::system::with_stdout(|out| {
-        let _ = out.clear();
         let _ = out.enable_cursor(false);
     });
+    render::each(cfg.serial_menu, |out| out.clear());
 
-    draw(cfg, selected, timeout);
+    let sb = secureboot::state();
+    let mut view = View::new(issues);
+    view.render(cfg, selected, timeout, sb);
+    beep::cue(Cue::Menu);
 
-    loop {
-        uefi::boot::stall(Duration::from_millis(100));
+    // Index 0: one-second countdown timer; index 1 (if present): key event.
+    // Without a timer the loop polls, counting stalls up to each second.
+    let mut events: Vec<Event> = Vec::with_capacity(2);
+    if let Ok(timer) = unsafe { boot::create_event(EventType::TIMER, Tpl::APPLICATION, None, None) }
+    {
+        let _ = boot::set_timer(&timer, TimerTrigger::Periodic(COUNTDOWN_TICK_100NS));
+        events.push(timer);
+        if let Some(key_event) = keyboard::wait_event() {
+            events.push(key_event);
+        }
+    }
+    let mut polled = Duration::ZERO;
 
-        let key = uefi::system::with_stdin(|stdin| stdin.read_key());
+    let chosen = 'menu: loop {
+        let tick = if events.is_empty() {
+            boot::stall(POLL_INTERVAL);
+            polled += POLL_INTERVAL;
+            let second = polled >= Duration::from_secs(1);
+            if second {
+                polled = Duration::ZERO;
+            }
+            second
+        } else {
+            boot::wait_for_event(&mut events).unwrap_or(0) == 0
+        };
 
-        if let Ok(Some(key)) = key {
-            timeout = None;
+        while let Some(press) = keyboard::read() {
+            timeout = match (timeout, &press.key) {
+                (Countdown::Off, _) => Countdown::Off,
+                (_, Key::Special(ScanCode::ESCAPE)) => Countdown::Cancelled,
+                (Countdown::Cancelled, _) => Countdown::Cancelled,
+                _ => Countdown::Paused { idle: 0 },
+            };
 
-            match key {
-                Key::Special(ScanCode::UP) if selected > 0 => {
+            if press.is_enter() {
+                let selection = index_to_selection(cfg, selected);
+                let Selection::Submenu(first) = selection else {
+                    break 'menu (selection, false, None);
+                };
+                if let Some(idx) = submenu(cfg, first) {
+                    break 'menu (Selection::Entry(idx), false, None);
+                }
+                view.invalidate(cfg);
+                continue;
+            }
+            match (press.ctrl_letter(), &press.key) {
+                (Some('e'), _) => {
+                    if let Some(extra) = edit_params(cfg, selected) {
+                        let selection = index_to_selection(cfg, selected);
+                        break 'menu (selection, false, Some(extra));
+                    }
+                    view.invalidate(cfg);
+                }
+                (Some('l'), _) => {
+                    show_log(cfg);
+                    view.invalidate(cfg);
+                }
+                (_, Key::Printable(c)) if u16::from(*c) == 0x0009 => {
+                    show_plan(cfg, selected);
+                    view.invalidate(cfg);
+                }
+                (_, Key::Special(ScanCode::UP)) if selected > 0 => {
                     selected -= 1;
+                    beep::cue(Cue::Move);
                 }
-                Key::Special(ScanCode::DOWN) if selected < total - 1 => {
+                (_, Key::Special(ScanCode::DOWN)) if selected + 1 < total => {
                     selected += 1;
-                }
-                Key::Printable(c) if u16::from(c) == 0x000D => {
-                    return confirm(cfg, selected);
+                    beep::cue(Cue::Move);
                 }
                 _ => {}
             }
+        }
+
+        if tick {
+            timeout = match timeout {
+                // A submenu under the cursor boots the default entry, or its
+                // own first entry if there is none.
+                Countdown::Running(0) => match index_to_selection(cfg, selected) {
+                    Selection::Submenu(first) => {
+                        let default = cfg.default_entry_index();
+                        let idx = if default < cfg.entry.len() {
+                            default
+                        } else {
+                            first
+                        };
+                        break (Selection::Entry(idx), true, None);
+                    }
+                    selection => break (selection, true, None),
+                },
+                Countdown::Running(t) => Countdown::Running(t - 1),
+                Countdown::Paused { idle } if idle + 1 >= cfg.timeout_resume_secs => {
+                    Countdown::Running(cfg.timeout)
+                }
+                Countdown::Paused { idle } => Countdown::Paused { idle: idle + 1 },
+                other => other,
+            };
+        }
+
+        view.render(cfg, selected, timeout, sb);
+    };
+
+    if !events.is_empty() {
+        let timer = events.swap_remove(0);
+        let _ = boot::set_timer(&timer, TimerTrigger::Cancel);
+        let _ = boot::close_event(timer);
+    }
+
+    let (selection, auto, extra_cmdline) = chosen;
+    Choice {
+        index: confirm(cfg, selection),
+        auto,
+        extra_cmdline,
+    }
+}
+
+/// List the entries of the submenu holding `cfg.entry[first]` until one is
+/// picked with Enter, or Esc goes back to the top-level menu with `None`.
+fn submenu(cfg: &Config, first: usize) -> Option<usize> {
+    let name = cfg.entry[first].submenu.as_ref();
+    let members: Vec<usize> = (first..cfg.entry.len())
+        .filter(|&i| cfg.entry[i].submenu.as_ref() == name)
+        .collect();
+    let title = name.map_or("", |n| n.as_str());
+    let mut selected = 0;
+    let mut drawn = None;
+    render::each(cfg.serial_menu, |out| out.clear());
+    loop {
+        if drawn != Some(selected) {
+            render::each(cfg.serial_menu, |out| {
+                out.move_to(0, 0);
+                out.set_color(Color::White, Color::Black);
+                let _ = writeln!(out);
+                write_line(out, "  ", title);
+                let _ = writeln!(out);
+                for (n, &i) in members.iter().enumerate() {
+                    draw_item(out, n == selected, &cfg.entry[i].name);
+                }
+                out.set_color(Color::DarkGray, Color::Black);
+                let _ = write!(
+                    out,
+                    "\n  Up/Down to select, Enter to boot, Esc to go back\n"
+                );
+                out.set_color(Color::White, Color::Black);
+            });
+            drawn = Some(selected);
+        }
+        boot::stall(Duration::from_millis(10));
+        let Some(press) = keyboard::read() else {
+            continue;
+        };
+        if press.is_enter() {
+            return Some(members[selected]);
+        }
+        match press.key {
+            Key::Special(ScanCode::ESCAPE) => return None,
+            Key::Special(ScanCode::UP) if selected > 0 => {
+                selected -= 1;
+                beep::cue(Cue::Move);
+            }
+            Key::Special(ScanCode::DOWN) if selected + 1 < members.len() => {
+                selected += 1;
+                beep::cue(Cue::Move);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Ask for parameters to append to the selected entry's command line for
+/// this boot (Ctrl+E). `None` if cancelled, empty, or not a boot entry.
+fn edit_params(cfg: &Config, selected: usize) -> Option<String> {
+    let Selection::Entry(idx) = index_to_selection(cfg, selected) else {
+        return None;
+    };
+    let entry = &cfg.entry[idx];
+    entry.protocol?;
 
-            draw(cfg, selected, timeout);
+    uefi::system::with_stdout(|out| {
+        let _ = out.set_color(Color::White, Color::Black);
+        let _ = out.clear();
+        let _ = out.enable_cursor(true);
+    });
+    crate::println!("Editing \"{}\" for this boot; Esc cancels.", entry.name);
+    let extra = read_line("Append to the command line: ");
+    uefi::system::with_stdout(|out| {
+        let _ = out.enable_cursor(false);
+    });
+    extra
+        .map(|s| String::from(s.trim()))
+        .filter(|s| !s.is_empty())
+}
+
+/// Show what booting the selected entry would read, without reading it,
+/// until a key is pressed (Tab).
+fn show_plan(cfg: &Config, selected: usize) {
+    let Selection::Entry(idx) = index_to_selection(cfg, selected) else {
+        return;
+    };
+    let entry = &cfg.entry[idx];
+    uefi::system::with_stdout(|out| {
+        let _ = out.set_color(Color::White, Color::Black);
+        let _ = out.clear();
+    });
+    crate::println!("{}:", entry.name);
+    for line in download::plan(cfg, entry) {
+        crate::println!("  {}", line);
+    }
+    crate::println!();
+    crate::println!("Press any key to return to menu...");
+    while keyboard::read().is_none() {
+        boot::stall(Duration::from_millis(10));
+    }
+}
+
+/// Show the end of `audit_log` until a key is pressed (Ctrl+L).
+fn show_log(cfg: &Config) {
+    uefi::system::with_stdout(|out| {
+        let _ = out.set_color(Color::White, Color::Black);
+        let _ = out.clear();
+    });
+    match cfg.audit_log.as_deref() {
+        None => crate::println!("No audit_log is configured."),
+        Some(path) => {
+            match fsutil::open_esp_root().and_then(|mut root| fsutil::read_file(&mut root, path)) {
+                Ok(data) => {
+                    let text = String::from_utf8_lossy(&data);
+                    let lines: Vec<&str> = text.lines().collect();
+                    crate::println!("{}:", path);
+                    for line in &lines[lines.len().saturating_sub(LOG_LINES)..] {
+                        crate::println!("{}", line);
+                    }
+                }
+                Err(e) => crate::println!("{}: {:?}", path, e.status()),
+            }
+        }
+    }
+    crate::println!();
+    crate::println!("Press any key to return to menu...");
+    while keyboard::read().is_none() {
+        boot::stall(Duration::from_millis(10));
+    }
+}
+
+/// Read a line from the console without echoing it, for passphrases and
+/// keys. Enter finishes, Backspace deletes, Esc aborts with `None`.
+pub fn read_secret(prompt: &str) -> Option<String> {
+    read_input(prompt, false)
+}
+
+/// Like [`read_secret`], but echoing what is typed.
+pub fn read_line(prompt: &str) -> Option<String> {
+    read_input(prompt, true)
+}
+
+fn read_input(prompt: &str, echo: bool) -> Option<String> {
+    crate::print!("{}", prompt);
+    let mut s = String::new();
+    loop {
+        uefi::boot::stall(Duration::from_millis(10));
+        let Some(KeyPress { key, .. }) = keyboard::read() else {
+            continue;
+        };
+        match key {
+            Key::Printable(c) if u16::from(c) == 0x000D => {
+                crate::println!();
+                return Some(s);
+            }
+            Key::Printable(c) if u16::from(c) == 0x0008 => {
+                if s.pop().is_some() && echo {
+                    crate::print!("\u{8} \u{8}");
+                }
+            }
+            Key::Printable(c) => {
+                s.push(char::from(c));
+                if echo {
+                    crate::print!("{}", char::from(c));
+                }
+            }
+            Key::Special(ScanCode::ESCAPE) => {
+                crate::println!();
+                return None;
+            }
+            _ => {}
         }
+    }
+}
 
-        tick_count += 1;
-        if tick_count >= 10 {
-            tick_count = 0;
-            if let Some(ref mut t) = timeout {
-                if *t == 0 {
-                    return confirm(cfg, selected);
+/// Offer `options`, each a key and what it does, and wait for one of those
+/// keys. Esc gives `None`.
+pub fn choose(options: &[(char, &str)]) -> Option<char> {
+    let mut line = String::new();
+    for (key, what) in options {
+        let _ = write!(line, "  [{}] {}", key, what);
+    }
+    crate::println!("{}", line);
+    loop {
+        uefi::boot::stall(Duration::from_millis(10));
+        let Some(KeyPress { key, .. }) = keyboard::read() else {
+            continue;
+        };
+        match key {
+            Key::Printable(c) => {
+                let c = char::from(c).to_ascii_lowercase();
+                if options.iter().any(|(key, _)| *key == c) {
+                    return Some(c);
                 }
-                *t -= 1;
-                draw(cfg, selected, timeout);
             }
+            Key::Special(ScanCode::ESCAPE) => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Ask for the entry's passphrase if it has a `password_hash`
+/// (`sha256:<hex>` of the passphrase). Allows three attempts.
+pub fn check_password(entry: &Entry) -> bool {
+    let Some(expected) = entry.password_hash.as_deref() else {
+        return true;
+    };
+    let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
+
+    for _ in 0..3 {
+        let Some(input) = read_secret("Password: ") else {
+            return false;
+        };
+        let got = sha256::to_hex(&sha256::digest(input.as_bytes()));
+        if got.eq_ignore_ascii_case(expected.trim()) {
+            return true;
         }
+        crate::println!("Incorrect password.");
     }
+    false
 }
 
-/// Act on the current selection. Returns the boot-entry index if it's an
-/// `Entry`; firmware/shutdown paths diverge and never return.
-fn confirm(cfg: &Config, selected: usize) -> usize {
-    match index_to_selection(cfg, selected) {
-        Selection::Entry(idx) => {
-            uefi::system::with_stdout(|out| {
-                let _ = out.set_color(Color::White, Color::Black);
-                let _ = out.clear();
+/// Act on the chosen item. Returns the boot-entry index if it's a
+/// bootable `Entry`; firmware/shutdown/action paths diverge and never return.
+fn confirm(cfg: &Config, selection: Selection) -> usize {
+    match selection {
+        Selection::Entry(idx) | Selection::Submenu(idx) => {
+            // `self-update` is carried out by the caller, like a boot.
+            if let Some(action) = cfg.entry[idx].action
+                && action != Action::SelfUpdate
+            {
+                run_action(action);
+            }
+            render::each(cfg.serial_menu, |out| {
+                out.set_color(Color::White, Color::Black);
+                out.clear();
                 let _ = write!(out, "Booting {}...\n", cfg.entry[idx].name);
             });
             idx
         }
-        Selection::Firmware => reboot_to_firmware(),
-        Selection::Shutdown => {
-            uefi::runtime::reset(ResetType::SHUTDOWN, uefi::Status::SUCCESS, None);
-        }
+        Selection::Firmware => run_action(Action::Firmware),
+        Selection::Shutdown => run_action(Action::Shutdown),
+    }
+}
+
+/// Carry out one of the reset actions; `self-update` is not one of them.
+pub fn run_action(action: Action) -> ! {
+    match action {
+        Action::Reboot => uefi::runtime::reset(ResetType::WARM, uefi::Status::SUCCESS, None),
+        Action::ColdReset => uefi::runtime::reset(ResetType::COLD, uefi::Status::SUCCESS, None),
+        Action::Shutdown => uefi::runtime::reset(ResetType::SHUTDOWN, uefi::Status::SUCCESS, None),
+        Action::Firmware => reboot_to_firmware(),
+        Action::Exit => exit_to_firmware_boot_manager(),
+        Action::SelfUpdate => unreachable!("self-update is not a reset"),
+    }
+}
+
+/// Return control to the firmware boot manager, which continues with the
+/// next `BootOrder` entry.
+fn exit_to_firmware_boot_manager() -> ! {
+    uefi::system::with_stdout(|out| {
+        let _ = out.set_color(Color::White, Color::Black);
+        let _ = out.clear();
+        let _ = out.enable_cursor(true);
+    });
+    unsafe {
+        boot::exit(
+            boot::image_handle(),
+            uefi::Status::SUCCESS,
+            0,
+            core::ptr::null_mut(),
+        )
     }
 }
 
@@ -134,67 +561,264 @@ fn reboot_to_firmware() -> ! {
     uefi::runtime::reset(ResetType::COLD, uefi::Status::SUCCESS, None);
 }
 
-fn draw(cfg: &Config, selected: usize, timeout: Option<usize>) {
-    uefi::system::with_stdout(|out| {
-        let _ = out.set_cursor_position(0, 0);
+/// Rows used by the title block above the first item.
+const HEADER_ROWS: usize = 3;
 
-        let _ = out.set_color(Color::White, Color::Black);
-        let _ = write!(out, "\n");
-        let _ = write!(out, "  Alpheratz Boot Loader\n");
-        let _ = write!(out, "\n");
+fn item_label(cfg: &Config, idx: usize) -> String {
+    match index_to_selection(cfg, idx) {
+        Selection::Entry(i) => cfg.entry[i].name.clone(),
+        Selection::Submenu(i) => {
+            alloc::format!("{} >", cfg.entry[i].submenu.as_deref().unwrap_or(""))
+        }
+        Selection::Firmware => String::from("UEFI Firmware Settings"),
+        Selection::Shutdown => String::from("Shutdown"),
+    }
+}
 
-        let mut row: usize = 0;
+/// Screen row of menu item `idx`, accounting for the blank separator line
+/// between boot entries and the firmware/shutdown items.
+fn item_row(cfg: &Config, idx: usize) -> usize {
+    let entries = entry_items(cfg);
+    let gap = (idx >= entries && entries > 0) as usize;
+    HEADER_ROWS + idx + gap
+}
+
+fn countdown_row(cfg: &Config) -> usize {
+    item_row(cfg, total_items(cfg) - 1) + 2
+}
 
-        for (i, entry) in cfg.entry.iter().enumerate() {
-            draw_item(out, i == selected, &entry.name);
-            row += 1;
+fn detail_row(cfg: &Config) -> usize {
+    countdown_row(cfg) + 1
+}
+
+/// Below the detail line come a blank line, the key help and the Secure
+/// Boot state.
+fn footer_row(cfg: &Config) -> usize {
+    detail_row(cfg) + 4
+}
+
+/// Wall clock, machine identity, firmware and loader version, so a photo
+/// of the screen says what it was taken of.
+fn footer(cfg: &Config) -> String {
+    let mut line = String::new();
+    if let Ok(t) = uefi::runtime::get_time() {
+        let _ = write!(
+            line,
+            "{:04}-{:02}-{:02} {:02}:{:02}  ",
+            t.year(),
+            t.month(),
+            t.day(),
+            t.hour(),
+            t.minute()
+        );
+    }
+    if let Some(identity) = &cfg.identity {
+        for part in [&identity.hostname, &identity.uuid].into_iter().flatten() {
+            let _ = write!(line, "{}  ", part);
         }
+    }
+    let _ = write!(
+        line,
+        "{} {:#x}  Alpheratz {}",
+        uefi::system::firmware_vendor(),
+        uefi::system::firmware_revision(),
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Some(commit) = option_env!("ALPHERATZ_COMMIT").filter(|c| !c.is_empty()) {
+        let _ = write!(line, " ({})", commit);
+    }
+    line
+}
 
-        if (cfg.firmware || cfg.shutdown) && !cfg.entry.is_empty() {
-            let _ = write!(out, "\n");
+/// The selected entry's `os`, `version` and `description`, if it has any,
+/// or the size of the selected submenu.
+fn item_detail(cfg: &Config, idx: usize) -> String {
+    let i = match index_to_selection(cfg, idx) {
+        Selection::Entry(i) => i,
+        Selection::Submenu(i) => {
+            let name = &cfg.entry[i].submenu;
+            let count = cfg.entry.iter().filter(|e| &e.submenu == name).count();
+            return alloc::format!("{} entries", count);
+        }
+        _ => return String::new(),
+    };
+    let entry = &cfg.entry[i];
+    let mut detail = String::new();
+    for part in [&entry.os, &entry.version].into_iter().flatten() {
+        if !detail.is_empty() {
+            detail.push(' ');
         }
+        detail.push_str(part);
+    }
+    if let Some(description) = &entry.description {
+        if !detail.is_empty() {
+            detail.push_str(" - ");
+        }
+        detail.push_str(description);
+    }
+    detail
+}
+
+/// Remembers what is on screen so that only changed rows are rewritten.
+struct View<'a> {
+    drawn: bool,
+    selected: usize,
+    timeout: Countdown,
+    footer: String,
+    issues: &'a [Issue],
+}
 
-        if cfg.firmware {
-            draw_item(out, row == selected, "UEFI Firmware Settings");
-            row += 1;
+impl<'a> View<'a> {
+    fn new(issues: &'a [Issue]) -> Self {
+        View {
+            drawn: false,
+            selected: 0,
+            timeout: Countdown::Off,
+            footer: String::new(),
+            issues,
         }
-        if cfg.shutdown {
-            draw_item(out, row == selected, "Shutdown");
-            #[allow(unused_assignments)]
-            {
-                row += 1;
-            }
+    }
+
+    /// Forget what is on screen after something else drew over it.
+    fn invalidate(&mut self, cfg: &Config) {
+        render::each(cfg.serial_menu, |out| out.clear());
+        self.drawn = false;
+    }
+
+    fn render(&mut self, cfg: &Config, selected: usize, timeout: Countdown, sb: secureboot::State) {
+        let footer = footer(cfg);
+        if !self.drawn {
+            draw(cfg, selected, timeout, sb, &footer, self.issues);
+        } else {
+            render::each(cfg.serial_menu, |out| {
+                if selected != self.selected {
+                    for (idx, is_selected) in [(self.selected, false), (selected, true)] {
+                        out.move_to(0, item_row(cfg, idx));
+                        draw_item(out, is_selected, &item_label(cfg, idx));
+                    }
+                    out.move_to(0, detail_row(cfg));
+                    draw_detail(out, &item_detail(cfg, selected));
+                }
+                if timeout != self.timeout {
+                    out.move_to(0, countdown_row(cfg));
+                    draw_countdown(out, timeout);
+                }
+                if footer != self.footer {
+                    out.move_to(0, footer_row(cfg));
+                    draw_footer(out, &footer);
+                }
+                out.set_color(Color::White, Color::Black);
+            });
         }
 
-        let _ = out.set_color(Color::LightGray, Color::Black);
+        self.drawn = true;
+        self.selected = selected;
+        self.timeout = timeout;
+        self.footer = footer;
+    }
+}
+
+fn draw(
+    cfg: &Config,
+    selected: usize,
+    timeout: Countdown,
+    sb: secureboot::State,
+    footer: &str,
+    issues: &[Issue],
+) {
+    render::each(cfg.serial_menu, |out| {
+        out.move_to(0, 0);
+
+        out.set_color(Color::White, Color::Black);
+        let _ = write!(out, "\n");
+        let _ = write!(out, "  Alpheratz Boot Loader\n");
         let _ = write!(out, "\n");
 
-        match timeout {
-            Some(secs) => {
-                let _ = write!(
-                    out,
-                    "  Auto boot in {}s...                              \n",
-                    secs
-                );
-            }
-            None => {
-                let _ = write!(out, "                                                   \n");
+        let entries = entry_items(cfg);
+        for idx in 0..total_items(cfg) {
+            if idx == entries && idx > 0 {
+                let _ = writeln!(out);
             }
+            draw_item(out, idx == selected, &item_label(cfg, idx));
         }
 
-        let _ = out.set_color(Color::DarkGray, Color::Black);
-        let _ = write!(out, "\n  Up/Down to select, Enter to boot\n");
-        let _ = out.set_color(Color::White, Color::Black);
+        out.set_color(Color::LightGray, Color::Black);
+        let _ = write!(out, "\n");
+        draw_countdown(out, timeout);
+        draw_detail(out, &item_detail(cfg, selected));
+
+        out.set_color(Color::DarkGray, Color::Black);
+        let _ = write!(
+            out,
+            "\n  Up/Down to select, Enter to boot, Tab: details, Ctrl+E to edit, Ctrl+L: log\n"
+        );
+        let _ = writeln!(out, "  Secure Boot: {:<20}", sb);
+        draw_footer(out, footer);
+        draw_issues(out, issues);
+        out.set_color(Color::White, Color::Black);
     });
 }
 
-fn draw_item(out: &mut uefi::proto::console::text::Output, is_selected: bool, label: &str) {
+/// Config problems found at load, so they are seen before booting fails.
+fn draw_issues(out: &mut dyn Renderer, issues: &[Issue]) {
+    if issues.is_empty() {
+        return;
+    }
+    let _ = writeln!(out);
+    for issue in issues.iter().take(FOOTER_ISSUES) {
+        let color = match issue.severity {
+            Severity::Error => Color::LightRed,
+            Severity::Warning => Color::Yellow,
+        };
+        out.set_color(color, Color::Black);
+        let mut line = String::new();
+        let _ = write!(line, "{}", issue);
+        write_line(out, "  ", &line);
+    }
+    if issues.len() > FOOTER_ISSUES {
+        out.set_color(Color::DarkGray, Color::Black);
+        let more = issues.len() - FOOTER_ISSUES;
+        let _ = writeln!(out, "  ... and {} more; run with --check to list all", more);
+    }
+}
+
+fn draw_countdown(out: &mut dyn Renderer, timeout: Countdown) {
+    out.set_color(Color::LightGray, Color::Black);
+    let text = match timeout {
+        Countdown::Running(secs) => alloc::format!("Auto boot in {}s...", secs),
+        Countdown::Paused { .. } => String::from("Auto boot paused (Esc to cancel)"),
+        Countdown::Cancelled => String::from("Auto boot cancelled"),
+        Countdown::Off => String::new(),
+    };
+    write_line(out, "  ", &text);
+}
+
+fn draw_footer(out: &mut dyn Renderer, footer: &str) {
+    out.set_color(Color::DarkGray, Color::Black);
+    write_line(out, "  ", footer);
+}
+
+fn draw_detail(out: &mut dyn Renderer, detail: &str) {
+    out.set_color(Color::Cyan, Color::Black);
+    write_line(out, "  ", detail);
+}
+
+fn draw_item(out: &mut dyn Renderer, is_selected: bool, label: &str) {
     if is_selected {
-        let _ = out.set_color(Color::White, Color::Blue);
-        let _ = write!(out, "  > {:<66}\n", label);
-        let _ = out.set_color(Color::White, Color::Black);
+        out.set_color(Color::White, Color::Blue);
+        write_line(out, "  > ", label);
+        out.set_color(Color::White, Color::Black);
     } else {
-        let _ = out.set_color(Color::LightGray, Color::Black);
-        let _ = write!(out, "    {:<66}\n", label);
+        out.set_color(Color::LightGray, Color::Black);
+        write_line(out, "    ", label);
     }
 }
+
+/// Write `text` after `indent`, cut or padded to one cell short of the
+/// screen width: padding overwrites whatever was there before, and leaving
+/// the last column empty keeps consoles from wrapping onto the next row.
+fn write_line(out: &mut dyn Renderer, indent: &str, text: &str) {
+    let width = out.columns().saturating_sub(indent.len() + 1);
+    let text: String = text.chars().take(width).collect();
+    let _ = writeln!(out, "{}{:<width$}", indent, text, width = width);
+}
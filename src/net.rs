@@ -3,19 +3,32 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use core::ffi::c_void;
 use core::fmt::Write;
 
+use uefi::Event;
 use uefi::Identify;
-use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::boot::{self, EventType, OpenProtocolAttributes, OpenProtocolParams, TimerTrigger, Tpl};
 use uefi::prelude::*;
+use uefi::proto::device_path::{DevicePath, DeviceSubType, DeviceType};
 use uefi::proto::network::ip4config2::Ip4Config2;
 use uefi::proto::network::snp::SimpleNetwork;
-use uefi_raw::protocol::network::ip4_config2::Ip4Config2DataType;
+use uefi::proto::unsafe_protocol;
+use uefi::runtime::{Daylight, Time, TimeParams};
+use uefi_raw::protocol::network::ip4_config2::{Ip4Config2DataType, Ip4Config2Policy};
 
-use crate::config::{Config, NetworkType};
+use crate::config::{Config, Network, NetworkType};
+use crate::deadline::Deadline;
+use crate::error::{self, AlpheratzError, Phase};
+use crate::wifi;
+
+use alpheratz_core::vars::parse_ipv4;
+pub use alpheratz_core::vars::{Lease, ipv4_to_string};
 
 /// Open a protocol with GET_PROTOCOL attribute — does not affect driver binding.
-unsafe fn open_snp_readonly(handle: Handle) -> uefi::Result<boot::ScopedProtocol<SimpleNetwork>> {
+pub unsafe fn open_snp_readonly(
+    handle: Handle,
+) -> uefi::Result<boot::ScopedProtocol<SimpleNetwork>> {
     unsafe {
         boot::open_protocol::<SimpleNetwork>(
             OpenProtocolParams {
@@ -47,14 +60,14 @@ fn parse_mac(s: &str) -> Option<[u8; 6]> {
     Some(out)
 }
 
-fn snp_mac6(snp: &SimpleNetwork) -> [u8; 6] {
+pub fn snp_mac6(snp: &SimpleNetwork) -> [u8; 6] {
     let mac = snp.mode().current_address;
     let mut out = [0u8; 6];
     out.copy_from_slice(&mac.0[0..6]);
     out
 }
 
-fn mac_to_string(mac: [u8; 6]) -> String {
+pub fn mac_to_string(mac: [u8; 6]) -> String {
     let mut s = String::with_capacity(17);
     for (i, b) in mac.iter().enumerate() {
         if i > 0 {
@@ -65,17 +78,23 @@ fn mac_to_string(mac: [u8; 6]) -> String {
     s
 }
 
-fn locate_snp_handles() -> uefi::Result<Vec<Handle>> {
+pub fn locate_snp_handles() -> uefi::Result<Vec<Handle>> {
     let handles = boot::locate_handle_buffer(boot::SearchType::ByProtocol(&SimpleNetwork::GUID))?;
     Ok(handles.to_vec())
 }
 
-pub fn select_nic_handle(cfg: &Config) -> uefi::Result<Handle> {
+fn media_absent(snp: &SimpleNetwork) -> bool {
+    let mode = snp.mode();
+    bool::from(mode.media_present_supported) && !bool::from(mode.media_present)
+}
+
+/// Return every NIC worth trying, in order: the one bound by `network.bind`
+/// first, then wireless interfaces when `[network.wifi]` is set, then the
+/// remaining (wired) interfaces. Wired interfaces that report no media
+/// present yet come last, left to [`wait_for_link`], since autonegotiation
+/// can take seconds after the driver starts.
+pub fn candidate_nic_handles(cfg: &Config) -> uefi::Result<Vec<Handle>> {
     let handles = locate_snp_handles()?;
-    if handles.is_empty() {
-        return Err(uefi::Error::from(Status::NOT_FOUND));
-    }
-    let fallback = handles[0];
 
     let want = cfg
         .network
@@ -83,18 +102,41 @@ pub fn select_nic_handle(cfg: &Config) -> uefi::Result<Handle> {
         .and_then(|n| n.bind.as_deref())
         .and_then(parse_mac);
 
-    if let Some(want) = want {
-        for &h in handles.iter() {
-            if let Ok(snp) = unsafe { open_snp_readonly(h) } {
-                if snp_mac6(&snp) == want {
-                    return Ok(h);
-                }
+    let has_wifi = cfg.network.as_ref().is_some_and(|n| n.wifi.is_some());
+
+    let mut bound: Vec<Handle> = Vec::new();
+    let mut wireless: Vec<Handle> = Vec::new();
+    let mut rest: Vec<Handle> = Vec::new();
+    let mut no_link: Vec<Handle> = Vec::new();
+
+    for &h in handles.iter() {
+        if wifi::is_wireless(h) {
+            if has_wifi {
+                wireless.push(h);
             }
+            continue;
+        }
+        let Ok(snp) = (unsafe { open_snp_readonly(h) }) else {
+            rest.push(h);
+            continue;
+        };
+        if want == Some(snp_mac6(&snp)) {
+            bound.push(h);
+        } else if media_absent(&snp) {
+            crate::println!("NIC {}: no link yet", mac_to_string(snp_mac6(&snp)));
+            no_link.push(h);
+        } else {
+            rest.push(h);
         }
-        Ok(fallback)
-    } else {
-        Ok(fallback)
     }
+
+    bound.extend(wireless);
+    bound.extend(rest);
+    bound.extend(no_link);
+    if bound.is_empty() {
+        return Err(uefi::Error::from(Status::NOT_FOUND));
+    }
+    Ok(bound)
 }
 
 /// Recursively connect all controllers so higher-level network drivers get
@@ -114,7 +156,7 @@ fn open_ip4config2(preferred: Handle) -> uefi::Result<boot::ScopedProtocol<Ip4Co
     }
 
     let handles = boot::locate_handle_buffer(boot::SearchType::ByProtocol(&Ip4Config2::GUID))?;
-    uefi::println!("  Ip4Config2 handles found: {}", handles.len());
+    crate::println!("  Ip4Config2 handles found: {}", handles.len());
 
     for &h in handles.iter() {
         if let Ok(p) = Ip4Config2::new(h) {
@@ -125,15 +167,716 @@ fn open_ip4config2(preferred: Handle) -> uefi::Result<boot::ScopedProtocol<Ip4Co
     Err(uefi::Error::from(Status::NOT_FOUND))
 }
 
+const DEFAULT_LINK_TIMEOUT_SECS: usize = 5;
+
+/// Poll the SNP media-present flag until the PHY reports link or
+/// `network.link_timeout_secs` elapses. Returns whether link came up.
+fn wait_for_link(cfg: &Config, nic: Handle) -> bool {
+    let Ok(snp) = (unsafe { open_snp_readonly(nic) }) else {
+        return true;
+    };
+    if !bool::from(snp.mode().media_present_supported) {
+        return true;
+    }
+
+    let timeout = cfg
+        .network
+        .as_ref()
+        .and_then(|n| n.link_timeout_secs)
+        .unwrap_or(DEFAULT_LINK_TIMEOUT_SECS);
+    let ticks = timeout * 10;
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+    for tick in 0..=ticks {
+        // GetStatus refreshes MediaPresent on drivers that track it lazily.
+        let _ = snp.get_interrupt_status();
+        if bool::from(snp.mode().media_present) {
+            if tick > 0 {
+                crate::println!("\r  Link up.            ");
+            }
+            return true;
+        }
+        crate::print!("\r  Waiting for link... {}", SPINNER[tick % SPINNER.len()]);
+        boot::stall(core::time::Duration::from_millis(100));
+    }
+
+    crate::println!("\r  No link after {}s.   ", timeout);
+    false
+}
+
+/// EFI_VLAN_CONFIG_PROTOCOL, installed by MNP on the NIC controller handle.
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("9e23d768-d2f3-4366-9fc3-3a7aba864374")]
+struct VlanConfig {
+    set: unsafe extern "efiapi" fn(this: *mut VlanConfig, vlan_id: u16, priority: u8) -> Status,
+    find: unsafe extern "efiapi" fn(
+        this: *mut VlanConfig,
+        vlan_id: *const u16,
+        number_of_vlan: *mut u16,
+        entries: *mut *mut c_void,
+    ) -> Status,
+    remove: unsafe extern "efiapi" fn(this: *mut VlanConfig, vlan_id: u16) -> Status,
+}
+
+fn device_path_vlan_id(handle: Handle) -> Option<u16> {
+    let dp = unsafe {
+        boot::open_protocol::<DevicePath>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+
+    dp.node_iter()
+        .find(|n| {
+            n.device_type() == DeviceType::MESSAGING
+                && n.sub_type() == DeviceSubType::MESSAGING_VLAN
+        })
+        .and_then(|n| n.data().get(0..2).map(|b| u16::from_le_bytes([b[0], b[1]])))
+}
+
+/// Create (or reuse) a tagged VLAN child on `nic` and return the child handle
+/// carrying the IP stack for that VLAN.
+fn configure_vlan(nic: Handle, vlan_id: u16) -> uefi::Result<Handle> {
+    crate::println!("  VLAN: {}", vlan_id);
+
+    let mut vlan = unsafe {
+        boot::open_protocol::<VlanConfig>(
+            OpenProtocolParams {
+                handle: nic,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .inspect_err(|e| crate::println!("  VlanConfig not available: {:?}", e.status()))?;
+
+    let this: *mut VlanConfig = &mut *vlan;
+    let status = unsafe { (vlan.set)(this, vlan_id, 0) };
+    if status.is_error() {
+        crate::println!("  VlanConfig.Set failed: {:?}", status);
+        return Err(uefi::Error::from(status));
+    }
+    drop(vlan);
+
+    let _ = boot::connect_controller(nic, None, None, true);
+    connect_all_controllers();
+
+    let handles = boot::locate_handle_buffer(boot::SearchType::ByProtocol(&Ip4Config2::GUID))?;
+    handles
+        .iter()
+        .copied()
+        .find(|&h| device_path_vlan_id(h) == Some(vlan_id))
+        .ok_or_else(|| {
+            crate::println!("  No IP stack on VLAN {}", vlan_id);
+            uefi::Error::from(Status::NOT_FOUND)
+        })
+}
+
 fn count_protocol_handles(guid: &uefi::Guid) -> usize {
     boot::locate_handle_buffer(boot::SearchType::ByProtocol(guid))
         .map(|h| h.len())
         .unwrap_or(0)
 }
 
-pub fn bring_up_ipv4(cfg: &Config, nic: Handle) -> uefi::Result<()> {
+/// Parse `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS` (a trailing `Z` is ignored).
+fn parse_assume_time(s: &str) -> Option<Time> {
+    let s = s.trim().trim_end_matches('Z');
+    let (date, time) = s.split_once(['T', ' ']).unwrap_or((s, "00:00:00"));
+
+    let mut d = date.splitn(3, '-');
+    let year = d.next()?.parse::<u16>().ok()?;
+    let month = d.next()?.parse::<u8>().ok()?;
+    let day = d.next()?.parse::<u8>().ok()?;
+    let mut t = time.splitn(3, ':').map(|p| p.parse::<u8>().ok());
+    let (hour, minute, second) = (t.next()??, t.next()??, t.next().unwrap_or(Some(0))?);
+
+    Time::new(TimeParams {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        nanosecond: 0,
+        time_zone: None,
+        daylight: Daylight::empty(),
+    })
+    .ok()
+}
+
+fn time_key(t: &Time) -> (u16, u8, u8, u8, u8, u8) {
+    (
+        t.year(),
+        t.month(),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second(),
+    )
+}
+
+/// Move the RTC forward to `network.assume_time` if it reads earlier than
+/// that, so TLS certificates don't look "not yet valid" on boards with a
+/// dead RTC battery.
+pub fn sync_clock(cfg: &Config) {
+    let Some(raw) = cfg.network.as_ref().and_then(|n| n.assume_time.as_deref()) else {
+        return;
+    };
+    let Some(assumed) = parse_assume_time(raw) else {
+        crate::println!("  Invalid assume_time: {}", raw);
+        return;
+    };
+
+    let behind = match uefi::runtime::get_time() {
+        Ok(now) => time_key(&now) < time_key(&assumed),
+        Err(_) => true,
+    };
+    if !behind {
+        return;
+    }
+
+    crate::println!("Clock is behind, setting time to {}", raw);
+    if let Err(e) = unsafe { uefi::runtime::set_time(&assumed) } {
+        crate::println!("  SetTime failed: {:?}", e.status());
+    }
+}
+
+/// EFI_DHCP4_CONFIG_DATA
+#[repr(C)]
+struct Dhcp4ConfigData {
+    discover_try_count: u32,
+    discover_timeout: *mut u32,
+    request_try_count: u32,
+    request_timeout: *mut u32,
+    client_address: [u8; 4],
+    callback: *const c_void,
+    callback_context: *mut c_void,
+    option_count: u32,
+    option_list: *mut *mut c_void,
+}
+
+/// EFI_DHCP4_MODE_DATA
+#[repr(C)]
+struct Dhcp4ModeData {
+    state: u32,
+    config_data: Dhcp4ConfigData,
+    client_address: [u8; 4],
+    client_mac_address: [u8; 32],
+    server_address: [u8; 4],
+    router_address: [u8; 4],
+    subnet_mask: [u8; 4],
+    lease_time: u32,
+    reply_packet: *mut c_void,
+}
+
+/// EFI_DHCP4_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("8a219718-4ef5-4761-91c8-c0f04bda9e56")]
+struct Dhcp4 {
+    get_mode_data: unsafe extern "efiapi" fn(this: *mut Dhcp4, data: *mut Dhcp4ModeData) -> Status,
+    configure: *const c_void,
+    start: *const c_void,
+    renew_rebind: unsafe extern "efiapi" fn(
+        this: *mut Dhcp4,
+        rebind_request: bool,
+        completion_event: *mut c_void,
+    ) -> Status,
+    release: *const c_void,
+    stop: *const c_void,
+    build: *const c_void,
+    transmit_receive: *const c_void,
+    parse: *const c_void,
+}
+
+fn mode_data(dhcp: &mut Dhcp4) -> Option<Dhcp4ModeData> {
+    let this: *mut Dhcp4 = dhcp;
+    let mut data: Dhcp4ModeData = unsafe { core::mem::zeroed() };
+    unsafe { (dhcp.get_mode_data)(this, &mut data) }
+        .to_result()
+        .ok()?;
+    Some(data)
+}
+
+/// The DHCP4 child holding the lease on `ip` (the one Ip4Config2 drives
+/// among them), found by asking each for its mode data.
+fn dhcp_child(ip: [u8; 4]) -> Option<boot::ScopedProtocol<Dhcp4>> {
+    let handles = boot::locate_handle_buffer(boot::SearchType::ByProtocol(&Dhcp4::GUID)).ok()?;
+    handles.iter().find_map(|&handle| {
+        let mut dhcp = unsafe {
+            boot::open_protocol::<Dhcp4>(
+                OpenProtocolParams {
+                    handle,
+                    agent: boot::image_handle(),
+                    controller: None,
+                },
+                OpenProtocolAttributes::GetProtocol,
+            )
+        }
+        .ok()?;
+        let data = mode_data(&mut dhcp)?;
+        (data.client_address == ip && data.server_address != [0; 4]).then_some(dhcp)
+    })
+}
+
+/// A DHCP lease time in seconds, `None` when infinite.
+fn lease_secs(lease_time: u32) -> Option<u32> {
+    (lease_time != u32::MAX).then_some(lease_time)
+}
+
+/// Renew the DHCP lease on `lease.ip` with the server that granted it and
+/// update its duration. Returns whether the server extended it.
+pub fn renew(lease: &mut Lease) -> bool {
+    let Some(mut dhcp) = lease.ip.and_then(dhcp_child) else {
+        return false;
+    };
+    let this: *mut Dhcp4 = &mut *dhcp;
+    // Without a completion event the call returns once the exchange is over.
+    let status = unsafe { (dhcp.renew_rebind)(this, false, core::ptr::null_mut()) };
+    if status.is_error() {
+        crate::println!("  DHCP renewal failed: {:?}", status);
+        return false;
+    }
+    if let Some(data) = mode_data(&mut dhcp) {
+        lease.lease_secs = lease_secs(data.lease_time);
+    }
+    true
+}
+
+/// Signals halfway through a DHCP lease (T1 in RFC 2131), when it is due
+/// for renewal. Never signals for static addresses and infinite leases.
+pub struct RenewTimer {
+    event: Option<Event>,
+}
+
+impl RenewTimer {
+    pub fn start(lease: &Lease) -> Self {
+        let event = lease.lease_secs.filter(|&s| s > 1).and_then(|s| {
+            let e =
+                unsafe { boot::create_event(EventType::TIMER, Tpl::CALLBACK, None, None) }.ok()?;
+            boot::set_timer(&e, TimerTrigger::Relative(s as u64 / 2 * 10_000_000)).ok()?;
+            Some(e)
+        });
+        RenewTimer { event }
+    }
+
+    pub fn due(&self) -> bool {
+        self.event
+            .as_ref()
+            .is_some_and(|e| boot::check_event(unsafe { e.unsafe_clone() }).unwrap_or(false))
+    }
+}
+
+impl Drop for RenewTimer {
+    fn drop(&mut self) {
+        if let Some(e) = self.event.take() {
+            let _ = boot::close_event(e);
+        }
+    }
+}
+
+fn first_ipv4(data: &[u8]) -> Option<[u8; 4]> {
+    data.get(0..4).map(|b| [b[0], b[1], b[2], b[3]])
+}
+
+fn read_lease(ip4: &mut Ip4Config2) -> Lease {
+    let mut lease = Lease::default();
+    if let Ok(info) = ip4.get_interface_info() {
+        lease.ip = Some(info.station_addr.0);
+        lease.netmask = Some(info.subnet_mask.0);
+    }
+    if let Ok(gw) = ip4.get_data(Ip4Config2DataType::GATEWAY) {
+        lease.gateway = first_ipv4(&gw);
+    }
+    if let Ok(dns) = ip4.get_data(Ip4Config2DataType::DNS_SERVER) {
+        lease.dns = dns.chunks_exact(4).filter_map(first_ipv4).collect();
+    }
+    if let Some(data) = lease
+        .ip
+        .and_then(dhcp_child)
+        .and_then(|mut d| mode_data(&mut d))
+    {
+        lease.dhcp_server = Some(data.server_address);
+        lease.lease_secs = lease_secs(data.lease_time);
+    }
+    lease
+}
+
+fn print_lease(lease: &Lease) {
+    if let Some(a) = lease.ip {
+        crate::println!("  IP:      {}", ipv4_to_string(a));
+    }
+    if let Some(a) = lease.netmask {
+        crate::println!("  Netmask: {}", ipv4_to_string(a));
+    }
+    if let Some(a) = lease.gateway {
+        crate::println!("  Gateway: {}", ipv4_to_string(a));
+    }
+    for &a in &lease.dns {
+        crate::println!("  DNS:     {}", ipv4_to_string(a));
+    }
+    if let Some(a) = lease.dhcp_server {
+        crate::println!("  DHCP:    {}", ipv4_to_string(a));
+    }
+    if let Some(s) = lease.lease_secs {
+        crate::println!("  Lease:   {}s", s);
+    }
+    crate::println!("IPv4 ready.");
+}
+
+/// The addresses of a `type = "static"` network section.
+fn static_lease(net: &Network) -> uefi::Result<Lease> {
+    fn addr(name: &str, value: &str) -> uefi::Result<[u8; 4]> {
+        parse_ipv4(value).ok_or_else(|| {
+            crate::println!("  network.{} is not an IPv4 address: {}", name, value);
+            uefi::Error::from(Status::INVALID_PARAMETER)
+        })
+    }
+    let (Some(ip), Some(netmask)) = (net.address.as_deref(), net.netmask.as_deref()) else {
+        crate::println!("  Static network needs both network.address and network.netmask");
+        return Err(uefi::Error::from(Status::INVALID_PARAMETER));
+    };
+    Ok(Lease {
+        ip: Some(addr("address", ip)?),
+        netmask: Some(addr("netmask", netmask)?),
+        gateway: net
+            .gateway
+            .as_deref()
+            .map(|g| addr("gateway", g))
+            .transpose()?,
+        dns: net
+            .dns
+            .iter()
+            .map(|d| addr("dns", d))
+            .collect::<uefi::Result<_>>()?,
+        dhcp_server: None,
+        lease_secs: None,
+    })
+}
+
+const DEFAULT_DHCP_ATTEMPTS: u32 = 3;
+const DEFAULT_DHCP_TIMEOUT_SECS: usize = 5;
+
+fn has_address(ip4: &mut Ip4Config2) -> bool {
+    ip4.get_interface_info()
+        .is_ok_and(|info| info.station_addr.0 != [0; 4])
+}
+
+/// Run DHCP until an address is assigned, for up to `network.dhcp_attempts`
+/// tries. The first waits `network.dhcp_timeout_secs`, each retry twice as
+/// long as the one before, and restarts DHCP from DISCOVER. Gives up early
+/// once `deadline` has passed.
+fn dhcp(cfg: &Config, ip4: &mut Ip4Config2, deadline: &Deadline) -> uefi::Result<()> {
+    let net = cfg.network.as_ref();
+    let attempts = net
+        .and_then(|n| n.dhcp_attempts)
+        .unwrap_or(DEFAULT_DHCP_ATTEMPTS)
+        .max(1);
+    let mut timeout = net
+        .and_then(|n| n.dhcp_timeout_secs)
+        .unwrap_or(DEFAULT_DHCP_TIMEOUT_SECS);
+    if has_address(ip4) {
+        return Ok(());
+    }
+
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            crate::println!(
+                "  Retrying DHCP ({}/{}) for {}s...",
+                attempt,
+                attempts,
+                timeout
+            );
+            // Leaving the DHCP policy stops the client; going back starts over.
+            ip4.set_policy(Ip4Config2Policy::STATIC)?;
+        }
+        ip4.set_policy(Ip4Config2Policy::DHCP)?;
+        for _ in 0..timeout * 10 {
+            if has_address(ip4) {
+                return Ok(());
+            }
+            deadline.check()?;
+            boot::stall(core::time::Duration::from_millis(100));
+        }
+        crate::println!("  No DHCP lease after {}s", timeout);
+        timeout *= 2;
+    }
+    Err(uefi::Error::from(Status::TIMEOUT))
+}
+
+/// EFI_IP4_CONFIG2_PROTOCOL, for the data-change notifications the uefi
+/// wrapper does not expose.
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("5b446ed1-e30b-4faa-871a-3654eca36080")]
+struct Ip4Config2Notify {
+    set_data: *const c_void,
+    get_data: *const c_void,
+    register_data_notify: unsafe extern "efiapi" fn(
+        this: *mut Ip4Config2Notify,
+        data_type: Ip4Config2DataType,
+        event: *mut c_void,
+    ) -> Status,
+    unregister_data_notify: unsafe extern "efiapi" fn(
+        this: *mut Ip4Config2Notify,
+        data_type: Ip4Config2DataType,
+        event: *mut c_void,
+    ) -> Status,
+}
+
+/// One NIC running DHCP in [`race_dhcp`], with the event Ip4Config2
+/// signals when its interface info (and so its address) changes.
+struct Racer {
+    nic: Handle,
+    mac: String,
+    ip4: boot::ScopedProtocol<Ip4Config2>,
+    /// Policy the NIC had before the race, put back if it loses.
+    policy: Ip4Config2Policy,
+    notify: Option<(boot::ScopedProtocol<Ip4Config2Notify>, Event)>,
+}
+
+impl Racer {
+    fn new(nic: Handle) -> Option<Self> {
+        let mac = unsafe { open_snp_readonly(nic) }
+            .map(|snp| mac_to_string(snp_mac6(&snp)))
+            .unwrap_or_else(|_| String::from("?"));
+        let Ok(mut ip4) = Ip4Config2::new(nic) else {
+            crate::println!("  NIC {}: no IPv4 stack, left out", mac);
+            return None;
+        };
+        let policy = ip4
+            .get_data(Ip4Config2DataType::POLICY)
+            .ok()
+            .and_then(|data| Some(i32::from_ne_bytes(data.get(..4)?.try_into().ok()?)))
+            .map_or(Ip4Config2Policy::STATIC, Ip4Config2Policy);
+        let notify = unsafe {
+            boot::open_protocol::<Ip4Config2Notify>(
+                OpenProtocolParams {
+                    handle: nic,
+                    agent: boot::image_handle(),
+                    controller: None,
+                },
+                OpenProtocolAttributes::GetProtocol,
+            )
+        }
+        .ok()
+        .and_then(|mut proto| {
+            let event =
+                unsafe { boot::create_event(EventType::empty(), Tpl::CALLBACK, None, None) }
+                    .ok()?;
+            let this: *mut Ip4Config2Notify = &mut *proto;
+            let status = unsafe {
+                (proto.register_data_notify)(
+                    this,
+                    Ip4Config2DataType::INTERFACE_INFO,
+                    event.as_ptr(),
+                )
+            };
+            if status.is_error() {
+                let _ = boot::close_event(event);
+                return None;
+            }
+            Some((proto, event))
+        });
+        Some(Racer {
+            nic,
+            mac,
+            ip4,
+            policy,
+            notify,
+        })
+    }
+
+    /// Start DHCP over from DISCOVER.
+    fn restart(&mut self) -> uefi::Result<()> {
+        let _ = self.ip4.set_policy(Ip4Config2Policy::STATIC);
+        self.ip4.set_policy(Ip4Config2Policy::DHCP)
+    }
+}
+
+impl Drop for Racer {
+    fn drop(&mut self) {
+        if let Some((mut proto, event)) = self.notify.take() {
+            let this: *mut Ip4Config2Notify = &mut *proto;
+            let _ = unsafe {
+                (proto.unregister_data_notify)(
+                    this,
+                    Ip4Config2DataType::INTERFACE_INFO,
+                    event.as_ptr(),
+                )
+            };
+            let _ = boot::close_event(event);
+        }
+    }
+}
+
+/// Whether [`race_dhcp`] can stand in for trying `nics` one at a time:
+/// more than one NIC, DHCP, and nothing tying the boot to one port.
+pub fn can_race(cfg: &Config, nics: &[Handle]) -> bool {
+    let net = cfg.network.as_ref();
+    nics.len() > 1
+        && net.is_none_or(|n| {
+            n.network_type.unwrap_or(NetworkType::Dhcp) == NetworkType::Dhcp
+                && n.bind.is_none()
+                && n.vlan.is_none()
+                && n.wifi.is_none()
+        })
+}
+
+/// Run DHCP on every NIC in `nics` at once and keep whichever gets a lease
+/// first, so a server with one cabled port out of four does not wait out
+/// the others. Wakes on each NIC's address change as well as once a second,
+/// and restarts every client on the schedule [`dhcp`] uses for one NIC.
+/// Each loser is put back on the policy it had before the race: one that
+/// was static stops its client, one that was on DHCP restarts it and keeps
+/// acquiring a lease in the background.
+/// Gives up early once `deadline` has passed.
+pub fn race_dhcp(
+    cfg: &Config,
+    nics: &[Handle],
+    deadline: &Deadline,
+) -> error::Result<(Handle, Lease)> {
+    for &nic in nics {
+        let _ = boot::connect_controller(nic, None, None, true);
+    }
+    connect_all_controllers();
+
+    let mut racers: Vec<Racer> = nics.iter().filter_map(|&nic| Racer::new(nic)).collect();
+    if racers.is_empty() {
+        return Err(AlpheratzError::Network {
+            phase: Phase::Stack,
+            status: Status::NOT_FOUND,
+        });
+    }
+    crate::println!("Racing DHCP on {} interfaces...", racers.len());
+
+    let net = cfg.network.as_ref();
+    let attempts = net
+        .and_then(|n| n.dhcp_attempts)
+        .unwrap_or(DEFAULT_DHCP_ATTEMPTS)
+        .max(1);
+    let mut timeout = net
+        .and_then(|n| n.dhcp_timeout_secs)
+        .unwrap_or(DEFAULT_DHCP_TIMEOUT_SECS);
+
+    let tick = unsafe { boot::create_event(EventType::TIMER, Tpl::CALLBACK, None, None) }
+        .map_err(|e| AlpheratzError::network(Phase::Dhcp, e))?;
+    let _ = boot::set_timer(&tick, TimerTrigger::Periodic(10_000_000));
+
+    let mut winner = racers.iter_mut().position(|r| has_address(&mut r.ip4));
+    'race: for attempt in 1..=attempts {
+        if winner.is_some() {
+            break;
+        }
+        if attempt > 1 {
+            crate::println!(
+                "  Retrying DHCP ({}/{}) for {}s...",
+                attempt,
+                attempts,
+                timeout
+            );
+        }
+        racers.retain_mut(|r| match r.restart() {
+            Ok(()) => true,
+            Err(e) => {
+                crate::println!("  NIC {}: DHCP failed to start: {:?}", r.mac, e.status());
+                false
+            }
+        });
+        if racers.is_empty() {
+            break;
+        }
+        // Index 0: one-second tick; the rest: address-change notifications.
+        let mut events: Vec<Event> = Vec::with_capacity(racers.len() + 1);
+        events.push(unsafe { tick.unsafe_clone() });
+        for racer in racers.iter() {
+            if let Some((_, event)) = &racer.notify {
+                events.push(unsafe { event.unsafe_clone() });
+            }
+        }
+
+        let mut elapsed = 0;
+        while elapsed < timeout {
+            if boot::wait_for_event(&mut events).unwrap_or(0) == 0 {
+                elapsed += 1;
+            }
+            winner = racers.iter_mut().position(|r| has_address(&mut r.ip4));
+            if winner.is_some() || deadline.check().is_err() {
+                break 'race;
+            }
+        }
+        crate::println!("  No DHCP lease after {}s", timeout);
+        timeout *= 2;
+    }
+
+    let _ = boot::set_timer(&tick, TimerTrigger::Cancel);
+    let _ = boot::close_event(tick);
+
+    let won = winner.map(|i| racers.swap_remove(i));
+    for racer in racers.iter_mut() {
+        let _ = racer.ip4.set_policy(Ip4Config2Policy::STATIC);
+        if racer.policy != Ip4Config2Policy::STATIC {
+            let _ = racer.ip4.set_policy(racer.policy);
+        }
+    }
+    let Some(mut won) = won else {
+        return Err(AlpheratzError::Network {
+            phase: Phase::Dhcp,
+            status: Status::TIMEOUT,
+        });
+    };
+    crate::println!("NIC: {} (first to get a lease)", won.mac);
+    let lease = read_lease(&mut won.ip4);
+    print_lease(&lease);
+    Ok((won.nic, lease))
+}
+
+/// Switch Ip4Config2 to the static policy and set address, gateway and DNS.
+fn apply_static(ip4: &mut Ip4Config2, lease: &Lease) -> uefi::Result<()> {
+    // Setting an address starts duplicate detection and reports NOT_READY
+    // until it completes; the address is usable shortly after.
+    fn settle(r: uefi::Result<()>) -> uefi::Result<()> {
+        match r {
+            Err(e) if e.status() == Status::NOT_READY => {
+                boot::stall(core::time::Duration::from_secs(1));
+                Ok(())
+            }
+            r => r,
+        }
+    }
+
+    ip4.set_policy(Ip4Config2Policy::STATIC)?;
+    let mut manual = [0u8; 8];
+    manual[..4].copy_from_slice(&lease.ip.unwrap_or_default());
+    manual[4..].copy_from_slice(&lease.netmask.unwrap_or_default());
+    settle(ip4.set_data(Ip4Config2DataType::MANUAL_ADDRESS, &mut manual))?;
+    if let Some(mut gw) = lease.gateway {
+        settle(ip4.set_data(Ip4Config2DataType::GATEWAY, &mut gw))?;
+    }
+    if !lease.dns.is_empty() {
+        let mut dns: Vec<u8> = lease.dns.iter().flatten().copied().collect();
+        settle(ip4.set_data(Ip4Config2DataType::DNS_SERVER, &mut dns))?;
+    }
+    Ok(())
+}
+
+/// Bring up IPv4 on `nic` and return the handle upper-layer protocols
+/// (HTTP, DNS, …) should bind to — the VLAN child when `network.vlan` is set.
+/// DHCP gives up once `deadline` has passed.
+pub fn bring_up_ipv4(
+    cfg: &Config,
+    nic: Handle,
+    deadline: &Deadline,
+) -> error::Result<(Handle, Lease)> {
     if let Ok(snp) = unsafe { open_snp_readonly(nic) } {
-        uefi::println!("NIC: {}", mac_to_string(snp_mac6(&snp)));
+        crate::println!("NIC: {}", mac_to_string(snp_mac6(&snp)));
     }
 
     for pass in 0..6u32 {
@@ -144,46 +887,68 @@ pub fn bring_up_ipv4(cfg: &Config, nic: Handle) -> uefi::Result<()> {
             break;
         }
         if pass == 5 {
-            uefi::println!("  Network stack failed to initialize");
+            crate::println!("  Network stack failed to initialize");
         }
     }
 
-    let want_dhcp = cfg
+    let network_type = cfg
         .network
         .as_ref()
         .and_then(|n| n.network_type)
         .unwrap_or(NetworkType::Dhcp);
 
-    match want_dhcp {
+    if wifi::is_wireless(nic)
+        && let Some(w) = cfg.network.as_ref().and_then(|n| n.wifi.as_ref())
+    {
+        wifi::connect(w, nic).map_err(|e| AlpheratzError::network(Phase::Wifi, e))?;
+    }
+
+    if !wait_for_link(cfg, nic) {
+        return Err(AlpheratzError::Network {
+            phase: Phase::Link,
+            status: Status::NO_MEDIA,
+        });
+    }
+
+    let nic = match cfg.network.as_ref().and_then(|n| n.vlan) {
+        Some(id) => configure_vlan(nic, id).map_err(|e| AlpheratzError::network(Phase::Vlan, e))?,
+        None => nic,
+    };
+
+    match network_type {
         NetworkType::Dhcp => {
-            uefi::println!("Waiting for DHCP...");
+            crate::println!("Waiting for DHCP...");
 
             let mut ip4 = open_ip4config2(nic).map_err(|e| {
-                uefi::println!("  Ip4Config2 not found on any handle: {:?}", e.status());
-                e
+                crate::println!("  Ip4Config2 not found on any handle: {:?}", e.status());
+                AlpheratzError::network(Phase::Stack, e)
             })?;
 
-            ip4.ifup().map_err(|e| {
-                uefi::println!("  ifup failed: {:?}", e.status());
-                e
+            dhcp(cfg, &mut ip4, deadline).map_err(|e| {
+                crate::println!("  DHCP failed: {:?}", e.status());
+                AlpheratzError::network(Phase::Dhcp, e)
             })?;
 
-            if let Ok(info) = ip4.get_interface_info() {
-                uefi::println!("  IP:      {}", info.station_addr);
-                uefi::println!("  Netmask: {}", info.subnet_mask);
-            }
-            if let Ok(gw) = ip4.get_data(Ip4Config2DataType::GATEWAY) {
-                if gw.len() >= 4 {
-                    uefi::println!("  Gateway: {}.{}.{}.{}", gw[0], gw[1], gw[2], gw[3]);
-                }
-            }
-            if let Ok(dns) = ip4.get_data(Ip4Config2DataType::DNS_SERVER) {
-                for c in dns.chunks_exact(4) {
-                    uefi::println!("  DNS:     {}.{}.{}.{}", c[0], c[1], c[2], c[3]);
-                }
-            }
-            uefi::println!("IPv4 ready.");
+            let lease = read_lease(&mut ip4);
+            print_lease(&lease);
+            Ok((nic, lease))
+        }
+        NetworkType::Static => {
+            let lease = static_lease(cfg.network.as_ref().unwrap())
+                .map_err(|e| AlpheratzError::network(Phase::Static, e))?;
+            crate::println!("Configuring static IPv4...");
+
+            let mut ip4 = open_ip4config2(nic).map_err(|e| {
+                crate::println!("  Ip4Config2 not found on any handle: {:?}", e.status());
+                AlpheratzError::network(Phase::Stack, e)
+            })?;
+            apply_static(&mut ip4, &lease).map_err(|e| {
+                crate::println!("  Static configuration failed: {:?}", e.status());
+                AlpheratzError::network(Phase::Static, e)
+            })?;
+
+            print_lease(&lease);
+            Ok((nic, lease))
         }
     }
-    Ok(())
 }
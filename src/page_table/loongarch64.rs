@@ -1,6 +1,6 @@
-use uefi::boot::{AllocateType, MemoryType};
+use uefi::boot::MemoryType;
 
-use crate::PAGE_SIZE;
+use super::{MapError, PageTableBuilder, Perms, PteFormat, TablePool, Tables};
 use crate::serial::serial_str;
 
 // LoongArch PTE flags
@@ -10,9 +10,13 @@ const PTE_D: u64 = 1 << 1;  // Dirty
 const PTE_PLV0: u64 = 0 << 2; // Privilege Level 0 (kernel)
 const PTE_MAT_CC: u64 = 1 << 4; // Memory Access Type: Coherent Cached
 const PTE_G: u64 = 1 << 6;  // Global
+const PTE_NX: u64 = 1 << 62; // No Execute
 
-/// Combined leaf-PTE flags for kernel read-write cached memory.
-const LEAF_ATTRS: u64 = PTE_V | PTE_D | PTE_PLV0 | PTE_MAT_CC | PTE_G;
+/// Leaf-PTE flags for kernel cached memory; `PTE_D` is added for writable
+/// pages.
+const LEAF_ATTRS: u64 = PTE_V | PTE_PLV0 | PTE_MAT_CC | PTE_G;
+
+const ADDRESS_MASK: u64 = 0x0000_FFFF_FFFF_F000;
 
 // Direct Mapping Window values
 
@@ -28,10 +32,6 @@ pub const DMW1_VALUE: u64 = (0x9 << 60) | (1 << 4) | (1 << 0);
 
 // Page-table layout constants
 
-/// PGD index for the kernel virtual-address mapping.
-/// For VA with bits [47:39] = 510 (if kernel is outside DMW range).
-pub const KERNEL_PGD_INDEX: usize = 510;
-
 /// Virtual address where physical memory is linearly mapped (via DMW1).
 pub const PHYSICAL_MEMORY_OFFSET: u64 = 0x9000_0000_0000_0000;
 
@@ -55,21 +55,64 @@ pub const PWCL_VALUE: u64 = 12 | (9 << 5) | (21 << 10) | (9 << 15);
 /// Encoding: `Dir2Base | (Dir2Width << 5) | (Dir3Base << 10) | (Dir3Width << 15)`
 pub const PWCH_VALUE: u64 = 30 | (9 << 5) | (39 << 10) | (9 << 15);
 
-/// Holds allocated page-table pages and DMW configuration.
-pub struct PageTableConfig {
-    pgd: u64,
-    pud_kernel: u64,
-    pmd_kernel: u64,
-    pte_base: u64,
-    kernel_phys: u64,
-    kernel_4k_pages: usize,
-    pte_count: usize,
+/// Number of translation levels (PGD, PUD, PMD, PTE).
+pub const LEVELS: usize = 4;
+
+/// 4-level walk configured by [`PWCL_VALUE`] / [`PWCH_VALUE`]. Only 4 KiB
+/// pages are used: identity and physical-memory mappings go through DMW.
+pub(crate) struct Format;
+
+impl PteFormat for Format {
+    const LEVELS: usize = LEVELS;
+    const MAX_BLOCK_LEVEL: usize = 0;
+
+    /// LoongArch directory entries are simply the physical address of the
+    /// next-level table (page-aligned, no flag bits).
+    fn table(phys: u64) -> u64 {
+        phys
+    }
+
+    fn leaf(phys: u64, perms: Perms, _level: usize) -> u64 {
+        let mut pte = phys | LEAF_ATTRS;
+        if perms.write {
+            pte |= PTE_D;
+        }
+        if !perms.exec {
+            pte |= PTE_NX;
+        }
+        pte
+    }
+
+    fn is_present(pte: u64) -> bool {
+        pte != 0
+    }
+
+    fn is_table(_pte: u64, level: usize) -> bool {
+        level > 0
+    }
+
+    fn address(pte: u64) -> u64 {
+        pte & ADDRESS_MASK
+    }
+}
+
+/// Page tables rooted at a single PGD.
+///
+/// Before activating them the boot code must also:
+///
+/// 1. Write [`DMW0_VALUE`] / [`DMW1_VALUE`] to `CSR.DMW0` / `CSR.DMW1`.
+/// 2. Write [`PWCL_VALUE`] / [`PWCH_VALUE`] to `CSR.PWCL` / `CSR.PWCH`.
+/// 3. Write the finalized PGD address to `CSR.PGDL`.
+/// 4. Install a TLB refill handler and enable paging (`CSR.CRMD.PG = 1`).
+pub struct PageTables {
+    pool: TablePool,
+    tables: Tables<Format>,
 }
 
-impl PageTableConfig {
+impl PageTables {
     /// Physical address of the Page Global Directory.
     pub fn pgd(&self) -> u64 {
-        self.pgd
+        self.tables.root
     }
 
     /// DMW0 register value (uncached mapping, VSEG = 0x8).
@@ -83,116 +126,32 @@ impl PageTableConfig {
     }
 }
 
-/// Allocate all page-table memory via UEFI boot services.
-///
-/// Must be called **before** `exit_boot_services`.
-///
-/// # Safety
-/// Caller must ensure UEFI boot services are still available.
-pub unsafe fn allocate_page_tables(kernel_phys: u64, kernel_size: usize) -> PageTableConfig {
-    let kernel_4k_pages = (kernel_size + PAGE_SIZE - 1) / PAGE_SIZE;
-    let pte_count = (kernel_4k_pages + 511) / 512;
-
-    // PGD + PUD_KERNEL + PMD_KERNEL + PTE[n]
-    let total_pages = 3 + pte_count;
-    let pages_ptr = uefi::boot::allocate_pages(
-        AllocateType::AnyPages,
-        MemoryType::LOADER_DATA,
-        total_pages,
-    )
-    .expect("Failed to allocate page tables");
-
-    let base = pages_ptr.as_ptr() as u64;
-    let mut off = 0u64;
-
-    let pgd = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pud_kernel = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pmd_kernel = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pte_base = base + off;
-
-    PageTableConfig {
-        pgd,
-        pud_kernel,
-        pmd_kernel,
-        pte_base,
-        kernel_phys,
-        kernel_4k_pages,
-        pte_count,
-    }
-}
+impl PageTableBuilder for PageTables {
+    /// Physical address of the PGD, suitable for writing to `CSR.PGDL`.
+    type Root = u64;
 
-/// Fill in all page-table entries.
-///
-/// Must be called **after** `exit_boot_services`.
-///
-/// Returns the physical address of the PGD, suitable for writing to
-/// `CSR.PGDL`.
-///
-/// ## Caller responsibilities
-///
-/// Before activating the page tables the boot code must also:
-///
-/// 1. Write [`DMW0_VALUE`] / [`DMW1_VALUE`] to `CSR.DMW0` / `CSR.DMW1`.
-/// 2. Write [`PWCL_VALUE`] / [`PWCH_VALUE`] to `CSR.PWCL` / `CSR.PWCH`.
-/// 3. Write the returned PGD address to `CSR.PGDL`.
-/// 4. Install a TLB refill handler and enable paging (`CSR.CRMD.PG = 1`).
-///
-/// # Memory map produced
-///
-/// Identity and physical-memory mappings are handled via DMW (no page-table
-/// entries required).  The page table only covers the kernel:
-///
-/// | Virtual range | Physical | Granularity |
-/// |---|---|---|
-/// | PGD\[510\] base + kernel | `kernel_phys` … | 4 KiB pages |
-///
-/// # Safety
-/// Caller must ensure boot services have been exited and the addresses in
-/// `cfg` are still valid.
-pub unsafe fn init_page_tables(cfg: &PageTableConfig) -> u64 {
-    let total_pages = 3 + cfg.pte_count;
-
-    serial_str("[PT] Initializing LoongArch64 page tables...\r\n");
-
-    unsafe {
-        core::ptr::write_bytes(cfg.pgd as *mut u8, 0, PAGE_SIZE * total_pages);
-
-        let pgd = cfg.pgd as *mut u64;
-        let pud = cfg.pud_kernel as *mut u64;
-        let pmd = cfg.pmd_kernel as *mut u64;
-        let pte_base = cfg.pte_base;
-
-        // PGD[KERNEL_PGD_INDEX] → PUD_KERNEL
-        // LoongArch directory entries are simply the physical address of the
-        // next-level table (page-aligned, no flag bits).
-        *pgd.add(KERNEL_PGD_INDEX) = cfg.pud_kernel;
-
-        // PUD_KERNEL[0] → PMD_KERNEL
-        *pud.add(0) = cfg.pmd_kernel;
-
-        // PMD_KERNEL[0..n] → PTE pages
-        for i in 0..cfg.pte_count {
-            let pte_addr = pte_base + i as u64 * PAGE_SIZE as u64;
-            *pmd.add(i) = pte_addr;
-        }
+    unsafe fn allocate(pages: usize, memory: MemoryType) -> Self {
+        let mut pool = unsafe { TablePool::allocate(pages, memory) };
+        let tables = Tables::new(&mut pool).expect("Failed to allocate PGD");
+        PageTables { pool, tables }
+    }
 
-        // PTE: map each 4 KiB kernel page
-        for i in 0..cfg.kernel_4k_pages {
-            let tbl_idx = i / 512;
-            let ent_idx = i % 512;
-            let pte = (pte_base + tbl_idx as u64 * PAGE_SIZE as u64) as *mut u64;
-            let phys = cfg.kernel_phys + i as u64 * PAGE_SIZE as u64;
-            *pte.add(ent_idx) = phys | LEAF_ATTRS;
-        }
+    fn map_range(
+        &mut self,
+        virt: u64,
+        phys: u64,
+        size: u64,
+        perms: Perms,
+    ) -> Result<(), MapError> {
+        self.tables.map(&mut self.pool, virt, phys, size, perms)
     }
 
-    serial_str("[PT] LoongArch64 page tables initialized\r\n");
+    fn translate(&self, virt: u64) -> Option<u64> {
+        self.tables.translate(virt)
+    }
 
-    cfg.pgd
+    fn finalize(self) -> u64 {
+        serial_str("[PT] LoongArch64 page tables initialized\r\n");
+        self.tables.root
+    }
 }
@@ -1,5 +1,9 @@
 #![allow(dead_code, unused_imports)]
 
+use uefi::boot::{AllocateType, MemoryType};
+
+use crate::PAGE_SIZE;
+
 #[cfg(target_arch = "x86_64")]
 mod x86_64;
 #[cfg(target_arch = "x86_64")]
@@ -19,3 +23,221 @@ pub use self::riscv64::*;
 mod loongarch64;
 #[cfg(target_arch = "loongarch64")]
 pub use self::loongarch64::*;
+
+/// Access rights of a mapped range. Read access is always granted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Perms {
+    pub write: bool,
+    pub exec: bool,
+}
+
+impl Perms {
+    pub const RO: Perms = Perms {
+        write: false,
+        exec: false,
+    };
+    pub const RW: Perms = Perms {
+        write: true,
+        exec: false,
+    };
+    pub const RX: Perms = Perms {
+        write: false,
+        exec: true,
+    };
+    pub const RWX: Perms = Perms {
+        write: true,
+        exec: true,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// `virt`, `phys` or `size` is not 4 KiB aligned.
+    Unaligned,
+    /// The table pool reserved by `allocate` is used up.
+    OutOfTables,
+    /// Part of the range is already mapped.
+    AlreadyMapped,
+}
+
+/// Builds the page tables the kernel starts with.
+///
+/// `allocate` reserves table memory while boot services are available;
+/// `map_range` and `finalize` make no UEFI calls and may run after
+/// `exit_boot_services`.
+pub trait PageTableBuilder: Sized {
+    /// What the boot code loads into the translation registers.
+    type Root;
+
+    /// Reserve `pages` 4 KiB pages of type `memory` for page tables.
+    ///
+    /// # Safety
+    /// Caller must ensure UEFI boot services are still available.
+    unsafe fn allocate(pages: usize, memory: MemoryType) -> Self;
+
+    /// Map `size` bytes at `virt` to `phys`, using the largest block size
+    /// that alignment and the architecture allow.
+    fn map_range(&mut self, virt: u64, phys: u64, size: u64, perms: Perms) -> Result<(), MapError>;
+
+    /// The physical address `virt` maps to, if it is mapped.
+    fn translate(&self, virt: u64) -> Option<u64>;
+
+    fn finalize(self) -> Self::Root;
+}
+
+/// Upper bound on the table pages needed to map `size` bytes with 4 KiB
+/// pages in a `levels`-deep hierarchy, wherever the range falls.
+pub fn tables_for(size: u64, levels: usize) -> usize {
+    let mut entries = size.div_ceil(PAGE_SIZE as u64) as usize;
+    let mut total = 0;
+    for _ in 1..levels {
+        // One table per 512 entries, plus one for a range straddling a boundary.
+        entries = entries.div_ceil(512) + 1;
+        total += entries;
+    }
+    total
+}
+
+/// Architecture-specific page table entry encoding used by [`Tables`].
+pub(crate) trait PteFormat {
+    /// Number of translation levels; level 0 holds 4 KiB pages.
+    const LEVELS: usize;
+    /// Highest level at which a block (huge page) entry may be written.
+    const MAX_BLOCK_LEVEL: usize;
+
+    fn table(phys: u64) -> u64;
+    fn leaf(phys: u64, perms: Perms, level: usize) -> u64;
+    fn is_present(pte: u64) -> bool;
+    /// Whether a present entry at `level` (> 0) points to another table.
+    fn is_table(pte: u64, level: usize) -> bool;
+    fn address(pte: u64) -> u64;
+}
+
+/// Contiguous pages handed out one table at a time.
+pub(crate) struct TablePool {
+    next: u64,
+    end: u64,
+}
+
+impl TablePool {
+    /// # Safety
+    /// Caller must ensure UEFI boot services are still available.
+    pub(crate) unsafe fn allocate(pages: usize, memory: MemoryType) -> Self {
+        let ptr = uefi::boot::allocate_pages(AllocateType::AnyPages, memory, pages)
+            .expect("Failed to allocate page tables");
+        let base = ptr.as_ptr() as u64;
+        TablePool {
+            next: base,
+            end: base + (pages * PAGE_SIZE) as u64,
+        }
+    }
+
+    /// A zeroed table, or `None` once the pool is exhausted.
+    pub(crate) fn take(&mut self) -> Option<u64> {
+        if self.next >= self.end {
+            return None;
+        }
+        let table = self.next;
+        self.next += PAGE_SIZE as u64;
+        unsafe { core::ptr::write_bytes(table as *mut u8, 0, PAGE_SIZE) };
+        Some(table)
+    }
+}
+
+fn level_size(level: usize) -> u64 {
+    (PAGE_SIZE as u64) << (9 * level)
+}
+
+fn index(virt: u64, level: usize) -> usize {
+    ((virt >> (12 + 9 * level)) & 0x1FF) as usize
+}
+
+/// A page table hierarchy under one root, in the encoding `F`.
+pub(crate) struct Tables<F: PteFormat> {
+    pub(crate) root: u64,
+    _format: core::marker::PhantomData<F>,
+}
+
+impl<F: PteFormat> Tables<F> {
+    pub(crate) fn new(pool: &mut TablePool) -> Option<Self> {
+        Some(Tables {
+            root: pool.take()?,
+            _format: core::marker::PhantomData,
+        })
+    }
+
+    pub(crate) fn map(
+        &mut self,
+        pool: &mut TablePool,
+        virt: u64,
+        phys: u64,
+        size: u64,
+        perms: Perms,
+    ) -> Result<(), MapError> {
+        let page = PAGE_SIZE as u64;
+        if !virt.is_multiple_of(page) || !phys.is_multiple_of(page) || !size.is_multiple_of(page) {
+            return Err(MapError::Unaligned);
+        }
+
+        let mut done = 0;
+        while done < size {
+            let (v, p, left) = (virt + done, phys + done, size - done);
+            let level = (1..=F::MAX_BLOCK_LEVEL)
+                .rev()
+                .find(|&l| {
+                    let s = level_size(l);
+                    v.is_multiple_of(s) && p.is_multiple_of(s) && left >= s
+                })
+                .unwrap_or(0);
+            self.set(pool, v, F::leaf(p, perms, level), level)?;
+            done += level_size(level);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn translate(&self, virt: u64) -> Option<u64> {
+        let mut table = self.root;
+        for level in (0..F::LEVELS).rev() {
+            let pte = unsafe { *(table as *const u64).add(index(virt, level)) };
+            if !F::is_present(pte) {
+                return None;
+            }
+            if level == 0 || !F::is_table(pte, level) {
+                return Some(F::address(pte) + virt % level_size(level));
+            }
+            table = F::address(pte);
+        }
+        None
+    }
+
+    /// Write `entry` at `level` for `virt`, creating intermediate tables.
+    fn set(
+        &mut self,
+        pool: &mut TablePool,
+        virt: u64,
+        entry: u64,
+        level: usize,
+    ) -> Result<(), MapError> {
+        let mut table = self.root;
+        for l in (level + 1..F::LEVELS).rev() {
+            let slot = unsafe { (table as *mut u64).add(index(virt, l)) };
+            let pte = unsafe { *slot };
+            table = if !F::is_present(pte) {
+                let next = pool.take().ok_or(MapError::OutOfTables)?;
+                unsafe { *slot = F::table(next) };
+                next
+            } else if F::is_table(pte, l) {
+                F::address(pte)
+            } else {
+                return Err(MapError::AlreadyMapped);
+            };
+        }
+
+        let slot = unsafe { (table as *mut u64).add(index(virt, level)) };
+        if F::is_present(unsafe { *slot }) {
+            return Err(MapError::AlreadyMapped);
+        }
+        unsafe { *slot = entry };
+        Ok(())
+    }
+}
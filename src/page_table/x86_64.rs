@@ -1,17 +1,13 @@
-use uefi::boot::{AllocateType, MemoryType};
+use uefi::boot::MemoryType;
 
-use crate::PAGE_SIZE;
+use super::{MapError, PageTableBuilder, Perms, PteFormat, TablePool, Tables};
 use crate::serial::serial_str;
 
 const PAGE_PRESENT: u64 = 1 << 0;
 const PAGE_WRITABLE: u64 = 1 << 1;
 const PAGE_HUGE: u64 = 1 << 7;
 
-/// Default PML4 entry index for kernel virtual address mapping.
-///
-/// Note: the actual kernel PML4 index can be chosen at runtime (e.g. derived
-/// from an ELF kernel's virtual base), and is stored in [`PageTableConfig`].
-pub const DEFAULT_KERNEL_PML4_INDEX: usize = 510;
+const ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
 
 /// PML4 entry index for the physical memory direct mapping.
 /// Index 256 → virtual base 0xFFFF_8000_0000_0000.
@@ -20,191 +16,84 @@ pub const PHYS_MAP_PML4_INDEX: usize = 256;
 /// Virtual address offset where all physical memory is linearly mapped.
 pub const PHYSICAL_MEMORY_OFFSET: u64 = 0xFFFF_8000_0000_0000;
 
-/// Holds physical addresses of all allocated page-table pages and kernel
-/// geometry so that [`init_page_tables`] can fill them in after UEFI boot
-/// services have been exited.
-pub struct PageTableConfig {
-    pml4: u64,
-    pdpt_low: u64,
-    pdpt_kernel: u64,
-    pdpt_phys_map: u64,
-    pd_low_base: u64,
-    pd_kernel: u64,
-    pd_phys_map_base: u64,
-    pt_base: u64,
-    kernel_phys: u64,
-    kernel_4k_pages: usize,
-    pt_count: usize,
-    kernel_pml4_index: usize,
-}
+/// Number of translation levels (PML4, PDPT, PD, PT).
+pub const LEVELS: usize = 4;
 
-impl PageTableConfig {
-    pub fn root(&self) -> u64 {
-        self.pml4
-    }
-}
+/// 4-level paging. Blocks stop at 2 MiB because 1 GiB pages are optional
+/// (CPUID pdpe1gb). Execute permission is not enforced: the NX bit is only
+/// valid once the kernel sets EFER.NXE.
+pub(crate) struct Format;
 
-/// Allocate all page-table memory via UEFI boot services.
-///
-/// Must be called **before** `exit_boot_services`.  The returned config is
-/// later passed to [`init_page_tables`].
-///
-/// # Safety
-/// Caller must ensure UEFI boot services are still available.
-pub unsafe fn allocate_page_tables(
-    kernel_phys: u64,
-    kernel_size: usize,
-    kernel_pml4_index: usize,
-) -> PageTableConfig {
-    let kernel_4k_pages = (kernel_size + PAGE_SIZE - 1) / PAGE_SIZE;
-    let pt_count = (kernel_4k_pages + 511) / 512;
-
-    // PML4 + PDPT_LOW + PDPT_KERNEL + PDPT_PHYS_MAP
-    // + PD_LOW[4] + PD_KERNEL + PD_PHYS_MAP[4] + PT[n]
-    let total_pages = 1 + 3 + 4 + 1 + 4 + pt_count;
-    let pages_ptr = uefi::boot::allocate_pages(
-        AllocateType::AnyPages,
-        MemoryType::LOADER_DATA,
-        total_pages,
-    )
-    .expect("Failed to allocate page tables");
-
-    let base = pages_ptr.as_ptr() as u64;
-    let mut off = 0u64;
-
-    let pml4 = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pdpt_low = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pdpt_kernel = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pdpt_phys_map = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pd_low_base = base + off;
-    off += 4 * PAGE_SIZE as u64;
-
-    let pd_kernel = base + off;
-    off += PAGE_SIZE as u64;
-
-    let pd_phys_map_base = base + off;
-    off += 4 * PAGE_SIZE as u64;
-
-    let pt_base = base + off;
-
-    PageTableConfig {
-        pml4,
-        pdpt_low,
-        pdpt_kernel,
-        pdpt_phys_map,
-        pd_low_base,
-        pd_kernel,
-        pd_phys_map_base,
-        pt_base,
-        kernel_phys,
-        kernel_4k_pages,
-        pt_count,
-        kernel_pml4_index,
+impl PteFormat for Format {
+    const LEVELS: usize = LEVELS;
+    const MAX_BLOCK_LEVEL: usize = 1;
+
+    fn table(phys: u64) -> u64 {
+        phys | PAGE_PRESENT | PAGE_WRITABLE
     }
-}
 
-/// Fill in all page-table entries.
-///
-/// Must be called **after** `exit_boot_services` (no UEFI calls inside).
-///
-/// Returns the physical address of the PML4 table, suitable for loading
-/// into CR3.
-///
-/// # Memory map produced
-///
-/// | Virtual range | Physical range | Granularity |
-/// |---|---|---|
-/// | 0 – 4 GiB (identity) | 0 – 4 GiB | 2 MiB huge pages |
-/// | `PHYSICAL_MEMORY_OFFSET` + 0 – 4 GiB | 0 – 4 GiB | 2 MiB huge pages |
-/// | Kernel at PML4\[510\] | `kernel_phys` … | 4 KiB pages |
-///
-/// # Safety
-/// Caller must ensure boot services have been exited and the addresses in
-/// `cfg` are still valid.
-pub unsafe fn init_page_tables(cfg: &PageTableConfig) -> u64 {
-    let pml4 = cfg.pml4 as *mut u64;
-    let pdpt_low = cfg.pdpt_low as *mut u64;
-    let pdpt_kernel = cfg.pdpt_kernel as *mut u64;
-    let pdpt_phys_map = cfg.pdpt_phys_map as *mut u64;
-    let pd_low_base = cfg.pd_low_base;
-    let pd_kernel = cfg.pd_kernel as *mut u64;
-    let pd_phys_map_base = cfg.pd_phys_map_base;
-    let pt_base = cfg.pt_base;
-
-    let total_pages = 1 + 3 + 4 + 1 + 4 + cfg.pt_count;
-
-    serial_str("[PT] Initializing page tables...\r\n");
-
-    unsafe {
-        core::ptr::write_bytes(pml4 as *mut u8, 0, PAGE_SIZE * total_pages);
-
-        // PML4[0] → PDPT_LOW  (identity mapping for first 4 GiB)
-        *pml4.add(0) = cfg.pdpt_low | PAGE_PRESENT | PAGE_WRITABLE;
-
-        // PML4[KERNEL] → PDPT_KERNEL
-        *pml4.add(cfg.kernel_pml4_index) = cfg.pdpt_kernel | PAGE_PRESENT | PAGE_WRITABLE;
-
-        // PML4[PHYS_MAP] → PDPT_PHYS_MAP
-        *pml4.add(PHYS_MAP_PML4_INDEX) = cfg.pdpt_phys_map | PAGE_PRESENT | PAGE_WRITABLE;
-
-        // PDPT_LOW[0..4] → PD_LOW[0..4]
-        for i in 0..4usize {
-            let pd_addr = pd_low_base + i as u64 * PAGE_SIZE as u64;
-            *pdpt_low.add(i) = pd_addr | PAGE_PRESENT | PAGE_WRITABLE;
+    fn leaf(phys: u64, perms: Perms, level: usize) -> u64 {
+        let mut pte = phys | PAGE_PRESENT;
+        if perms.write {
+            pte |= PAGE_WRITABLE;
         }
-
-        // PD_LOW: identity-map first 4 GiB with 2 MiB huge pages
-        for gb in 0..4u64 {
-            let pd = (pd_low_base + gb * PAGE_SIZE as u64) as *mut u64;
-            for i in 0..512u64 {
-                let phys = (gb * 512 + i) * 0x20_0000;
-                *pd.add(i as usize) = phys | PAGE_PRESENT | PAGE_WRITABLE | PAGE_HUGE;
-            }
+        if level > 0 {
+            pte |= PAGE_HUGE;
         }
+        pte
+    }
 
-        // PDPT_PHYS_MAP[0..4] → PD_PHYS_MAP[0..4]
-        for i in 0..4usize {
-            let pd_addr = pd_phys_map_base + i as u64 * PAGE_SIZE as u64;
-            *pdpt_phys_map.add(i) = pd_addr | PAGE_PRESENT | PAGE_WRITABLE;
-        }
+    fn is_present(pte: u64) -> bool {
+        pte & PAGE_PRESENT != 0
+    }
 
-        // PD_PHYS_MAP: map first 4 GiB with 2 MiB huge pages
-        for gb in 0..4u64 {
-            let pd = (pd_phys_map_base + gb * PAGE_SIZE as u64) as *mut u64;
-            for i in 0..512u64 {
-                let phys = (gb * 512 + i) * 0x20_0000;
-                *pd.add(i as usize) = phys | PAGE_PRESENT | PAGE_WRITABLE | PAGE_HUGE;
-            }
-        }
+    fn is_table(pte: u64, _level: usize) -> bool {
+        pte & PAGE_HUGE == 0
+    }
+
+    fn address(pte: u64) -> u64 {
+        pte & ADDRESS_MASK
+    }
+}
 
-        // PDPT_KERNEL[0] → PD_KERNEL
-        *pdpt_kernel.add(0) = cfg.pd_kernel | PAGE_PRESENT | PAGE_WRITABLE;
+/// Page tables rooted at a single PML4.
+pub struct PageTables {
+    pool: TablePool,
+    tables: Tables<Format>,
+}
 
-        // PD_KERNEL[0..n] → PT pages
-        for i in 0..cfg.pt_count {
-            let pt_addr = pt_base + i as u64 * PAGE_SIZE as u64;
-            *pd_kernel.add(i) = pt_addr | PAGE_PRESENT | PAGE_WRITABLE;
-        }
+impl PageTables {
+    pub fn root(&self) -> u64 {
+        self.tables.root
+    }
+}
 
-        // PT: map each 4 KiB kernel page
-        for i in 0..cfg.kernel_4k_pages {
-            let pt_idx = i / 512;
-            let pte_idx = i % 512;
-            let pt = (pt_base + pt_idx as u64 * PAGE_SIZE as u64) as *mut u64;
-            let phys = cfg.kernel_phys + i as u64 * PAGE_SIZE as u64;
-            *pt.add(pte_idx) = phys | PAGE_PRESENT | PAGE_WRITABLE;
-        }
+impl PageTableBuilder for PageTables {
+    /// Physical address of the PML4, suitable for loading into CR3.
+    type Root = u64;
+
+    unsafe fn allocate(pages: usize, memory: MemoryType) -> Self {
+        let mut pool = unsafe { TablePool::allocate(pages, memory) };
+        let tables = Tables::new(&mut pool).expect("Failed to allocate PML4");
+        PageTables { pool, tables }
+    }
+
+    fn map_range(
+        &mut self,
+        virt: u64,
+        phys: u64,
+        size: u64,
+        perms: Perms,
+    ) -> Result<(), MapError> {
+        self.tables.map(&mut self.pool, virt, phys, size, perms)
     }
 
-    serial_str("[PT] Page tables initialized\r\n");
+    fn translate(&self, virt: u64) -> Option<u64> {
+        self.tables.translate(virt)
+    }
 
-    cfg.pml4
+    fn finalize(self) -> u64 {
+        serial_str("[PT] Page tables initialized\r\n");
+        self.tables.root
+    }
 }
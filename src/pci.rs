@@ -0,0 +1,67 @@
+//! Finding the PCI controllers `connect = "nic"` drivers are connected to.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use core::ffi::c_void;
+
+use uefi::Identify;
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::prelude::*;
+use uefi::proto::unsafe_protocol;
+
+/// PCI base class of network controllers.
+const CLASS_NETWORK: u8 = 0x02;
+/// EfiPciIoWidthUint8.
+const WIDTH_U8: u32 = 0;
+
+/// EFI_PCI_IO_PROTOCOL, up to the configuration space accessors; the
+/// members after them are never touched.
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("4cf5b200-68b8-4ca5-9eec-b23e3f50029a")]
+struct PciIo {
+    poll_mem: *const c_void,
+    poll_io: *const c_void,
+    mem: [*const c_void; 2],
+    io: [*const c_void; 2],
+    pci_read: unsafe extern "efiapi" fn(
+        this: *mut PciIo,
+        width: u32,
+        offset: u32,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    pci_write: *const c_void,
+}
+
+/// Base class code of the PCI function behind `handle`.
+fn class(handle: Handle) -> Option<u8> {
+    let mut pci = unsafe {
+        boot::open_protocol::<PciIo>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+    let this: *mut PciIo = &mut *pci;
+    let mut class = 0u8;
+    // The base class is the byte at 0x0B of the configuration header.
+    let status = unsafe { (pci.pci_read)(this, WIDTH_U8, 0x0B, 1, (&raw mut class).cast()) };
+    status.is_success().then_some(class)
+}
+
+/// Handles of every PCI network controller.
+pub fn network_controllers() -> Vec<Handle> {
+    boot::locate_handle_buffer(boot::SearchType::ByProtocol(&PciIo::GUID))
+        .map(|h| h.to_vec())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|&h| class(h) == Some(CLASS_NETWORK))
+        .collect()
+}
@@ -0,0 +1,87 @@
+//! Detached PKCS#7 signatures, checked by firmware through
+//! EFI_PKCS7_VERIFY_PROTOCOL against the Secure Boot `db` and `dbx`, so
+//! only a key the platform already trusts can vouch for a file.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use core::ffi::c_void;
+use core::ptr;
+
+use alpheratz_core::manifest::signature_lists;
+use uefi::prelude::*;
+use uefi::proto::unsafe_protocol;
+use uefi::runtime::VariableVendor;
+use uefi::{boot, cstr16};
+
+/// EFI_PKCS7_VERIFY_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("47889fb2-d671-4fab-a0ca-df0e44df70d6")]
+struct Pkcs7Verify {
+    verify_buffer: unsafe extern "efiapi" fn(
+        this: *mut Self,
+        signed_data: *const c_void,
+        signed_data_size: usize,
+        in_data: *const c_void,
+        in_data_size: usize,
+        allowed_db: *const *const c_void,
+        revoked_db: *const *const c_void,
+        time_stamp_db: *const *const c_void,
+        content: *mut c_void,
+        content_size: *mut usize,
+    ) -> Status,
+    verify_signature: *const c_void,
+}
+
+fn read_db(name: &uefi::CStr16) -> Option<Box<[u8]>> {
+    let (data, _) =
+        uefi::runtime::get_variable_boxed(name, &VariableVendor::IMAGE_SECURITY_DATABASE).ok()?;
+    Some(data)
+}
+
+/// The NULL-terminated list of EFI_SIGNATURE_LIST pointers VerifyBuffer
+/// takes for a database.
+fn list_pointers(db: &[u8]) -> Vec<*const c_void> {
+    let mut out: Vec<*const c_void> = signature_lists(db)
+        .into_iter()
+        .map(|list| list.as_ptr().cast())
+        .collect();
+    out.push(ptr::null());
+    out
+}
+
+/// Check `data` against the DER PKCS#7 `signature` made over it: signed by
+/// a certificate in `db` and by none in `dbx`. Without a `db` or the
+/// protocol nothing can be checked, and that is a failure too.
+pub fn verify_detached(data: &[u8], signature: &[u8]) -> uefi::Result<()> {
+    let db = read_db(cstr16!("db")).ok_or(uefi::Error::from(Status::SECURITY_VIOLATION))?;
+    let dbx = read_db(cstr16!("dbx")).unwrap_or_default();
+    let allowed = list_pointers(&db);
+    if allowed.len() == 1 {
+        return Err(uefi::Error::from(Status::SECURITY_VIOLATION));
+    }
+    let revoked = list_pointers(&dbx);
+
+    let handle = boot::get_handle_for_protocol::<Pkcs7Verify>()?;
+    let mut verify = boot::open_protocol_exclusive::<Pkcs7Verify>(handle)?;
+    let this: *mut Pkcs7Verify = &mut *verify;
+    let mut content_size = 0;
+    let status = unsafe {
+        (verify.verify_buffer)(
+            this,
+            signature.as_ptr().cast(),
+            signature.len(),
+            data.as_ptr().cast(),
+            data.len(),
+            allowed.as_ptr(),
+            revoked.as_ptr(),
+            ptr::null(),
+            ptr::null_mut(),
+            &mut content_size,
+        )
+    };
+    status.to_result()
+}
@@ -0,0 +1,184 @@
+//! The PXE base code the loader was chainloaded by: the boot server its DHCP
+//! reply named, and TFTP reads through the same stack.
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use core::ffi::c_void;
+
+use uefi::Identify;
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::prelude::*;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::unsafe_protocol;
+
+use alpheratz_core::pxe::BootServer;
+
+use crate::memcheck;
+
+const TFTP_GET_FILE_SIZE: u32 = 1;
+const TFTP_READ_FILE: u32 = 2;
+
+/// One EFI_PXE_BASE_CODE_PACKET.
+type Packet = [u32; 368];
+
+/// The leading part of EFI_PXE_BASE_CODE_MODE, up to the cached replies.
+#[allow(dead_code)]
+#[repr(C)]
+struct Mode {
+    started: bool,
+    ipv6_available: bool,
+    ipv6_supported: bool,
+    using_ipv6: bool,
+    bis_supported: bool,
+    bis_detected: bool,
+    auto_arp: bool,
+    send_guid: bool,
+    dhcp_discover_valid: bool,
+    dhcp_ack_received: bool,
+    proxy_offer_received: bool,
+    pxe_discover_valid: bool,
+    pxe_reply_received: bool,
+    pxe_bis_reply_received: bool,
+    icmp_error_received: bool,
+    tftp_error_received: bool,
+    make_callbacks: bool,
+    ttl: u8,
+    tos: u8,
+    station_ip: [u32; 4],
+    subnet_mask: [u32; 4],
+    dhcp_discover: Packet,
+    dhcp_ack: Packet,
+    proxy_offer: Packet,
+    pxe_discover: Packet,
+    pxe_reply: Packet,
+}
+
+/// EFI_PXE_BASE_CODE_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("03c4e603-ac28-11d3-9a2d-0090273fc14d")]
+struct BaseCode {
+    revision: u64,
+    start: *const c_void,
+    stop: *const c_void,
+    dhcp: *const c_void,
+    discover: *const c_void,
+    mtftp: unsafe extern "efiapi" fn(
+        this: *mut BaseCode,
+        operation: u32,
+        buffer: *mut c_void,
+        overwrite: bool,
+        buffer_size: *mut u64,
+        block_size: *const usize,
+        server_ip: *const [u8; 16],
+        filename: *const u8,
+        info: *const c_void,
+        dont_use_buffer: bool,
+    ) -> Status,
+    udp_write: *const c_void,
+    udp_read: *const c_void,
+    set_ip_filter: *const c_void,
+    arp: *const c_void,
+    set_parameters: *const c_void,
+    set_station_ip: *const c_void,
+    set_packets: *const c_void,
+    mode: *const Mode,
+}
+
+fn open(handle: Handle) -> Option<boot::ScopedProtocol<BaseCode>> {
+    unsafe {
+        boot::open_protocol::<BaseCode>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()
+}
+
+fn as_bytes(packet: &Packet) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(packet.as_ptr().cast(), size_of::<Packet>()) }
+}
+
+/// A started IPv4 PXE stack and the boot server it was pointed at.
+pub struct Pxe {
+    handle: Handle,
+    pub boot: BootServer,
+}
+
+impl Pxe {
+    /// The PXE stack on the device the loader was read from, else the first
+    /// started one. A ProxyDHCP or boot server reply names the server ahead
+    /// of the plain DHCP ACK.
+    pub fn find() -> Option<Self> {
+        let device = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())
+            .ok()
+            .and_then(|li| li.device());
+        let handles =
+            boot::locate_handle_buffer(boot::SearchType::ByProtocol(&BaseCode::GUID)).ok()?;
+        let mut order: Vec<Handle> = device.into_iter().collect();
+        order.extend(handles.iter().copied().filter(|&h| Some(h) != device));
+
+        order.into_iter().find_map(|handle| {
+            let pxe = open(handle)?;
+            let mode = unsafe { pxe.mode.as_ref() }?;
+            if !mode.started || mode.using_ipv6 {
+                return None;
+            }
+            let replies = [
+                (mode.pxe_reply_received, &mode.pxe_reply),
+                (mode.proxy_offer_received, &mode.proxy_offer),
+                (mode.dhcp_ack_received, &mode.dhcp_ack),
+            ];
+            let boot = replies
+                .into_iter()
+                .filter(|(received, _)| *received)
+                .find_map(|(_, packet)| BootServer::parse(as_bytes(packet)))?;
+            Some(Pxe { handle, boot })
+        })
+    }
+
+    /// Read `path` from the TFTP server at `server`, refusing files larger
+    /// than `max` bytes.
+    pub fn read(&self, server: [u8; 4], path: &str, max: Option<usize>) -> uefi::Result<Vec<u8>> {
+        let mut pxe = open(self.handle).ok_or_else(|| uefi::Error::from(Status::NOT_FOUND))?;
+        let this: *mut BaseCode = &mut *pxe;
+        let mut ip = [0u8; 16];
+        ip[..4].copy_from_slice(&server);
+        let mut name = Vec::from(path.as_bytes());
+        name.push(0);
+
+        let mtftp = |operation: u32, buffer: *mut c_void, size: &mut u64| unsafe {
+            (pxe.mtftp)(
+                this,
+                operation,
+                buffer,
+                false,
+                size,
+                core::ptr::null(),
+                &ip,
+                name.as_ptr(),
+                core::ptr::null(),
+                false,
+            )
+        };
+
+        let mut size = 0u64;
+        mtftp(TFTP_GET_FILE_SIZE, core::ptr::null_mut(), &mut size).to_result()?;
+        let len = usize::try_from(size).map_err(|_| uefi::Error::from(Status::BAD_BUFFER_SIZE))?;
+        if max.is_some_and(|m| len > m) {
+            return Err(uefi::Error::from(Status::BAD_BUFFER_SIZE));
+        }
+        memcheck::ensure_available(len)?;
+        let mut data = vec![0; len];
+        mtftp(TFTP_READ_FILE, data.as_mut_ptr().cast(), &mut size).to_result()?;
+        data.truncate(size as usize);
+        Ok(data)
+    }
+}
@@ -0,0 +1,163 @@
+//! Screen backends the menu draws through: UEFI simple text output (or
+//! double-size text on the framebuffer for the large-text theme), and
+//! ANSI/VT100 escape sequences on the serial port for terminals that do not
+//! see ConOut.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use uefi::proto::console::text::{Color, Output};
+
+use crate::config::Theme;
+use crate::{fbcon, serial};
+
+static HIGH_CONTRAST: AtomicBool = AtomicBool::new(false);
+static LARGE_TEXT: AtomicBool = AtomicBool::new(false);
+
+/// Apply the theme's accessibility switches. Large text needs a GOP
+/// framebuffer and is ignored without one.
+pub fn configure(theme: &Theme) {
+    HIGH_CONTRAST.store(theme.high_contrast, Ordering::Relaxed);
+    if theme.large_text {
+        fbcon::capture();
+        LARGE_TEXT.store(fbcon::captured(), Ordering::Relaxed);
+    }
+}
+
+/// `fg` on `bg` as drawn: in high contrast, highlights become black on
+/// white and dim text white, while the bright warning colours stay.
+pub fn palette(fg: Color, bg: Color) -> (Color, Color) {
+    if !HIGH_CONTRAST.load(Ordering::Relaxed) {
+        return (fg, bg);
+    }
+    match (fg, bg) {
+        (_, Color::Black) => match fg {
+            Color::LightRed | Color::Yellow | Color::Black => (fg, bg),
+            _ => (Color::White, Color::Black),
+        },
+        _ => (Color::Black, Color::White),
+    }
+}
+
+/// A character-cell screen. Text written through [`Write`] uses `\n` line
+/// endings; backends translate as needed.
+pub trait Renderer: Write {
+    fn clear(&mut self);
+    /// Move the cursor to zero-based `col`, `row`.
+    fn move_to(&mut self, col: usize, row: usize);
+    fn set_color(&mut self, fg: Color, bg: Color);
+    /// Width of the screen in character cells.
+    fn columns(&mut self) -> usize;
+}
+
+/// Assumed width of a serial terminal, which cannot be asked without
+/// reading its reply, and of a console whose mode is unknown.
+const DEFAULT_COLUMNS: usize = 80;
+
+/// The firmware console.
+pub struct TextOutput<'a>(pub &'a mut Output);
+
+impl Write for TextOutput<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0.write_str(s)
+    }
+}
+
+impl Renderer for TextOutput<'_> {
+    fn clear(&mut self) {
+        let _ = self.0.clear();
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        let _ = self.0.set_cursor_position(col, row);
+    }
+
+    fn set_color(&mut self, fg: Color, bg: Color) {
+        let (fg, bg) = palette(fg, bg);
+        let _ = self.0.set_color(fg, bg);
+    }
+
+    fn columns(&mut self) -> usize {
+        match self.0.current_mode() {
+            Ok(Some(mode)) => mode.columns(),
+            _ => DEFAULT_COLUMNS,
+        }
+    }
+}
+
+/// ANSI escape sequences on the serial port.
+pub struct Ansi;
+
+/// SGR colour index (0-7) and whether it is the bright variant.
+fn ansi_color(c: Color) -> (u8, bool) {
+    match c {
+        Color::Black => (0, false),
+        Color::Red => (1, false),
+        Color::Green => (2, false),
+        Color::Brown => (3, false),
+        Color::Blue => (4, false),
+        Color::Magenta => (5, false),
+        Color::Cyan => (6, false),
+        Color::LightGray => (7, false),
+        Color::DarkGray => (0, true),
+        Color::LightRed => (1, true),
+        Color::LightGreen => (2, true),
+        Color::Yellow => (3, true),
+        Color::LightBlue => (4, true),
+        Color::LightMagenta => (5, true),
+        Color::LightCyan => (6, true),
+        Color::White => (7, true),
+    }
+}
+
+impl Write for Ansi {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                serial::serial_str("\r\n");
+            }
+            serial::serial_str(line);
+        }
+        Ok(())
+    }
+}
+
+impl Renderer for Ansi {
+    fn clear(&mut self) {
+        serial::serial_str("\x1b[0m\x1b[2J\x1b[H");
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        let _ = write!(self, "\x1b[{};{}H", row + 1, col + 1);
+    }
+
+    fn set_color(&mut self, fg: Color, bg: Color) {
+        let (fg, bg) = palette(fg, bg);
+        let (f, bright) = ansi_color(fg);
+        // Bright backgrounds are not universally supported; use the base hue.
+        let (b, _) = ansi_color(bg);
+        let _ = write!(
+            self,
+            "\x1b[{};{}m",
+            if bright { 90 + f } else { 30 + f },
+            40 + b
+        );
+    }
+
+    fn columns(&mut self) -> usize {
+        DEFAULT_COLUMNS
+    }
+}
+
+/// Run `f` on the firmware console (or the large-text framebuffer), and
+/// again on the serial port when `serial_menu` is set.
+pub fn each(serial_menu: bool, mut f: impl FnMut(&mut dyn Renderer)) {
+    if LARGE_TEXT.load(Ordering::Relaxed) {
+        f(&mut fbcon::Large::default());
+    } else {
+        uefi::system::with_stdout(|out| f(&mut TextOutput(out)));
+    }
+    if serial_menu {
+        f(&mut Ansi);
+    }
+}
@@ -0,0 +1,68 @@
+use uefi::cstr16;
+use uefi::runtime::VariableVendor;
+
+use crate::config::{Config, Protocol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Enabled,
+    Disabled,
+    Setup,
+    Unknown,
+}
+
+impl core::fmt::Display for State {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            State::Enabled => f.write_str("enabled"),
+            State::Disabled => f.write_str("disabled"),
+            State::Setup => f.write_str("setup mode"),
+            State::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+fn read_flag(name: &uefi::CStr16) -> Option<bool> {
+    let (data, _) =
+        uefi::runtime::get_variable_boxed(name, &VariableVendor::GLOBAL_VARIABLE).ok()?;
+    data.first().map(|&b| b != 0)
+}
+
+/// Read the `SecureBoot` and `SetupMode` global variables.
+pub fn state() -> State {
+    match (
+        read_flag(cstr16!("SecureBoot")),
+        read_flag(cstr16!("SetupMode")),
+    ) {
+        (_, Some(true)) => State::Setup,
+        (Some(true), _) => State::Enabled,
+        (Some(false), _) => State::Disabled,
+        (None, _) => State::Unknown,
+    }
+}
+
+/// Refuse payloads that cannot be signature-checked by firmware when
+/// `require_secure_boot` is set. Returns a user-facing explanation on refusal.
+pub fn check(cfg: &Config, protocol: Protocol, kernel: &[u8]) -> Result<(), &'static str> {
+    if !cfg.require_secure_boot {
+        return Ok(());
+    }
+    if state() != State::Enabled {
+        return Err("require_secure_boot is set but Secure Boot is not enabled in firmware.");
+    }
+    match protocol {
+        Protocol::Canicula => Err(
+            "Canicula ELF kernels are not signed PE images and cannot be verified under Secure Boot.",
+        ),
+        Protocol::Multiboot1 => Err(
+            "Multiboot kernels are not signed PE images and cannot be verified under Secure Boot.",
+        ),
+        Protocol::Linux if !kernel.starts_with(b"MZ") => {
+            Err("Kernel is not a PE/COFF EFI stub image and cannot be verified under Secure Boot.")
+        }
+        Protocol::Efi if !kernel.starts_with(b"MZ") => {
+            Err("Image is not a PE/COFF EFI application and cannot be verified under Secure Boot.")
+        }
+        Protocol::Linux | Protocol::Efi => Ok(()),
+    }
+}
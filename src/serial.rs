@@ -1,4 +1,6 @@
-/// Low-level serial output for use after exit_boot_services(),
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Low-level serial I/O for use after exit_boot_services(),
 /// when UEFI stdout is no longer available.
 
 fn serial_byte(b: u8) {
@@ -37,3 +39,68 @@ pub fn serial_hex(val: u64) {
         serial_byte(HEX[((val >> (i * 4)) & 0xF) as usize]);
     }
 }
+
+/// A received byte, if one is waiting.
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+fn serial_poll() -> Option<u8> {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        let lsr: u8;
+        core::arch::asm!("in al, dx", out("al") lsr, in("dx") 0x3FDu16);
+        if lsr & 1 == 0 {
+            return None;
+        }
+        let b: u8;
+        core::arch::asm!("in al, dx", out("al") b, in("dx") 0x3F8u16);
+        Some(b)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        // PL011: FR.RXFE set while the receive FIFO is empty.
+        let fr = core::ptr::read_volatile(0x0900_0018 as *const u32);
+        if fr & (1 << 4) != 0 {
+            return None;
+        }
+        Some(core::ptr::read_volatile(0x0900_0000 as *const u8))
+    }
+
+    #[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+    unsafe {
+        #[cfg(target_arch = "riscv64")]
+        const BASE: usize = 0x1000_0000;
+        #[cfg(target_arch = "loongarch64")]
+        const BASE: usize = 0x1FE0_01E0;
+        // 16550: LSR.DR set when a byte is waiting.
+        if core::ptr::read_volatile((BASE + 5) as *const u8) & 1 == 0 {
+            return None;
+        }
+        Some(core::ptr::read_volatile(BASE as *const u8))
+    }
+}
+
+/// Set to non-zero by a debugger to resume from [`debug_halt`].
+#[unsafe(no_mangle)]
+static ALPHERATZ_DEBUG_CONTINUE: AtomicU32 = AtomicU32::new(0);
+
+/// Print `addresses` and spin until a byte arrives on the serial port or a
+/// debugger writes to `ALPHERATZ_DEBUG_CONTINUE`, so one can be attached
+/// before the kernel runs. Called after boot services have exited.
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+pub fn debug_halt(addresses: &[(&str, u64)]) {
+    serial_str("[LOADER] debug_halt\r\n");
+    for (name, value) in addresses {
+        serial_str("[LOADER]   ");
+        serial_str(name);
+        serial_str(": ");
+        serial_hex(*value);
+        serial_str("\r\n");
+    }
+    serial_str("[LOADER] Send a byte on serial, or set the u32 at ");
+    serial_hex(&raw const ALPHERATZ_DEBUG_CONTINUE as u64);
+    serial_str(" to 1, to continue\r\n");
+    while serial_poll().is_none() && ALPHERATZ_DEBUG_CONTINUE.load(Ordering::SeqCst) == 0 {
+        core::hint::spin_loop();
+    }
+    serial_str("[LOADER] Continuing\r\n");
+}
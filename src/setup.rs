@@ -0,0 +1,313 @@
+//! First-boot wizard, run when `bootloader.toml` does not exist: scan the
+//! ESP for kernels and EFI loaders, ask a few questions and write the result.
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt::Write;
+
+use alpheratz_core::uki;
+#[cfg(feature = "network")]
+use alpheratz_core::vars::parse_ipv4;
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileMode};
+
+use crate::config::Config;
+use crate::fsutil;
+use crate::menu;
+
+/// How deep below the volume root to look for boot files.
+const MAX_DEPTH: usize = 3;
+/// Most candidates offered, so a crowded ESP stays answerable.
+const MAX_FOUND: usize = 20;
+
+enum Kind {
+    Linux { initrd: Option<String> },
+    Efi,
+}
+
+struct Found {
+    path: String,
+    kind: Kind,
+}
+
+fn is_kernel(name: &str) -> bool {
+    name.starts_with("vmlinuz") || name == "bzimage" || name == "image"
+}
+
+fn is_initrd(name: &str) -> bool {
+    name.starts_with("initrd") || name.starts_with("initramfs")
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.ends_with('\\') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}\\{}", dir, name)
+    }
+}
+
+/// The initrd in `files` belonging to `kernel`: one sharing its version
+/// suffix (`vmlinuz-6.1` / `initrd.img-6.1`), else the only one there is.
+fn pair_initrd<'a>(kernel: &str, files: &'a [String]) -> Option<&'a String> {
+    let initrds: Vec<&String> = files
+        .iter()
+        .filter(|f| is_initrd(&f.to_ascii_lowercase()))
+        .collect();
+    let suffix = kernel.find('-').map(|i| &kernel[i..]);
+    suffix
+        .and_then(|s| initrds.iter().copied().find(|f| f.ends_with(s)))
+        .or_else(|| (initrds.len() == 1).then(|| initrds[0]))
+}
+
+/// Collect boot candidates below `dir`. `\EFI\BOOT` is skipped: it holds
+/// Alpheratz itself.
+fn scan(root: &mut Directory, dir: &str, depth: usize, out: &mut Vec<Found>) {
+    let Ok(dir16) = uefi::CString16::try_from(dir) else {
+        return;
+    };
+    let Some(mut d) = root
+        .open(dir16.as_ref(), FileMode::Read, FileAttribute::empty())
+        .ok()
+        .and_then(|h| h.into_directory())
+    else {
+        return;
+    };
+
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    while let Ok(Some(info)) = d.read_entry_boxed() {
+        let name = String::from(info.file_name());
+        if name == "." || name == ".." {
+            continue;
+        }
+        if info.is_directory() {
+            dirs.push(name);
+        } else {
+            files.push(name);
+        }
+    }
+
+    for name in &files {
+        if out.len() == MAX_FOUND {
+            return;
+        }
+        let lc = name.to_ascii_lowercase();
+        let kind = if is_kernel(&lc) {
+            Kind::Linux {
+                initrd: pair_initrd(name, &files).map(|i| join(dir, i)),
+            }
+        } else if lc.ends_with(".efi") {
+            Kind::Efi
+        } else {
+            continue;
+        };
+        out.push(Found {
+            path: join(dir, name),
+            kind,
+        });
+    }
+
+    if depth == MAX_DEPTH {
+        return;
+    }
+    for name in dirs {
+        let path = join(dir, &name);
+        if !path.eq_ignore_ascii_case("\\EFI\\BOOT") {
+            scan(root, &path, depth + 1, out);
+        }
+    }
+}
+
+/// The `.osrel` fields of the unified kernel image at `path`; empty for
+/// plain EFI applications.
+fn os_release(root: &mut Directory, path: &str) -> uki::OsRelease {
+    fsutil::read_file(root, path)
+        .ok()
+        .and_then(|image| {
+            let osrel = uki::section(&image, ".osrel")?;
+            Some(uki::os_release(core::str::from_utf8(osrel).ok()?))
+        })
+        .unwrap_or_default()
+}
+
+/// Ask a yes/no question; Enter or Esc takes `default`.
+fn confirm(prompt: &str, default: bool) -> bool {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    match menu::read_line(&format!("{} {} ", prompt, hint))
+        .as_deref()
+        .map(str::trim)
+    {
+        Some(a) if a.eq_ignore_ascii_case("y") || a.eq_ignore_ascii_case("yes") => true,
+        Some(a) if a.eq_ignore_ascii_case("n") || a.eq_ignore_ascii_case("no") => false,
+        _ => default,
+    }
+}
+
+/// TOML basic string for `s`.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Ask for an IPv4 address until a valid one (or nothing, if `optional`)
+/// is entered.
+#[cfg(feature = "network")]
+fn ask_ipv4(prompt: &str, optional: bool) -> Option<String> {
+    loop {
+        let answer = menu::read_line(prompt)?;
+        let answer = answer.trim();
+        if answer.is_empty() && optional {
+            return None;
+        }
+        if parse_ipv4(answer).is_some() {
+            return Some(String::from(answer));
+        }
+        crate::println!("  Not an IPv4 address: {}", answer);
+    }
+}
+
+#[cfg(feature = "network")]
+fn ask_network(text: &mut String) {
+    let mode = menu::read_line("Network: [d]hcp, [s]tatic or [n]one? [d] ").unwrap_or_default();
+    match mode.trim().to_ascii_lowercase().as_str() {
+        "n" | "none" => {}
+        "s" | "static" => {
+            let mut net = String::from("\n[network]\ntype = \"static\"\n");
+            for (key, prompt, optional) in [
+                ("address", "  Address: ", false),
+                ("netmask", "  Netmask: ", false),
+                ("gateway", "  Gateway (optional): ", true),
+            ] {
+                match ask_ipv4(prompt, optional) {
+                    Some(a) => {
+                        let _ = writeln!(net, "{} = {}", key, quote(&a));
+                    }
+                    None if optional => {}
+                    None => {
+                        crate::println!("  Using DHCP instead.");
+                        let _ = writeln!(text, "\n[network]\ntype = \"dhcp\"");
+                        return;
+                    }
+                }
+            }
+            if let Some(dns) = ask_ipv4("  DNS server (optional): ", true) {
+                let _ = writeln!(net, "dns = [{}]", quote(&dns));
+            }
+            text.push_str(&net);
+        }
+        _ => {
+            let _ = writeln!(text, "\n[network]\ntype = \"dhcp\"");
+        }
+    }
+}
+
+/// Run the wizard. Returns the configuration built, whether or not it was
+/// saved to `path`; `None` if the ESP cannot be read.
+pub fn run(path: &str) -> Option<Config> {
+    crate::println!("No configuration found at {}; starting setup.", path);
+    crate::println!("Press Enter to accept the default shown in brackets.");
+    crate::println!();
+
+    let mut root = fsutil::open_esp_root().ok()?;
+    let mut found = Vec::new();
+    scan(&mut root, "\\", 0, &mut found);
+    if found.is_empty() {
+        crate::println!("No kernels or EFI loaders found on the ESP.");
+    }
+
+    let chosen: Vec<Found> = found
+        .into_iter()
+        .filter(|f| {
+            let what = match f.kind {
+                Kind::Linux { .. } => "Linux kernel",
+                Kind::Efi => "EFI loader",
+            };
+            confirm(&format!("Add {} {}?", what, f.path), true)
+        })
+        .collect();
+
+    let cmdline = if chosen.iter().any(|f| matches!(f.kind, Kind::Linux { .. })) {
+        menu::read_line("Kernel command line for Linux entries: ").unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    let mut text = String::from("# Written by the Alpheratz setup wizard.\ntimeout = 5\n");
+
+    #[cfg(feature = "network")]
+    ask_network(&mut text);
+
+    for f in &chosen {
+        let _ = writeln!(text, "\n[[entry]]");
+        match &f.kind {
+            Kind::Linux { initrd } => {
+                let _ = writeln!(text, "name = {}", quote(&format!("Linux {}", f.path)));
+                let _ = writeln!(text, "protocol = \"linux\"\nfiles = [");
+                let _ = writeln!(
+                    text,
+                    "    {{ type = \"kernel\", search = \"esp\", file = {} }},",
+                    quote(&f.path)
+                );
+                if let Some(rd) = initrd {
+                    let _ = writeln!(
+                        text,
+                        "    {{ type = \"initrd\", search = \"esp\", file = {} }},",
+                        quote(rd)
+                    );
+                }
+                if !cmdline.trim().is_empty() {
+                    let _ = writeln!(
+                        text,
+                        "    {{ type = \"cmdline\", search = \"inline\", content = {} }},",
+                        quote(cmdline.trim())
+                    );
+                }
+                let _ = writeln!(text, "]");
+            }
+            Kind::Efi => {
+                let dir = &f.path[..f.path.rfind('\\').unwrap_or(0)];
+                let rel = os_release(&mut root, &f.path);
+                let name = match &rel.name {
+                    Some(os) => format!("{} ({})", os, f.path),
+                    None => format!("EFI {}", f.path),
+                };
+                let _ = writeln!(text, "name = {}", quote(&name));
+                if let Some(os) = &rel.name {
+                    let _ = writeln!(text, "os = {}", quote(os));
+                }
+                if let Some(version) = &rel.version {
+                    let _ = writeln!(text, "version = {}", quote(version));
+                }
+                let _ = writeln!(text, "protocol = \"efi\"\nworkdir = {}", quote(dir));
+                let _ = writeln!(
+                    text,
+                    "files = [\n    {{ type = \"kernel\", search = \"esp\", file = {} }},\n]",
+                    quote(&f.path)
+                );
+            }
+        }
+    }
+    let _ = writeln!(text, "\n[[entry]]\nname = \"Reboot\"\naction = \"reboot\"");
+
+    let cfg = match Config::from_str(&text) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            crate::println!("Generated configuration is invalid: {}", e);
+            return None;
+        }
+    };
+
+    crate::println!();
+    for line in text.lines() {
+        crate::println!("  {}", line);
+    }
+    if confirm(&format!("Write this to {}?", path), true) {
+        match fsutil::write_file_atomic(&mut root, path, text.as_bytes()) {
+            Ok(()) => crate::println!("Saved."),
+            Err(e) => crate::println!("Writing {} failed: {:?}", path, e.status()),
+        }
+    }
+    Some(cfg)
+}
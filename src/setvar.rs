@@ -0,0 +1,41 @@
+use alpheratz_core::hex::parse_hex;
+use uefi::runtime::{VariableAttributes, VariableVendor};
+use uefi::{CString16, Guid};
+
+use crate::config::{Entry, SetVar, VarAttribute};
+
+fn vendor(s: &str) -> Option<VariableVendor> {
+    if s.eq_ignore_ascii_case("global") {
+        return Some(VariableVendor::GLOBAL_VARIABLE);
+    }
+    s.parse::<Guid>().ok().map(VariableVendor)
+}
+
+fn attributes(list: &[VarAttribute]) -> VariableAttributes {
+    list.iter().fold(VariableAttributes::empty(), |acc, a| {
+        acc | match a {
+            VarAttribute::NonVolatile => VariableAttributes::NON_VOLATILE,
+            VarAttribute::BootserviceAccess => VariableAttributes::BOOTSERVICE_ACCESS,
+            VarAttribute::RuntimeAccess => VariableAttributes::RUNTIME_ACCESS,
+        }
+    })
+}
+
+fn set(var: &SetVar) -> Result<(), &'static str> {
+    let name = CString16::try_from(var.name.as_str()).map_err(|_| "invalid name")?;
+    let vendor = vendor(&var.vendor).ok_or("invalid vendor GUID")?;
+    let data = parse_hex(&var.data).ok_or("data is not valid hex")?;
+
+    uefi::runtime::set_variable(&name, &vendor, attributes(&var.attributes), &data)
+        .map_err(|_| "SetVariable failed")
+}
+
+/// Apply the entry's `[[entry.setvar]]` list just before handing off.
+/// Failures are reported but never block the boot.
+pub fn apply(entry: &Entry) {
+    for var in &entry.setvar {
+        if let Err(reason) = set(var) {
+            crate::println!("setvar {}: {}", var.name, reason);
+        }
+    }
+}
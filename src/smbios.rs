@@ -0,0 +1,41 @@
+//! Locating the SMBIOS structure table for `when.smbios_*` entry filters.
+
+use alpheratz_core::smbios::{self, Machine};
+use uefi::table::cfg::ConfigTableEntry;
+
+/// The machine's SMBIOS strings, from the 3.x entry point if firmware has
+/// one, else the 2.x one. Empty without SMBIOS.
+pub fn machine() -> Machine {
+    let table = uefi::system::with_config_table(|entries| {
+        let find = |guid| {
+            entries
+                .iter()
+                .find(|e| e.guid == guid)
+                .map(|e| e.address as *const u8)
+        };
+        if let Some(ep) = find(ConfigTableEntry::SMBIOS3_GUID) {
+            // "_SM3_", u32 maximum table size at 0x0C, u64 address at 0x10.
+            let ep = unsafe { core::slice::from_raw_parts(ep, 0x18) };
+            if ep.starts_with(b"_SM3_") {
+                let len = u32::from_le_bytes(ep[0x0C..0x10].try_into().unwrap());
+                let addr = u64::from_le_bytes(ep[0x10..0x18].try_into().unwrap());
+                return Some((addr, len as usize));
+            }
+        }
+        let ep = find(ConfigTableEntry::SMBIOS_GUID)?;
+        // "_SM_", u16 table length at 0x16, u32 address at 0x18.
+        let ep = unsafe { core::slice::from_raw_parts(ep, 0x1C) };
+        if !ep.starts_with(b"_SM_") {
+            return None;
+        }
+        let len = u16::from_le_bytes(ep[0x16..0x18].try_into().unwrap());
+        let addr = u32::from_le_bytes(ep[0x18..0x1C].try_into().unwrap());
+        Some((addr as u64, len as usize))
+    });
+    match table {
+        Some((addr, len)) if addr != 0 => {
+            smbios::parse(unsafe { core::slice::from_raw_parts(addr as *const u8, len) })
+        }
+        _ => Machine::default(),
+    }
+}
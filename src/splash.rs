@@ -0,0 +1,109 @@
+//! Progress bar drawn on the GOP framebuffer while a quiet entry boots.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::proto::console::gop::{BltOp, BltPixel, GraphicsOutput};
+
+use crate::config::{Rgb, Theme};
+
+/// Boot stages in the order the bar advances through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Network,
+    Download,
+    Verify,
+    Load,
+    Handoff,
+}
+
+const STAGES: usize = 5;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static BAR: AtomicU32 = AtomicU32::new(0);
+static BACKGROUND: AtomicU32 = AtomicU32::new(0);
+
+fn pack(c: Rgb) -> u32 {
+    u32::from_be_bytes([0, c.0, c.1, c.2])
+}
+
+fn pixel(packed: u32) -> BltPixel {
+    let [_, r, g, b] = packed.to_be_bytes();
+    BltPixel::new(r, g, b)
+}
+
+/// Run `f` with the GOP and its resolution, without taking it away from
+/// the text console.
+fn with_gop(f: impl FnOnce(&mut GraphicsOutput, usize, usize)) {
+    let Ok(handle) = boot::get_handle_for_protocol::<GraphicsOutput>() else {
+        return;
+    };
+    let gop = unsafe {
+        boot::open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    };
+    if let Ok(mut gop) = gop {
+        let (w, h) = gop.current_mode_info().resolution();
+        f(&mut gop, w, h);
+    }
+}
+
+/// Bar geometry for a `w`×`h` screen: x, y, width, height.
+fn bar_rect(w: usize, h: usize) -> (usize, usize, usize, usize) {
+    let width = w / 2;
+    let height = (h / 60).max(4);
+    ((w - width) / 2, h * 2 / 3, width, height)
+}
+
+/// Paint the background and an empty bar, and start tracking stages.
+pub fn start(theme: &Theme) {
+    BAR.store(pack(theme.bar), Ordering::Relaxed);
+    BACKGROUND.store(pack(theme.background), Ordering::Relaxed);
+    ACTIVE.store(true, Ordering::Relaxed);
+    with_gop(|gop, w, h| {
+        let _ = gop.blt(BltOp::VideoFill {
+            color: pixel(pack(theme.background)),
+            dest: (0, 0),
+            dims: (w, h),
+        });
+    });
+    stage(Stage::Network);
+}
+
+/// Stop drawing, e.g. when a failure brings the text console back.
+pub fn stop() {
+    ACTIVE.store(false, Ordering::Relaxed);
+}
+
+/// Move the bar to the beginning of `stage`.
+pub fn stage(stage: Stage) {
+    progress(stage, 0, 1);
+}
+
+/// Fill the bar up to `done` of `total` steps through `stage`.
+pub fn progress(stage: Stage, done: usize, total: usize) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    with_gop(|gop, w, h| {
+        let (x, y, width, height) = bar_rect(w, h);
+        let steps = stage as usize * total.max(1) + done.min(total);
+        let filled = width * steps / (STAGES * total.max(1));
+        let _ = gop.blt(BltOp::VideoFill {
+            color: pixel(BAR.load(Ordering::Relaxed)),
+            dest: (x, y),
+            dims: (filled, height),
+        });
+        let _ = gop.blt(BltOp::VideoFill {
+            color: pixel(BACKGROUND.load(Ordering::Relaxed)),
+            dest: (x + filled, y),
+            dims: (width - filled, height),
+        });
+    });
+}
@@ -0,0 +1,130 @@
+//! Content-addressed store on the ESP: each file lives at
+//! `\EFI\alpheratz\store\<sha256>`, so entries pinning the same digest share
+//! one copy and a file's name doubles as its checksum. `refs\<sha256 of
+//! URL>` names the copy last stored for each URL, which delta updates patch.
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use uefi::CString16;
+use uefi::prelude::*;
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileMode};
+
+use crate::fsutil;
+use crate::sha256;
+
+pub const DIR: &str = "\\EFI\\alpheratz\\store";
+
+fn path(digest: &str) -> String {
+    format!("{}\\{}", DIR, digest)
+}
+
+/// Where the digest last stored for `url` is recorded.
+fn ref_path(url: &str) -> String {
+    let key = sha256::to_hex(&sha256::digest(url.as_bytes()));
+    format!("{}\\refs\\{}", DIR, key)
+}
+
+fn remove(root: &mut Directory, path: &str) -> uefi::Result<()> {
+    let path16 =
+        CString16::try_from(path).map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    root.open(&path16, FileMode::ReadWrite, FileAttribute::empty())?
+        .delete()
+}
+
+/// Contents stored under `digest` (lowercase hex). A file that no longer
+/// hashes to its name is removed and reported as missing.
+pub fn get(root: &mut Directory, digest: &str, max: Option<usize>) -> Option<Vec<u8>> {
+    let path = path(digest);
+    if !fsutil::exists(root, &path) {
+        return None;
+    }
+    let data = fsutil::read_file_max(root, &path, max).ok()?;
+    if sha256::to_hex(&sha256::digest(&data)) == digest {
+        return Some(data);
+    }
+    crate::println!("  {} is corrupt; removing it", path);
+    let _ = remove(root, &path);
+    None
+}
+
+/// Delete the least recently written files until the store holds at most
+/// `budget` bytes.
+fn evict(root: &mut Directory, budget: usize) -> uefi::Result<()> {
+    let dir16 = CString16::try_from(DIR).unwrap();
+    let Some(mut dir) = root
+        .open(&dir16, FileMode::Read, FileAttribute::empty())
+        .ok()
+        .and_then(|h| h.into_directory())
+    else {
+        return Ok(());
+    };
+
+    // (modification time, size, name)
+    let mut files = Vec::new();
+    while let Ok(Some(info)) = dir.read_entry_boxed() {
+        if info.is_directory() {
+            continue;
+        }
+        let t = info.modification_time();
+        let when = (
+            t.year(),
+            t.month(),
+            t.day(),
+            t.hour(),
+            t.minute(),
+            t.second(),
+        );
+        files.push((
+            when,
+            info.file_size() as usize,
+            String::from(info.file_name()),
+        ));
+    }
+    files.sort_unstable_by_key(|f| f.0);
+
+    let mut total: usize = files.iter().map(|f| f.1).sum();
+    for (_, size, name) in files {
+        if total <= budget {
+            break;
+        }
+        crate::println!("  Evicting {} from the store", name);
+        remove(root, &path(&name))?;
+        total -= size;
+    }
+    Ok(())
+}
+
+/// Keep `data`, already checked to hash to `digest`, unless a copy exists.
+/// Older files are evicted first so the store stays within `max_size`;
+/// anything larger than that is not stored at all.
+pub fn put(root: &mut Directory, digest: &str, data: &[u8], max_size: usize) -> uefi::Result<()> {
+    let path = path(digest);
+    if data.len() > max_size || fsutil::exists(root, &path) {
+        return Ok(());
+    }
+    evict(root, max_size - data.len())?;
+    fsutil::write_file(root, &path, data)
+}
+
+/// Digest of the copy last stored for `url`, if that copy is still there.
+pub fn previous(root: &mut Directory, url: &str) -> Option<String> {
+    let ref_file = ref_path(url);
+    fsutil::recover_atomic(root, &ref_file);
+    let data = fsutil::read_file_max(root, &ref_file, Some(64)).ok()?;
+    let digest = core::str::from_utf8(&data).ok()?;
+    if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digest = digest.to_ascii_lowercase();
+    fsutil::exists(root, &path(&digest)).then_some(digest)
+}
+
+/// Record `digest` as the current copy of `url`, the base its next delta
+/// applies to.
+pub fn remember(root: &mut Directory, url: &str, digest: &str) -> uefi::Result<()> {
+    fsutil::write_file_atomic(root, &ref_path(url), digest.as_bytes())
+}
@@ -0,0 +1,148 @@
+//! TCP connect probe run before the first request to a server, so a server
+//! that cannot be reached is reported as such instead of as a TLS or HTTP
+//! failure.
+
+use core::ffi::c_void;
+use core::ptr;
+use core::time::Duration;
+
+use uefi::boot::{self, EventType, OpenProtocolAttributes, OpenProtocolParams, Tpl};
+use uefi::prelude::*;
+use uefi::proto::unsafe_protocol;
+
+/// EFI_TCP4_SERVICE_BINDING_PROTOCOL
+#[repr(C)]
+#[unsafe_protocol("00720665-67eb-4a99-baf7-d3c33a1c7cd6")]
+struct Tcp4ServiceBinding {
+    create_child: unsafe extern "efiapi" fn(this: *mut Self, child: *mut *mut c_void) -> Status,
+    destroy_child: unsafe extern "efiapi" fn(this: *mut Self, child: *mut c_void) -> Status,
+}
+
+/// EFI_TCP4_ACCESS_POINT
+#[repr(C)]
+struct AccessPoint {
+    use_default_address: bool,
+    station_address: [u8; 4],
+    subnet_mask: [u8; 4],
+    station_port: u16,
+    remote_address: [u8; 4],
+    remote_port: u16,
+    active_flag: bool,
+}
+
+/// EFI_TCP4_CONFIG_DATA
+#[repr(C)]
+struct ConfigData {
+    type_of_service: u8,
+    time_to_live: u8,
+    access_point: AccessPoint,
+    control_option: *const c_void,
+}
+
+/// EFI_TCP4_COMPLETION_TOKEN, which is also all of a connection token.
+#[repr(C)]
+struct Token {
+    event: *mut c_void,
+    status: Status,
+}
+
+/// EFI_TCP4_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("65530bc7-a359-410f-b010-5aadc7ec2b62")]
+struct Tcp4 {
+    get_mode_data: *const c_void,
+    configure: unsafe extern "efiapi" fn(this: *mut Self, data: *const ConfigData) -> Status,
+    routes: *const c_void,
+    connect: unsafe extern "efiapi" fn(this: *mut Self, token: *mut Token) -> Status,
+    accept: *const c_void,
+    transmit: *const c_void,
+    receive: *const c_void,
+    close: *const c_void,
+    cancel: unsafe extern "efiapi" fn(this: *mut Self, token: *mut Token) -> Status,
+    poll: unsafe extern "efiapi" fn(this: *mut Self) -> Status,
+}
+
+// Network statuses of UEFI spec appendix D that `uefi::Status` lacks.
+pub const NETWORK_UNREACHABLE: Status = Status(Status::ERROR_BIT | 100);
+pub const HOST_UNREACHABLE: Status = Status(Status::ERROR_BIT | 101);
+pub const PORT_UNREACHABLE: Status = Status(Status::ERROR_BIT | 103);
+pub const CONNECTION_RESET: Status = Status(Status::ERROR_BIT | 105);
+pub const CONNECTION_REFUSED: Status = Status(Status::ERROR_BIT | 106);
+
+unsafe fn open_get<P: uefi::proto::ProtocolPointer + ?Sized>(
+    handle: Handle,
+) -> uefi::Result<boot::ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+/// Open a TCP connection to `address:port` over `nic` and drop it again.
+/// The outer error means the probe could not be run (no TCP4 stack); the
+/// inner status is the outcome of the connection attempt, `TIMEOUT` when
+/// nothing answered within `timeout`.
+pub fn probe(nic: Handle, address: [u8; 4], port: u16, timeout: Duration) -> uefi::Result<Status> {
+    let mut sb = unsafe { open_get::<Tcp4ServiceBinding>(nic)? };
+    let sb_this: *mut Tcp4ServiceBinding = &mut *sb;
+    let mut child: *mut c_void = ptr::null_mut();
+    unsafe { (sb.create_child)(sb_this, &mut child) }.to_result()?;
+    let outcome = (|| {
+        let handle =
+            unsafe { Handle::from_ptr(child) }.ok_or(uefi::Error::from(Status::NOT_FOUND))?;
+        let mut tcp = unsafe { open_get::<Tcp4>(handle)? };
+        let this: *mut Tcp4 = &mut *tcp;
+        let data = ConfigData {
+            type_of_service: 0,
+            time_to_live: 64,
+            access_point: AccessPoint {
+                use_default_address: true,
+                station_address: [0; 4],
+                subnet_mask: [0; 4],
+                station_port: 0,
+                remote_address: address,
+                remote_port: port,
+                active_flag: true,
+            },
+            control_option: ptr::null(),
+        };
+        unsafe { (tcp.configure)(this, &data) }.to_result()?;
+
+        let event = unsafe { boot::create_event(EventType::empty(), Tpl::CALLBACK, None, None)? };
+        let mut token = Token {
+            event: event.as_ptr(),
+            status: Status::NOT_READY,
+        };
+        let status = match unsafe { (tcp.connect)(this, &mut token) } {
+            s if s.is_error() => s,
+            _ => {
+                let mut waited = Duration::ZERO;
+                loop {
+                    let _ = unsafe { (tcp.poll)(this) };
+                    if boot::check_event(unsafe { event.unsafe_clone() }).unwrap_or(false) {
+                        break unsafe { ptr::read_volatile(&token.status) };
+                    }
+                    if waited >= timeout {
+                        let _ = unsafe { (tcp.cancel)(this, &mut token) };
+                        break Status::TIMEOUT;
+                    }
+                    boot::stall(Duration::from_millis(1));
+                    waited += Duration::from_millis(1);
+                }
+            }
+        };
+        // Resetting the instance aborts the connection and flushes tokens.
+        let _ = unsafe { (tcp.configure)(this, ptr::null()) };
+        let _ = boot::close_event(event);
+        Ok(status)
+    })();
+    let _ = unsafe { (sb.destroy_child)(sb_this, child) };
+    outcome
+}
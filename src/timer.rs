@@ -0,0 +1,148 @@
+//! Timer calibration done while firmware services are still around, so a
+//! kernel does not have to recalibrate after exit_boot_services().
+#![cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+
+/// Calibrate against firmware Stall() for this long when nothing better
+/// is available.
+#[cfg(target_arch = "x86_64")]
+const STALL_US: u64 = 50_000;
+
+/// Frequency of the ACPI PM timer.
+#[cfg(target_arch = "x86_64")]
+const PM_TIMER_HZ: u64 = 3_579_545;
+
+#[cfg(target_arch = "x86_64")]
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// TSC frequency from CPUID leaf 0x15 (crystal clock and TSC ratio), when
+/// the CPU reports both.
+#[cfg(target_arch = "x86_64")]
+fn tsc_hz_cpuid() -> Option<u64> {
+    use core::arch::x86_64::__cpuid;
+
+    if __cpuid(0).eax < 0x15 {
+        return None;
+    }
+    let leaf = __cpuid(0x15);
+    if leaf.eax == 0 || leaf.ebx == 0 || leaf.ecx == 0 {
+        return None;
+    }
+    Some(u64::from(leaf.ecx) * u64::from(leaf.ebx) / u64::from(leaf.eax))
+}
+
+/// The FADT (signature "FACP") reachable from the ACPI RSDP.
+#[cfg(target_arch = "x86_64")]
+unsafe fn fadt() -> Option<*const u8> {
+    use uefi::table::cfg::ConfigTableEntry;
+
+    let rsdp = uefi::system::with_config_table(|entries| {
+        entries
+            .iter()
+            .find(|e| e.guid == ConfigTableEntry::ACPI2_GUID)
+            .or_else(|| {
+                entries
+                    .iter()
+                    .find(|e| e.guid == ConfigTableEntry::ACPI_GUID)
+            })
+            .map(|e| e.address as *const u8)
+    })?;
+    unsafe {
+        let read32 = |p: *const u8| (p as *const u32).read_unaligned();
+        let revision = *rsdp.add(15);
+        let xsdt = if revision >= 2 {
+            (rsdp.add(24) as *const u64).read_unaligned()
+        } else {
+            0
+        };
+        let (table, entry_size) = if xsdt != 0 {
+            (xsdt as *const u8, 8)
+        } else {
+            (read32(rsdp.add(16)) as u64 as *const u8, 4)
+        };
+        let count = (read32(table.add(4)) as usize).saturating_sub(36) / entry_size;
+        (0..count).find_map(|i| {
+            let slot = table.add(36 + i * entry_size);
+            let addr = if entry_size == 8 {
+                (slot as *const u64).read_unaligned()
+            } else {
+                u64::from(read32(slot))
+            };
+            let sdt = addr as *const u8;
+            (core::slice::from_raw_parts(sdt, 4) == b"FACP").then_some(sdt)
+        })
+    }
+}
+
+/// TSC frequency measured against the ACPI PM timer, if the FADT names an
+/// I/O port for it.
+#[cfg(target_arch = "x86_64")]
+fn tsc_hz_pm_timer() -> Option<u64> {
+    let (port, mask) = unsafe {
+        let fadt = fadt()?;
+        let length = (fadt.add(4) as *const u32).read_unaligned();
+        let mut port = (fadt.add(76) as *const u32).read_unaligned() as u64;
+        // X_PM_TMR_BLK, a Generic Address Structure in system I/O space.
+        if port == 0 && length >= 220 && *fadt.add(208) == 1 {
+            port = (fadt.add(212) as *const u64).read_unaligned();
+        }
+        let flags = (fadt.add(112) as *const u32).read_unaligned();
+        let mask: u32 = if flags & (1 << 8) != 0 {
+            u32::MAX
+        } else {
+            0x00FF_FFFF
+        };
+        (u16::try_from(port).ok().filter(|&p| p != 0)?, mask)
+    };
+    let read = || {
+        let v: u32;
+        unsafe { core::arch::asm!("in eax, dx", out("eax") v, in("dx") port) };
+        v & mask
+    };
+
+    let ticks = PM_TIMER_HZ * STALL_US / 1_000_000;
+    let start = read();
+    let tsc_start = rdtsc();
+    let mut elapsed = 0u64;
+    while elapsed < ticks {
+        elapsed = u64::from(read().wrapping_sub(start) & mask);
+    }
+    let tsc = rdtsc() - tsc_start;
+    Some(tsc * PM_TIMER_HZ / elapsed)
+}
+
+/// TSC frequency measured against firmware Stall().
+#[cfg(target_arch = "x86_64")]
+fn tsc_hz_stall() -> u64 {
+    let start = rdtsc();
+    uefi::boot::stall(core::time::Duration::from_micros(STALL_US));
+    (rdtsc() - start) * 1_000_000 / STALL_US
+}
+
+/// Frequency in Hz of the CPU's free-running counter: the TSC on x86_64,
+/// the generic timer (CNTFRQ_EL0) on aarch64. `None` where unknown.
+pub fn counter_hz() -> Option<u64> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let (hz, source) = match tsc_hz_cpuid() {
+            Some(hz) => (hz, "CPUID 0x15"),
+            None => match tsc_hz_pm_timer() {
+                Some(hz) => (hz, "ACPI PM timer"),
+                None => (tsc_hz_stall(), "firmware Stall()"),
+            },
+        };
+        log::info!("TSC frequency: {} Hz ({})", hz, source);
+        Some(hz)
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let hz: u64;
+        unsafe { core::arch::asm!("mrs {}, cntfrq_el0", out(reg) hz) };
+        Some(hz)
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        None
+    }
+}
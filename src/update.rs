@@ -0,0 +1,98 @@
+//! The `self-update` action: download the loader image `[self_update]`
+//! names, check it, put it in place of the file this loader was started
+//! from and reboot into it, keeping the current image as the
+//! [`fallback`](crate::fallback) copy.
+
+extern crate alloc;
+
+use alloc::string::String;
+use core::time::Duration;
+
+use alpheratz_core::uki;
+use uefi::boot::{self, LoadImageSource};
+use uefi::prelude::*;
+use uefi::runtime::ResetType;
+
+use crate::config::{Config, SelfUpdate};
+use crate::download;
+use crate::error::{self, AlpheratzError};
+use crate::fallback;
+use crate::fsutil;
+use crate::secureboot::{self, State};
+use crate::sha256;
+
+/// Largest loader image downloaded.
+const LOADER_MAX: usize = 16 * 1024 * 1024;
+
+/// Check `image` before it replaces the loader: the `sha256` pin, which is
+/// required unless Secure Boot is enabled, an Authenticode signature, and
+/// that firmware agrees to load it, which under Secure Boot is where the
+/// signature is checked against `db`. The image is never started.
+fn verify(update: &SelfUpdate, image: &[u8]) -> error::Result<()> {
+    match &update.sha256 {
+        Some(pin) => {
+            let got = sha256::to_hex(&sha256::digest(image));
+            if !got.eq_ignore_ascii_case(pin.trim()) {
+                return Err(AlpheratzError::Verify {
+                    source: update.url.clone(),
+                    expected: pin.to_ascii_lowercase(),
+                    got,
+                });
+            }
+        }
+        None if secureboot::state() != State::Enabled => {
+            return Err(AlpheratzError::Config(String::from(
+                "[self_update] needs a sha256 pin while Secure Boot is not enabled",
+            )));
+        }
+        None => {}
+    }
+    if !uki::is_signed(image) {
+        crate::println!("  The new loader is not signed.");
+        return Err(AlpheratzError::Uefi(Status::SECURITY_VIOLATION));
+    }
+    let handle = boot::load_image(
+        boot::image_handle(),
+        LoadImageSource::FromBuffer {
+            buffer: image,
+            file_path: None,
+        },
+    )
+    .inspect_err(|e| crate::println!("  Firmware refused the new loader: {:?}", e.status()))?;
+    let _ = boot::unload_image(handle);
+    Ok(())
+}
+
+/// Replace the running loader's file with the `[self_update]` image and
+/// reboot. Only returns if the update could not be carried out, in which
+/// case the current loader is left in place.
+pub fn run(cfg: &Config) -> error::Result<()> {
+    let update = cfg.self_update.as_ref().ok_or_else(|| {
+        AlpheratzError::Config(String::from("`self-update` needs a [self_update] section"))
+    })?;
+    let path = fsutil::loader_path().ok_or_else(|| {
+        AlpheratzError::Config(String::from(
+            "the loader was not started from a file on the ESP",
+        ))
+    })?;
+    let name = &path[path.rfind('\\').map_or(0, |i| i + 1)..];
+    if name.eq_ignore_ascii_case(fallback::PREV_NAME) {
+        return Err(AlpheratzError::Config(String::from(
+            "running from the fallback copy; boot the updated loader to update it",
+        )));
+    }
+
+    let image = download::fetch(cfg, &update.url, LOADER_MAX)?;
+    verify(update, &image)?;
+
+    crate::println!("Keeping {} as the fallback copy...", path);
+    fallback::arm(&path).map_err(|e| AlpheratzError::fs(&path, e))?;
+    crate::println!("Replacing {}...", path);
+    let mut root = fsutil::open_esp_root()?;
+    fsutil::write_file_atomic(&mut root, &path, &image)
+        .map_err(|e| AlpheratzError::fs(&path, e))?;
+
+    crate::println!("Rebooting into the new loader...");
+    boot::stall(Duration::from_secs(2));
+    uefi::runtime::reset(ResetType::WARM, Status::SUCCESS, None)
+}
@@ -0,0 +1,70 @@
+//! Picking the GOP mode at startup, for firmware that comes up in 640x480.
+
+use uefi::boot::{self, OpenProtocolAttributes, OpenProtocolParams};
+use uefi::proto::console::gop::GraphicsOutput;
+
+use crate::config::Video;
+
+/// Switch the display to the mode `video` asks for. Failures leave the
+/// firmware's mode in place; the menu works in any of them.
+pub fn apply(video: Video) {
+    if video == Video::Keep {
+        return;
+    }
+    let Ok(handle) = boot::get_handle_for_protocol::<GraphicsOutput>() else {
+        return;
+    };
+    // Not exclusive: that would disconnect the firmware's text console.
+    let gop = unsafe {
+        boot::open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    };
+    let Ok(mut gop) = gop else {
+        return;
+    };
+
+    let current = gop.current_mode_info().resolution();
+    let mode = match video {
+        Video::Keep => return,
+        Video::Max => gop.modes().max_by_key(|m| {
+            let (w, h) = m.info().resolution();
+            w * h
+        }),
+        Video::Size(w, h) => {
+            let wanted = (w as usize, h as usize);
+            let mode = gop.modes().find(|m| m.info().resolution() == wanted);
+            if mode.is_none() {
+                crate::println!(
+                    "No {}x{} video mode; keeping {}x{}.",
+                    w,
+                    h,
+                    current.0,
+                    current.1
+                );
+            }
+            mode
+        }
+    };
+    let Some(mode) = mode else {
+        return;
+    };
+    if mode.info().resolution() == current {
+        return;
+    }
+    if let Err(e) = gop.set_mode(&mode) {
+        let (w, h) = mode.info().resolution();
+        crate::println!("Cannot switch to {}x{}: {:?}", w, h, e.status());
+        return;
+    }
+    drop(gop);
+    // The text console was drawn for the old size.
+    uefi::system::with_stdout(|out| {
+        let _ = out.clear();
+    });
+}
@@ -0,0 +1,256 @@
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::time::Duration;
+
+use uefi::boot::{self, EventType, OpenProtocolAttributes, OpenProtocolParams, Tpl};
+use uefi::prelude::*;
+use uefi::proto::unsafe_protocol;
+
+use crate::config::Wifi;
+
+/// IEEE 802.11 OUI used for the standard AKM and cipher suite selectors.
+const IEEE_OUI: [u8; 3] = [0x00, 0x0F, 0xAC];
+const AKM_PSK: u8 = 2;
+const CIPHER_CCMP: u8 = 4;
+
+const BSS_TYPE_INFRASTRUCTURE: u32 = 1;
+
+/// EFI_SUPPLICANT_DATA_TYPE values used here.
+const SUPPLICANT_PSK_PASSWORD: u32 = 3;
+const SUPPLICANT_TARGET_SSID_NAME: u32 = 4;
+
+/// Connect result code for `ConnectSuccess`.
+const CONNECT_SUCCESS: u32 = 0;
+
+const CONNECT_TIMEOUT_SECS: u64 = 20;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SuiteSelector {
+    oui: [u8; 3],
+    suite_type: u8,
+}
+
+#[repr(C)]
+struct SuiteList {
+    count: u16,
+    list: [SuiteSelector; 1],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Ssid {
+    len: u8,
+    ssid: [u8; 32],
+}
+
+#[repr(C)]
+struct Network80211 {
+    bss_type: u32,
+    ssid: Ssid,
+    akm_suite: *const SuiteList,
+    cipher_suite: *const SuiteList,
+}
+
+#[repr(C)]
+struct ConnectNetworkData {
+    network: *const Network80211,
+    failure_timeout: u32,
+}
+
+#[repr(C)]
+struct ConnectNetworkToken {
+    event: *mut c_void,
+    status: Status,
+    data: *const ConnectNetworkData,
+    result_code: u32,
+}
+
+/// Everything ConnectNetwork keeps pointers into until it signals the
+/// token's event, in one allocation that can outlive [`connect`].
+struct ConnectRequest {
+    psk_suite: SuiteList,
+    ccmp_suite: SuiteList,
+    network: Network80211,
+    data: ConnectNetworkData,
+    token: ConnectNetworkToken,
+}
+
+/// EFI_WIRELESS_MAC_CONNECTION_II_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("1b0fb9bf-699d-4fdd-a7c3-2546681bf63b")]
+struct WirelessMacConnection2 {
+    get_networks: unsafe extern "efiapi" fn(this: *mut Self, token: *mut c_void) -> Status,
+    connect_network:
+        unsafe extern "efiapi" fn(this: *mut Self, token: *mut ConnectNetworkToken) -> Status,
+    disconnect_network: unsafe extern "efiapi" fn(this: *mut Self, token: *mut c_void) -> Status,
+}
+
+/// EFI_SUPPLICANT_PROTOCOL
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_protocol("54fcc43e-aa89-4333-9a85-cdea24051e9e")]
+struct Supplicant {
+    build_response_packet: *const c_void,
+    process_packet: *const c_void,
+    set_data: unsafe extern "efiapi" fn(
+        this: *mut Self,
+        data_type: u32,
+        data: *const c_void,
+        data_size: usize,
+    ) -> Status,
+    get_data: *const c_void,
+}
+
+unsafe fn open_get<P: uefi::proto::ProtocolPointer + ?Sized>(
+    handle: Handle,
+) -> uefi::Result<boot::ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+/// Whether `nic` is a wireless interface managed by the firmware Wi-Fi stack.
+pub fn is_wireless(nic: Handle) -> bool {
+    boot::test_protocol::<WirelessMacConnection2>(OpenProtocolParams {
+        handle: nic,
+        agent: boot::image_handle(),
+        controller: None,
+    })
+    .unwrap_or(false)
+}
+
+fn make_ssid(name: &str) -> Option<Ssid> {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() || bytes.len() > 32 {
+        return None;
+    }
+    let mut ssid = Ssid {
+        len: bytes.len() as u8,
+        ssid: [0; 32],
+    };
+    ssid.ssid[..bytes.len()].copy_from_slice(bytes);
+    Some(ssid)
+}
+
+/// Hand the SSID and PSK to the supplicant, then associate with the access
+/// point and wait for the connection to complete.
+pub fn connect(wifi: &Wifi, nic: Handle) -> uefi::Result<()> {
+    let ssid = make_ssid(&wifi.ssid).ok_or_else(|| {
+        crate::println!("  Invalid SSID: {}", wifi.ssid);
+        uefi::Error::from(Status::INVALID_PARAMETER)
+    })?;
+    crate::println!("  Wi-Fi: joining \"{}\"...", wifi.ssid);
+
+    if let Some(psk) = wifi.psk.as_deref() {
+        let mut supplicant = unsafe { open_get::<Supplicant>(nic) }
+            .inspect_err(|e| crate::println!("  Supplicant not available: {:?}", e.status()))?;
+        let this: *mut Supplicant = &mut *supplicant;
+
+        let mut password = alloc::vec::Vec::from(psk.as_bytes());
+        password.push(0);
+        unsafe {
+            let status = (supplicant.set_data)(
+                this,
+                SUPPLICANT_TARGET_SSID_NAME,
+                &ssid as *const Ssid as *const c_void,
+                core::mem::size_of::<Ssid>(),
+            );
+            if status.is_error() {
+                crate::println!("  Supplicant.SetData(SSID) failed: {:?}", status);
+                return Err(uefi::Error::from(status));
+            }
+            let status = (supplicant.set_data)(
+                this,
+                SUPPLICANT_PSK_PASSWORD,
+                password.as_ptr() as *const c_void,
+                password.len(),
+            );
+            if status.is_error() {
+                crate::println!("  Supplicant.SetData(PSK) failed: {:?}", status);
+                return Err(uefi::Error::from(status));
+            }
+        }
+    }
+
+    let suite = |suite_type| SuiteList {
+        count: 1,
+        list: [SuiteSelector {
+            oui: IEEE_OUI,
+            suite_type,
+        }],
+    };
+    let event = unsafe { boot::create_event(EventType::empty(), Tpl::CALLBACK, None, None)? };
+    let mut request = Box::new(ConnectRequest {
+        psk_suite: suite(AKM_PSK),
+        ccmp_suite: suite(CIPHER_CCMP),
+        network: Network80211 {
+            bss_type: BSS_TYPE_INFRASTRUCTURE,
+            ssid,
+            akm_suite: core::ptr::null(),
+            cipher_suite: core::ptr::null(),
+        },
+        data: ConnectNetworkData {
+            network: core::ptr::null(),
+            failure_timeout: CONNECT_TIMEOUT_SECS as u32,
+        },
+        token: ConnectNetworkToken {
+            event: event.as_ptr(),
+            status: Status::NOT_READY,
+            data: core::ptr::null(),
+            result_code: CONNECT_SUCCESS,
+        },
+    });
+    if wifi.psk.is_some() {
+        request.network.akm_suite = &request.psk_suite;
+        request.network.cipher_suite = &request.ccmp_suite;
+    }
+    request.data.network = &request.network;
+    request.token.data = &request.data;
+
+    let mut wmc = unsafe { open_get::<WirelessMacConnection2>(nic)? };
+    let this: *mut WirelessMacConnection2 = &mut *wmc;
+    let status = unsafe { (wmc.connect_network)(this, &mut request.token) };
+    if status.is_error() {
+        let _ = boot::close_event(event);
+        crate::println!("  ConnectNetwork failed: {:?}", status);
+        return Err(uefi::Error::from(status));
+    }
+
+    let mut signaled = false;
+    for _ in 0..CONNECT_TIMEOUT_SECS * 10 {
+        if boot::check_event(unsafe { event.unsafe_clone() }).unwrap_or(false) {
+            signaled = true;
+            break;
+        }
+        boot::stall(Duration::from_millis(100));
+    }
+    if !signaled {
+        // Firmware still holds the request and may complete it later, so it
+        // and its event have to stay valid for good.
+        Box::leak(request);
+        crate::println!("  Wi-Fi: timed out");
+        return Err(uefi::Error::from(Status::TIMEOUT));
+    }
+    let _ = boot::close_event(event);
+
+    let status = unsafe { core::ptr::read_volatile(&request.token.status) };
+    let result = unsafe { core::ptr::read_volatile(&request.token.result_code) };
+    if status.is_error() || result != CONNECT_SUCCESS {
+        crate::println!("  Wi-Fi: connect failed ({:?}, result {})", status, result);
+        return Err(uefi::Error::from(Status::NO_MEDIA));
+    }
+
+    crate::println!("  Wi-Fi: connected.");
+    Ok(())
+}